@@ -0,0 +1,47 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use langchain_rust::prompt::{PromptArgs, PromptFromatter, PromptTemplate, TemplateFormat};
+
+const BATCH_SIZE: usize = 10_000;
+
+fn build_batch() -> Vec<PromptArgs> {
+    (0..BATCH_SIZE)
+        .map(|i| {
+            PromptArgs::from([
+                ("name".to_string(), format!("user {}", i).into()),
+                (
+                    "question".to_string(),
+                    format!("What is the answer to question {}?", i).into(),
+                ),
+                (
+                    "context".to_string(),
+                    "Lorem ipsum dolor sit amet, consectetur adipiscing elit. "
+                        .repeat(20)
+                        .into(),
+                ),
+            ])
+        })
+        .collect()
+}
+
+fn bench_format_batch(c: &mut Criterion) {
+    let prompt = PromptTemplate::from_template(
+        "You are helping {name}.\n\nContext:\n{context}\n\nQuestion: {question}\nAnswer:",
+        TemplateFormat::FString,
+    )
+    .unwrap();
+    let batch = build_batch();
+
+    let mut group = c.benchmark_group("format_batch_10k");
+    group.sample_size(10);
+    group.bench_function("sequential", |b| {
+        b.iter(|| prompt.format_batch(black_box(&batch)))
+    });
+    #[cfg(feature = "rayon")]
+    group.bench_function("parallel", |b| {
+        b.iter(|| prompt.format_batch_parallel(black_box(&batch)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_format_batch);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use langchain_rust::prompt::{PromptArgs, PromptFromatter, PromptTemplate, TemplateFormat};
+
+const VARIABLES: usize = 50;
+const TEMPLATE_SIZE: usize = 100 * 1024;
+
+fn build_template() -> (String, Vec<String>, PromptArgs) {
+    let variables: Vec<String> = (0..VARIABLES).map(|i| format!("var_{}", i)).collect();
+    let mut template = String::with_capacity(TEMPLATE_SIZE);
+    let mut i = 0;
+    while template.len() < TEMPLATE_SIZE {
+        template.push_str("Lorem ipsum dolor sit amet, consectetur adipiscing elit. {");
+        template.push_str(&variables[i % VARIABLES]);
+        template.push_str("}\n");
+        i += 1;
+    }
+    let args = variables
+        .iter()
+        .map(|v| {
+            (
+                v.clone(),
+                serde_json::Value::String(format!("value of {}", v)),
+            )
+        })
+        .collect();
+    (template, variables, args)
+}
+
+// The `String::replace` loop `format` used before rendering in a single pass.
+fn format_with_replace(template: &str, args: &PromptArgs) -> String {
+    let mut prompt = template.to_string();
+    for (key, value) in args {
+        let key = format!("{{{}}}", key);
+        prompt = prompt.replace(&key, value.as_str().unwrap_or_default());
+    }
+    prompt
+}
+
+fn bench_format(c: &mut Criterion) {
+    let (template, variables, args) = build_template();
+    let prompt = PromptTemplate::new(template.clone(), variables, TemplateFormat::FString);
+
+    let mut group = c.benchmark_group("format_50_variables_100kb");
+    group.bench_function("single_pass", |b| {
+        b.iter(|| prompt.format(black_box(args.clone())).unwrap())
+    });
+    group.bench_function("format_into_reused_buffer", |b| {
+        let mut buffer = String::with_capacity(2 * TEMPLATE_SIZE);
+        b.iter(|| {
+            buffer.clear();
+            prompt
+                .format_into(black_box(args.clone()), &mut buffer)
+                .unwrap();
+        })
+    });
+    group.bench_function("replace_loop", |b| {
+        b.iter(|| format_with_replace(black_box(&template), black_box(&args)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_format);
+criterion_main!(benches);
@@ -1,8 +1,8 @@
 use langchain_rust::{
-    chain::{Chain, LLMChainBuilder},
+    chain::{Chain, LLMChainBuilder, SequentialChainBuilder},
     llm::openai::{OpenAI, OpenAIModel},
     prompt::HumanMessagePromptTemplate,
-    prompt_args, sequential_chain, template_jinja2,
+    prompt_args, template_jinja2,
 };
 use std::io::{self, Write}; // Include io Library for terminal input
 
@@ -32,7 +32,12 @@ async fn main() {
         .build()
         .unwrap();
 
-    let sequential_chain = sequential_chain!(get_name_chain, get_slogan_chain);
+    let sequential_chain = SequentialChainBuilder::new()
+        .add_chain(get_name_chain)
+        .add_chain(get_slogan_chain)
+        .input_keys(vec!["producto"])
+        .try_build()
+        .unwrap();
 
     print!("Please enter a product: ");
     io::stdout().flush().unwrap(); // Display prompt to terminal
@@ -0,0 +1,264 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, Data, DeriveInput, Error, Field, Fields, Ident, LitStr, Token, Type,
+};
+
+mod template;
+
+/// Derives `langchain_rust::prompt::IntoPromptArgs` and a conversion into `PromptArgs` for a
+/// struct with named fields, so it can be passed to `PromptTemplate::format` or
+/// `PromptFromatter::format_from` directly:
+///
+/// - Each field is a variable named after it, unless renamed with `#[prompt(rename = "x")]`.
+/// - Values are serialized with `serde`, or converted with `Display` for fields marked
+///   `#[prompt(display)]`.
+/// - `#[prompt(skip)]` fields are left out, and so are `Option` fields set to `None` and, with
+///   a warning, fields failing to be serialized.
+/// - Two fields can't be named the same variable.
+///
+/// # Usage
+/// ```rust
+/// use langchain_rust::prompt::{IntoPromptArgs, PromptArgs, PromptTemplate, TemplateFormat};
+///
+/// #[derive(IntoPromptArgs)]
+/// struct QuestionArgs {
+///     #[prompt(rename = "user")]
+///     name: String,
+///     age: u32,
+///     nickname: Option<String>,
+///     #[prompt(skip)]
+///     request_id: u64,
+/// }
+///
+/// let prompt = PromptTemplate::from_template(
+///     "{user} ({age}) asks: who am I?",
+///     TemplateFormat::FString,
+/// )
+/// .unwrap();
+/// let args = QuestionArgs { name: "Ana".into(), age: 30, nickname: None, request_id: 7 };
+/// assert_eq!(prompt.format(args).unwrap(), "Ana (30) asks: who am I?");
+/// ```
+///
+/// Fields must be serializable, or displayable when marked `#[prompt(display)]`, and only
+/// structs with named fields are supported; see `tests/ui` for what fails to compile.
+#[proc_macro_derive(IntoPromptArgs, attributes(prompt))]
+pub fn derive_into_prompt_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct FieldOptions {
+    rename: Option<String>,
+    skip: bool,
+    display: bool,
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "IntoPromptArgs can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "IntoPromptArgs can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut inserts = Vec::new();
+    let mut names = Vec::new();
+    for field in fields {
+        let options = field_options(field)?;
+        if options.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().expect("named fields have an ident");
+        let name = options.rename.unwrap_or_else(|| ident.to_string());
+        if names.contains(&name) {
+            return Err(Error::new_spanned(
+                ident,
+                format!("another field is already named `{}`", name),
+            ));
+        }
+        names.push(name.clone());
+        let to_value = if options.display {
+            quote!(::langchain_rust::prompt::__display_prompt_value)
+        } else {
+            quote!(::langchain_rust::prompt::__serialize_prompt_value)
+        };
+        inserts.push(if is_option(&field.ty) {
+            quote! {
+                if let ::core::option::Option::Some(value) = &args.#ident {
+                    if let ::core::option::Option::Some(value) = #to_value(#name, value) {
+                        prompt_args.insert(::std::string::String::from(#name), value);
+                    }
+                }
+            }
+        } else {
+            quote! {
+                if let ::core::option::Option::Some(value) = #to_value(#name, &args.#ident) {
+                    prompt_args.insert(::std::string::String::from(#name), value);
+                }
+            }
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::core::convert::From<#ident #ty_generics>
+            for ::langchain_rust::prompt::PromptArgs #where_clause
+        {
+            fn from(args: #ident #ty_generics) -> Self {
+                let mut prompt_args = ::langchain_rust::prompt::PromptArgs::new();
+                #(#inserts)*
+                prompt_args
+            }
+        }
+
+        impl #impl_generics ::langchain_rust::prompt::IntoPromptArgs for #ident #ty_generics
+            #where_clause
+        {
+            fn into_prompt_args_checked(
+                self,
+            ) -> (
+                ::langchain_rust::prompt::PromptArgs,
+                ::std::vec::Vec<::std::string::String>,
+            ) {
+                (self.into(), ::std::vec::Vec::new())
+            }
+        }
+    })
+}
+
+fn field_options(field: &Field) -> Result<FieldOptions, Error> {
+    let mut options = FieldOptions {
+        rename: None,
+        skip: false,
+        display: false,
+    };
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("prompt"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else if meta.path.is_ident("display") {
+                options.display = true;
+            } else {
+                return Err(meta.error("expected `rename = \"...\"`, `skip` or `display`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+/// Creates a `langchain_rust::prompt::PromptTemplate` from a template literal checked at
+/// compile time, with the variables found in it. A malformed placeholder, or two variables
+/// differing only in case, like `{name}` and `{Name}`, is a compile error.
+///
+/// The template is an FString one, unless a format is given: `format = fstring` or
+/// `format = jinja2`.
+///
+/// # Usage
+/// ```rust
+/// use langchain_rust::{prompt::{PromptFromatter, prompt_template}, prompt_args};
+///
+/// let prompt = prompt_template!("Hello {name}, you are {age}");
+/// assert_eq!(prompt.variables(), vec!["name", "age"]);
+/// assert_eq!(
+///     prompt.format(prompt_args! { "name" => "Ana", "age" => 30 }).unwrap(),
+///     "Hello Ana, you are 30"
+/// );
+///
+/// let prompt = prompt_template!("Hello {{ name | upper }}", format = jinja2);
+/// assert_eq!(prompt.variables(), vec!["name"]);
+/// ```
+///
+/// See `tests/ui` for the templates that fail to compile, and their errors.
+#[proc_macro]
+pub fn prompt_template(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as TemplateInput);
+    expand_template(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct TemplateInput {
+    template: LitStr,
+    format: Option<Ident>,
+}
+
+impl Parse for TemplateInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let template = input.parse()?;
+        let mut format = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key: Ident = input.parse()?;
+            if key != "format" {
+                return Err(Error::new_spanned(key, "expected `format = ...`"));
+            }
+            input.parse::<Token![=]>()?;
+            format = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(Self { template, format })
+    }
+}
+
+fn expand_template(input: TemplateInput) -> Result<TokenStream2, Error> {
+    let (format, format_tokens) = match &input.format {
+        None => (
+            template::Format::FString,
+            quote!(::langchain_rust::prompt::TemplateFormat::FString),
+        ),
+        Some(format) if format == "fstring" => (
+            template::Format::FString,
+            quote!(::langchain_rust::prompt::TemplateFormat::FString),
+        ),
+        Some(format) if format == "jinja2" => (
+            template::Format::Jinja2,
+            quote!(::langchain_rust::prompt::TemplateFormat::Jinja2),
+        ),
+        Some(format) => return Err(Error::new_spanned(format, "expected `fstring` or `jinja2`")),
+    };
+    let template = &input.template;
+    let variables = template::variables(&template.value(), &format)
+        .map_err(|reason| Error::new_spanned(template, format!("invalid template: {}", reason)))?;
+    Ok(quote! {
+        ::langchain_rust::prompt::PromptTemplate::new(
+            ::std::string::String::from(#template),
+            ::std::vec![#(::std::string::String::from(#variables)),*],
+            #format_tokens,
+        )
+    })
+}
+
+// Whether the type is spelled `Option<T>`, which is all a derive macro can tell.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
@@ -0,0 +1,211 @@
+//! Compile-time checks of template literals for `prompt_template!`, following the rules of
+//! the template parser of `langchain_rust::prompt`, whose FString scanner they share. Format
+//! specs and date formats are left to the runtime, which validates them when formatting.
+
+use langchain_rust_fstring::{
+    is_env_name, is_identifier, is_index, is_path, Scanner, Tag, MAX_BLOCK_DEPTH,
+};
+
+const FSTRING_FILTERS: &[&str] = &["join", "lower", "trim", "truncate", "upper"];
+
+/// The template formats `prompt_template!` can check.
+pub(crate) enum Format {
+    FString,
+    Jinja2,
+}
+
+/// Returns the deduplicated variables of the template, in order of first appearance, or why
+/// it's invalid.
+pub(crate) fn variables(template: &str, format: &Format) -> Result<Vec<String>, String> {
+    let variables = match format {
+        Format::FString => fstring_variables(template)?,
+        Format::Jinja2 => jinja2_variables(template)?,
+    };
+    // `{name}` and `{Name}` in the same template is a typo rather than two variables
+    for (i, variable) in variables.iter().enumerate() {
+        if let Some(other) = variables[..i]
+            .iter()
+            .find(|other| other.to_lowercase() == variable.to_lowercase())
+        {
+            return Err(format!(
+                "variables `{}` and `{}` differ only in case",
+                other, variable
+            ));
+        }
+    }
+    Ok(variables)
+}
+
+fn fstring_variables(template: &str) -> Result<Vec<String>, String> {
+    let mut variables = Vec::new();
+    let mut blocks = Vec::new();
+    let mut positional: Option<bool> = None;
+    for tag in Scanner::new(template) {
+        let (span, tag) = tag.map_err(|e| e.to_string())?;
+        let i = span.start;
+        let Tag::Placeholder(placeholder) = tag else {
+            continue;
+        };
+        let body = placeholder.trim();
+        let body = match body.split_once('|') {
+            Some((name, filters)) => {
+                check_filters(filters).map_err(|reason| format!("{} at {}", reason, i))?;
+                name.trim()
+            }
+            None => body,
+        };
+        let path = match body {
+            "#else" => {
+                if blocks.is_empty() {
+                    return Err(format!(
+                        "`{{#else}}` outside of an `{{#if}}` block at {}",
+                        i
+                    ));
+                }
+                None
+            }
+            "/if" => {
+                blocks
+                    .pop()
+                    .ok_or_else(|| format!("`{{/if}}` without an `{{#if}}` block at {}", i))?;
+                None
+            }
+            _ if body.strip_prefix("env:").is_some_and(is_env_name) => None,
+            _ => match body.strip_prefix("#if ").map(str::trim) {
+                Some(condition) if is_path(condition) => {
+                    if blocks.len() == MAX_BLOCK_DEPTH {
+                        return Err(format!(
+                            "conditional blocks can only be nested one level deep at {}",
+                            i
+                        ));
+                    }
+                    blocks.push(i);
+                    Some(condition)
+                }
+                _ => {
+                    let name = body.split_once(':').map_or(body, |(name, _)| name.trim());
+                    if !is_path(name) && !is_index(name) {
+                        return Err(format!(
+                            "invalid placeholder `{{{}}}` at {}",
+                            placeholder, i
+                        ));
+                    }
+                    Some(name)
+                }
+            },
+        };
+        if let Some(path) = path {
+            let is_positional = is_index(path);
+            if *positional.get_or_insert(is_positional) != is_positional {
+                return Err(format!(
+                    "positional and named placeholders can't be mixed at {}",
+                    i
+                ));
+            }
+            push_unique(&mut variables, path.split('.').next().unwrap_or(path));
+        }
+    }
+    if let Some(position) = blocks.pop() {
+        return Err(format!("unclosed `{{#if}}` block at {}", position));
+    }
+    Ok(variables)
+}
+
+// Checks the names of the filters; their arguments are checked by the runtime.
+fn check_filters(filters: &str) -> Result<(), String> {
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in filters.char_indices().chain([(filters.len(), '|')]) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '|' if !quoted => {
+                let filter = &filters[start..i];
+                let name = filter.split(':').next().unwrap_or_default().trim();
+                if !FSTRING_FILTERS.contains(&name) {
+                    return Err(format!("unknown filter `{}`", name));
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn jinja2_variables(template: &str) -> Result<Vec<String>, String> {
+    let mut variables = Vec::new();
+    let mut offset = 0;
+    // the targets of each open loop, which like `loop` aren't variables of the template
+    let mut loops: Vec<Vec<&str>> = Vec::new();
+    let is_local = |loops: &[Vec<&str>], name: &str| {
+        !loops.is_empty() && (name == "loop" || loops.iter().flatten().any(|t| *t == name))
+    };
+    while let Some(start) = template[offset..].find('{').map(|p| p + offset) {
+        let close = match template[start..].get(..2) {
+            Some("{{") => "}}",
+            Some("{%") => "%}",
+            Some("{#") => "#}",
+            _ => {
+                offset = start + 1;
+                continue;
+            }
+        };
+        let body_start = start + 2;
+        let end = template[body_start..]
+            .find(close)
+            .map(|p| p + body_start)
+            .ok_or_else(|| format!("unclosed `{}` at {}", &template[start..body_start], start))?;
+        if close == "}}" {
+            let expression = template[body_start..end].trim();
+            let name = expression
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or_default();
+            if !is_identifier(name) {
+                return Err(format!(
+                    "invalid expression `{{{{{}}}}}` at {}",
+                    &template[body_start..end],
+                    start
+                ));
+            }
+            if !is_local(&loops, name) {
+                push_unique(&mut variables, name);
+            }
+        } else if close == "%}" {
+            let tag =
+                template[body_start..end].trim_matches(|c: char| c.is_whitespace() || c == '-');
+            if tag == "endfor" {
+                loops.pop();
+            } else if let Some((targets, sequence)) = tag
+                .strip_prefix("for ")
+                .and_then(|tag| tag.split_once(" in "))
+            {
+                let sequence = sequence
+                    .trim_start()
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .next()
+                    .unwrap_or_default();
+                if is_identifier(sequence) && !is_local(&loops, sequence) {
+                    push_unique(&mut variables, sequence);
+                }
+                loops.push(
+                    targets
+                        .split(',')
+                        .map(|target| target.trim().trim_matches(['(', ')']))
+                        .collect(),
+                );
+            }
+        }
+        offset = end + 2;
+    }
+    Ok(variables)
+}
+
+fn push_unique(variables: &mut Vec<String>, name: &str) {
+    if !variables.iter().any(|v| v == name) {
+        variables.push(name.to_string());
+    }
+}
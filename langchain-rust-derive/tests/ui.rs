@@ -0,0 +1,7 @@
+// Inputs the macros must reject, with the errors they report in `tests/ui/*.stderr`. Run
+// with `TRYBUILD=overwrite` to update the errors after changing them.
+#[test]
+fn test_ui() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
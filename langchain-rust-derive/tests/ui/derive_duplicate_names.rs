@@ -0,0 +1,10 @@
+use langchain_rust::prompt::IntoPromptArgs;
+
+#[derive(IntoPromptArgs)]
+struct Args {
+    name: String,
+    #[prompt(rename = "name")]
+    nickname: String,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use langchain_rust::prompt::IntoPromptArgs;
+
+#[derive(IntoPromptArgs)]
+struct Args(String, u32);
+
+fn main() {}
@@ -0,0 +1,9 @@
+use langchain_rust::prompt::IntoPromptArgs;
+
+#[derive(IntoPromptArgs)]
+struct Args {
+    #[prompt(display)]
+    items: Vec<String>,
+}
+
+fn main() {}
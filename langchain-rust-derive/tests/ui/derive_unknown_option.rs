@@ -0,0 +1,9 @@
+use langchain_rust::prompt::IntoPromptArgs;
+
+#[derive(IntoPromptArgs)]
+struct Args {
+    #[prompt(flatten)]
+    name: String,
+}
+
+fn main() {}
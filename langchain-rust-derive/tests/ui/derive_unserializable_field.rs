@@ -0,0 +1,10 @@
+use langchain_rust::prompt::IntoPromptArgs;
+
+struct Connection;
+
+#[derive(IntoPromptArgs)]
+struct Args {
+    connection: Connection,
+}
+
+fn main() {}
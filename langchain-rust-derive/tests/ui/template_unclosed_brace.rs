@@ -0,0 +1,6 @@
+use langchain_rust::prompt::prompt_template;
+
+fn main() {
+    let _ = prompt_template!("Hello {name");
+    let _ = prompt_template!("Hello {{ name }", format = jinja2);
+}
@@ -0,0 +1,5 @@
+use langchain_rust::prompt::prompt_template;
+
+fn main() {
+    let _ = prompt_template!("Points: {points|bullets}");
+}
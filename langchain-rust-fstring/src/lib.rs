@@ -0,0 +1,210 @@
+//! The scanner of FString templates, shared by the template parser of `langchain-rust` and
+//! the compile-time checks of `langchain-rust-derive`, so both split templates the same way.
+//!
+//! The scanner only finds the tags of a template: escaped braces, comments and placeholders.
+//! What a placeholder means, a variable, a block tag or an environment variable, is left to
+//! its callers.
+
+use std::{fmt, ops::Range};
+
+/// How deep conditional blocks can be nested, a block inside a block at most.
+pub const MAX_BLOCK_DEPTH: usize = 2;
+
+/// A tag of an FString template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag<'a> {
+    /// `{{` or `}}`, which render as a single brace.
+    EscapedBrace,
+    /// A `{# ... #}` comment, which can span lines and hold braces.
+    Comment,
+    /// A `{...}` placeholder, with the text between its braces.
+    Placeholder(&'a str),
+}
+
+/// Why an FString template couldn't be scanned, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanError {
+    pub reason: &'static str,
+    pub span: Range<usize>,
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.reason, self.span.start)
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+/// Iterates over the tags of an FString template with their spans, in order. The text
+/// between them is literal. Scanning stops at the first error.
+///
+/// # Usage
+/// ```rust
+/// use langchain_rust_fstring::{Scanner, Tag};
+///
+/// let tags: Vec<_> = Scanner::new("{{x}} {# note #}{name}").collect::<Result<_, _>>().unwrap();
+/// assert_eq!(
+///     tags,
+///     vec![
+///         (0..2, Tag::EscapedBrace),
+///         (3..5, Tag::EscapedBrace),
+///         (6..16, Tag::Comment),
+///         (16..22, Tag::Placeholder("name")),
+///     ]
+/// );
+/// ```
+pub struct Scanner<'a> {
+    template: &'a str,
+    position: usize,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(template: &'a str) -> Self {
+        Self {
+            template,
+            position: 0,
+        }
+    }
+
+    fn fail(
+        &mut self,
+        reason: &'static str,
+        span: Range<usize>,
+    ) -> Option<<Self as Iterator>::Item> {
+        self.position = self.template.len();
+        Some(Err(ScanError { reason, span }))
+    }
+}
+
+impl<'a> Iterator for Scanner<'a> {
+    type Item = Result<(Range<usize>, Tag<'a>), ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let template = self.template;
+        let bytes = template.as_bytes();
+        let start = self.position + template[self.position..].find(['{', '}'])?;
+        let (end, tag) = match bytes[start] {
+            b'{' | b'}' if bytes.get(start + 1) == Some(&bytes[start]) => {
+                (start + 2, Tag::EscapedBrace)
+            }
+            b'{' if bytes.get(start + 1) == Some(&b'#')
+                && !is_block_tag(&template[start + 2..]) =>
+            {
+                match template[start + 2..].find("#}") {
+                    Some(p) => (start + 2 + p + 2, Tag::Comment),
+                    None => return self.fail("unclosed comment", start..template.len()),
+                }
+            }
+            b'{' => match placeholder_end(template, start) {
+                Some(close) => (close + 1, Tag::Placeholder(&template[start + 1..close])),
+                None => return self.fail("unclosed placeholder", start..template.len()),
+            },
+            _ => return self.fail("single `}` must be escaped as `}}`", start..start + 1),
+        };
+        self.position = end;
+        Some(Ok((start..end, tag)))
+    }
+}
+
+/// Returns the position of the `}` closing the placeholder opened at `open`. Once a pipe is
+/// seen, braces in double-quoted filter arguments don't close it.
+pub fn placeholder_end(template: &str, open: usize) -> Option<usize> {
+    let bytes = template.as_bytes();
+    let (mut piped, mut quoted) = (false, false);
+    let mut i = open + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if quoted => i += 1,
+            b'"' if piped => quoted = !quoted,
+            b'|' if !quoted => piped = true,
+            b'}' if !quoted => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Whether the text after a `{#` is a block tag, `{#if ...}` or `{#else}`, rather than a
+/// comment.
+pub fn is_block_tag(rest: &str) -> bool {
+    rest.starts_with("if ")
+        || rest
+            .strip_prefix("else")
+            .is_some_and(|rest| rest.trim_start().starts_with('}'))
+}
+
+/// Whether `name` is an identifier followed by any number of `.key` or `.index` segments.
+pub fn is_path(name: &str) -> bool {
+    let mut segments = name.split('.');
+    segments.next().is_some_and(is_identifier) && segments.all(|s| is_identifier(s) || is_index(s))
+}
+
+/// Whether `name` is the position of a positional placeholder, like `0` in `{0}`.
+pub fn is_index(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_digit())
+}
+
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => chars.all(|c| c.is_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+/// Whether `name` can be the name of an environment variable in an `{env:NAME}` placeholder.
+pub fn is_env_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(template: &str) -> Result<Vec<(Range<usize>, Tag<'_>)>, ScanError> {
+        Scanner::new(template).collect()
+    }
+
+    #[test]
+    fn test_scanner() {
+        assert_eq!(
+            scan(r#"{#if a}{a|join:"}"}{#else}{# {b} #}{/if}"#).unwrap(),
+            vec![
+                (0..7, Tag::Placeholder("#if a")),
+                (7..19, Tag::Placeholder(r#"a|join:"}""#)),
+                (19..26, Tag::Placeholder("#else")),
+                (26..35, Tag::Comment),
+                (35..40, Tag::Placeholder("/if")),
+            ]
+        );
+        assert_eq!(scan("no tags").unwrap(), vec![]);
+
+        for (template, reason, span) in [
+            ("Hi {name", "unclosed placeholder", 3..8),
+            ("{# note", "unclosed comment", 0..7),
+            ("a } b", "single `}` must be escaped as `}}`", 2..3),
+        ] {
+            assert_eq!(
+                scan(template),
+                Err(ScanError { reason, span }),
+                "{}",
+                template
+            );
+        }
+        // nothing is scanned after an error
+        assert_eq!(Scanner::new("} {a}").filter(Result::is_ok).count(), 0);
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(placeholder_end(r#"{a|join:"}"} x}"#, 0), Some(11));
+        assert_eq!(placeholder_end(r#"{"a"}"#, 0), Some(4));
+        assert!(is_path("user.addresses.0.city") && !is_path("user.") && !is_path("0.a"));
+        assert!(is_index("12") && !is_index(""));
+        assert!(is_env_name("API_KEY") && !is_env_name("1KEY") && !is_env_name("clé"));
+        assert!(is_block_tag("else }") && is_block_tag("if a}") && !is_block_tag("elsewhere #}"));
+    }
+}
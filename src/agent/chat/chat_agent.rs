@@ -7,10 +7,7 @@ use crate::{
     agent::{agent::Agent, chat::prompt::FORMAT_INSTRUCTIONS, AgentError},
     chain::chain_trait::Chain,
     message_formatter,
-    prompt::{
-        HumanMessagePromptTemplate, MessageFormatterStruct, MessageOrTemplate, PromptArgs,
-        PromptFromatter,
-    },
+    prompt::{HumanMessagePromptTemplate, MessageFormatterStruct, MessageOrTemplate, PromptArgs},
     prompt_args,
     schemas::{
         agent::{AgentAction, AgentEvent},
@@ -45,25 +42,19 @@ impl ConversationalAgent {
             .collect::<Vec<_>>()
             .join(", ");
 
-        let sufix_prompt = template_jinja2!(suffix, "tools", "format_instructions");
+        let format_instructions = template_jinja2!(FORMAT_INSTRUCTIONS, "tool_names")
+            .format(prompt_args! { "tool_names" => tool_names })?;
 
-        let input_variables_fstring = prompt_args! {
-            "tools" => tool_string,
-            "format_instructions" => FORMAT_INSTRUCTIONS,
-            "tool_names"=>tool_names
-        };
+        let sufix_prompt = template_jinja2!(suffix, "tools", "format_instructions", "input")
+            .partial(prompt_args! {
+                "tools" => tool_string,
+                "format_instructions" => format_instructions,
+            });
 
-        let sufix_prompt = sufix_prompt.format(input_variables_fstring)?;
         let formatter = message_formatter![
             MessageOrTemplate::Message(Message::new_system_message(prefix)),
             MessageOrTemplate::MessagesPlaceholder("chat_history".to_string()),
-            MessageOrTemplate::Template(
-                HumanMessagePromptTemplate::new(template_jinja2!(
-                    &sufix_prompt.to_string(),
-                    "input"
-                ))
-                .into()
-            ),
+            MessageOrTemplate::Template(HumanMessagePromptTemplate::new(sufix_prompt).into()),
             MessageOrTemplate::MessagesPlaceholder("agent_scratchpad".to_string()),
         ];
         return Ok(formatter);
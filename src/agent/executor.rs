@@ -1,4 +1,4 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use serde_json::json;
@@ -13,7 +13,7 @@ use crate::{
         agent::{AgentAction, AgentEvent},
         memory::BaseMemory,
     },
-    tools::Tool,
+    tools::ToolRegistry,
 };
 
 use super::{agent::Agent, AgentError};
@@ -56,13 +56,9 @@ where
         self
     }
 
-    fn get_name_to_tools(&self) -> HashMap<String, Arc<dyn Tool>> {
-        let mut name_to_tool = HashMap::new();
-        for tool in self.agent.get_tools().iter() {
-            log::debug!("Loading Tool:{}", tool.name());
-            name_to_tool.insert(tool.name().trim().replace(" ", "_"), tool.clone());
-        }
-        name_to_tool
+    fn get_tool_registry(&self) -> Result<ToolRegistry, AgentError> {
+        ToolRegistry::from_tools(self.agent.get_tools())
+            .map_err(|e| AgentError::ToolError(e.to_string()))
     }
 }
 
@@ -73,7 +69,9 @@ where
 {
     async fn call(&self, input_variables: PromptArgs) -> Result<GenerateResult, ChainError> {
         let mut input_variables = input_variables.clone();
-        let name_to_tools = self.get_name_to_tools();
+        let tools = self
+            .get_tool_registry()
+            .map_err(|e| ChainError::AgentError(e.to_string()))?;
         let mut steps: Vec<(AgentAction, String)> = Vec::new();
         log::debug!("steps: {:?}", steps);
         if let Some(memory) = &self.memory {
@@ -98,7 +96,7 @@ where
                 AgentEvent::Action(actions) => {
                     for action in actions {
                         log::debug!("Action: {:?}", action.tool_input);
-                        let tool = name_to_tools
+                        let tool = tools
                             .get(&action.tool)
                             .ok_or_else(|| {
                                 AgentError::ToolError(format!("Tool {} not found", action.tool))
@@ -0,0 +1,2 @@
+mod prompt;
+pub use prompt::*;
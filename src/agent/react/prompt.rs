@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use crate::{
+    prompt::{PromptArgs, PromptError, PromptFromatter, PromptTemplate, TemplateFormat},
+    schemas::agent::AgentAction,
+    tools::{render_tools, Tool},
+};
+
+pub const REACT_TEMPLATE: &str = r#"Answer the following questions as best you can. You have access to the following tools:
+
+{tools}
+
+Use the following format:
+
+Question: the input question you must answer
+Thought: you should always think about what to do
+Action: the action to take, should be one of [{tool_names}]
+Action Input: the input to the action
+Observation: the result of the action
+... (this Thought/Action/Action Input/Observation can repeat N times)
+Thought: I now know the final answer
+Final Answer: the final answer to the original input question
+
+Begin!
+
+Question: {input}
+Thought:{agent_scratchpad}"#;
+
+const REQUIRED_PLACEHOLDERS: [&str; 4] = ["tools", "tool_names", "input", "agent_scratchpad"];
+
+/// Struct `AgentPromptTemplate` is a ReAct prompt, with the `{tools}` and `{tool_names}`
+/// placeholders bound to the descriptions and names of its tools, leaving `{input}` and
+/// `{agent_scratchpad}`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = create_react_prompt(&tools);
+/// let text = prompt.format_with_steps(prompt_args! { "input" => question }, &steps)?;
+/// ```
+#[derive(Clone)]
+pub struct AgentPromptTemplate {
+    prompt: PromptTemplate,
+}
+
+impl AgentPromptTemplate {
+    /// Creates a ReAct prompt from a custom template.
+    ///
+    /// # Errors
+    /// Returns `PromptError::MissingPlaceholders` if the template lacks any of the `{tools}`,
+    /// `{tool_names}`, `{input}` and `{agent_scratchpad}` placeholders.
+    pub fn new(template: PromptTemplate, tools: &[Arc<dyn Tool>]) -> Result<Self, PromptError> {
+        let missing: Vec<String> = REQUIRED_PLACEHOLDERS
+            .iter()
+            .filter(|placeholder| {
+                !template.variable_names().iter().any(|v| v == *placeholder)
+                    && !template.partial_variables().contains_key(**placeholder)
+            })
+            .map(|placeholder| placeholder.to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Err(PromptError::MissingPlaceholders(missing));
+        }
+
+        Ok(Self {
+            prompt: template.bind(render_tools(tools)),
+        })
+    }
+
+    /// Renders the steps taken so far as `Thought/Action/Action Input/Observation` lines,
+    /// ending with a new `Thought:` for the model to continue.
+    ///
+    /// An action's log, holding the model's thought and action, is rendered as is. Actions
+    /// without a log are rendered from their tool and input.
+    pub fn format_scratchpad(steps: &[(AgentAction, String)]) -> String {
+        let mut scratchpad = String::new();
+        for (action, observation) in steps {
+            if action.log.is_empty() {
+                scratchpad.push_str(&format!(
+                    "\nAction: {}\nAction Input: {}",
+                    action.tool, action.tool_input
+                ));
+            } else {
+                scratchpad.push_str(&action.log);
+            }
+            scratchpad.push_str(&format!("\nObservation: {}\nThought: ", observation));
+        }
+        scratchpad
+    }
+
+    /// Formats the prompt with the scratchpad of the steps.
+    pub fn format_with_steps(
+        &self,
+        input_variables: PromptArgs,
+        steps: &[(AgentAction, String)],
+    ) -> Result<String, PromptError> {
+        let mut input_variables = input_variables;
+        input_variables.insert(
+            "agent_scratchpad".to_string(),
+            Self::format_scratchpad(steps).into(),
+        );
+        self.prompt.format(input_variables)
+    }
+
+    pub fn prompt(&self) -> &PromptTemplate {
+        &self.prompt
+    }
+}
+
+impl PromptFromatter for AgentPromptTemplate {
+    fn template(&self) -> String {
+        self.prompt.template()
+    }
+
+    fn variables(&self) -> Vec<String> {
+        self.prompt.variables()
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        self.prompt.format(input_variables)
+    }
+}
+
+/// Creates an `AgentPromptTemplate` with the `REACT_TEMPLATE` for the tools.
+pub fn create_react_prompt(tools: &[Arc<dyn Tool>]) -> AgentPromptTemplate {
+    let template = PromptTemplate::from_template(REACT_TEMPLATE, TemplateFormat::FString)
+        .expect("the ReAct template is valid");
+    AgentPromptTemplate::new(template, tools).expect("the ReAct template has every placeholder")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use async_trait::async_trait;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::prompt_args;
+
+    struct Calculator {}
+
+    #[async_trait]
+    impl Tool for Calculator {
+        fn name(&self) -> String {
+            "calculator".to_string()
+        }
+
+        fn description(&self) -> String {
+            "Computes arithmetic expressions".to_string()
+        }
+
+        async fn run(&self, _input: Value) -> Result<String, Box<dyn Error>> {
+            Ok("4".to_string())
+        }
+    }
+
+    fn tools() -> Vec<Arc<dyn Tool>> {
+        vec![Arc::new(Calculator {})]
+    }
+
+    #[test]
+    fn test_react_prompt() {
+        let prompt = create_react_prompt(&tools());
+        assert_eq!(prompt.variables(), vec!["input", "agent_scratchpad"]);
+
+        let steps = vec![
+            (
+                AgentAction {
+                    tool: "calculator".into(),
+                    tool_input: "2 + 2".into(),
+                    log: " I should compute it\nAction: calculator\nAction Input: 2 + 2".into(),
+                },
+                "4".to_string(),
+            ),
+            (
+                AgentAction {
+                    tool: "calculator".into(),
+                    tool_input: "4 * 2".into(),
+                    log: String::new(),
+                },
+                "8".to_string(),
+            ),
+        ];
+        let text = prompt
+            .format_with_steps(prompt_args! { "input" => "What is (2 + 2) * 2?" }, &steps)
+            .unwrap();
+        assert!(text.contains("calculator: Computes arithmetic expressions\n"));
+        assert!(text.contains("should be one of [calculator]\n"));
+        assert!(text.ends_with(
+            "Question: What is (2 + 2) * 2?\nThought: I should compute it\nAction: calculator\nAction Input: 2 + 2\nObservation: 4\nThought: \nAction: calculator\nAction Input: 4 * 2\nObservation: 8\nThought: "
+        ));
+    }
+
+    #[test]
+    fn test_react_prompt_missing_placeholders() {
+        let template =
+            PromptTemplate::from_template("{tools}\n{input}", TemplateFormat::FString).unwrap();
+        match AgentPromptTemplate::new(template, &tools()) {
+            Err(PromptError::MissingPlaceholders(missing)) => {
+                assert_eq!(missing, vec!["tool_names", "agent_scratchpad"])
+            }
+            _ => panic!("expected missing placeholders"),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::{Mutex, PoisonError};
+
+use crate::{
+    chain::ChainError,
+    language_models::{LLMError, TokenUsage},
+    prompt::PromptArgs,
+    schemas::Message,
+};
+
+use super::{CallbackHandler, CallbackResult};
+
+/// An event recorded by `CollectingCallbackHandler`. Errors are kept as their message.
+#[derive(Debug, Clone)]
+pub enum CallbackEvent {
+    PromptFormatted {
+        template_name: String,
+        rendered: String,
+    },
+    LLMStart {
+        messages: Vec<Message>,
+    },
+    LLMEnd {
+        generation: String,
+        usage: Option<TokenUsage>,
+    },
+    LLMError(String),
+    ChainStart {
+        chain_name: String,
+        inputs: PromptArgs,
+    },
+    ChainEnd {
+        chain_name: String,
+        output: String,
+    },
+    ChainError {
+        chain_name: String,
+        error: String,
+    },
+}
+
+impl CallbackEvent {
+    /// Returns the name of the template or chain of the event.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            CallbackEvent::PromptFormatted { template_name, .. } => Some(template_name),
+            CallbackEvent::ChainStart { chain_name, .. }
+            | CallbackEvent::ChainEnd { chain_name, .. }
+            | CallbackEvent::ChainError { chain_name, .. } => Some(chain_name),
+            _ => None,
+        }
+    }
+}
+
+/// Struct `CollectingCallbackHandler` records every event it's notified of, for tests.
+#[derive(Debug, Default)]
+pub struct CollectingCallbackHandler {
+    events: Mutex<Vec<CallbackEvent>>,
+}
+
+impl CollectingCallbackHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<CallbackEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn clear(&self) {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    fn record(&self, event: CallbackEvent) -> CallbackResult {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(event);
+        Ok(())
+    }
+}
+
+impl CallbackHandler for CollectingCallbackHandler {
+    fn on_prompt_formatted(&self, template_name: &str, rendered: &str) -> CallbackResult {
+        self.record(CallbackEvent::PromptFormatted {
+            template_name: template_name.to_string(),
+            rendered: rendered.to_string(),
+        })
+    }
+
+    fn on_llm_start(&self, messages: &[Message]) -> CallbackResult {
+        self.record(CallbackEvent::LLMStart {
+            messages: messages.to_vec(),
+        })
+    }
+
+    fn on_llm_end(&self, generation: &str, usage: Option<&TokenUsage>) -> CallbackResult {
+        self.record(CallbackEvent::LLMEnd {
+            generation: generation.to_string(),
+            usage: usage.cloned(),
+        })
+    }
+
+    fn on_llm_error(&self, error: &LLMError) -> CallbackResult {
+        self.record(CallbackEvent::LLMError(error.to_string()))
+    }
+
+    fn on_chain_start(&self, chain_name: &str, inputs: &PromptArgs) -> CallbackResult {
+        self.record(CallbackEvent::ChainStart {
+            chain_name: chain_name.to_string(),
+            inputs: inputs.clone(),
+        })
+    }
+
+    fn on_chain_end(&self, chain_name: &str, output: &str) -> CallbackResult {
+        self.record(CallbackEvent::ChainEnd {
+            chain_name: chain_name.to_string(),
+            output: output.to_string(),
+        })
+    }
+
+    fn on_chain_error(&self, chain_name: &str, error: &ChainError) -> CallbackResult {
+        self.record(CallbackEvent::ChainError {
+            chain_name: chain_name.to_string(),
+            error: error.to_string(),
+        })
+    }
+}
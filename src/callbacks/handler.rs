@@ -0,0 +1,196 @@
+use std::{
+    error::Error,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use crate::{
+    chain::ChainError,
+    language_models::{LLMError, TokenUsage},
+    prompt::PromptArgs,
+    schemas::Message,
+};
+
+pub type CallbackResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+/// Trait `CallbackHandler` is notified of the steps of prompts, models and chains. Every hook
+/// does nothing by default.
+///
+/// Errors returned by a hook, and its panics, are logged and never stop the execution.
+pub trait CallbackHandler: Send + Sync {
+    /// Called with the text of a prompt once formatted.
+    fn on_prompt_formatted(&self, _template_name: &str, _rendered: &str) -> CallbackResult {
+        Ok(())
+    }
+
+    /// Called with the messages sent to a model.
+    fn on_llm_start(&self, _messages: &[Message]) -> CallbackResult {
+        Ok(())
+    }
+
+    /// Called with the generation of a model and its token usage, if reported.
+    fn on_llm_end(&self, _generation: &str, _usage: Option<&TokenUsage>) -> CallbackResult {
+        Ok(())
+    }
+
+    fn on_llm_error(&self, _error: &LLMError) -> CallbackResult {
+        Ok(())
+    }
+
+    fn on_chain_start(&self, _chain_name: &str, _inputs: &PromptArgs) -> CallbackResult {
+        Ok(())
+    }
+
+    fn on_chain_end(&self, _chain_name: &str, _output: &str) -> CallbackResult {
+        Ok(())
+    }
+
+    fn on_chain_error(&self, _chain_name: &str, _error: &ChainError) -> CallbackResult {
+        Ok(())
+    }
+}
+
+static GLOBAL_HANDLERS: RwLock<Vec<Arc<dyn CallbackHandler>>> = RwLock::new(Vec::new());
+
+// Held by tests that add or clear global handlers, since they're shared by every test.
+#[cfg(test)]
+pub(crate) static GLOBAL_HANDLERS_TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Registers a handler notified by every chain and prompt, along with their own handlers.
+pub fn add_global_handler(handler: Arc<dyn CallbackHandler>) {
+    GLOBAL_HANDLERS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(handler);
+}
+
+/// Removes the handlers registered with `add_global_handler`.
+pub fn clear_global_handlers() {
+    GLOBAL_HANDLERS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+/// Struct `Callbacks` is a list of handlers, notified after the global handlers.
+///
+/// # Usage
+/// ```rust,ignore
+/// let collector = Arc::new(CollectingCallbackHandler::new());
+/// let chain = LLMChainBuilder::new()
+///     .prompt(prompt)
+///     .llm(llm)
+///     .callback_handler(collector.clone())
+///     .build()?;
+/// chain.invoke(input).await?;
+/// println!("{:?}", collector.events());
+/// ```
+#[derive(Clone, Default)]
+pub struct Callbacks {
+    handlers: Vec<Arc<dyn CallbackHandler>>,
+}
+
+impl Callbacks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_handler(mut self, handler: Arc<dyn CallbackHandler>) -> Self {
+        self.handlers.push(handler);
+        self
+    }
+
+    pub fn add_handler(&mut self, handler: Arc<dyn CallbackHandler>) {
+        self.handlers.push(handler);
+    }
+
+    pub fn handlers(&self) -> &[Arc<dyn CallbackHandler>] {
+        &self.handlers
+    }
+
+    /// Notifies the global handlers, then these handlers, logging their errors and panics.
+    pub fn emit<F>(&self, event: &str, notify: F)
+    where
+        F: Fn(&dyn CallbackHandler) -> CallbackResult,
+    {
+        // cloned so handlers can register global handlers without a deadlock
+        let global = GLOBAL_HANDLERS
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        for handler in global.iter().chain(&self.handlers) {
+            match catch_unwind(AssertUnwindSafe(|| notify(handler.as_ref()))) {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::warn!("Callback handler failed on {}: {}", event, e),
+                Err(_) => log::warn!("Callback handler panicked on {}", event),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::callbacks::{CallbackEvent, CollectingCallbackHandler};
+
+    struct FailingHandler {}
+
+    impl CallbackHandler for FailingHandler {
+        fn on_prompt_formatted(&self, _template_name: &str, _rendered: &str) -> CallbackResult {
+            Err("cannot record the prompt".into())
+        }
+
+        fn on_chain_end(&self, _chain_name: &str, _output: &str) -> CallbackResult {
+            panic!("handler bug")
+        }
+    }
+
+    #[test]
+    fn test_failing_handlers_are_isolated() {
+        let collector = Arc::new(CollectingCallbackHandler::new());
+        let callbacks = Callbacks::new()
+            .with_handler(Arc::new(FailingHandler {}))
+            .with_handler(collector.clone());
+        callbacks.emit("prompt_formatted", |h| {
+            h.on_prompt_formatted("isolated", "Hello")
+        });
+        callbacks.emit("chain_end", |h| h.on_chain_end("isolated", "Bye"));
+
+        let events: Vec<CallbackEvent> = collector
+            .events()
+            .into_iter()
+            .filter(|event| event.name() == Some("isolated"))
+            .collect();
+        assert!(matches!(
+            &events[..],
+            [
+                CallbackEvent::PromptFormatted { rendered, .. },
+                CallbackEvent::ChainEnd { output, .. },
+            ] if rendered == "Hello" && output == "Bye"
+        ));
+    }
+
+    #[test]
+    fn test_global_handlers() {
+        let _lock = GLOBAL_HANDLERS_TEST_LOCK
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let collector = Arc::new(CollectingCallbackHandler::new());
+        add_global_handler(collector.clone());
+        Callbacks::new().emit("chain_end", |h| h.on_chain_end("global", "Done"));
+        clear_global_handlers();
+        Callbacks::new().emit("chain_end", |h| h.on_chain_end("global", "Again"));
+
+        let outputs: Vec<String> = collector
+            .events()
+            .into_iter()
+            .filter_map(|event| match event {
+                CallbackEvent::ChainEnd { chain_name, output } if chain_name == "global" => {
+                    Some(output)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(outputs, vec!["Done"]);
+    }
+}
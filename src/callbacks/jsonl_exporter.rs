@@ -0,0 +1,133 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{
+    chain::ChainError,
+    language_models::{LLMError, TokenUsage},
+    prompt::PromptArgs,
+    schemas::Message,
+};
+
+use super::{run_tree::RunTree, CallbackHandler, CallbackResult, RunRecord};
+
+/// Struct `JsonlExporter` appends the runs of the chains to a file, a `RunRecord` per line,
+/// in the shape `LangSmithExporter` sends, for machines without access to LangSmith.
+///
+/// Runs are nested by their `RunScope`, so an exporter can be shared between concurrent
+/// executions.
+///
+/// # Usage
+/// ```rust,ignore
+/// let exporter = Arc::new(JsonlExporter::new("runs.jsonl")?);
+/// let chain = LLMChainBuilder::new()
+///     .prompt(prompt)
+///     .llm(llm)
+///     .callback_handler(exporter)
+///     .build()?;
+/// ```
+pub struct JsonlExporter {
+    tree: RunTree,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl JsonlExporter {
+    /// Opens `path` to append runs to, creating it if needed.
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            tree: RunTree::new(),
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    fn write(&self, records: &[RunRecord]) -> CallbackResult {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        for record in records {
+            serde_json::to_writer(&mut *writer, record)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl CallbackHandler for JsonlExporter {
+    fn on_prompt_formatted(&self, template_name: &str, rendered: &str) -> CallbackResult {
+        self.write(&[self.tree.prompt_formatted(template_name, rendered)])
+    }
+
+    fn on_llm_start(&self, messages: &[Message]) -> CallbackResult {
+        self.tree.llm_start(messages);
+        Ok(())
+    }
+
+    fn on_llm_end(&self, generation: &str, usage: Option<&TokenUsage>) -> CallbackResult {
+        self.write(&self.tree.llm_end(generation, usage))
+    }
+
+    fn on_llm_error(&self, error: &LLMError) -> CallbackResult {
+        self.write(&self.tree.llm_error(error.to_string()))
+    }
+
+    fn on_chain_start(&self, chain_name: &str, inputs: &PromptArgs) -> CallbackResult {
+        self.tree.chain_start(chain_name, inputs);
+        Ok(())
+    }
+
+    fn on_chain_end(&self, chain_name: &str, output: &str) -> CallbackResult {
+        self.write(&self.tree.chain_end(chain_name, output))
+    }
+
+    fn on_chain_error(&self, chain_name: &str, error: &ChainError) -> CallbackResult {
+        self.write(&self.tree.chain_error(chain_name, error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        chain::{Chain, LLMChainBuilder},
+        llm::FakeLLM,
+        prompt_args, template_fstring,
+    };
+
+    #[tokio::test]
+    async fn test_jsonl_exporter() {
+        let path = std::env::temp_dir().join(format!("runs-{}.jsonl", std::process::id()));
+        let exporter = Arc::new(JsonlExporter::new(&path).unwrap());
+        let chain = LLMChainBuilder::new()
+            .prompt(template_fstring!("Say {word}", "word"))
+            .llm(FakeLLM::new().with_responses(vec!["hi!"]))
+            .callback_handler(exporter)
+            .build()
+            .unwrap();
+        chain.invoke(prompt_args! { "word" => "hi" }).await.unwrap();
+
+        let records: Vec<RunRecord> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        let [prompt, llm, chain] = &records[..] else {
+            panic!("unexpected runs: {:?}", records);
+        };
+        assert_eq!(prompt.outputs.as_ref().unwrap()["output"], "Say hi");
+        assert_eq!(
+            llm.outputs.as_ref().unwrap()["generations"][0]["text"],
+            "hi!"
+        );
+        assert_eq!(chain.inputs["word"], "hi");
+        assert_eq!(chain.outputs.as_ref().unwrap()["output"], "hi!");
+        for record in [prompt, llm] {
+            assert_eq!(record.parent_run_id.as_ref(), Some(&chain.id));
+        }
+    }
+}
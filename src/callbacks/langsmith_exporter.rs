@@ -0,0 +1,403 @@
+use std::{collections::VecDeque, sync::OnceLock, thread, time::Duration};
+
+use reqwest::Client;
+use serde_json::{json, Value};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
+
+use crate::{
+    chain::ChainError,
+    language_models::{LLMError, TokenUsage},
+    prompt::PromptArgs,
+    schemas::Message,
+};
+
+use super::{run_tree::RunTree, CallbackHandler, CallbackResult, RunRecord};
+
+enum Command {
+    Export(Box<RunRecord>),
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Clone)]
+struct ExportConfig {
+    api_url: String,
+    api_key: String,
+    project: String,
+    batch_size: usize,
+    max_buffered: usize,
+    max_retries: u32,
+    retry_delay: Duration,
+    flush_interval: Duration,
+    timeout: Duration,
+}
+
+/// Struct `LangSmithExporter` sends the runs of the chains to LangSmith: the chains, their
+/// prompts and their model calls, nested as they ran. `JsonlExporter` writes the same runs to
+/// a file instead.
+///
+/// Runs are sent in batches from a background thread, so exporting never fails nor slows
+/// the chains. A batch LangSmith doesn't accept, or doesn't answer within `with_timeout`, is
+/// retried with an exponential backoff, then dropped with a warning; runs are dropped the
+/// same way past `with_max_buffered` while LangSmith is unreachable.
+///
+/// Runs are nested by their `RunScope`, so an exporter can be shared between concurrent
+/// executions.
+///
+/// # Usage
+/// ```rust,ignore
+/// // the api key, endpoint and project are read from `LANGCHAIN_API_KEY`,
+/// // `LANGCHAIN_ENDPOINT` and `LANGCHAIN_PROJECT`
+/// let exporter = Arc::new(LangSmithExporter::new().with_project("rust-service"));
+/// let chain = LLMChainBuilder::new()
+///     .prompt(prompt)
+///     .llm(llm)
+///     .callback_handler(exporter.clone())
+///     .build()?;
+/// chain.invoke(input).await?;
+/// exporter.flush().await;
+/// ```
+pub struct LangSmithExporter {
+    tree: RunTree,
+    config: ExportConfig,
+    sender: OnceLock<mpsc::Sender<Command>>,
+}
+
+impl Default for LangSmithExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LangSmithExporter {
+    pub fn new() -> Self {
+        Self {
+            tree: RunTree::new(),
+            config: ExportConfig {
+                api_url: std::env::var("LANGCHAIN_ENDPOINT")
+                    .unwrap_or_else(|_| "https://api.smith.langchain.com".to_string()),
+                api_key: std::env::var("LANGCHAIN_API_KEY").unwrap_or_default(),
+                project: std::env::var("LANGCHAIN_PROJECT")
+                    .unwrap_or_else(|_| "default".to_string()),
+                batch_size: 100,
+                max_buffered: 1000,
+                max_retries: 3,
+                retry_delay: Duration::from_millis(500),
+                flush_interval: Duration::from_secs(1),
+                timeout: Duration::from_secs(10),
+            },
+            sender: OnceLock::new(),
+        }
+    }
+
+    pub fn with_api_url<S: Into<String>>(mut self, api_url: S) -> Self {
+        self.config.api_url = api_url.into();
+        self
+    }
+
+    pub fn with_api_key<S: Into<String>>(mut self, api_key: S) -> Self {
+        self.config.api_key = api_key.into();
+        self
+    }
+
+    /// Sets the project the runs are recorded in.
+    pub fn with_project<S: Into<String>>(mut self, project: S) -> Self {
+        self.config.project = project.into();
+        self
+    }
+
+    /// Sets the number of runs sent at once, 100 by default.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.config.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets the number of runs kept while they can't be sent, 1000 by default. Runs ended past
+    /// it are dropped, and so are the oldest runs waiting for a batch.
+    pub fn with_max_buffered(mut self, max_buffered: usize) -> Self {
+        self.config.max_buffered = max_buffered.max(1);
+        self
+    }
+
+    /// Sets the retries of a batch and the delay before the first one, doubled at each
+    /// retry, 3 and 500 milliseconds by default.
+    pub fn with_retries(mut self, max_retries: u32, retry_delay: Duration) -> Self {
+        self.config.max_retries = max_retries;
+        self.config.retry_delay = retry_delay;
+        self
+    }
+
+    /// Sets how long runs wait for a full batch before being sent anyway, 1 second by
+    /// default.
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.config.flush_interval = flush_interval;
+        self
+    }
+
+    /// Sets how long LangSmith has to answer a batch before it's retried, 10 seconds by
+    /// default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    /// Waits until the runs ended so far are sent or dropped.
+    pub async fn flush(&self) {
+        let Some(sender) = self.sender.get() else {
+            return;
+        };
+        let (done, flushed) = oneshot::channel();
+        if sender.send(Command::Flush(done)).await.is_ok() {
+            let _ = flushed.await;
+        }
+    }
+
+    fn export(&self, records: Vec<RunRecord>) -> CallbackResult {
+        let sender = self.sender.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel(self.config.max_buffered);
+            let config = self.config.clone();
+            // a thread of its own, so runs are sent whatever the runtime of the caller
+            let spawned = thread::Builder::new()
+                .name("langsmith-exporter".to_string())
+                .spawn(move || {
+                    match tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                    {
+                        Ok(runtime) => runtime.block_on(run_worker(config, receiver)),
+                        Err(e) => log::warn!("Cannot start the LangSmith exporter: {}", e),
+                    }
+                });
+            if let Err(e) = spawned {
+                log::warn!("Cannot start the LangSmith exporter: {}", e);
+            }
+            sender
+        });
+        for record in records {
+            match sender.try_send(Command::Export(Box::new(record))) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    log::warn!("LangSmith export queue is full, dropping run")
+                }
+                Err(TrySendError::Closed(_)) => {
+                    log::warn!("LangSmith exporter stopped, dropping run")
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CallbackHandler for LangSmithExporter {
+    fn on_prompt_formatted(&self, template_name: &str, rendered: &str) -> CallbackResult {
+        self.export(vec![self.tree.prompt_formatted(template_name, rendered)])
+    }
+
+    fn on_llm_start(&self, messages: &[Message]) -> CallbackResult {
+        self.tree.llm_start(messages);
+        Ok(())
+    }
+
+    fn on_llm_end(&self, generation: &str, usage: Option<&TokenUsage>) -> CallbackResult {
+        self.export(self.tree.llm_end(generation, usage))
+    }
+
+    fn on_llm_error(&self, error: &LLMError) -> CallbackResult {
+        self.export(self.tree.llm_error(error.to_string()))
+    }
+
+    fn on_chain_start(&self, chain_name: &str, inputs: &PromptArgs) -> CallbackResult {
+        self.tree.chain_start(chain_name, inputs);
+        Ok(())
+    }
+
+    fn on_chain_end(&self, chain_name: &str, output: &str) -> CallbackResult {
+        self.export(self.tree.chain_end(chain_name, output))
+    }
+
+    fn on_chain_error(&self, chain_name: &str, error: &ChainError) -> CallbackResult {
+        self.export(self.tree.chain_error(chain_name, error.to_string()))
+    }
+}
+
+async fn run_worker(config: ExportConfig, mut receiver: mpsc::Receiver<Command>) {
+    let client = Client::builder()
+        .timeout(config.timeout)
+        .build()
+        .unwrap_or_else(|e| {
+            log::warn!("Cannot set the LangSmith export timeout: {}", e);
+            Client::new()
+        });
+    let mut buffer: VecDeque<RunRecord> = VecDeque::new();
+    loop {
+        let command = if buffer.is_empty() {
+            receiver.recv().await
+        } else {
+            match tokio::time::timeout(config.flush_interval, receiver.recv()).await {
+                Ok(command) => command,
+                Err(_) => {
+                    send_buffer(&client, &config, &mut buffer).await;
+                    continue;
+                }
+            }
+        };
+        match command {
+            Some(Command::Export(record)) => {
+                if buffer.len() == config.max_buffered {
+                    log::warn!("LangSmith export buffer is full, dropping the oldest run");
+                    buffer.pop_front();
+                }
+                buffer.push_back(*record);
+                if buffer.len() >= config.batch_size {
+                    send_buffer(&client, &config, &mut buffer).await;
+                }
+            }
+            Some(Command::Flush(done)) => {
+                send_buffer(&client, &config, &mut buffer).await;
+                let _ = done.send(());
+            }
+            None => {
+                send_buffer(&client, &config, &mut buffer).await;
+                return;
+            }
+        }
+    }
+}
+
+async fn send_buffer(client: &Client, config: &ExportConfig, buffer: &mut VecDeque<RunRecord>) {
+    while !buffer.is_empty() {
+        let batch: Vec<RunRecord> = buffer
+            .drain(..config.batch_size.min(buffer.len()))
+            .collect();
+        send_batch(client, config, &batch).await;
+    }
+}
+
+async fn send_batch(client: &Client, config: &ExportConfig, batch: &[RunRecord]) {
+    let runs: Vec<Value> = batch
+        .iter()
+        .map(|record| {
+            let mut run = json!(record);
+            run["session_name"] = json!(config.project);
+            run
+        })
+        .collect();
+    let body = json!({ "post": runs });
+    let mut delay = config.retry_delay;
+    for attempt in 0..=config.max_retries {
+        let result = client
+            .post(format!(
+                "{}/runs/batch",
+                config.api_url.trim_end_matches('/')
+            ))
+            .header("x-api-key", &config.api_key)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => return,
+            Err(e) if attempt == config.max_retries => {
+                log::warn!(
+                    "Dropping {} runs LangSmith didn't accept after {} attempts: {}",
+                    batch.len(),
+                    attempt + 1,
+                    e
+                );
+            }
+            Err(e) => {
+                log::debug!("Retrying LangSmith export in {:?}: {}", delay, e);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+
+    fn run_chain(exporter: &LangSmithExporter) {
+        exporter
+            .on_chain_start("greet", &PromptArgs::new())
+            .unwrap();
+        exporter.on_prompt_formatted("greet", "Say hi").unwrap();
+        exporter
+            .on_llm_start(&[Message::new_human_message("Say hi")])
+            .unwrap();
+        exporter.on_llm_end("hi!", None).unwrap();
+        exporter.on_chain_end("greet", "hi!").unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_langsmith_exporter() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/runs/batch")
+            .match_header("x-api-key", "key")
+            .match_body(Matcher::PartialJson(json!({
+                "post": [
+                    {"name": "greet", "run_type": "prompt", "session_name": "tests"},
+                    {"name": "llm", "run_type": "llm"},
+                    {"name": "greet", "run_type": "chain", "outputs": {"output": "hi!"}}
+                ]
+            })))
+            .with_status(202)
+            .create_async()
+            .await;
+
+        let exporter = LangSmithExporter::new()
+            .with_api_url(server.url())
+            .with_api_key("key")
+            .with_project("tests");
+        run_chain(&exporter);
+        exporter.flush().await;
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_langsmith_exporter_retries_then_drops() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/runs/batch")
+            .with_status(500)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let exporter = LangSmithExporter::new()
+            .with_api_url(server.url())
+            .with_retries(2, Duration::from_millis(1));
+        run_chain(&exporter);
+        exporter.flush().await;
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_langsmith_exporter_times_out() {
+        // accepts the connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+
+        let exporter = LangSmithExporter::new()
+            .with_api_url(url)
+            .with_timeout(Duration::from_millis(50))
+            .with_retries(1, Duration::from_millis(1));
+        run_chain(&exporter);
+        tokio::time::timeout(Duration::from_secs(5), exporter.flush())
+            .await
+            .expect("the export doesn't wait for the endpoint forever");
+        server.abort();
+    }
+}
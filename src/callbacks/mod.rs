@@ -0,0 +1,24 @@
+mod handler;
+pub use handler::*;
+
+mod collecting;
+pub use collecting::*;
+
+mod run_scope;
+pub use run_scope::*;
+
+mod run_tree;
+pub use run_tree::{RunRecord, RunType};
+
+mod jsonl_exporter;
+pub use jsonl_exporter::*;
+
+#[cfg(feature = "langsmith")]
+mod langsmith_exporter;
+#[cfg(feature = "langsmith")]
+pub use langsmith_exporter::*;
+
+#[cfg(feature = "tracing")]
+mod tracing_handler;
+#[cfg(feature = "tracing")]
+pub use tracing_handler::*;
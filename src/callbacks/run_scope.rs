@@ -0,0 +1,93 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+tokio::task_local! {
+    static CURRENT_SCOPE: Arc<RunScope>;
+}
+
+static NEXT_SCOPE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Struct `RunScope` identifies an execution of a chain, so handlers tracking runs, like
+/// `LangSmithExporter`, can tell concurrent executions apart and nest each in the execution
+/// that started it. `LLMChain` runs every call and stream in a scope of its own; chains
+/// notifying the handlers themselves should do the same.
+///
+/// # Usage
+/// ```rust,ignore
+/// RunScope::new()
+///     .run(async {
+///         callbacks.emit("chain_start", |h| h.on_chain_start("my_chain", &inputs));
+///         // ...
+///     })
+///     .await;
+/// ```
+#[derive(Debug)]
+pub struct RunScope {
+    id: u64,
+    parent: Option<Arc<RunScope>>,
+}
+
+impl RunScope {
+    /// Creates a scope nested in the current one, if any.
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            id: NEXT_SCOPE_ID.fetch_add(1, Ordering::Relaxed),
+            parent: Self::current(),
+        })
+    }
+
+    /// Returns the scope the current future runs in.
+    pub fn current() -> Option<Arc<Self>> {
+        CURRENT_SCOPE.try_with(Arc::clone).ok()
+    }
+
+    /// Returns the id of the scope, unique in the process.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn parent(&self) -> Option<&Arc<Self>> {
+        self.parent.as_ref()
+    }
+
+    /// Runs `future` in the scope.
+    pub async fn run<F: Future>(self: Arc<Self>, future: F) -> F::Output {
+        CURRENT_SCOPE.scope(self, future).await
+    }
+
+    /// Runs `f` in the scope, like polling a stream of the execution.
+    pub fn enter<R, F: FnOnce() -> R>(self: &Arc<Self>, f: F) -> R {
+        CURRENT_SCOPE.sync_scope(self.clone(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_scope() {
+        assert!(RunScope::current().is_none());
+        let outer = RunScope::new();
+        let (outer_id, inner) = outer
+            .clone()
+            .run(async {
+                let inner = RunScope::new();
+                let current = inner.clone().run(async { RunScope::current() }).await;
+                (RunScope::current().unwrap().id(), current.unwrap())
+            })
+            .await;
+        assert_eq!(outer_id, outer.id());
+        assert_eq!(inner.parent().unwrap().id(), outer.id());
+        assert_ne!(inner.id(), outer.id());
+        assert_eq!(
+            outer.enter(|| RunScope::current().unwrap().id()),
+            outer.id()
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+};
+
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{language_models::TokenUsage, prompt::PromptArgs, schemas::Message};
+
+use super::RunScope;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunType {
+    Chain,
+    Llm,
+    Prompt,
+}
+
+/// Struct `RunRecord` is a finished run, in the shape of the LangSmith runs API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunRecord {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_run_id: Option<String>,
+    pub trace_id: String,
+    /// The start times and ids of the ancestors and the run, which LangSmith orders runs by.
+    pub dotted_order: String,
+    pub name: String,
+    pub run_type: RunType,
+    pub inputs: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Names the template of a prompt run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serialized: Option<Value>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+}
+
+/// Struct `RunTree` turns the events of the callback handlers into runs. The runs of each
+/// `RunScope` are tracked apart: a run is the child of the innermost run open in its scope,
+/// or else in the closest scope it's nested in. Events outside of a scope share one.
+#[derive(Default)]
+pub(crate) struct RunTree {
+    // the open runs by the id of their scope, 0 outside of a scope
+    open: Mutex<HashMap<u64, Vec<RunRecord>>>,
+}
+
+impl RunTree {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    fn start(&self, name: &str, run_type: RunType, inputs: Value) {
+        let scope = RunScope::current();
+        let mut open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+        let record = new_record(parent_run(&open, scope.as_deref()), name, run_type, inputs);
+        open.entry(scope_id(scope.as_deref()))
+            .or_default()
+            .push(record);
+    }
+
+    // Ends the innermost open run of the scope matching `is_run`, after the runs it left open.
+    fn end<F>(&self, is_run: F, outputs: Option<Value>, error: Option<String>) -> Vec<RunRecord>
+    where
+        F: Fn(&RunRecord) -> bool,
+    {
+        let id = scope_id(RunScope::current().as_deref());
+        let mut open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(runs) = open.get_mut(&id) else {
+            log::warn!("Run ended without being started");
+            return Vec::new();
+        };
+        let Some(position) = runs.iter().rposition(&is_run) else {
+            log::warn!("Run ended without being started");
+            return Vec::new();
+        };
+        let end_time = Utc::now();
+        let mut records: Vec<RunRecord> = runs
+            .drain(position..)
+            .rev()
+            .map(|record| RunRecord { end_time, ..record })
+            .collect();
+        if runs.is_empty() {
+            open.remove(&id);
+        }
+        if let Some(record) = records.last_mut() {
+            record.outputs = outputs;
+            record.error = error;
+        }
+        records
+    }
+
+    pub(crate) fn prompt_formatted(&self, template_name: &str, rendered: &str) -> RunRecord {
+        let scope = RunScope::current();
+        let open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+        let parent = parent_run(&open, scope.as_deref());
+        let mut record = new_record(parent, template_name, RunType::Prompt, json!({}));
+        record.outputs = Some(json!({ "output": rendered }));
+        record.serialized = Some(json!({ "name": template_name }));
+        record
+    }
+
+    pub(crate) fn llm_start(&self, messages: &[Message]) {
+        self.start("llm", RunType::Llm, json!({ "messages": messages }));
+    }
+
+    pub(crate) fn llm_end(&self, generation: &str, usage: Option<&TokenUsage>) -> Vec<RunRecord> {
+        let outputs = json!({
+            "generations": [{ "text": generation }],
+            "llm_output": { "token_usage": usage },
+        });
+        self.end(|run| run.run_type == RunType::Llm, Some(outputs), None)
+    }
+
+    pub(crate) fn llm_error(&self, error: String) -> Vec<RunRecord> {
+        self.end(|run| run.run_type == RunType::Llm, None, Some(error))
+    }
+
+    pub(crate) fn chain_start(&self, chain_name: &str, inputs: &PromptArgs) {
+        self.start(chain_name, RunType::Chain, json!(inputs));
+    }
+
+    pub(crate) fn chain_end(&self, chain_name: &str, output: &str) -> Vec<RunRecord> {
+        self.end(
+            |run| run.run_type == RunType::Chain && run.name == chain_name,
+            Some(json!({ "output": output })),
+            None,
+        )
+    }
+
+    pub(crate) fn chain_error(&self, chain_name: &str, error: String) -> Vec<RunRecord> {
+        self.end(
+            |run| run.run_type == RunType::Chain && run.name == chain_name,
+            None,
+            Some(error),
+        )
+    }
+}
+
+fn scope_id(scope: Option<&RunScope>) -> u64 {
+    scope.map_or(0, RunScope::id)
+}
+
+// The innermost run open in `scope`, or else in the scopes it's nested in.
+fn parent_run<'a>(
+    open: &'a HashMap<u64, Vec<RunRecord>>,
+    scope: Option<&RunScope>,
+) -> Option<&'a RunRecord> {
+    let Some(scope) = scope else {
+        return open.get(&0).and_then(|runs| runs.last());
+    };
+    let mut current = Some(scope);
+    while let Some(scope) = current {
+        if let Some(run) = open.get(&scope.id()).and_then(|runs| runs.last()) {
+            return Some(run);
+        }
+        current = scope.parent().map(|parent| parent.as_ref());
+    }
+    None
+}
+
+fn new_record(
+    parent: Option<&RunRecord>,
+    name: &str,
+    run_type: RunType,
+    inputs: Value,
+) -> RunRecord {
+    let id = new_run_id();
+    let start_time = Utc::now();
+    let order = format!("{}{}", start_time.format("%Y%m%dT%H%M%S%6fZ"), id);
+    RunRecord {
+        parent_run_id: parent.map(|parent| parent.id.clone()),
+        trace_id: parent.map_or_else(|| id.clone(), |parent| parent.trace_id.clone()),
+        dotted_order: match parent {
+            Some(parent) => format!("{}.{}", parent.dotted_order, order),
+            None => order,
+        },
+        id,
+        name: name.to_string(),
+        run_type,
+        inputs,
+        outputs: None,
+        error: None,
+        serialized: None,
+        start_time,
+        end_time: start_time,
+    }
+}
+
+// A random UUID, version 4.
+fn new_run_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_tree() {
+        let tree = RunTree::new();
+        tree.chain_start("outer", &PromptArgs::new());
+        tree.chain_start("inner", &PromptArgs::new());
+        let prompt = tree.prompt_formatted("inner", "Hi");
+        tree.llm_start(&[Message::new_human_message("Hi")]);
+        let llm = tree.llm_end("Hello", None);
+        let inner = tree.chain_end("inner", "Hello");
+        tree.llm_start(&[]);
+        // the llm run left open is ended along with its chain
+        let outer = tree.chain_error("outer", "failed".to_string());
+
+        let (inner, llm, outer) = (&inner[0], &llm[0], &outer[1]);
+        assert_eq!(outer.parent_run_id, None);
+        assert_eq!(outer.error.as_deref(), Some("failed"));
+        assert_eq!(inner.parent_run_id.as_ref(), Some(&outer.id));
+        assert_eq!(prompt.parent_run_id.as_ref(), Some(&inner.id));
+        assert_eq!(llm.parent_run_id.as_ref(), Some(&inner.id));
+        assert_eq!(
+            llm.outputs.as_ref().unwrap()["generations"][0]["text"],
+            "Hello"
+        );
+        assert!([&inner.trace_id, &llm.trace_id]
+            .iter()
+            .all(|id| **id == outer.id));
+        assert!(llm.dotted_order.starts_with(&inner.dotted_order));
+        assert_eq!(llm.id.len(), 36);
+        assert!(tree.chain_end("outer", "Done").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_tree_scopes() {
+        let tree = RunTree::new();
+        let run = |name: &'static str| {
+            let tree = &tree;
+            RunScope::new().run(async move {
+                tree.chain_start(name, &PromptArgs::new());
+                tokio::task::yield_now().await;
+                tree.llm_start(&[]);
+                tokio::task::yield_now().await;
+                let llm = tree.llm_end(name, None).remove(0);
+                (llm, tree.chain_end(name, name).remove(0))
+            })
+        };
+        // concurrent executions, like the calls of `LLMChain::apply`
+        let ((a_llm, a), (b_llm, b)) = tokio::join!(run("a"), run("b"));
+        assert_eq!(
+            (a.parent_run_id.as_ref(), b.parent_run_id.as_ref()),
+            (None, None)
+        );
+        assert_eq!(a_llm.parent_run_id.as_ref(), Some(&a.id));
+        assert_eq!(b_llm.parent_run_id.as_ref(), Some(&b.id));
+
+        // a run left open doesn't adopt the runs of other executions
+        RunScope::new()
+            .run(async { tree.chain_start("left_open", &PromptArgs::new()) })
+            .await;
+        let (_, next) = run("next").await;
+        assert_eq!(next.parent_run_id, None);
+
+        // an execution started by another is nested in its run
+        let (outer, inner) = RunScope::new()
+            .run(async {
+                tree.chain_start("outer", &PromptArgs::new());
+                let (_, inner) = run("inner").await;
+                (tree.chain_end("outer", "").remove(0), inner)
+            })
+            .await;
+        assert_eq!(inner.parent_run_id.as_ref(), Some(&outer.id));
+        assert_eq!(inner.trace_id, outer.id);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{
+    chain::ChainError,
+    language_models::{LLMError, TokenUsage},
+    prompt::PromptArgs,
+    schemas::Message,
+};
+
+use super::{CallbackHandler, CallbackResult};
+
+/// Struct `TracingCallbackHandler` emits a `tracing` event for every step, under the
+/// `langchain_rust` target. Prompts and generations are emitted at the debug level, errors at
+/// the error level and the rest at the info level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingCallbackHandler {}
+
+impl TracingCallbackHandler {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl CallbackHandler for TracingCallbackHandler {
+    fn on_prompt_formatted(&self, template_name: &str, rendered: &str) -> CallbackResult {
+        tracing::debug!(target: "langchain_rust", template_name, rendered, "prompt formatted");
+        Ok(())
+    }
+
+    fn on_llm_start(&self, messages: &[Message]) -> CallbackResult {
+        tracing::info!(target: "langchain_rust", messages = messages.len(), "llm start");
+        Ok(())
+    }
+
+    fn on_llm_end(&self, generation: &str, usage: Option<&TokenUsage>) -> CallbackResult {
+        tracing::debug!(
+            target: "langchain_rust",
+            generation,
+            prompt_tokens = usage.map(|usage| usage.prompt_tokens),
+            completion_tokens = usage.map(|usage| usage.completion_tokens),
+            total_tokens = usage.map(|usage| usage.total_tokens),
+            "llm end"
+        );
+        Ok(())
+    }
+
+    fn on_llm_error(&self, error: &LLMError) -> CallbackResult {
+        tracing::error!(target: "langchain_rust", error = %error, "llm error");
+        Ok(())
+    }
+
+    fn on_chain_start(&self, chain_name: &str, inputs: &PromptArgs) -> CallbackResult {
+        let mut keys: Vec<&String> = inputs.keys().collect();
+        keys.sort();
+        tracing::info!(target: "langchain_rust", chain_name, inputs = ?keys, "chain start");
+        Ok(())
+    }
+
+    fn on_chain_end(&self, chain_name: &str, output: &str) -> CallbackResult {
+        tracing::info!(target: "langchain_rust", chain_name, output, "chain end");
+        Ok(())
+    }
+
+    fn on_chain_error(&self, chain_name: &str, error: &ChainError) -> CallbackResult {
+        tracing::error!(target: "langchain_rust", chain_name, error = %error, "chain error");
+        Ok(())
+    }
+}
@@ -186,6 +186,12 @@ pub trait Chain: Sync + Send {
         return vec![];
     }
 
+    /// Returns the input keys the chain can't be called without, leaving out the variables of
+    /// its prompt that have a default value or are optional. Defaults to `get_input_keys`.
+    fn get_required_input_keys(&self) -> Vec<String> {
+        self.get_input_keys()
+    }
+
     fn get_output_keys(&self) -> Vec<String> {
         log::info!("Using defualt implementation");
         return vec![
@@ -9,7 +9,7 @@ use crate::{
     language_models::llm::LLM,
     memory::SimpleMemory,
     output_parsers::OutputParser,
-    prompt::HumanMessagePromptTemplate,
+    prompt::{AsyncPrompt, FormatPrompter, HumanMessagePromptTemplate},
     schemas::memory::BaseMemory,
     template_fstring,
 };
@@ -23,6 +23,7 @@ pub struct ConversationalChainBuilder {
     output_key: Option<String>,
     output_parser: Option<Box<dyn OutputParser>>,
     input_key: Option<String>,
+    prompt: Option<Arc<dyn AsyncPrompt>>,
 }
 
 impl ConversationalChainBuilder {
@@ -34,6 +35,7 @@ impl ConversationalChainBuilder {
             output_key: None,
             output_parser: None,
             input_key: None,
+            prompt: None,
         }
     }
 
@@ -57,6 +59,19 @@ impl ConversationalChainBuilder {
         self
     }
 
+    /// Replaces the default prompt, which takes the `history` and `input` variables. The
+    /// prompt gets the input variable and the memory's variables.
+    pub fn prompt<P: Into<Box<dyn FormatPrompter>>>(mut self, prompt: P) -> Self {
+        self.prompt = Some(Arc::new(prompt.into()));
+        self
+    }
+
+    /// Replaces the default prompt with one formatted without blocking, see `prompt`.
+    pub fn async_prompt(mut self, prompt: Arc<dyn AsyncPrompt>) -> Self {
+        self.prompt = Some(prompt);
+        self
+    }
+
     pub fn memory(mut self, memory: Arc<Mutex<dyn BaseMemory>>) -> Self {
         self.memory = Some(memory);
         self
@@ -71,15 +86,17 @@ impl ConversationalChainBuilder {
         let llm = self
             .llm
             .ok_or_else(|| ChainError::MissingObject("LLM must be set".into()))?;
-        let prompt = HumanMessagePromptTemplate::new(template_fstring!(
-            DEFAULT_TEMPLATE,
-            "history",
-            "input"
-        ));
+        let prompt = self.prompt.unwrap_or_else(|| {
+            Arc::new(HumanMessagePromptTemplate::new(template_fstring!(
+                DEFAULT_TEMPLATE,
+                "history",
+                "input"
+            )))
+        });
 
         let llm_chain = {
             let mut builder = LLMChainBuilder::new()
-                .prompt(prompt)
+                .async_prompt(prompt)
                 .llm(llm)
                 .output_key(self.output_key.unwrap_or_else(|| DEFAULT_OUTPUT_KEY.into()));
 
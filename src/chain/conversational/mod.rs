@@ -4,13 +4,14 @@ use async_stream::stream;
 use async_trait::async_trait;
 use futures::Stream;
 use futures_util::{pin_mut, StreamExt};
+use serde_json::Value;
 use tokio::sync::Mutex;
 
 use crate::{
     language_models::GenerateResult,
     prompt::PromptArgs,
     prompt_args,
-    schemas::{memory::BaseMemory, messages::Message, StreamData},
+    schemas::{memory::BaseMemory, StreamData},
 };
 
 const DEFAULT_INPUT_VARIABLE: &str = "input";
@@ -54,27 +55,31 @@ impl ConversationalChain {
     pub fn pompt_builder(&self) -> ConversationalChainPromptBuilder {
         ConversationalChainPromptBuilder::new()
     }
+
+    // Returns the human's input, saved to the memory with the AI's output.
+    fn input(&self, input_variables: &PromptArgs) -> Result<String, ChainError> {
+        match input_variables.get(&self.input_key) {
+            Some(Value::String(input)) => Ok(input.clone()),
+            Some(input) => Ok(input.to_string()),
+            None => Err(ChainError::MissingInputVariable(self.input_key.clone())),
+        }
+    }
 }
 
 #[async_trait]
 impl Chain for ConversationalChain {
     async fn call(&self, input_variables: PromptArgs) -> Result<GenerateResult, ChainError> {
-        let input_variable = &input_variables
-            .get(&self.input_key)
-            .ok_or(ChainError::MissingInputVariable(self.input_key.clone()))?;
-        let human_message = Message::new_human_message(input_variable);
-
-        let history = {
-            let memory = self.memory.lock().await;
-            memory.to_string()
-        };
+        let input = self.input(&input_variables)?;
+
         let mut input_variables = input_variables;
-        input_variables.insert("history".to_string(), history.into());
-        let result = self.llm.call(input_variables.clone()).await?;
+        input_variables.extend(self.memory.lock().await.load_memory_variables());
+        let result = self.llm.call(input_variables).await?;
 
         let mut memory = self.memory.lock().await;
-        memory.add_message(human_message);
-        memory.add_message(Message::new_ai_message(&result.generation));
+        memory
+            .save_context(&input, &result.generation)
+            .await
+            .map_err(ChainError::MemoryError)?;
         Ok(result)
     }
 
@@ -83,18 +88,10 @@ impl Chain for ConversationalChain {
         input_variables: PromptArgs,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamData, ChainError>> + Send>>, ChainError>
     {
-        let input_variable = &input_variables
-            .get(&self.input_key)
-            .ok_or(ChainError::MissingInputVariable(self.input_key.clone()))?;
-        let human_message = Message::new_human_message(input_variable);
-
-        let history = {
-            let memory = self.memory.lock().await;
-            memory.to_string()
-        };
+        let input = self.input(&input_variables)?;
 
         let mut input_variables = input_variables;
-        input_variables.insert("history".to_string(), history.into());
+        input_variables.extend(self.memory.lock().await.load_memory_variables());
 
         let complete_ai_message = Arc::new(Mutex::new(String::new()));
         let complete_ai_message_clone = complete_ai_message.clone();
@@ -120,8 +117,10 @@ impl Chain for ConversationalChain {
             }
 
             let mut memory = memory.lock().await;
-            memory.add_message(human_message);
-            memory.add_message(Message::new_ai_message(&complete_ai_message.lock().await));
+            let output = complete_ai_message.lock().await.clone();
+            if let Err(e) = memory.save_context(&input, &output).await {
+                yield Err(ChainError::MemoryError(e));
+            }
         };
 
         Ok(Box::pin(output_stream))
@@ -136,12 +135,75 @@ impl Chain for ConversationalChain {
 mod tests {
     use crate::{
         chain::conversational::builder::ConversationalChainBuilder,
-        llm::openai::{OpenAI, OpenAIModel},
-        prompt_args,
+        llm::{
+            openai::{OpenAI, OpenAIModel},
+            FakeLLM,
+        },
+        memory::ConversationBufferMemory,
+        message_formatter,
+        prompt::{HumanMessagePromptTemplate, MessageOrTemplate},
+        prompt_args, template_fstring,
     };
 
     use super::*;
 
+    #[tokio::test]
+    async fn test_conversational_chain_with_buffer_memory() {
+        let memory: Arc<Mutex<dyn BaseMemory>> =
+            ConversationBufferMemory::new().with_max_turns(1).into();
+        let chain = ConversationalChainBuilder::new()
+            .llm(FakeLLM::new().with_responses(vec!["Hello Luis!", "Bye!"]))
+            .prompt(template_fstring!("{history}|{input}", "history", "input"))
+            .memory(memory.clone())
+            .build()
+            .unwrap();
+        chain
+            .invoke(prompt_args! { "input" => "I'm Luis" })
+            .await
+            .unwrap();
+        chain
+            .invoke(prompt_args! { "input" => "Bye" })
+            .await
+            .unwrap();
+        assert_eq!(memory.lock().await.to_string(), "Human: Bye\nAI: Bye!");
+
+        // the FakeLLM echoes the prompt once its responses are used
+        let output = chain
+            .invoke(prompt_args! { "input" => "Who?" })
+            .await
+            .unwrap();
+        assert_eq!(output, "Human: Bye\nAI: Bye!|Who?");
+    }
+
+    #[tokio::test]
+    async fn test_conversational_chain_with_messages_placeholder() {
+        let memory: Arc<Mutex<dyn BaseMemory>> = ConversationBufferMemory::new()
+            .with_return_messages(true)
+            .into();
+        let prompt = message_formatter![
+            MessageOrTemplate::MessagesPlaceholder("history".to_string()),
+            MessageOrTemplate::Template(Box::new(HumanMessagePromptTemplate::new(
+                template_fstring!("{input}", "input")
+            ))),
+        ];
+        let chain = ConversationalChainBuilder::new()
+            .llm(FakeLLM::new())
+            .prompt(prompt)
+            .memory(memory.clone())
+            .build()
+            .unwrap();
+        chain
+            .invoke(prompt_args! { "input" => "one" })
+            .await
+            .unwrap();
+        let output = chain
+            .invoke(prompt_args! { "input" => "two" })
+            .await
+            .unwrap();
+        assert_eq!(output, "one\none\ntwo");
+        assert_eq!(memory.lock().await.messages().len(), 4);
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_invoke_conversational() {
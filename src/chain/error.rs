@@ -1,3 +1,5 @@
+use std::error::Error as StdError;
+
 use thiserror::Error;
 
 use crate::{language_models::LLMError, output_parsers::OutputParserError, prompt::PromptError};
@@ -22,6 +24,9 @@ pub enum ChainError {
     #[error("Missing input variable: {0}")]
     MissingInputVariable(String),
 
+    #[error("Chain {index} of the sequence is missing input variables: {}", .missing.join(", "))]
+    MissingSequenceInputs { index: usize, missing: Vec<String> },
+
     #[error("Serde json error: {0}")]
     SerdeJsonError(#[from] serde_json::Error),
 
@@ -39,4 +44,7 @@ pub enum ChainError {
 
     #[error("Agent error: {0}")]
     AgentError(String),
+
+    #[error("Memory error: {0}")]
+    MemoryError(#[source] Box<dyn StdError + Send + Sync>),
 }
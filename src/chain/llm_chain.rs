@@ -1,24 +1,34 @@
-use std::pin::Pin;
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
 
 use async_trait::async_trait;
-use futures::Stream;
-use futures_util::TryStreamExt;
+use futures::{future::try_join_all, Stream, StreamExt};
 
 use crate::{
-    language_models::{llm::LLM, GenerateResult},
+    callbacks::{CallbackHandler, Callbacks, RunScope},
+    language_models::{
+        llm::LLM, scoped_trackers, GenerateResult, LLMError, TokenUsage, UsageTracker,
+    },
     output_parsers::{OutputParser, SimpleParser},
-    prompt::{FormatPrompter, PromptArgs},
-    schemas::StreamData,
+    prompt::{AsyncPrompt, FormatPrompter, PromptArgs},
+    schemas::{Message, StreamData},
 };
 
 use super::{chain_trait::Chain, options::ChainCallOptions, ChainError};
 
+const DEFAULT_LLM_CHAIN_NAME: &str = "LLMChain";
+
 pub struct LLMChainBuilder {
-    prompt: Option<Box<dyn FormatPrompter>>,
+    prompt: Option<Arc<dyn AsyncPrompt>>,
     llm: Option<Box<dyn LLM>>,
     output_key: Option<String>,
     options: Option<ChainCallOptions>,
     output_parser: Option<Box<dyn OutputParser>>,
+    name: Option<String>,
+    callbacks: Callbacks,
 }
 
 impl LLMChainBuilder {
@@ -29,6 +39,8 @@ impl LLMChainBuilder {
             options: None,
             output_key: None,
             output_parser: None,
+            name: None,
+            callbacks: Callbacks::new(),
         }
     }
     pub fn options(mut self, options: ChainCallOptions) -> Self {
@@ -37,7 +49,13 @@ impl LLMChainBuilder {
     }
 
     pub fn prompt<P: Into<Box<dyn FormatPrompter>>>(mut self, prompt: P) -> Self {
-        self.prompt = Some(prompt.into());
+        self.prompt = Some(Arc::new(prompt.into()));
+        self
+    }
+
+    /// Sets a prompt formatted without blocking, like a `PromptTemplate` with resolvers.
+    pub fn async_prompt(mut self, prompt: Arc<dyn AsyncPrompt>) -> Self {
+        self.prompt = Some(prompt);
         self
     }
 
@@ -56,6 +74,23 @@ impl LLMChainBuilder {
         self
     }
 
+    /// Sets the name given to callback handlers for the chain and its prompt. Defaults to
+    /// `LLMChain`.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn callback_handler(mut self, handler: Arc<dyn CallbackHandler>) -> Self {
+        self.callbacks.add_handler(handler);
+        self
+    }
+
+    pub fn callbacks(mut self, callbacks: Callbacks) -> Self {
+        self.callbacks = callbacks;
+        self
+    }
+
     pub fn build(self) -> Result<LLMChain, ChainError> {
         let prompt = self
             .prompt
@@ -77,6 +112,11 @@ impl LLMChainBuilder {
             output_parser: self
                 .output_parser
                 .unwrap_or_else(|| Box::new(SimpleParser::default())),
+            name: self
+                .name
+                .unwrap_or_else(|| DEFAULT_LLM_CHAIN_NAME.to_string()),
+            callbacks: self.callbacks,
+            usage_tracker: None,
         };
 
         Ok(chain)
@@ -84,16 +124,313 @@ impl LLMChainBuilder {
 }
 
 pub struct LLMChain {
-    prompt: Box<dyn FormatPrompter>,
+    prompt: Arc<dyn AsyncPrompt>,
     llm: Box<dyn LLM>,
     output_key: String,
     output_parser: Box<dyn OutputParser>,
+    name: String,
+    callbacks: Callbacks,
+    usage_tracker: Option<Arc<UsageTracker>>,
+}
+
+impl LLMChain {
+    /// Records the tokens used by every call of the chain in the tracker, including streamed
+    /// calls once their final chunk arrives.
+    pub fn with_usage_tracking(mut self, tracker: Arc<UsageTracker>) -> Self {
+        self.usage_tracker = Some(tracker);
+        self
+    }
+
+    // Returns the trackers of the chain and of the enclosing `track_usage` scopes.
+    fn usage_trackers(&self) -> Vec<Arc<UsageTracker>> {
+        let mut trackers = scoped_trackers();
+        trackers.extend(self.usage_tracker.clone());
+        trackers
+    }
+
+    fn record_usage(trackers: &[Arc<UsageTracker>], model: &str, usage: &TokenUsage) {
+        for tracker in trackers {
+            tracker.record(model, usage);
+        }
+    }
+
+    /// Formats the prompt, calls the model and returns the parsed output.
+    ///
+    /// # Errors
+    /// Returns `ChainError::PromptError` if the prompt can't be formatted,
+    /// `ChainError::LLMError` if the model call fails and `ChainError::OutputParser` if the
+    /// output can't be parsed.
+    pub async fn run(&self, input_variables: PromptArgs) -> Result<String, ChainError> {
+        Ok(self.call(input_variables).await?.generation)
+    }
+
+    /// Runs the chain on each input concurrently, returning the outputs in the same order.
+    pub async fn apply(&self, inputs: Vec<PromptArgs>) -> Result<Vec<String>, ChainError> {
+        try_join_all(inputs.into_iter().map(|input| self.run(input))).await
+    }
+
+    // Formats the prompt and notifies the handlers of it.
+    async fn format_messages(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<Vec<Message>, ChainError> {
+        let prompt = self.prompt.aformat_prompt(input_variables).await?;
+        log::debug!("Prompt: {:?}", prompt);
+        self.callbacks.emit("prompt_formatted", |handler| {
+            handler.on_prompt_formatted(&self.name, &prompt.to_string())
+        });
+        Ok(prompt.to_chat_messages())
+    }
+
+    // Formats the prompt and calls the model, notifying the handlers of each step. Each call
+    // runs in a scope of its own, so concurrent calls aren't taken for nested ones.
+    async fn generate(
+        &self,
+        input_variables: PromptArgs,
+        parse: bool,
+    ) -> Result<GenerateResult, ChainError> {
+        RunScope::new()
+            .run(self.generate_in_scope(input_variables, parse))
+            .await
+    }
+
+    async fn generate_in_scope(
+        &self,
+        input_variables: PromptArgs,
+        parse: bool,
+    ) -> Result<GenerateResult, ChainError> {
+        self.callbacks.emit("chain_start", |handler| {
+            handler.on_chain_start(&self.name, &input_variables)
+        });
+        let result = async {
+            let messages = self.format_messages(input_variables).await?;
+            self.callbacks
+                .emit("llm_start", |handler| handler.on_llm_start(&messages));
+            let mut output = match self.llm.generate(&messages).await {
+                Ok(output) => output,
+                Err(e) => {
+                    self.callbacks
+                        .emit("llm_error", |handler| handler.on_llm_error(&e));
+                    return Err(e.into());
+                }
+            };
+            self.callbacks.emit("llm_end", |handler| {
+                handler.on_llm_end(&output.generation, output.tokens.as_ref())
+            });
+            if let Some(tokens) = &output.tokens {
+                Self::record_usage(&self.usage_trackers(), &self.llm.model_name(), tokens);
+            }
+            if parse {
+                output.generation = self.output_parser.parse(&output.generation).await?;
+            }
+            Ok(output)
+        }
+        .await;
+        match &result {
+            Ok(output) => self.callbacks.emit("chain_end", |handler| {
+                handler.on_chain_end(&self.name, &output.generation)
+            }),
+            Err(e) => self.callbacks.emit("chain_error", |handler| {
+                handler.on_chain_error(&self.name, e)
+            }),
+        }
+        result
+    }
+
+    // Starts the stream of `Chain::stream` in `scope`, where the stream is polled too.
+    async fn start_stream(
+        &self,
+        input_variables: PromptArgs,
+        scope: Arc<RunScope>,
+    ) -> Result<NotifyingStream, ChainError> {
+        self.callbacks.emit("chain_start", |handler| {
+            handler.on_chain_start(&self.name, &input_variables)
+        });
+        let messages = match self.format_messages(input_variables).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                self.callbacks.emit("chain_error", |handler| {
+                    handler.on_chain_error(&self.name, &e)
+                });
+                return Err(e);
+            }
+        };
+        self.callbacks
+            .emit("llm_start", |handler| handler.on_llm_start(&messages));
+        let llm_stream = match self.llm.stream(&messages).await {
+            Ok(llm_stream) => llm_stream,
+            Err(e) => {
+                self.callbacks
+                    .emit("llm_error", |handler| handler.on_llm_error(&e));
+                let e = ChainError::from(e);
+                self.callbacks.emit("chain_error", |handler| {
+                    handler.on_chain_error(&self.name, &e)
+                });
+                return Err(e);
+            }
+        };
+
+        Ok(NotifyingStream {
+            stream: llm_stream,
+            scope,
+            callbacks: self.callbacks.clone(),
+            name: self.name.clone(),
+            trackers: self.usage_trackers(),
+            model: self.llm.model_name(),
+            text: String::new(),
+            tokens: None,
+            finished: false,
+        })
+    }
+
+    /// Streams the model's output like `Chain::stream`, accumulating it so the output parser
+    /// can run on the full text once the stream ends, see `LLMChainStream::output`.
+    pub async fn stream_parsed(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<LLMChainStream<'_>, ChainError> {
+        Ok(LLMChainStream {
+            stream: Chain::stream(self, input_variables).await?,
+            output_parser: self.output_parser.as_ref(),
+            text: String::new(),
+        })
+    }
+}
+
+/// Struct `LLMChainStream` is the stream of an `LLMChain`'s output chunks, keeping the text
+/// streamed so far. Dropping it drops the model's stream, closing its connection.
+///
+/// # Usage
+/// ```rust,ignore
+/// let mut stream = chain.stream_parsed(prompt_args! { "input" => "Hi" }).await?;
+/// while let Some(chunk) = stream.next().await {
+///     chunk?.to_stdout()?;
+/// }
+/// let output = stream.output().await?;
+/// ```
+pub struct LLMChainStream<'a> {
+    stream: Pin<Box<dyn Stream<Item = Result<StreamData, ChainError>> + Send>>,
+    output_parser: &'a dyn OutputParser,
+    text: String,
+}
+
+impl LLMChainStream<'_> {
+    /// Returns the text streamed so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Consumes the rest of the stream and returns the full text parsed by the chain's output
+    /// parser.
+    pub async fn output(mut self) -> Result<String, ChainError> {
+        while let Some(chunk) = self.next().await {
+            chunk?;
+        }
+        Ok(self.output_parser.parse(&self.text).await?)
+    }
+}
+
+impl Stream for LLMChainStream<'_> {
+    type Item = Result<StreamData, ChainError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.stream.as_mut().poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            self.text.push_str(&chunk.content);
+        }
+        poll
+    }
+}
+
+// The model's stream of `Chain::stream`, recording the usage of its chunks and notifying the
+// handlers of the end of the run, or of its first error. A stream dropped before its end
+// fails the run.
+struct NotifyingStream {
+    stream: Pin<Box<dyn Stream<Item = Result<StreamData, LLMError>> + Send>>,
+    scope: Arc<RunScope>,
+    callbacks: Callbacks,
+    name: String,
+    trackers: Vec<Arc<UsageTracker>>,
+    model: String,
+    text: String,
+    tokens: Option<TokenUsage>,
+    finished: bool,
+}
+
+impl NotifyingStream {
+    fn fail(&mut self, error: LLMError) -> ChainError {
+        self.callbacks
+            .emit("llm_error", |handler| handler.on_llm_error(&error));
+        let error = ChainError::from(error);
+        self.callbacks.emit("chain_error", |handler| {
+            handler.on_chain_error(&self.name, &error)
+        });
+        self.finished = true;
+        error
+    }
+
+    fn poll_in_scope(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<StreamData, ChainError>>> {
+        match self.stream.as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                self.text.push_str(&chunk.content);
+                if let Some(tokens) = &chunk.tokens {
+                    LLMChain::record_usage(&self.trackers, &self.model, tokens);
+                    self.tokens = Some(tokens.clone());
+                }
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(Some(Err(e))) if self.finished => Poll::Ready(Some(Err(e.into()))),
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(self.fail(e)))),
+            Poll::Ready(None) => {
+                if !self.finished {
+                    self.finished = true;
+                    self.callbacks.emit("llm_end", |handler| {
+                        handler.on_llm_end(&self.text, self.tokens.as_ref())
+                    });
+                    self.callbacks.emit("chain_end", |handler| {
+                        handler.on_chain_end(&self.name, &self.text)
+                    });
+                }
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Stream for NotifyingStream {
+    type Item = Result<StreamData, ChainError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let scope = self.scope.clone();
+        scope.enter(|| self.poll_in_scope(cx))
+    }
+}
+
+impl Drop for NotifyingStream {
+    fn drop(&mut self) {
+        if !self.finished {
+            let scope = self.scope.clone();
+            scope.enter(|| {
+                self.fail(LLMError::OtherError(
+                    "Stream dropped before its end".to_string(),
+                ))
+            });
+        }
+    }
 }
 
 #[async_trait]
 impl Chain for LLMChain {
     fn get_input_keys(&self) -> Vec<String> {
-        return self.prompt.get_input_variables();
+        return self.prompt.input_variables();
+    }
+
+    fn get_required_input_keys(&self) -> Vec<String> {
+        self.prompt.required_input_variables()
     }
 
     fn get_output_keys(&self) -> Vec<String> {
@@ -101,23 +438,11 @@ impl Chain for LLMChain {
     }
 
     async fn call(&self, input_variables: PromptArgs) -> Result<GenerateResult, ChainError> {
-        let prompt = self.prompt.format_prompt(input_variables.clone())?;
-        log::debug!("Prompt: {:?}", prompt);
-        let mut output = self.llm.generate(&prompt.to_chat_messages()).await?;
-        output.generation = self.output_parser.parse(&output.generation).await?;
-
-        Ok(output)
+        self.generate(input_variables, true).await
     }
 
     async fn invoke(&self, input_variables: PromptArgs) -> Result<String, ChainError> {
-        let prompt = self.prompt.format_prompt(input_variables.clone())?;
-        log::debug!("Prompt: {:?}", prompt);
-        let output = self
-            .llm
-            .generate(&prompt.to_chat_messages())
-            .await?
-            .generation;
-        Ok(output)
+        Ok(self.generate(input_variables, false).await?.generation)
     }
 
     async fn stream(
@@ -125,29 +450,217 @@ impl Chain for LLMChain {
         input_variables: PromptArgs,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamData, ChainError>> + Send>>, ChainError>
     {
-        let prompt = self.prompt.format_prompt(input_variables.clone())?;
-        log::debug!("Prompt: {:?}", prompt);
-        let llm_stream = self.llm.stream(&prompt.to_chat_messages()).await?;
-
-        // Map the errors from LLMError to ChainError
-        let mapped_stream = llm_stream.map_err(ChainError::from);
-
-        Ok(Box::pin(mapped_stream))
+        let scope = RunScope::new();
+        let stream = scope
+            .clone()
+            .run(self.start_stream(input_variables, scope))
+            .await?;
+        Ok(Box::pin(stream))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
+        callbacks::{CallbackEvent, CollectingCallbackHandler},
         chain::options::ChainCallOptions,
-        llm::openai::{OpenAI, OpenAIModel},
+        language_models::track_usage,
+        llm::{
+            openai::{OpenAI, OpenAIModel},
+            FakeLLM,
+        },
         message_formatter,
-        prompt::{HumanMessagePromptTemplate, MessageOrTemplate},
+        output_parsers::MarkdownParser,
+        prompt::{HumanMessagePromptTemplate, MessageOrTemplate, PromptError},
         prompt_args, template_fstring,
     };
 
     use super::*;
 
+    #[tokio::test]
+    async fn test_run_and_apply() {
+        let chain = LLMChainBuilder::new()
+            .prompt(template_fstring!("Hello {name}", "name"))
+            .llm(FakeLLM::new())
+            .build()
+            .unwrap();
+        assert_eq!(
+            chain.run(prompt_args! { "name" => "Luis" }).await.unwrap(),
+            "Hello Luis"
+        );
+        assert_eq!(
+            chain
+                .apply(vec![
+                    prompt_args! { "name" => "Ana" },
+                    prompt_args! { "name" => "Bo" }
+                ])
+                .await
+                .unwrap(),
+            vec!["Hello Ana", "Hello Bo"]
+        );
+        assert!(matches!(
+            chain.run(prompt_args! {}).await,
+            Err(ChainError::PromptError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_async_prompt() {
+        let prompt = template_fstring!("Hello {name}, it's {weather}", "name", "weather")
+            .with_resolver("weather", |_| async { Ok::<_, PromptError>("sunny") });
+        let chain = Arc::new(
+            LLMChainBuilder::new()
+                .async_prompt(Arc::new(prompt))
+                .llm(FakeLLM::new())
+                .build()
+                .unwrap(),
+        );
+        let spawned = chain.clone();
+        let output =
+            tokio::spawn(async move { spawned.run(prompt_args! { "name" => "Luis" }).await })
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(output, "Hello Luis, it's sunny");
+    }
+
+    #[tokio::test]
+    async fn test_callbacks() {
+        let collector = Arc::new(CollectingCallbackHandler::new());
+        let chain = LLMChainBuilder::new()
+            .prompt(template_fstring!("Hello {name}", "name"))
+            .llm(FakeLLM::new().with_responses(vec!["Hi"]))
+            .name("greeter")
+            .callback_handler(collector.clone())
+            .build()
+            .unwrap();
+        chain
+            .invoke(prompt_args! { "name" => "Luis" })
+            .await
+            .unwrap();
+        let events = collector.events();
+        assert!(matches!(
+            &events[..],
+            [
+                CallbackEvent::ChainStart { chain_name, .. },
+                CallbackEvent::PromptFormatted { rendered, .. },
+                CallbackEvent::LLMStart { messages },
+                CallbackEvent::LLMEnd { generation, .. },
+                CallbackEvent::ChainEnd { output, .. },
+            ] if chain_name == "greeter"
+                && rendered == "Hello Luis"
+                && messages[0].content == "Hello Luis"
+                && generation == "Hi"
+                && output == "Hi"
+        ));
+
+        collector.clear();
+        assert!(chain.invoke(prompt_args! {}).await.is_err());
+        assert!(matches!(
+            &collector.events()[..],
+            [CallbackEvent::ChainStart { .. }, CallbackEvent::ChainError { error, .. }]
+                if error.contains("name")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream_callbacks() {
+        let collector = Arc::new(CollectingCallbackHandler::new());
+        let chain = LLMChainBuilder::new()
+            .prompt(template_fstring!("Hello {name}", "name"))
+            .llm(FakeLLM::new().with_responses(vec!["Hi there", "Bye now"]))
+            .name("streamer")
+            .callback_handler(collector.clone())
+            .build()
+            .unwrap();
+        let mut stream = chain
+            .stream(prompt_args! { "name" => "Luis" })
+            .await
+            .unwrap();
+        while stream.next().await.is_some() {}
+        assert!(matches!(
+            &collector.events()[..],
+            [
+                CallbackEvent::ChainStart { .. },
+                CallbackEvent::PromptFormatted { .. },
+                CallbackEvent::LLMStart { .. },
+                CallbackEvent::LLMEnd { generation, usage: Some(usage) },
+                CallbackEvent::ChainEnd { output, .. },
+            ] if generation == "Hi there" && usage.completion_tokens == 2 && output == "Hi there"
+        ));
+
+        // a stream dropped before its end, and a prompt that can't be formatted, fail the run
+        collector.clear();
+        let mut stream = chain
+            .stream(prompt_args! { "name" => "Luis" })
+            .await
+            .unwrap();
+        stream.next().await;
+        drop(stream);
+        assert!(chain.stream(prompt_args! {}).await.is_err());
+        assert!(matches!(
+            &collector.events()[..],
+            [
+                CallbackEvent::ChainStart { .. },
+                CallbackEvent::PromptFormatted { .. },
+                CallbackEvent::LLMStart { .. },
+                CallbackEvent::LLMError(_),
+                CallbackEvent::ChainError { .. },
+                CallbackEvent::ChainStart { .. },
+                CallbackEvent::ChainError { .. },
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_usage_tracking() {
+        let tracker = Arc::new(UsageTracker::new());
+        let chain = LLMChainBuilder::new()
+            .prompt(template_fstring!("Hello {name}", "name"))
+            .llm(FakeLLM::new().with_responses(vec!["Hi there", "Hi"]))
+            .build()
+            .unwrap()
+            .with_usage_tracking(tracker.clone());
+
+        let (_, usage) = track_usage(|| async {
+            chain
+                .invoke(prompt_args! { "name" => "Luis" })
+                .await
+                .unwrap();
+            let mut stream = chain
+                .stream(prompt_args! { "name" => "Ana" })
+                .await
+                .unwrap();
+            while stream.next().await.is_some() {}
+        })
+        .await;
+        assert_eq!(usage.total.prompt_tokens, 4);
+        assert_eq!(usage.total.completion_tokens, 3);
+        assert_eq!(usage.by_model["fake"].requests, 2);
+
+        chain.invoke(prompt_args! { "name" => "Bo" }).await.unwrap();
+        assert_eq!(tracker.snapshot().total.requests, 3);
+        assert_eq!(tracker.snapshot().total.total_tokens, 11);
+    }
+
+    #[tokio::test]
+    async fn test_stream_parsed() {
+        let chain = LLMChainBuilder::new()
+            .prompt(template_fstring!("{input}", "input"))
+            .llm(FakeLLM::new().with_responses(vec!["```json\n{\"a\": 1}\n```"]))
+            .output_parser(MarkdownParser::new())
+            .build()
+            .unwrap();
+        let mut stream = chain
+            .stream_parsed(prompt_args! { "input" => "Hi" })
+            .await
+            .unwrap();
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.content, "```json\n{\"a\": ");
+        assert_eq!(stream.text(), first.content);
+        assert_eq!(stream.output().await.unwrap(), "{\"a\": 1}");
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_invoke_chain() {
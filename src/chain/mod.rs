@@ -22,6 +22,9 @@ pub use question_answering::*;
 mod conversational_retrieval_qa;
 pub use conversational_retrieval_qa::*;
 
+mod retrieval_qa;
+pub use retrieval_qa::*;
+
 mod error;
 pub use error::*;
 
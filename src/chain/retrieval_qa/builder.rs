@@ -0,0 +1,129 @@
+use crate::{
+    chain::{ChainError, LLMChainBuilder, StuffDocument, DEFAULT_OUTPUT_KEY},
+    language_models::llm::LLM,
+    prompt::PromptTemplate,
+    schemas::Retriever,
+    template_fstring,
+};
+
+use super::RetrievalQA;
+
+const RETRIEVAL_QA_DEFAULT_INPUT_KEY: &str = "question";
+
+pub const DEFAULT_RETRIEVAL_QA_TEMPLATE: &str = r#"Use the following pieces of context to answer the question at the end. If you don't know the answer, just say that you don't know, don't try to make up an answer.
+
+{context}
+
+Question: {question}
+Helpful Answer:"#;
+
+/// Retrieval QA Chain Builder
+/// # Usage
+/// ```rust,ignore
+/// let chain = RetrievalQABuilder::new()
+///     .llm(llm)
+///     .retriever(Retriever::new(store, 10))
+///     .k(4)
+///     .score_threshold(0.7)
+///     .return_source_documents(true)
+///     .build()?;
+/// let (answer, sources) = chain.run_with_sources("Who wrote it?").await?;
+/// ```
+pub struct RetrievalQABuilder {
+    llm: Option<Box<dyn LLM>>,
+    retriever: Option<Box<dyn Retriever>>,
+    prompt: Option<PromptTemplate>,
+    k: Option<usize>,
+    score_threshold: Option<f64>,
+    return_source_documents: bool,
+    input_key: String,
+    output_key: String,
+}
+
+impl Default for RetrievalQABuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetrievalQABuilder {
+    pub fn new() -> Self {
+        Self {
+            llm: None,
+            retriever: None,
+            prompt: None,
+            k: None,
+            score_threshold: None,
+            return_source_documents: false,
+            input_key: RETRIEVAL_QA_DEFAULT_INPUT_KEY.to_string(),
+            output_key: DEFAULT_OUTPUT_KEY.to_string(),
+        }
+    }
+
+    pub fn llm<L: Into<Box<dyn LLM>>>(mut self, llm: L) -> Self {
+        self.llm = Some(llm.into());
+        self
+    }
+
+    pub fn retriever<R: Into<Box<dyn Retriever>>>(mut self, retriever: R) -> Self {
+        self.retriever = Some(retriever.into());
+        self
+    }
+
+    /// Sets the QA prompt, which takes the `context` and `question` variables. Defaults to
+    /// `DEFAULT_RETRIEVAL_QA_TEMPLATE`.
+    pub fn prompt(mut self, prompt: PromptTemplate) -> Self {
+        self.prompt = Some(prompt);
+        self
+    }
+
+    /// Keeps at most `k` of the retrieved documents, the first ones.
+    pub fn k(mut self, k: usize) -> Self {
+        self.k = Some(k);
+        self
+    }
+
+    /// Drops the retrieved documents with a score below the threshold.
+    pub fn score_threshold(mut self, score_threshold: f64) -> Self {
+        self.score_threshold = Some(score_threshold);
+        self
+    }
+
+    pub fn return_source_documents(mut self, return_source_documents: bool) -> Self {
+        self.return_source_documents = return_source_documents;
+        self
+    }
+
+    pub fn input_key<S: Into<String>>(mut self, input_key: S) -> Self {
+        self.input_key = input_key.into();
+        self
+    }
+
+    pub fn output_key<S: Into<String>>(mut self, output_key: S) -> Self {
+        self.output_key = output_key.into();
+        self
+    }
+
+    pub fn build(self) -> Result<RetrievalQA, ChainError> {
+        let llm = self
+            .llm
+            .ok_or_else(|| ChainError::MissingObject("LLM must be set".into()))?;
+        let retriever = self
+            .retriever
+            .ok_or_else(|| ChainError::MissingObject("Retriever must be set".into()))?;
+        let prompt = self.prompt.unwrap_or_else(|| {
+            template_fstring!(DEFAULT_RETRIEVAL_QA_TEMPLATE, "context", "question")
+        });
+        let llm_chain = LLMChainBuilder::new().prompt(prompt).llm(llm).build()?;
+
+        Ok(RetrievalQA {
+            retriever,
+            combine_documents_chain: StuffDocument::new(llm_chain),
+            k: self.k,
+            score_threshold: self.score_threshold,
+            return_source_documents: self.return_source_documents,
+            input_key: self.input_key,
+            output_key: self.output_key,
+        })
+    }
+}
@@ -0,0 +1,221 @@
+use std::{collections::HashMap, pin::Pin};
+
+use async_trait::async_trait;
+use futures::Stream;
+use serde_json::{json, Value};
+
+use crate::{
+    chain::{Chain, ChainError, StuffDocument, DEFAULT_RESULT_KEY},
+    language_models::GenerateResult,
+    prompt::PromptArgs,
+    prompt_args,
+    schemas::{Document, Retriever, StreamData},
+};
+
+const RETRIEVAL_QA_DEFAULT_SOURCE_DOCUMENT_KEY: &str = "source_documents";
+
+/// Struct `RetrievalQA` answers a question with the documents a retriever finds for it,
+/// stuffed into the `context` of a QA prompt. Build it with `RetrievalQABuilder`.
+///
+/// Retrieval failures are returned as `ChainError::RetrieverError`, prompt formatting
+/// failures as `ChainError::PromptError` and generation failures as `ChainError::LLMError`.
+pub struct RetrievalQA {
+    pub(crate) retriever: Box<dyn Retriever>,
+    pub(crate) combine_documents_chain: StuffDocument,
+    pub(crate) k: Option<usize>,
+    pub(crate) score_threshold: Option<f64>,
+    pub(crate) return_source_documents: bool,
+    pub(crate) input_key: String,  //Default is `question`
+    pub(crate) output_key: String, //default is output
+}
+
+impl RetrievalQA {
+    /// Answers the question.
+    pub async fn run<S: Into<String>>(&self, question: S) -> Result<String, ChainError> {
+        Ok(self.run_with_sources(question).await?.0)
+    }
+
+    /// Answers the question, returning the documents the answer is based on.
+    pub async fn run_with_sources<S: Into<String>>(
+        &self,
+        question: S,
+    ) -> Result<(String, Vec<Document>), ChainError> {
+        let (result, documents) = self.answer(&question.into()).await?;
+        Ok((result.generation, documents))
+    }
+
+    async fn retrieve(&self, question: &str) -> Result<Vec<Document>, ChainError> {
+        let mut documents = self
+            .retriever
+            .get_relevant_documents(question)
+            .await
+            .map_err(|e| ChainError::RetrieverError(e.to_string()))?;
+        if let Some(score_threshold) = self.score_threshold {
+            documents.retain(|document| document.score >= score_threshold);
+        }
+        if let Some(k) = self.k {
+            documents.truncate(k);
+        }
+        log::debug!("Retrieved {} documents", documents.len());
+        Ok(documents)
+    }
+
+    async fn answer(&self, question: &str) -> Result<(GenerateResult, Vec<Document>), ChainError> {
+        let documents = self.retrieve(question).await?;
+        let result = self
+            .combine_documents_chain
+            .call(prompt_args! {
+                "input_documents" => documents,
+                "question" => question,
+            })
+            .await?;
+        Ok((result, documents))
+    }
+
+    fn question(&self, input_variables: &PromptArgs) -> Result<String, ChainError> {
+        match input_variables.get(&self.input_key) {
+            Some(Value::String(question)) => Ok(question.clone()),
+            Some(question) => Ok(question.to_string()),
+            None => Err(ChainError::MissingInputVariable(self.input_key.clone())),
+        }
+    }
+}
+
+#[async_trait]
+impl Chain for RetrievalQA {
+    async fn call(&self, input_variables: PromptArgs) -> Result<GenerateResult, ChainError> {
+        let question = self.question(&input_variables)?;
+        Ok(self.answer(&question).await?.0)
+    }
+
+    async fn execute(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<HashMap<String, Value>, ChainError> {
+        let question = self.question(&input_variables)?;
+        let (output, documents) = self.answer(&question).await?;
+
+        let mut result = HashMap::new();
+        result.insert(self.output_key.clone(), json!(output.generation));
+        result.insert(DEFAULT_RESULT_KEY.to_string(), json!(output));
+        if self.return_source_documents {
+            result.insert(
+                RETRIEVAL_QA_DEFAULT_SOURCE_DOCUMENT_KEY.to_string(),
+                json!(documents),
+            );
+        }
+        Ok(result)
+    }
+
+    async fn stream(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamData, ChainError>> + Send>>, ChainError>
+    {
+        let question = self.question(&input_variables)?;
+        let documents = self.retrieve(&question).await?;
+        self.combine_documents_chain
+            .stream(prompt_args! {
+                "input_documents" => documents,
+                "question" => question,
+            })
+            .await
+    }
+
+    fn get_input_keys(&self) -> Vec<String> {
+        vec![self.input_key.clone()]
+    }
+
+    fn get_output_keys(&self) -> Vec<String> {
+        let mut keys = vec![self.output_key.clone(), DEFAULT_RESULT_KEY.to_string()];
+        if self.return_source_documents {
+            keys.push(RETRIEVAL_QA_DEFAULT_SOURCE_DOCUMENT_KEY.to_string());
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+    use crate::{chain::RetrievalQABuilder, llm::FakeLLM, template_fstring};
+
+    struct RetrieverTest {}
+
+    #[async_trait]
+    impl Retriever for RetrieverTest {
+        async fn get_relevant_documents(
+            &self,
+            _question: &str,
+        ) -> Result<Vec<Document>, Box<dyn Error>> {
+            Ok(vec![
+                Document::new("Luis is 24").with_score(0.9),
+                Document::new("Luis uses Nvim").with_score(0.8),
+                Document::new("Paris is in France").with_score(0.2),
+            ])
+        }
+    }
+
+    struct FailingRetriever {}
+
+    #[async_trait]
+    impl Retriever for FailingRetriever {
+        async fn get_relevant_documents(
+            &self,
+            _question: &str,
+        ) -> Result<Vec<Document>, Box<dyn Error>> {
+            Err("store unavailable".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_qa() {
+        let chain = RetrievalQABuilder::new()
+            .llm(FakeLLM::new())
+            .retriever(RetrieverTest {})
+            .prompt(template_fstring!(
+                "{context}\nQ: {question}",
+                "context",
+                "question"
+            ))
+            .score_threshold(0.5)
+            .return_source_documents(true)
+            .build()
+            .unwrap();
+
+        let (answer, sources) = chain.run_with_sources("How old is Luis?").await.unwrap();
+        assert_eq!(answer, "Luis is 24\n\nLuis uses Nvim\nQ: How old is Luis?");
+        assert_eq!(sources.len(), 2);
+
+        let output = chain
+            .execute(prompt_args! { "question" => "How old is Luis?" })
+            .await
+            .unwrap();
+        assert_eq!(output["source_documents"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_qa_default_prompt_and_errors() {
+        let chain = RetrievalQABuilder::new()
+            .llm(FakeLLM::new())
+            .retriever(RetrieverTest {})
+            .k(1)
+            .build()
+            .unwrap();
+        let answer = chain.run("How old is Luis?").await.unwrap();
+        assert!(answer.contains("\n\nLuis is 24\n\nQuestion: How old is Luis?\nHelpful Answer:"));
+        assert!(!answer.contains("Nvim"));
+
+        let chain = RetrievalQABuilder::new()
+            .llm(FakeLLM::new())
+            .retriever(FailingRetriever {})
+            .build()
+            .unwrap();
+        assert!(matches!(
+            chain.run("How old is Luis?").await,
+            Err(ChainError::RetrieverError(e)) if e == "store unavailable"
+        ));
+    }
+}
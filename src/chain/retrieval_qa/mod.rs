@@ -0,0 +1,5 @@
+mod builder;
+pub use builder::*;
+
+mod chain;
+pub use chain::*;
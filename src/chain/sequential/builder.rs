@@ -1,16 +1,22 @@
 use std::collections::HashSet;
 
-use crate::chain::Chain;
+use crate::chain::{Chain, ChainError, DEFAULT_OUTPUT_KEY};
 
 use super::SequentialChain;
 
 pub struct SequentialChainBuilder {
     chains: Vec<Box<dyn Chain>>,
+    input_keys: Option<Vec<String>>,
+    return_intermediate_outputs: bool,
 }
 
 impl SequentialChainBuilder {
     pub fn new() -> Self {
-        Self { chains: Vec::new() }
+        Self {
+            chains: Vec::new(),
+            input_keys: None,
+            return_intermediate_outputs: true,
+        }
     }
 
     pub fn add_chain<C: Chain + 'static>(mut self, chain: C) -> Self {
@@ -18,27 +24,81 @@ impl SequentialChainBuilder {
         self
     }
 
+    /// Declares the variables the sequence is called with, so `try_build` checks that every
+    /// chain's required inputs come from them or from the outputs of the chains before it.
+    /// Without them, the inputs are the chains' input keys that no chain before produces.
+    pub fn input_keys<S: Into<String>>(mut self, input_keys: Vec<S>) -> Self {
+        self.input_keys = Some(input_keys.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Returns the output of every chain, not only the last one's. Defaults to true.
+    pub fn return_intermediate_outputs(mut self, return_intermediate_outputs: bool) -> Self {
+        self.return_intermediate_outputs = return_intermediate_outputs;
+        self
+    }
+
+    /// Builds the chain without checking the declared inputs, see `try_build`.
     pub fn build(self) -> SequentialChain {
-        let outputs: HashSet<String> = self
-            .chains
-            .iter()
-            .flat_map(|c| c.get_output_keys())
-            .collect();
-
-        let input_keys: HashSet<String> = self
-            .chains
-            .iter()
-            .flat_map(|c| c.get_input_keys())
-            .collect();
+        let outputs: Vec<String> = self.chains.iter().map(|c| output_key(c.as_ref())).collect();
+        let input_keys = match self.input_keys {
+            Some(input_keys) => input_keys.into_iter().collect(),
+            None => {
+                let mut input_keys = HashSet::new();
+                for (index, chain) in self.chains.iter().enumerate() {
+                    input_keys.extend(
+                        chain
+                            .get_input_keys()
+                            .into_iter()
+                            .filter(|key| !outputs[..index].contains(key)),
+                    );
+                }
+                input_keys
+            }
+        };
 
         SequentialChain {
             chains: self.chains,
             input_keys,
             outputs,
+            return_intermediate_outputs: self.return_intermediate_outputs,
+        }
+    }
+
+    /// Builds the chain like `build`, first checking that the required inputs of every chain
+    /// are declared with `input_keys` or are outputs of the chains before it.
+    ///
+    /// # Errors
+    /// Returns `ChainError::MissingSequenceInputs` with the first chain whose required inputs
+    /// are neither declared inputs nor outputs of previous chains.
+    pub fn try_build(self) -> Result<SequentialChain, ChainError> {
+        if let Some(input_keys) = &self.input_keys {
+            let mut available: HashSet<String> = input_keys.iter().cloned().collect();
+            for (index, chain) in self.chains.iter().enumerate() {
+                let missing: Vec<String> = chain
+                    .get_required_input_keys()
+                    .into_iter()
+                    .filter(|key| !available.contains(key))
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(ChainError::MissingSequenceInputs { index, missing });
+                }
+                available.insert(output_key(chain.as_ref()));
+            }
         }
+        Ok(self.build())
     }
 }
 
+// The key a chain's output is passed to the next chains under.
+fn output_key(chain: &dyn Chain) -> String {
+    chain
+        .get_output_keys()
+        .first()
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_OUTPUT_KEY.to_string())
+}
+
 #[macro_export]
 macro_rules! sequential_chain {
     ( $( $chain:expr ),* $(,)? ) => {
@@ -4,7 +4,7 @@ use async_trait::async_trait;
 use serde_json::{json, Value};
 
 use crate::{
-    chain::{Chain, ChainError, DEFAULT_OUTPUT_KEY, DEFAULT_RESULT_KEY},
+    chain::{Chain, ChainError, DEFAULT_RESULT_KEY},
     language_models::{GenerateResult, TokenUsage},
     prompt::PromptArgs,
 };
@@ -13,7 +13,9 @@ use crate::{
 pub struct SequentialChain {
     pub(crate) chains: Vec<Box<dyn Chain>>,
     pub(crate) input_keys: HashSet<String>,
-    pub(crate) outputs: HashSet<String>,
+    // the output key of each chain
+    pub(crate) outputs: Vec<String>,
+    pub(crate) return_intermediate_outputs: bool,
 }
 
 #[async_trait]
@@ -33,7 +35,14 @@ impl Chain for SequentialChain {
             .map(|result| result.generation)
     }
     fn get_input_keys(&self) -> Vec<String> {
-        self.outputs.iter().cloned().collect()
+        self.input_keys.iter().cloned().collect()
+    }
+
+    fn get_output_keys(&self) -> Vec<String> {
+        match (self.return_intermediate_outputs, self.outputs.last()) {
+            (false, Some(output_key)) => vec![output_key.clone()],
+            _ => self.outputs.clone(),
+        }
     }
 
     async fn execute(
@@ -44,14 +53,9 @@ impl Chain for SequentialChain {
         let mut final_token_usage: Option<TokenUsage> = None;
         let mut output_result = HashMap::new();
         let mut final_result = GenerateResult::default();
-        for chain in self.chains.iter() {
+        for (chain, output_key) in self.chains.iter().zip(&self.outputs) {
             let output = chain.execute(input_variables.clone()).await?;
-            //Get the oput key for the chain result
-            let output_key = chain
-                .get_output_keys()
-                .get(0)
-                .unwrap_or(&DEFAULT_OUTPUT_KEY.to_string())
-                .clone();
+            let output_key = output_key.clone();
             //Get the ouput complete result
             let result = output
                 .get(DEFAULT_RESULT_KEY)
@@ -78,6 +82,10 @@ impl Chain for SequentialChain {
             }
         }
 
+        if !self.return_intermediate_outputs {
+            output_result.retain(|key, _| self.outputs.last() == Some(key));
+        }
+
         //add the filan token count to the result
         final_result.tokens = final_token_usage;
         output_result.insert(DEFAULT_RESULT_KEY.to_string(), json!(final_result));
@@ -87,12 +95,116 @@ impl Chain for SequentialChain {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use crate::{
-        chain::{Chain, LLMChainBuilder},
-        llm::openai::OpenAI,
+        chain::{Chain, ChainError, LLMChain, LLMChainBuilder, SequentialChainBuilder},
+        llm::{openai::OpenAI, FakeLLM},
+        prompt::{PromptTemplate, TemplateFormat},
         prompt_args, sequential_chain, template_fstring,
     };
 
+    // Builds a chain answering with its formatted prompt.
+    fn echo_chain(template: &str, output_key: &str) -> LLMChain {
+        LLMChainBuilder::new()
+            .prompt(PromptTemplate::from_template(template, TemplateFormat::FString).unwrap())
+            .llm(FakeLLM::new())
+            .output_key(output_key)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sequential_chain_outputs() {
+        let chain = SequentialChainBuilder::new()
+            .add_chain(echo_chain("Outline of {topic}", "outline"))
+            .add_chain(echo_chain("Expand {outline} for {audience}", "article"))
+            .return_intermediate_outputs(false)
+            .build();
+        let mut input_keys = chain.get_input_keys();
+        input_keys.sort();
+        assert_eq!(input_keys, vec!["audience", "topic"]);
+        assert_eq!(chain.get_output_keys(), vec!["article"]);
+
+        let output = chain
+            .execute(prompt_args! { "topic" => "Rust", "audience" => "kids" })
+            .await
+            .unwrap();
+        assert_eq!(output["article"], "Expand Outline of Rust for kids");
+        assert!(!output.contains_key("outline"));
+
+        let chain = sequential_chain!(
+            echo_chain("Outline of {topic}", "outline"),
+            echo_chain("Expand {outline}", "article"),
+        );
+        assert_eq!(
+            chain
+                .invoke(prompt_args! { "topic" => "Rust" })
+                .await
+                .unwrap(),
+            "Expand Outline of Rust"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sequential_chain_intermediate_outputs() {
+        let chain = SequentialChainBuilder::new()
+            .add_chain(echo_chain("Outline of {topic}", "outline"))
+            .add_chain(echo_chain("Expand {outline}", "article"))
+            .input_keys(vec!["topic"])
+            .try_build()
+            .unwrap();
+        assert_eq!(chain.get_output_keys(), vec!["outline", "article"]);
+        let output = chain
+            .execute(prompt_args! { "topic" => "Rust" })
+            .await
+            .unwrap();
+        assert_eq!(output["outline"], "Outline of Rust");
+        assert_eq!(output["article"], "Expand Outline of Rust");
+    }
+
+    #[test]
+    fn test_sequential_chain_validation() {
+        let result = SequentialChainBuilder::new()
+            .add_chain(echo_chain("Outline of {topic}", "outline"))
+            .add_chain(echo_chain(
+                "Expand {outline} in {language} for {audience}",
+                "article",
+            ))
+            .input_keys(vec!["topic", "language"])
+            .try_build();
+        match result {
+            Err(ChainError::MissingSequenceInputs { index, missing }) => {
+                assert_eq!(index, 1);
+                assert_eq!(missing, vec!["audience"]);
+            }
+            _ => panic!("expected missing sequence inputs"),
+        }
+
+        // variables with a default value don't have to be declared
+        let prompt = PromptTemplate::from_template(
+            "Expand {outline} in {language}",
+            TemplateFormat::FString,
+        )
+        .unwrap()
+        .with_defaults(HashMap::from([(
+            "language".to_string(),
+            "English".to_string(),
+        )]));
+        let with_default = LLMChainBuilder::new()
+            .prompt(prompt)
+            .llm(FakeLLM::new())
+            .output_key("article")
+            .build()
+            .unwrap();
+        let result = SequentialChainBuilder::new()
+            .add_chain(echo_chain("Outline of {topic}", "outline"))
+            .add_chain(with_default)
+            .input_keys(vec!["topic"])
+            .try_build();
+        assert!(result.is_ok());
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_sequential() {
@@ -1,5 +1,7 @@
 mod builder;
 mod chain;
+mod simple;
 
 pub use builder::*;
 pub use chain::*;
+pub use simple::*;
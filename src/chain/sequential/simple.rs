@@ -0,0 +1,184 @@
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::{
+    chain::{Chain, ChainError, DEFAULT_OUTPUT_KEY},
+    language_models::{GenerateResult, TokenUsage},
+    prompt::PromptArgs,
+};
+
+const DEFAULT_INPUT_KEY: &str = "input";
+
+pub struct SimpleSequentialChainBuilder {
+    chains: Vec<Box<dyn Chain>>,
+    input_key: Option<String>,
+    output_key: Option<String>,
+}
+
+impl Default for SimpleSequentialChainBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimpleSequentialChainBuilder {
+    pub fn new() -> Self {
+        Self {
+            chains: Vec::new(),
+            input_key: None,
+            output_key: None,
+        }
+    }
+
+    pub fn add_chain<C: Chain + 'static>(mut self, chain: C) -> Self {
+        self.chains.push(Box::new(chain));
+        self
+    }
+
+    /// Sets the variable the sequence is called with. Defaults to `input`.
+    pub fn input_key<S: Into<String>>(mut self, input_key: S) -> Self {
+        self.input_key = Some(input_key.into());
+        self
+    }
+
+    pub fn output_key<S: Into<String>>(mut self, output_key: S) -> Self {
+        self.output_key = Some(output_key.into());
+        self
+    }
+
+    /// # Errors
+    /// Returns `ChainError::MissingObject` without chains and `ChainError::OtherError` if a
+    /// chain doesn't take exactly one input variable.
+    pub fn build(self) -> Result<SimpleSequentialChain, ChainError> {
+        if self.chains.is_empty() {
+            return Err(ChainError::MissingObject(
+                "At least one chain must be added".into(),
+            ));
+        }
+        let mut input_keys = Vec::with_capacity(self.chains.len());
+        for (index, chain) in self.chains.iter().enumerate() {
+            match chain.get_input_keys().as_slice() {
+                [input_key] => input_keys.push(input_key.clone()),
+                keys => {
+                    return Err(ChainError::OtherError(format!(
+                        "Chain {} of a simple sequence must take exactly one input variable, it takes: {}",
+                        index,
+                        keys.join(", ")
+                    )))
+                }
+            }
+        }
+
+        Ok(SimpleSequentialChain {
+            chains: self.chains.into_iter().zip(input_keys).collect(),
+            input_key: self
+                .input_key
+                .unwrap_or_else(|| DEFAULT_INPUT_KEY.to_string()),
+            output_key: self
+                .output_key
+                .unwrap_or_else(|| DEFAULT_OUTPUT_KEY.to_string()),
+        })
+    }
+}
+
+/// Struct `SimpleSequentialChain` runs chains taking one input variable each, passing the
+/// output of every chain as the input of the next one.
+///
+/// # Usage
+/// ```rust,ignore
+/// let chain = SimpleSequentialChainBuilder::new()
+///     .add_chain(outline_chain) // "Write an outline about {topic}"
+///     .add_chain(expand_chain) // "Expand this outline: {outline}"
+///     .build()?;
+/// let article = chain.invoke(prompt_args! { "input" => "Rust" }).await?;
+/// ```
+pub struct SimpleSequentialChain {
+    // each chain with its input key
+    chains: Vec<(Box<dyn Chain>, String)>,
+    input_key: String,
+    output_key: String,
+}
+
+#[async_trait]
+impl Chain for SimpleSequentialChain {
+    async fn call(&self, input_variables: PromptArgs) -> Result<GenerateResult, ChainError> {
+        let input = match input_variables.get(&self.input_key) {
+            Some(input) => input,
+            None if input_variables.len() == 1 => input_variables.values().next().unwrap(),
+            None => return Err(ChainError::MissingInputVariable(self.input_key.clone())),
+        };
+        let mut text = match input {
+            Value::String(text) => text.clone(),
+            value => value.to_string(),
+        };
+
+        let mut tokens: Option<TokenUsage> = None;
+        for (chain, input_key) in self.chains.iter() {
+            let result = chain
+                .call(PromptArgs::from([(input_key.clone(), Value::String(text))]))
+                .await?;
+            log::debug!("{}", result.generation);
+            text = result.generation;
+            if let Some(usage) = result.tokens {
+                tokens = Some(tokens.map_or(usage.clone(), |tokens| tokens.sum(&usage)));
+            }
+        }
+
+        Ok(GenerateResult {
+            tokens,
+            generation: text,
+        })
+    }
+
+    fn get_input_keys(&self) -> Vec<String> {
+        vec![self.input_key.clone()]
+    }
+
+    fn get_output_keys(&self) -> Vec<String> {
+        vec![self.output_key.clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chain::LLMChainBuilder, llm::FakeLLM, prompt_args, template_fstring};
+
+    #[tokio::test]
+    async fn test_simple_sequential_chain() {
+        let outline = LLMChainBuilder::new()
+            .prompt(template_fstring!("Outline of {topic}", "topic"))
+            .llm(FakeLLM::new())
+            .build()
+            .unwrap();
+        let expand = LLMChainBuilder::new()
+            .prompt(template_fstring!("Expand {outline}", "outline"))
+            .llm(FakeLLM::new())
+            .build()
+            .unwrap();
+        let chain = SimpleSequentialChainBuilder::new()
+            .add_chain(outline)
+            .add_chain(expand)
+            .build()
+            .unwrap();
+        assert_eq!(
+            chain
+                .invoke(prompt_args! { "input" => "Rust" })
+                .await
+                .unwrap(),
+            "Expand Outline of Rust"
+        );
+
+        let two_inputs = LLMChainBuilder::new()
+            .prompt(template_fstring!("{a} {b}", "a", "b"))
+            .llm(FakeLLM::new())
+            .build()
+            .unwrap();
+        assert!(matches!(
+            SimpleSequentialChainBuilder::new()
+                .add_chain(two_inputs)
+                .build(),
+            Err(ChainError::OtherError(_))
+        ));
+    }
+}
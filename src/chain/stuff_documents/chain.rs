@@ -1,4 +1,4 @@
-use std::pin::Pin;
+use std::{pin::Pin, sync::Arc};
 
 use async_trait::async_trait;
 use futures::Stream;
@@ -9,7 +9,7 @@ use crate::{
         load_stuff_qa, options::ChainCallOptions, Chain, ChainError, LLMChain, StuffQAPromptBuilder,
     },
     language_models::{llm::LLM, GenerateResult},
-    prompt::PromptArgs,
+    prompt::{HeuristicTokenCounter, PromptArgs, PromptError, PromptTemplate, TokenCounter},
     schemas::{Document, StreamData},
 };
 
@@ -18,11 +18,41 @@ const COMBINE_DOCUMENTS_DEFAULT_OUTPUT_KEY: &str = "text";
 const COMBINE_DOCUMENTS_DEFAULT_DOCUMENT_VARIABLE_NAME: &str = "context";
 const STUFF_DOCUMENTS_DEFAULT_SEPARATOR: &str = "\n\n";
 
+/// What `StuffDocument` does with a document missing a variable of its document prompt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingMetadataBehavior {
+    /// Leave the document out.
+    #[default]
+    Skip,
+    /// Fail with `PromptError::MissingVariable`.
+    Error,
+}
+
+/// Struct `StuffDocument` renders documents into a single variable, `context` by default, of
+/// its inner `LLMChain`'s prompt.
+///
+/// Each document is rendered through the document prompt, which gets the `page_content` and
+/// metadata fields of the document as variables, and the results are joined with the
+/// separator. With a token budget, the documents after the last one fitting in it are dropped.
+///
+/// # Usage
+/// ```rust,ignore
+/// let chain = StuffDocument::new(llm_chain)
+///     .with_document_prompt(template_fstring!("[{source}] {page_content}", "source", "page_content"))
+///     .with_max_tokens(3000);
+/// let (answer, included) = chain
+///     .run(&documents, prompt_args! { "question" => "Who wrote it?" })
+///     .await?;
+/// ```
 pub struct StuffDocument {
     llm_chain: LLMChain,
     input_key: String,
     document_variable_name: String,
     separator: String,
+    document_prompt: Option<PromptTemplate>,
+    missing_metadata_behavior: MissingMetadataBehavior,
+    max_tokens: Option<usize>,
+    token_counter: Arc<dyn TokenCounter>,
 }
 
 impl StuffDocument {
@@ -32,14 +62,131 @@ impl StuffDocument {
             input_key: COMBINE_DOCUMENTS_DEFAULT_INPUT_KEY.to_string(),
             document_variable_name: COMBINE_DOCUMENTS_DEFAULT_DOCUMENT_VARIABLE_NAME.to_string(),
             separator: STUFF_DOCUMENTS_DEFAULT_SEPARATOR.to_string(),
+            document_prompt: None,
+            missing_metadata_behavior: MissingMetadataBehavior::default(),
+            max_tokens: None,
+            token_counter: Arc::new(HeuristicTokenCounter::default()),
         }
     }
 
-    fn join_documents(&self, docs: Vec<Document>) -> String {
-        docs.iter()
-            .map(|doc| doc.page_content.clone())
-            .collect::<Vec<_>>()
-            .join(&self.separator)
+    /// Sets the prompt each document is rendered with. Defaults to the page content alone.
+    pub fn with_document_prompt(mut self, document_prompt: PromptTemplate) -> Self {
+        self.document_prompt = Some(document_prompt);
+        self
+    }
+
+    pub fn with_separator<S: Into<String>>(mut self, separator: S) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Sets the variable of the inner chain's prompt getting the documents. Defaults to
+    /// `context`.
+    pub fn with_document_variable_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.document_variable_name = name.into();
+        self
+    }
+
+    /// Sets the input variable holding the documents when called as a `Chain`. Defaults to
+    /// `input_documents`.
+    pub fn with_input_key<S: Into<String>>(mut self, input_key: S) -> Self {
+        self.input_key = input_key.into();
+        self
+    }
+
+    pub fn with_missing_metadata_behavior(mut self, behavior: MissingMetadataBehavior) -> Self {
+        self.missing_metadata_behavior = behavior;
+        self
+    }
+
+    /// Sets the maximum number of tokens of the rendered documents.
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_token_counter<C: TokenCounter + 'static>(mut self, counter: C) -> Self {
+        self.token_counter = Arc::new(counter);
+        self
+    }
+
+    /// Renders the documents into the inner chain's prompt, with the other input variables,
+    /// and runs it. Returns the output and the number of documents included.
+    pub async fn run(
+        &self,
+        documents: &[Document],
+        input_variables: PromptArgs,
+    ) -> Result<(String, usize), ChainError> {
+        let (context, included) = self.join_documents(documents)?;
+        let mut input_variables = input_variables;
+        input_variables.insert(self.document_variable_name.clone(), Value::String(context));
+        Ok((self.llm_chain.run(input_variables).await?, included))
+    }
+
+    // Renders and joins the documents within the token budget, returning the number included.
+    fn join_documents(&self, documents: &[Document]) -> Result<(String, usize), ChainError> {
+        let mut context = String::new();
+        let mut tokens = 0;
+        let mut included = 0;
+        for document in documents {
+            let Some(rendered) = self.render_document(document)? else {
+                continue;
+            };
+            let separator = if included > 0 {
+                self.separator.as_str()
+            } else {
+                ""
+            };
+            if let Some(max_tokens) = self.max_tokens {
+                tokens += self.token_counter.count_tokens(separator)
+                    + self.token_counter.count_tokens(&rendered);
+                if tokens > max_tokens {
+                    break;
+                }
+            }
+            context.push_str(separator);
+            context.push_str(&rendered);
+            included += 1;
+        }
+        log::debug!("Stuffed {} of {} documents", included, documents.len());
+        Ok((context, included))
+    }
+
+    // Returns `None` for a document skipped for missing variables.
+    fn render_document(&self, document: &Document) -> Result<Option<String>, ChainError> {
+        let Some(prompt) = &self.document_prompt else {
+            return Ok(Some(document.page_content.clone()));
+        };
+        let mut variables: PromptArgs = document.metadata.clone();
+        variables.insert(
+            "page_content".to_string(),
+            Value::String(document.page_content.clone()),
+        );
+        let missing = prompt
+            .required_variables()
+            .into_iter()
+            .find(|variable| !variables.contains_key(variable));
+        match (missing, self.missing_metadata_behavior) {
+            (None, _) => Ok(Some(prompt.format(variables)?)),
+            (Some(variable), MissingMetadataBehavior::Skip) => {
+                log::debug!("Skipping a document without the `{}` variable", variable);
+                Ok(None)
+            }
+            (Some(variable), MissingMetadataBehavior::Error) => {
+                Err(PromptError::MissingVariable(variable).into())
+            }
+        }
+    }
+
+    // Reads the documents of the input variables.
+    fn input_documents(&self, input_variables: &PromptArgs) -> Result<Vec<Document>, ChainError> {
+        let docs = input_variables
+            .get(&self.input_key)
+            .ok_or_else(|| ChainError::MissingInputVariable(self.input_key.clone()))?;
+        serde_json::from_value(docs.clone()).map_err(|e| ChainError::IncorrectInputVariable {
+            source: e,
+            expected_type: "Vec<Document>".to_string(),
+        })
     }
 
     pub fn qa_prompt_builder<'a>(&self) -> StuffQAPromptBuilder<'a> {
@@ -116,21 +263,11 @@ impl StuffDocument {
 #[async_trait]
 impl Chain for StuffDocument {
     async fn call(&self, input_variables: PromptArgs) -> Result<GenerateResult, ChainError> {
-        let docs = input_variables
-            .get(&self.input_key)
-            .ok_or_else(|| ChainError::MissingInputVariable(self.input_key.clone()))?;
-
-        let documents: Vec<Document> = serde_json::from_value(docs.clone()).map_err(|e| {
-            ChainError::IncorrectInputVariable {
-                source: e,
-                expected_type: "Vec<Document>".to_string(),
-            }
-        })?;
-
+        let documents = self.input_documents(&input_variables)?;
         let mut input_values = input_variables.clone();
         input_values.insert(
             self.document_variable_name.clone(),
-            Value::String(self.join_documents(documents)),
+            Value::String(self.join_documents(&documents)?.0),
         );
 
         self.llm_chain.call(input_values).await
@@ -141,21 +278,11 @@ impl Chain for StuffDocument {
         input_variables: PromptArgs,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamData, ChainError>> + Send>>, ChainError>
     {
-        let docs = input_variables
-            .get(&self.input_key)
-            .ok_or_else(|| ChainError::MissingInputVariable(self.input_key.clone()))?;
-
-        let documents: Vec<Document> = serde_json::from_value(docs.clone()).map_err(|e| {
-            ChainError::IncorrectInputVariable {
-                source: e,
-                expected_type: "Vec<Document>".to_string(),
-            }
-        })?;
-
+        let documents = self.input_documents(&input_variables)?;
         let mut input_values = input_variables.clone();
         input_values.insert(
             self.document_variable_name.clone(),
-            Value::String(self.join_documents(documents)),
+            Value::String(self.join_documents(&documents)?.0),
         );
         self.llm_chain.stream(input_values).await
     }
@@ -164,3 +291,87 @@ impl Chain for StuffDocument {
         vec![self.input_key.clone()]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        chain::LLMChainBuilder, llm::FakeLLM, prompt::HeuristicTokenCounter, prompt_args,
+        template_fstring,
+    };
+
+    fn chain() -> StuffDocument {
+        let llm_chain = LLMChainBuilder::new()
+            .prompt(template_fstring!(
+                "{context}\nQuestion: {question}",
+                "context",
+                "question"
+            ))
+            .llm(FakeLLM::new())
+            .build()
+            .unwrap();
+        StuffDocument::new(llm_chain).with_document_prompt(template_fstring!(
+            "[{source}] {page_content}",
+            "source",
+            "page_content"
+        ))
+    }
+
+    fn documents() -> Vec<Document> {
+        let source = |source: &str| HashMap::from([("source".to_string(), json!(source))]);
+        vec![
+            Document::new("Rust is fast").with_metadata(source("a.md")),
+            Document::new("No source"),
+            Document::new("Rust is safe").with_metadata(source("b.md")),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_stuff_documents_run() {
+        let (output, included) = chain()
+            .run(&documents(), prompt_args! { "question" => "Why Rust?" })
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            "[a.md] Rust is fast\n\n[b.md] Rust is safe\nQuestion: Why Rust?"
+        );
+        assert_eq!(included, 2);
+
+        let result = chain()
+            .with_missing_metadata_behavior(MissingMetadataBehavior::Error)
+            .run(&documents(), prompt_args! { "question" => "Why Rust?" })
+            .await;
+        assert!(matches!(
+            result,
+            Err(ChainError::PromptError(PromptError::MissingVariable(variable))) if variable == "source"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stuff_documents_token_budget() {
+        let chain = chain()
+            .with_separator(" | ")
+            .with_max_tokens(8)
+            .with_token_counter(HeuristicTokenCounter::Words);
+        let (output, included) = chain
+            .run(&documents(), prompt_args! { "question" => "Why?" })
+            .await
+            .unwrap();
+        assert_eq!(output, "[a.md] Rust is fast\nQuestion: Why?");
+        assert_eq!(included, 1);
+
+        let input = prompt_args! {
+            "input_documents" => documents(),
+            "question" => "Why?",
+        };
+        assert_eq!(
+            chain.with_max_tokens(9).invoke(input).await.unwrap(),
+            "[a.md] Rust is fast | [b.md] Rust is safe\nQuestion: Why?"
+        );
+    }
+}
@@ -21,4 +21,11 @@ pub enum EmbedderError {
 
     #[error("FastEmbed error: {0}")]
     FastEmbedError(String),
+
+    #[error("Embedding documents {indices:?} failed: {source}")]
+    BatchError {
+        indices: Vec<usize>,
+        #[source]
+        source: Box<EmbedderError>,
+    },
 }
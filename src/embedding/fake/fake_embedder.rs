@@ -0,0 +1,88 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use async_trait::async_trait;
+
+use crate::embedding::{embedder_trait::Embedder, EmbedderError};
+
+/// Struct `FakeEmbedder` embeds texts without a model, for tests and examples. Each word is
+/// hashed into one of the dimensions and the vector is normalized, so the same text always
+/// gets the same embedding and texts sharing words are similar.
+#[derive(Debug, Clone)]
+pub struct FakeEmbedder {
+    dimensions: usize,
+}
+
+impl Default for FakeEmbedder {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+impl FakeEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    fn embed(&self, text: &str) -> Vec<f64> {
+        let mut embedding = vec![0.0; self.dimensions];
+        for word in text.split_whitespace() {
+            let mut hasher = DefaultHasher::new();
+            word.to_lowercase().hash(&mut hasher);
+            embedding[(hasher.finish() % self.dimensions as u64) as usize] += 1.0;
+        }
+        let norm = embedding.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            embedding.iter_mut().for_each(|x| *x /= norm);
+        }
+        embedding
+    }
+}
+
+impl From<FakeEmbedder> for Box<dyn Embedder> {
+    fn from(embedder: FakeEmbedder) -> Self {
+        Box::new(embedder)
+    }
+}
+
+#[async_trait]
+impl Embedder for FakeEmbedder {
+    async fn embed_documents(&self, documents: &[String]) -> Result<Vec<Vec<f64>>, EmbedderError> {
+        Ok(documents
+            .iter()
+            .map(|document| self.embed(document))
+            .collect())
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f64>, EmbedderError> {
+        Ok(self.embed(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosine(a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b).map(|(a, b)| a * b).sum()
+    }
+
+    #[tokio::test]
+    async fn test_fake_embedder() {
+        let embedder = FakeEmbedder::new(16);
+        let query = embedder.embed_query("rust is fast").await.unwrap();
+        assert_eq!(query.len(), 16);
+        assert_eq!(query, embedder.embed_query("Rust is FAST").await.unwrap());
+
+        let documents = embedder
+            .embed_documents(&["rust is fast".into(), "bananas".into()])
+            .await
+            .unwrap();
+        assert!((cosine(&query, &documents[0]) - 1.0).abs() < 1e-9);
+        assert!(cosine(&query, &documents[1]) < 1.0);
+    }
+}
@@ -7,3 +7,6 @@ pub use error::*;
 
 mod fastembed;
 pub use fastembed::*;
+
+mod fake;
+pub use fake::*;
@@ -7,11 +7,18 @@ use async_openai::{
     Client,
 };
 use async_trait::async_trait;
+use futures::future::join_all;
 
+/// Struct `OpenAiEmbedder` embeds texts with the embeddings API of OpenAI, or of any
+/// compatible server by setting the base URL of its config.
+///
+/// Documents are sent in batches of `batch_size`, concurrently. Rate limited requests are
+/// retried by the client with exponential backoff.
 #[derive(Debug)]
 pub struct OpenAiEmbedder<C: Config> {
     config: C,
     model: String,
+    batch_size: usize,
 }
 
 impl<C: Config + Send + Sync + 'static> Into<Box<dyn Embedder>> for OpenAiEmbedder<C> {
@@ -25,6 +32,7 @@ impl<C: Config> OpenAiEmbedder<C> {
         OpenAiEmbedder {
             config,
             model: String::from("text-embedding-ada-002"),
+            batch_size: 512,
         }
     }
 
@@ -37,6 +45,30 @@ impl<C: Config> OpenAiEmbedder<C> {
         self.config = config;
         self
     }
+
+    /// Sets the number of documents embedded per request. Defaults to 512.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    async fn embed_batch(
+        &self,
+        client: &Client<C>,
+        documents: &[String],
+    ) -> Result<Vec<Vec<f64>>, EmbedderError> {
+        let request = CreateEmbeddingRequestArgs::default()
+            .model(&self.model)
+            .input(EmbeddingInput::StringArray(documents.into()))
+            .build()?;
+
+        let mut data = client.embeddings().create(request).await?.data;
+        data.sort_by_key(|item| item.index);
+        Ok(data
+            .into_iter()
+            .map(|item| item.embedding.into_iter().map(|x| x as f64).collect())
+            .collect())
+    }
 }
 
 impl Default for OpenAiEmbedder<OpenAIConfig> {
@@ -47,29 +79,40 @@ impl Default for OpenAiEmbedder<OpenAIConfig> {
 
 #[async_trait]
 impl<C: Config + Send + Sync> Embedder for OpenAiEmbedder<C> {
+    /// # Errors
+    /// Returns `EmbedderError::BatchError` with the indices of the documents of the failed
+    /// batches and the error of the first one.
     async fn embed_documents(&self, documents: &[String]) -> Result<Vec<Vec<f64>>, EmbedderError> {
         let client = Client::with_config(self.config.clone());
+        let batches = documents.chunks(self.batch_size);
+        let results = join_all(batches.map(|batch| self.embed_batch(&client, batch))).await;
+
+        let mut embeddings = Vec::with_capacity(documents.len());
+        let mut failed: Option<(Vec<usize>, EmbedderError)> = None;
+        for (i, result) in results.into_iter().enumerate() {
+            let start = i * self.batch_size;
+            match (result, &mut failed) {
+                (Ok(batch), None) => embeddings.extend(batch),
+                (Ok(_), Some(_)) => {}
+                (Err(e), None) => {
+                    failed = Some((
+                        (start..documents.len().min(start + self.batch_size)).collect(),
+                        e,
+                    ))
+                }
+                (Err(_), Some((indices, _))) => {
+                    indices.extend(start..documents.len().min(start + self.batch_size))
+                }
+            }
+        }
 
-        let request = CreateEmbeddingRequestArgs::default()
-            .model(&self.model)
-            .input(EmbeddingInput::StringArray(documents.into()))
-            .build()?;
-
-        let response = client.embeddings().create(request).await?;
-
-        let embeddings = response
-            .data
-            .into_iter()
-            .map(|item| item.embedding)
-            .map(|embedding| {
-                embedding
-                    .into_iter()
-                    .map(|x| x as f64)
-                    .collect::<Vec<f64>>()
-            })
-            .collect();
-
-        Ok(embeddings)
+        match failed {
+            None => Ok(embeddings),
+            Some((indices, source)) => Err(EmbedderError::BatchError {
+                indices,
+                source: Box::new(source),
+            }),
+        }
     }
 
     async fn embed_query(&self, text: &str) -> Result<Vec<f64>, EmbedderError> {
@@ -91,3 +134,68 @@ impl<C: Config + Send + Sync> Embedder for OpenAiEmbedder<C> {
             .collect::<Vec<f64>>())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_embed_documents_in_batches() {
+        let mut server = mockito::Server::new_async().await;
+        let embeddings = |values: &[f32]| {
+            json!({
+                "object": "list",
+                "model": "text-embedding-ada-002",
+                // out of order, sorted by index
+                "data": values.iter().enumerate().rev().map(|(i, v)| json!({
+                    "object": "embedding", "index": i, "embedding": [v, v]
+                })).collect::<Vec<_>>(),
+                "usage": {"prompt_tokens": 2, "total_tokens": 2}
+            })
+            .to_string()
+        };
+        server
+            .mock("POST", "/v1/embeddings")
+            .match_body(Matcher::PartialJson(json!({"input": ["a", "b"]})))
+            .with_body(embeddings(&[1.0, 2.0]))
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/v1/embeddings")
+            .match_body(Matcher::PartialJson(json!({"input": ["c"]})))
+            .with_body(embeddings(&[3.0]))
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/v1/embeddings")
+            .match_body(Matcher::PartialJson(json!({"input": ["d", "e"]})))
+            .with_status(400)
+            .with_body(
+                json!({"error": {"message": "Invalid input", "type": "invalid_request_error", "param": null, "code": null}})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let embedder =
+            OpenAiEmbedder::new(OpenAIConfig::new().with_api_base(format!("{}/v1", server.url())))
+                .with_batch_size(2);
+        let documents = |texts: &[&str]| texts.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let result = embedder
+            .embed_documents(&documents(&["a", "b", "c"]))
+            .await
+            .unwrap();
+        assert_eq!(result, vec![vec![1.0, 1.0], vec![2.0, 2.0], vec![3.0, 3.0]]);
+
+        match embedder
+            .embed_documents(&documents(&["a", "b", "d", "e"]))
+            .await
+        {
+            Err(EmbedderError::BatchError { indices, .. }) => assert_eq!(indices, vec![2, 3]),
+            other => panic!("expected a batch error, got {:?}", other),
+        }
+    }
+}
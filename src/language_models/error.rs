@@ -1,6 +1,6 @@
 use async_openai::error::OpenAIError;
 use reqwest::Error as ReqwestError;
-use serde_json::Error as SerdeJsonError;
+use serde_json::{Error as SerdeJsonError, Value};
 use thiserror::Error;
 use tokio::time::error::Elapsed;
 
@@ -9,7 +9,20 @@ use crate::llm::AnthropicError;
 #[derive(Error, Debug)]
 pub enum LLMError {
     #[error("OpenAI error: {0}")]
-    OpenAIError(#[from] OpenAIError),
+    OpenAIError(OpenAIError),
+
+    #[error("Rate limit exceeded: {0}")]
+    RateLimitError(String),
+
+    /// The account ran out of credits, which unlike a rate limit isn't worth retrying.
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("Authentication failed: {0}")]
+    AuthenticationError(String),
+
+    #[error("Context length exceeded: {0}")]
+    ContextLengthExceeded(String),
 
     #[error("Anthropic error: {0}")]
     AnthropicError(#[from] AnthropicError),
@@ -35,3 +48,36 @@ pub enum LLMError {
     #[error("Error: {0}")]
     OtherError(String),
 }
+
+impl From<OpenAIError> for LLMError {
+    /// Maps the API errors of OpenAI, and of compatible servers, that callers usually handle
+    /// differently to their own variants.
+    fn from(error: OpenAIError) -> Self {
+        if let OpenAIError::ApiError(api_error) = &error {
+            let code = api_error.code.as_ref().and_then(Value::as_str);
+            let kind = api_error.r#type.as_deref();
+            let message = api_error.message.clone();
+            match (code, kind) {
+                (Some("insufficient_quota"), _) | (_, Some("insufficient_quota")) => {
+                    return LLMError::QuotaExceeded(message)
+                }
+                (Some("rate_limit_exceeded"), _)
+                | (_, Some("requests" | "tokens" | "rate_limit_error")) => {
+                    return LLMError::RateLimitError(message)
+                }
+                (Some("invalid_api_key" | "invalid_authentication"), _)
+                | (_, Some("authentication_error")) => {
+                    return LLMError::AuthenticationError(message)
+                }
+                (Some("context_length_exceeded"), _) => {
+                    return LLMError::ContextLengthExceeded(message)
+                }
+                _ if message.contains("maximum context length") => {
+                    return LLMError::ContextLengthExceeded(message)
+                }
+                _ => {}
+            }
+        }
+        LLMError::OpenAIError(error)
+    }
+}
@@ -20,6 +20,11 @@ pub trait LLM: Sync + Send + LLMClone {
         _messages: &[Message],
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamData, LLMError>> + Send>>, LLMError>;
 
+    /// Returns the name of the model, used to break down the token usage by model.
+    fn model_name(&self) -> String {
+        "unknown".to_string()
+    }
+
     /// This is usefull when you want to create a chain and override
     /// LLM options
     fn add_options(&mut self, _options: CallOptions) {
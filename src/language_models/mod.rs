@@ -8,6 +8,9 @@ pub mod options;
 mod error;
 pub use error::*;
 
+mod usage;
+pub use usage::*;
+
 //TODO: check if its this should have a data:serde::Value to save all other things, like OpenAI
 //function responses
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::TokenUsage;
+
+/// The tokens used by the model calls recorded by a `UsageTracker`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub requests: u64,
+}
+
+impl ModelUsage {
+    fn add(&mut self, usage: &TokenUsage) {
+        self.prompt_tokens += usage.prompt_tokens as u64;
+        self.completion_tokens += usage.completion_tokens as u64;
+        self.total_tokens += usage.total_tokens as u64;
+        self.requests += 1;
+    }
+}
+
+/// The totals of a `UsageTracker`, and the totals of each model.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageSnapshot {
+    pub total: ModelUsage,
+    pub by_model: HashMap<String, ModelUsage>,
+}
+
+/// Struct `UsageTracker` sums the tokens used by model calls. Share it between chains with an
+/// `Arc`; the totals are atomic counters.
+///
+/// # Usage
+/// ```rust,ignore
+/// let tracker = Arc::new(UsageTracker::new());
+/// let chain = LLMChainBuilder::new()
+///     .prompt(prompt)
+///     .llm(llm)
+///     .build()?
+///     .with_usage_tracking(tracker.clone());
+/// chain.invoke(input).await?;
+/// println!("{} tokens", tracker.snapshot().total.total_tokens);
+/// ```
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    prompt_tokens: AtomicU64,
+    completion_tokens: AtomicU64,
+    total_tokens: AtomicU64,
+    requests: AtomicU64,
+    by_model: Mutex<HashMap<String, ModelUsage>>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the tokens used by a call to the model.
+    pub fn record(&self, model: &str, usage: &TokenUsage) {
+        self.prompt_tokens
+            .fetch_add(usage.prompt_tokens as u64, Ordering::Relaxed);
+        self.completion_tokens
+            .fetch_add(usage.completion_tokens as u64, Ordering::Relaxed);
+        self.total_tokens
+            .fetch_add(usage.total_tokens as u64, Ordering::Relaxed);
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.by_model
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(model.to_string())
+            .or_default()
+            .add(usage);
+    }
+
+    pub fn snapshot(&self) -> UsageSnapshot {
+        UsageSnapshot {
+            total: ModelUsage {
+                prompt_tokens: self.prompt_tokens.load(Ordering::Relaxed),
+                completion_tokens: self.completion_tokens.load(Ordering::Relaxed),
+                total_tokens: self.total_tokens.load(Ordering::Relaxed),
+                requests: self.requests.load(Ordering::Relaxed),
+            },
+            by_model: self
+                .by_model
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.prompt_tokens.store(0, Ordering::Relaxed);
+        self.completion_tokens.store(0, Ordering::Relaxed);
+        self.total_tokens.store(0, Ordering::Relaxed);
+        self.requests.store(0, Ordering::Relaxed);
+        self.by_model
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+tokio::task_local! {
+    static SCOPED_TRACKERS: Vec<Arc<UsageTracker>>;
+}
+
+/// Runs the block and returns the tokens used by the chains it runs, along with its output.
+/// Scopes can be nested, and the calls are recorded in every enclosing scope.
+///
+/// Only the calls made by the block's own task are tracked, not those of tasks it spawns.
+///
+/// # Usage
+/// ```rust,ignore
+/// let (answer, usage) = track_usage(|| async { chain.invoke(input).await }).await;
+/// println!("{} tokens", usage.total.total_tokens);
+/// ```
+pub async fn track_usage<F, Fut, T>(block: F) -> (T, UsageSnapshot)
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = T>,
+{
+    let tracker = Arc::new(UsageTracker::new());
+    let mut trackers = scoped_trackers();
+    trackers.push(tracker.clone());
+    let output = SCOPED_TRACKERS.scope(trackers, block()).await;
+    (output, tracker.snapshot())
+}
+
+// Returns the trackers of the enclosing `track_usage` scopes.
+pub(crate) fn scoped_trackers() -> Vec<Arc<UsageTracker>> {
+    SCOPED_TRACKERS
+        .try_with(|trackers| trackers.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(prompt_tokens: u32, completion_tokens: u32) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_usage_tracker() {
+        let tracker = UsageTracker::new();
+        tracker.record("gpt-4", &usage(10, 5));
+        tracker.record("gpt-4", &usage(3, 2));
+        tracker.record("gpt-3.5-turbo", &usage(1, 1));
+
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.total.total_tokens, 22);
+        assert_eq!(snapshot.total.requests, 3);
+        assert_eq!(
+            snapshot.by_model["gpt-4"],
+            ModelUsage {
+                prompt_tokens: 13,
+                completion_tokens: 7,
+                total_tokens: 20,
+                requests: 2
+            }
+        );
+
+        tracker.reset();
+        assert_eq!(tracker.snapshot(), UsageSnapshot::default());
+    }
+
+    #[tokio::test]
+    async fn test_track_usage_scopes() {
+        let ((_, inner), outer) = track_usage(|| async {
+            for tracker in scoped_trackers() {
+                tracker.record("a", &usage(1, 1));
+            }
+            track_usage(|| async {
+                for tracker in scoped_trackers() {
+                    tracker.record("b", &usage(2, 2));
+                }
+            })
+            .await
+        })
+        .await;
+        assert_eq!(inner.total.total_tokens, 4);
+        assert_eq!(outer.total.total_tokens, 6);
+        assert!(scoped_trackers().is_empty());
+    }
+}
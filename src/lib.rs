@@ -1,5 +1,9 @@
 #![allow(dead_code)]
+// lets the code generated by the derive macros name the crate in its own tests
+extern crate self as langchain_rust;
+
 pub mod agent;
+pub mod callbacks;
 pub mod chain;
 pub mod document_loaders;
 pub mod embedding;
@@ -202,6 +202,10 @@ impl LLM for Claude {
         Ok(Box::pin(processed_stream))
     }
 
+    fn model_name(&self) -> String {
+        self.model.clone()
+    }
+
     fn add_options(&mut self, options: CallOptions) {
         self.options.merge_options(options)
     }
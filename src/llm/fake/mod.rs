@@ -0,0 +1,130 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use futures::{stream, Stream, StreamExt};
+use serde_json::Value;
+
+use crate::{
+    language_models::{llm::LLM, GenerateResult, LLMError, TokenUsage},
+    schemas::{Message, StreamData},
+};
+
+/// Struct `FakeLLM` answers without any network access, for tests and examples. It echoes the
+/// content of the messages it gets, one per line, unless it was given responses, which it
+/// returns in turn before echoing again.
+///
+/// It reports the words of the messages and of the response as the tokens used, the final
+/// chunk of a stream carrying them.
+///
+/// # Usage
+/// ```rust,ignore
+/// let chain = LLMChainBuilder::new()
+///     .prompt(template_fstring!("Hello {name}", "name"))
+///     .llm(FakeLLM::new())
+///     .build()?;
+/// assert_eq!(chain.run(prompt_args! { "name" => "Luis" }).await?, "Hello Luis");
+/// ```
+#[derive(Clone, Default)]
+pub struct FakeLLM {
+    responses: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakeLLM {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the responses returned in turn, the first one first.
+    pub fn with_responses<S: Into<String>>(self, responses: Vec<S>) -> Self {
+        let mut responses: Vec<String> = responses.into_iter().map(Into::into).collect();
+        responses.reverse();
+        *self.responses.lock().unwrap_or_else(|e| e.into_inner()) = responses;
+        self
+    }
+
+    fn respond(&self, messages: &[Message]) -> String {
+        self.responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop()
+            .unwrap_or_else(|| {
+                messages
+                    .iter()
+                    .map(|m| m.content.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\n")
+            })
+    }
+}
+
+#[async_trait]
+impl LLM for FakeLLM {
+    async fn generate(&self, messages: &[Message]) -> Result<GenerateResult, LLMError> {
+        let generation = self.respond(messages);
+        Ok(GenerateResult {
+            tokens: Some(usage(messages, &generation)),
+            generation,
+        })
+    }
+
+    /// Streams the response a word at a time.
+    async fn stream(
+        &self,
+        messages: &[Message],
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamData, LLMError>> + Send>>, LLMError> {
+        let response = self.respond(messages);
+        let mut chunks: Vec<StreamData> = response
+            .split_inclusive(' ')
+            .map(|chunk| StreamData::new(Value::String(chunk.to_string()), chunk))
+            .collect();
+        if let Some(last) = chunks.pop() {
+            chunks.push(last.with_tokens(usage(messages, &response)));
+        }
+        Ok(Box::pin(stream::iter(chunks).map(Ok)))
+    }
+
+    fn model_name(&self) -> String {
+        "fake".to_string()
+    }
+}
+
+fn usage(messages: &[Message], generation: &str) -> TokenUsage {
+    let prompt_tokens = messages
+        .iter()
+        .map(|m| m.content.split_whitespace().count() as u32)
+        .sum();
+    let completion_tokens = generation.split_whitespace().count() as u32;
+    TokenUsage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fake_llm() {
+        let llm = FakeLLM::new();
+        assert_eq!(llm.invoke("Hello there").await.unwrap(), "Hello there");
+
+        let llm = llm.with_responses(vec!["first", "second"]);
+        assert_eq!(llm.invoke("a").await.unwrap(), "first");
+        assert_eq!(llm.invoke("b").await.unwrap(), "second");
+        assert_eq!(llm.invoke("c").await.unwrap(), "c");
+
+        let chunks: Vec<String> = llm
+            .stream(&[Message::new_human_message("one two three")])
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap().content)
+            .collect()
+            .await;
+        assert_eq!(chunks, vec!["one ", "two ", "three"]);
+    }
+}
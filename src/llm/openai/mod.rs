@@ -2,22 +2,29 @@ use std::pin::Pin;
 
 pub use async_openai::config::{AzureConfig, Config, OpenAIConfig};
 use async_openai::{
+    error::{ApiError, OpenAIError},
     types::{
         ChatChoiceStream, ChatCompletionMessageToolCall, ChatCompletionRequestAssistantMessageArgs,
-        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
+        ChatCompletionRequestMessage, ChatCompletionRequestMessageContentPartImage,
+        ChatCompletionRequestMessageContentPartText, ChatCompletionRequestSystemMessageArgs,
         ChatCompletionRequestToolMessageArgs, ChatCompletionRequestUserMessageArgs,
-        ChatCompletionToolArgs, ChatCompletionToolType, CreateChatCompletionRequest,
-        CreateChatCompletionRequestArgs, FunctionObjectArgs,
+        ChatCompletionRequestUserMessageContent, ChatCompletionToolArgs, ChatCompletionToolType,
+        CreateChatCompletionRequest, CreateChatCompletionRequestArgs, FunctionObjectArgs, ImageUrl,
+        ImageUrlDetail,
     },
     Client,
 };
+use async_stream::stream;
 use async_trait::async_trait;
 use futures::{Stream, StreamExt};
+use reqwest_eventsource::{retry, Error as EventSourceError, Event, EventSource};
+use serde::Deserialize;
+use serde_json::Value;
 
 use crate::{
     language_models::{llm::LLM, options::CallOptions, GenerateResult, LLMError, TokenUsage},
     schemas::{
-        messages::{Message, MessageType},
+        messages::{ContentPart, ImageDetail, Message, MessageType},
         FunctionCallBehavior, StreamData,
     },
 };
@@ -45,11 +52,22 @@ impl Into<String> for OpenAIModel {
     }
 }
 
+/// Struct `OpenAI` calls the chat completions API of OpenAI, or of any compatible server, such
+/// as a local one, by setting the base URL of its config.
+///
+/// # Usage
+/// ```rust,ignore
+/// let llm = OpenAI::new(OpenAIConfig::new().with_api_base("http://localhost:8000/v1"))
+///     .with_model("llama3")
+///     .with_options(CallOptions::new().with_temperature(0.2).with_max_tokens(256));
+/// let result = llm.generate(&[Message::new_human_message("Hi")]).await?;
+/// ```
 #[derive(Clone)]
 pub struct OpenAI<C: Config> {
     config: C,
     options: CallOptions,
     model: String,
+    stream_usage: Option<bool>,
 }
 
 impl<C: Config> OpenAI<C> {
@@ -58,6 +76,7 @@ impl<C: Config> OpenAI<C> {
             config,
             options: CallOptions::default(),
             model: OpenAIModel::Gpt35.to_string(),
+            stream_usage: None,
         }
     }
 
@@ -75,6 +94,19 @@ impl<C: Config> OpenAI<C> {
         self.options = options;
         self
     }
+
+    /// Sets whether `stream` asks for the token usage of the completion with `stream_options`,
+    /// which comes in a last chunk. By default it only does for api.openai.com, since some
+    /// compatible servers reject the option.
+    pub fn with_stream_usage(mut self, stream_usage: bool) -> Self {
+        self.stream_usage = Some(stream_usage);
+        self
+    }
+
+    fn includes_stream_usage(&self) -> bool {
+        self.stream_usage
+            .unwrap_or_else(|| self.config.api_base().starts_with("https://api.openai.com"))
+    }
 }
 
 impl Default for OpenAI<OpenAIConfig> {
@@ -151,34 +183,77 @@ impl<C: Config + Send + Sync + 'static> LLM for OpenAI<C> {
             .map(|res| res.generation)
     }
 
+    /// Streams the completion from the server-sent events of the API. The request is owned
+    /// by the returned stream, so dropping the stream closes the connection.
     async fn stream(
         &self,
         messages: &[Message],
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamData, LLMError>> + Send>>, LLMError> {
-        let client = Client::with_config(self.config.clone());
-        let request = self.generate_request(messages)?;
-
-        let original_stream = client.chat().create_stream(request).await?;
-
-        let new_stream = original_stream.map(|result| match result {
-            Ok(completion) => {
-                let value_completion = serde_json::to_value(completion).map_err(LLMError::from)?;
-                let content = value_completion
-                    .pointer("/choices/0/delta/content")
-                    .ok_or(LLMError::ContentNotFound(
-                        "/choices/0/delta/content".to_string(),
-                    ))?
-                    .clone();
-
-                Ok(StreamData::new(
-                    value_completion,
-                    content.as_str().unwrap_or(""),
-                ))
+        let mut body = serde_json::to_value(self.generate_request(messages)?)?;
+        body["stream"] = Value::Bool(true);
+        if self.includes_stream_usage() {
+            body["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+        let request = reqwest::Client::new()
+            .post(self.config.url("/chat/completions"))
+            .query(&self.config.query())
+            .headers(self.config.headers())
+            .json(&body);
+        let mut event_source = EventSource::new(request)
+            .map_err(|e| LLMError::OtherError(format!("Cannot stream the request: {}", e)))?;
+        event_source.set_retry_policy(Box::new(retry::Never));
+
+        let stream = stream! {
+            while let Some(event) = event_source.next().await {
+                match event {
+                    Ok(Event::Open) => continue,
+                    Ok(Event::Message(message)) if message.data == "[DONE]" => break,
+                    Ok(Event::Message(message)) => {
+                        let value: Value = match serde_json::from_str(&message.data) {
+                            Ok(value) => value,
+                            Err(e) => {
+                                yield Err(LLMError::from(e));
+                                break;
+                            }
+                        };
+                        let content = value
+                            .pointer("/choices/0/delta/content")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                            .to_string();
+                        let tokens = value
+                            .get("usage")
+                            .filter(|usage| !usage.is_null())
+                            .and_then(|usage| serde_json::from_value::<TokenUsage>(usage.clone()).ok());
+                        let data = StreamData::new(value, content);
+                        yield Ok(match tokens {
+                            Some(tokens) => data.with_tokens(tokens),
+                            None => data,
+                        });
+                    }
+                    Err(EventSourceError::StreamEnded) => break,
+                    Err(EventSourceError::InvalidStatusCode(status, response)) => {
+                        let body = response.text().await.unwrap_or_default();
+                        yield Err(match serde_json::from_str::<WrappedApiError>(&body) {
+                            Ok(wrapped) => LLMError::from(OpenAIError::ApiError(wrapped.error)),
+                            Err(_) => LLMError::OtherError(format!("{}: {}", status, body)),
+                        });
+                        break;
+                    }
+                    Err(e) => {
+                        yield Err(LLMError::OtherError(e.to_string()));
+                        break;
+                    }
+                }
             }
-            Err(e) => Err(LLMError::from(e)),
-        });
+            event_source.close();
+        };
 
-        Ok(Box::pin(new_stream))
+        Ok(Box::pin(stream))
+    }
+
+    fn model_name(&self) -> String {
+        self.model.clone()
     }
 
     fn add_options(&mut self, options: CallOptions) {
@@ -186,6 +261,12 @@ impl<C: Config + Send + Sync + 'static> LLM for OpenAI<C> {
     }
 }
 
+// The body of the API's error responses.
+#[derive(Deserialize)]
+struct WrappedApiError {
+    error: ApiError,
+}
+
 impl<C: Config> OpenAI<C> {
     fn to_openai_messages(
         &self,
@@ -211,7 +292,7 @@ impl<C: Config> OpenAI<C> {
                 }),
                 MessageType::HumanMessage => openai_messages.push(
                     ChatCompletionRequestUserMessageArgs::default()
-                        .content(m.content.clone())
+                        .content(to_openai_user_content(m))
                         .build()?
                         .into(),
                 ),
@@ -244,6 +325,18 @@ impl<C: Config> OpenAI<C> {
         if let Some(max_tokens) = self.options.max_tokens {
             request_builder.max_tokens(max_tokens);
         }
+        if let Some(temperature) = self.options.temperature {
+            request_builder.temperature(temperature);
+        }
+        if let Some(top_p) = self.options.top_p {
+            request_builder.top_p(top_p);
+        }
+        if let Some(frequency_penalty) = self.options.frequency_penalty {
+            request_builder.frequency_penalty(frequency_penalty);
+        }
+        if let Some(presence_penalty) = self.options.presence_penalty {
+            request_builder.presence_penalty(presence_penalty);
+        }
         request_builder.model(self.model.to_string());
         if let Some(stop_words) = &self.options.stop_words {
             request_builder.stop(stop_words);
@@ -277,6 +370,37 @@ impl<C: Config> OpenAI<C> {
         Ok(request_builder.build()?)
     }
 }
+
+// The content of a user message: a `content` array for multimodal messages, its text otherwise.
+fn to_openai_user_content(message: &Message) -> ChatCompletionRequestUserMessageContent {
+    let Some(parts) = &message.parts else {
+        return ChatCompletionRequestUserMessageContent::Text(message.content.clone());
+    };
+    let parts = parts
+        .iter()
+        .map(|part| match part {
+            ContentPart::Text { text } => ChatCompletionRequestMessageContentPartText {
+                r#type: "text".into(),
+                text: text.clone(),
+            }
+            .into(),
+            ContentPart::ImageUrl { url, detail } => ChatCompletionRequestMessageContentPartImage {
+                r#type: "image_url".into(),
+                image_url: ImageUrl {
+                    url: url.clone(),
+                    detail: match detail.unwrap_or_default() {
+                        ImageDetail::Auto => ImageUrlDetail::Auto,
+                        ImageDetail::Low => ImageUrlDetail::Low,
+                        ImageDetail::High => ImageUrlDetail::High,
+                    },
+                },
+            }
+            .into(),
+        })
+        .collect();
+    ChatCompletionRequestUserMessageContent::Array(parts)
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -288,6 +412,198 @@ mod tests {
     use tokio::sync::Mutex;
     use tokio::test;
 
+    #[test]
+    async fn test_generate_with_mock_server() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/chat/completions")
+            .match_header("authorization", "Bearer key")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "model": "local-model",
+                "temperature": 0.5,
+                "max_tokens": 16,
+                "stop": ["\n"],
+                "messages": [{"role": "user", "content": "Hello"}]
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1712000000,
+                    "model": "local-model",
+                    "choices": [{
+                        "index": 0,
+                        "message": {"role": "assistant", "content": "Hi there!"},
+                        "finish_reason": "stop"
+                    }],
+                    "usage": {"prompt_tokens": 8, "completion_tokens": 3, "total_tokens": 11}
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let config = OpenAIConfig::new()
+            .with_api_base(format!("{}/v1", server.url()))
+            .with_api_key("key");
+        let llm = OpenAI::new(config).with_model("local-model").with_options(
+            CallOptions::new()
+                .with_temperature(0.5)
+                .with_max_tokens(16)
+                .with_stop_words(vec!["\n".to_string()]),
+        );
+        let result = llm
+            .generate(&[Message::new_human_message("Hello")])
+            .await
+            .unwrap();
+        mock.assert_async().await;
+        assert_eq!(result.generation, "Hi there!");
+        let tokens = result.tokens.unwrap();
+        assert_eq!(
+            (
+                tokens.prompt_tokens,
+                tokens.completion_tokens,
+                tokens.total_tokens
+            ),
+            (8, 3, 11)
+        );
+    }
+
+    #[test]
+    async fn test_multimodal_messages() {
+        let openai = OpenAI::default();
+        let message = Message::new_human_message("").with_parts(vec![
+            ContentPart::text("What is this?"),
+            ContentPart::ImageUrl {
+                url: "https://example.com/cat.png".to_string(),
+                detail: Some(ImageDetail::Low),
+            },
+        ]);
+        let request = openai
+            .generate_request(&[Message::new_system_message("Be brief"), message])
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["messages"],
+            json!([
+                {"role": "system", "content": "Be brief"},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "What is this?"},
+                    {"type": "image_url", "image_url": {"url": "https://example.com/cat.png", "detail": "low"}}
+                ]}
+            ])
+        );
+    }
+
+    #[test]
+    async fn test_api_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let error = |code: &str, message: &str| {
+            json!({
+                "error": {"message": message, "type": "invalid_request_error", "param": null, "code": code}
+            })
+            .to_string()
+        };
+        server
+            .mock("POST", "/v1/chat/completions")
+            .match_header("authorization", "Bearer wrong")
+            .with_status(401)
+            .with_body(error("invalid_api_key", "Incorrect API key provided"))
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/v1/chat/completions")
+            .match_header("authorization", "Bearer key")
+            .with_status(400)
+            .with_body(error(
+                "context_length_exceeded",
+                "This model's maximum context length is 8 tokens",
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/v1/chat/completions")
+            .match_header("authorization", "Bearer broke")
+            .with_status(429)
+            .with_body(
+                json!({
+                    "error": {"message": "You exceeded your current quota", "type": "insufficient_quota", "param": null, "code": "insufficient_quota"}
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let llm = |key: &str| {
+            OpenAI::new(
+                OpenAIConfig::new()
+                    .with_api_base(format!("{}/v1", server.url()))
+                    .with_api_key(key),
+            )
+        };
+        assert!(matches!(
+            llm("wrong").invoke("Hello").await,
+            Err(LLMError::AuthenticationError(_))
+        ));
+        assert!(matches!(
+            llm("key").invoke("Hello").await,
+            Err(LLMError::ContextLengthExceeded(message)) if message.contains("8 tokens")
+        ));
+        assert!(matches!(
+            llm("broke").invoke("Hello").await,
+            Err(LLMError::QuotaExceeded(_))
+        ));
+    }
+
+    #[test]
+    async fn test_stream_with_mock_server() {
+        let chunk = |content: &str| {
+            json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion.chunk",
+                "created": 1712000000,
+                "model": "local-model",
+                "choices": [{"index": 0, "delta": {"content": content}, "finish_reason": null}]
+            })
+        };
+        let body = format!(
+            "data: {}\n\ndata: {}\n\ndata: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+            chunk("Hi"),
+            chunk(" there"),
+            json!({"id": "chatcmpl-1", "choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}),
+            json!({"id": "chatcmpl-1", "choices": [], "usage": {"prompt_tokens": 8, "completion_tokens": 2, "total_tokens": 10}})
+        );
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/chat/completions")
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"stream": true, "stream_options": {"include_usage": true}}),
+            ))
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let llm = OpenAI::new(OpenAIConfig::new().with_api_base(format!("{}/v1", server.url())));
+        assert!(OpenAI::default().includes_stream_usage());
+        assert!(!llm.includes_stream_usage());
+        let chunks: Vec<StreamData> = llm
+            .with_stream_usage(true)
+            .stream(&[Message::new_human_message("Hello")])
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        mock.assert_async().await;
+        let contents: Vec<&str> = chunks.iter().map(|chunk| chunk.content.as_str()).collect();
+        assert_eq!(contents, vec!["Hi", " there", "", ""]);
+        assert!(chunks[..3].iter().all(|chunk| chunk.tokens.is_none()));
+        assert_eq!(chunks[3].tokens.as_ref().unwrap().total_tokens, 10);
+    }
+
     #[test]
     #[ignore]
     async fn test_ivoke() {
@@ -0,0 +1,181 @@
+use std::sync::Arc;
+
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::{
+    prompt::PromptArgs,
+    schemas::{
+        memory::BaseMemory,
+        messages::{Message, MessageType},
+    },
+};
+
+/// Struct `ConversationBufferMemory` keeps the messages of a conversation and renders them
+/// for a prompt, either as a transcript like `Human: Hi\nAI: Hello` for a `{history}` variable
+/// of a string prompt, or as the messages themselves for a messages placeholder of a chat
+/// prompt.
+///
+/// # Usage
+/// ```rust,ignore
+/// let memory = ConversationBufferMemory::new()
+///     .with_human_prefix("User")
+///     .with_ai_prefix("Assistant")
+///     .with_max_turns(10);
+/// let chain = ConversationalChainBuilder::new()
+///     .llm(open_ai)
+///     .memory(memory.into())
+///     .build()?;
+/// ```
+pub struct ConversationBufferMemory {
+    messages: Vec<Message>,
+    human_prefix: String,
+    ai_prefix: String,
+    memory_key: String,
+    return_messages: bool,
+    max_turns: Option<usize>,
+}
+
+impl Default for ConversationBufferMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConversationBufferMemory {
+    pub fn new() -> Self {
+        Self {
+            messages: Vec::new(),
+            human_prefix: "Human".to_string(),
+            ai_prefix: "AI".to_string(),
+            memory_key: "history".to_string(),
+            return_messages: false,
+            max_turns: None,
+        }
+    }
+
+    /// Sets the prefix of the human messages in the transcript. Defaults to `Human`.
+    pub fn with_human_prefix<S: Into<String>>(mut self, human_prefix: S) -> Self {
+        self.human_prefix = human_prefix.into();
+        self
+    }
+
+    /// Sets the prefix of the AI messages in the transcript. Defaults to `AI`.
+    pub fn with_ai_prefix<S: Into<String>>(mut self, ai_prefix: S) -> Self {
+        self.ai_prefix = ai_prefix.into();
+        self
+    }
+
+    /// Sets the variable holding the history. Defaults to `history`.
+    pub fn with_memory_key<S: Into<String>>(mut self, memory_key: S) -> Self {
+        self.memory_key = memory_key.into();
+        self
+    }
+
+    /// Returns the history as messages, for a messages placeholder, instead of a transcript.
+    pub fn with_return_messages(mut self, return_messages: bool) -> Self {
+        self.return_messages = return_messages;
+        self
+    }
+
+    /// Keeps only the last `max_turns` turns, each starting with a human message.
+    pub fn with_max_turns(mut self, max_turns: usize) -> Self {
+        self.max_turns = Some(max_turns);
+        self.enforce_max_turns();
+        self
+    }
+
+    fn enforce_max_turns(&mut self) {
+        let Some(max_turns) = self.max_turns else {
+            return;
+        };
+        let turns: Vec<usize> = self
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| message.message_type == MessageType::HumanMessage)
+            .map(|(i, _)| i)
+            .collect();
+        if turns.len() > max_turns {
+            self.messages.drain(..turns[turns.len() - max_turns]);
+        }
+    }
+}
+
+impl From<ConversationBufferMemory> for Arc<Mutex<dyn BaseMemory>> {
+    fn from(memory: ConversationBufferMemory) -> Self {
+        Arc::new(Mutex::new(memory))
+    }
+}
+
+impl BaseMemory for ConversationBufferMemory {
+    fn messages(&self) -> Vec<Message> {
+        self.messages.clone()
+    }
+
+    fn add_message(&mut self, message: Message) {
+        self.messages.push(message);
+        self.enforce_max_turns();
+    }
+
+    fn clear(&mut self) {
+        self.messages.clear();
+    }
+
+    fn to_string(&self) -> String {
+        transcript(&self.messages, &self.human_prefix, &self.ai_prefix)
+    }
+
+    fn load_memory_variables(&self) -> PromptArgs {
+        let history = if self.return_messages {
+            json!(self.messages)
+        } else {
+            json!(self.to_string())
+        };
+        PromptArgs::from([(self.memory_key.clone(), history)])
+    }
+}
+
+// Renders messages as lines like `Human: Hi`.
+pub(crate) fn transcript(messages: &[Message], human_prefix: &str, ai_prefix: &str) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let prefix = match message.message_type {
+                MessageType::HumanMessage => human_prefix,
+                MessageType::AIMessage => ai_prefix,
+                MessageType::SystemMessage => "System",
+                MessageType::ToolMessage => "Tool",
+            };
+            format!("{}: {}", prefix, message.content)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_conversation_buffer_memory() {
+        let mut memory = ConversationBufferMemory::new().with_ai_prefix("Assistant");
+        memory.save_context("Hi", "Hello!").await.unwrap();
+        memory.save_context("How are you?", "Fine.").await.unwrap();
+        assert_eq!(
+            memory.load_memory_variables()["history"],
+            "Human: Hi\nAssistant: Hello!\nHuman: How are you?\nAssistant: Fine."
+        );
+
+        let mut memory = memory.with_max_turns(1).with_return_messages(true);
+        let history = &memory.load_memory_variables()["history"];
+        let messages = Message::messages_from_value(history).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content, "How are you?");
+
+        memory.save_context("Bye", "Goodbye.").await.unwrap();
+        assert_eq!(memory.messages()[0].content, "Bye");
+        memory.clear();
+        assert!(memory.messages().is_empty());
+    }
+}
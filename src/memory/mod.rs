@@ -1,7 +1,11 @@
+mod conversation_buffer;
 mod dummy_memory;
 mod simple_memory;
+mod summary;
 mod window_buffer;
 
+pub use conversation_buffer::*;
 pub use dummy_memory::*;
 pub use simple_memory::*;
+pub use summary::*;
 pub use window_buffer::*;
@@ -0,0 +1,244 @@
+use std::{error::Error, sync::Arc};
+
+use async_trait::async_trait;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::{
+    language_models::llm::LLM,
+    prompt::{PromptArgs, PromptFromatter},
+    prompt_args,
+    schemas::{memory::BaseMemory, messages::Message},
+    template_fstring,
+};
+
+use super::transcript;
+
+pub const DEFAULT_SUMMARY_TEMPLATE: &str = r#"Progressively summarize the lines of conversation provided, adding onto the previous summary returning a new summary.
+
+EXAMPLE
+Current summary:
+The human asks what the AI thinks of artificial intelligence. The AI thinks artificial intelligence is a force for good.
+
+New lines of conversation:
+Human: Why do you think artificial intelligence is a force for good?
+AI: Because artificial intelligence will help humans reach their full potential.
+
+New summary:
+The human asks what the AI thinks of artificial intelligence. The AI thinks artificial intelligence is a force for good because it will help humans reach their full potential.
+END OF EXAMPLE
+
+Current summary:
+{summary}
+
+New lines of conversation:
+{new_lines}
+
+New summary:"#;
+
+/// Struct `ConversationSummaryMemory` keeps a running summary of a conversation instead of
+/// its messages. Every `save_context` asks the LLM for a new summary from the current one and
+/// the new turn, and the `history` variable holds that summary.
+///
+/// With `with_keep_last`, the most recent messages are kept as they are and only older ones
+/// are summarized, the history being the summary followed by their transcript.
+///
+/// Messages added with `add_message` are kept as they are until the next `save_context`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let memory = ConversationSummaryMemory::new(OpenAI::default()).with_keep_last(4);
+/// let chain = ConversationalChainBuilder::new()
+///     .llm(open_ai)
+///     .memory(memory.into())
+///     .build()?;
+/// ```
+pub struct ConversationSummaryMemory {
+    llm: Box<dyn LLM>,
+    prompt: Box<dyn PromptFromatter>,
+    summary: String,
+    messages: Vec<Message>,
+    keep_last: usize,
+    human_prefix: String,
+    ai_prefix: String,
+    memory_key: String,
+}
+
+impl ConversationSummaryMemory {
+    pub fn new<L: Into<Box<dyn LLM>>>(llm: L) -> Self {
+        Self {
+            llm: llm.into(),
+            prompt: Box::new(template_fstring!(
+                DEFAULT_SUMMARY_TEMPLATE,
+                "summary",
+                "new_lines"
+            )),
+            summary: String::new(),
+            messages: Vec::new(),
+            keep_last: 0,
+            human_prefix: "Human".to_string(),
+            ai_prefix: "AI".to_string(),
+            memory_key: "history".to_string(),
+        }
+    }
+
+    /// Replaces the summarization prompt, which takes the `summary` and `new_lines` variables.
+    pub fn with_prompt<P: PromptFromatter + 'static>(mut self, prompt: P) -> Self {
+        self.prompt = Box::new(prompt);
+        self
+    }
+
+    /// Keeps the last `keep_last` messages out of the summary. Defaults to 0.
+    pub fn with_keep_last(mut self, keep_last: usize) -> Self {
+        self.keep_last = keep_last;
+        self
+    }
+
+    /// Starts from an existing summary, such as one saved from a previous session.
+    pub fn with_summary<S: Into<String>>(mut self, summary: S) -> Self {
+        self.summary = summary.into();
+        self
+    }
+
+    pub fn with_human_prefix<S: Into<String>>(mut self, human_prefix: S) -> Self {
+        self.human_prefix = human_prefix.into();
+        self
+    }
+
+    pub fn with_ai_prefix<S: Into<String>>(mut self, ai_prefix: S) -> Self {
+        self.ai_prefix = ai_prefix.into();
+        self
+    }
+
+    pub fn with_memory_key<S: Into<String>>(mut self, memory_key: S) -> Self {
+        self.memory_key = memory_key.into();
+        self
+    }
+
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+
+    // Folds the messages beyond the last `keep_last` into the summary.
+    async fn summarize(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let overflow = self.messages.len().saturating_sub(self.keep_last);
+        if overflow == 0 {
+            return Ok(());
+        }
+        let new_lines = transcript(
+            &self.messages[..overflow],
+            &self.human_prefix,
+            &self.ai_prefix,
+        );
+        let prompt = self.prompt.format(prompt_args! {
+            "summary" => self.summary,
+            "new_lines" => new_lines,
+        })?;
+        self.summary = self.llm.invoke(&prompt).await?.trim().to_string();
+        self.messages.drain(..overflow);
+        Ok(())
+    }
+}
+
+impl From<ConversationSummaryMemory> for Arc<Mutex<dyn BaseMemory>> {
+    fn from(memory: ConversationSummaryMemory) -> Self {
+        Arc::new(Mutex::new(memory))
+    }
+}
+
+#[async_trait]
+impl BaseMemory for ConversationSummaryMemory {
+    /// Returns the summary as a system message, followed by the messages kept as they are.
+    fn messages(&self) -> Vec<Message> {
+        let summary =
+            (!self.summary.is_empty()).then(|| Message::new_system_message(&self.summary));
+        summary.into_iter().chain(self.messages.clone()).collect()
+    }
+
+    fn add_message(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    fn clear(&mut self) {
+        self.summary.clear();
+        self.messages.clear();
+    }
+
+    fn to_string(&self) -> String {
+        let messages = transcript(&self.messages, &self.human_prefix, &self.ai_prefix);
+        match (self.summary.is_empty(), messages.is_empty()) {
+            (true, _) => messages,
+            (false, true) => self.summary.clone(),
+            (false, false) => format!("{}\n{}", self.summary, messages),
+        }
+    }
+
+    fn load_memory_variables(&self) -> PromptArgs {
+        PromptArgs::from([(self.memory_key.clone(), json!(self.to_string()))])
+    }
+
+    async fn save_context(
+        &mut self,
+        input: &str,
+        output: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.messages.push(Message::new_human_message(input));
+        self.messages.push(Message::new_ai_message(output));
+        self.summarize().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::FakeLLM;
+
+    #[tokio::test]
+    async fn test_conversation_summary_memory() {
+        let llm = FakeLLM::new().with_responses(vec!["Luis greets the AI.", "Luis leaves."]);
+        let mut memory = ConversationSummaryMemory::new(llm);
+        memory
+            .save_context("Hi, I'm Luis", "Hello Luis!")
+            .await
+            .unwrap();
+        assert_eq!(
+            memory.load_memory_variables()["history"],
+            "Luis greets the AI."
+        );
+        memory.save_context("Bye", "Goodbye!").await.unwrap();
+        assert_eq!(memory.summary(), "Luis leaves.");
+        assert_eq!(memory.messages().len(), 1);
+
+        memory.clear();
+        assert_eq!(memory.summary(), "");
+        assert!(memory.messages().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_conversation_summary_memory_prompt() {
+        // the FakeLLM answers with the prompt, so the summary is the formatted prompt
+        let mut memory = ConversationSummaryMemory::new(FakeLLM::new())
+            .with_prompt(template_fstring!(
+                "[{summary}] + [{new_lines}]",
+                "summary",
+                "new_lines"
+            ))
+            .with_summary("S");
+        memory.save_context("Hi", "Hello").await.unwrap();
+        assert_eq!(memory.summary(), "[S] + [Human: Hi\nAI: Hello]");
+    }
+
+    #[tokio::test]
+    async fn test_conversation_summary_memory_keep_last() {
+        let llm = FakeLLM::new().with_responses(vec!["First turn.", "Two turns."]);
+        let mut memory = ConversationSummaryMemory::new(llm).with_keep_last(2);
+        memory.save_context("one", "1").await.unwrap();
+        assert_eq!(memory.summary(), "");
+        assert_eq!(memory.to_string(), "Human: one\nAI: 1");
+
+        memory.save_context("two", "2").await.unwrap();
+        assert_eq!(memory.to_string(), "First turn.\nHuman: two\nAI: 2");
+        memory.save_context("three", "3").await.unwrap();
+        assert_eq!(memory.to_string(), "Two turns.\nHuman: three\nAI: 3");
+    }
+}
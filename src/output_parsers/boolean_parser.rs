@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+
+use super::{FormatInstructions, OutputParser, OutputParserError};
+
+/// Parses a yes or no answer from its first word, case-insensitively: `yes`, `y` and `true`
+/// are true, `no`, `n` and `false` are false, so `Yes, it is.` and `TRUE.` are both true.
+#[derive(Default)]
+pub struct BooleanParser {}
+
+impl BooleanParser {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[async_trait]
+impl OutputParser<bool> for BooleanParser {
+    async fn parse(&self, output: &str) -> Result<bool, OutputParserError> {
+        let answer = output
+            .split(|c: char| !c.is_alphanumeric())
+            .find(|word| !word.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        match answer.as_str() {
+            "yes" | "y" | "true" => Ok(true),
+            "no" | "n" | "false" => Ok(false),
+            _ => Err(OutputParserError::invalid_output(
+                output,
+                "expected yes, no, true or false",
+            )),
+        }
+    }
+}
+
+impl FormatInstructions for BooleanParser {
+    fn format_instructions(&self) -> String {
+        "Answer with Yes or No.".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_boolean_parser() {
+        let parser = BooleanParser::new();
+        for output in ["Yes", "true", "TRUE.", " yes, it is", "Y"] {
+            assert!(parser.parse(output).await.unwrap(), "{}", output);
+        }
+        for output in ["No", "false!", "NO. It isn't"] {
+            assert!(!parser.parse(output).await.unwrap(), "{}", output);
+        }
+        match parser.parse("Maybe").await {
+            Err(OutputParserError::InvalidOutput { text, .. }) => assert_eq!(text, "Maybe"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use regex::Error as RegexError;
 use thiserror::Error;
 
+use crate::{language_models::LLMError, prompt::PromptError};
+
 #[derive(Error, Debug)]
 pub enum OutputParserError {
     #[error("Regex error: {0}")]
@@ -8,4 +10,43 @@ pub enum OutputParserError {
 
     #[error("Parsing error: {0}")]
     ParsingError(String),
+
+    #[error("Could not parse output: {reason}")]
+    InvalidOutput { text: String, reason: String },
+
+    #[error("Could not deserialize output at {path}: {reason}")]
+    DeserializationError {
+        path: String,
+        reason: String,
+        json: String,
+    },
+
+    #[error("Output still invalid after {attempts} fixing attempts: {source}")]
+    FixingFailed {
+        attempts: usize,
+        outputs: Vec<String>,
+        #[source]
+        source: Box<OutputParserError>,
+    },
+
+    #[error("LLM error: {0}")]
+    LLMError(Box<LLMError>),
+
+    #[error("Prompt error: {0}")]
+    PromptError(#[from] PromptError),
+}
+
+impl From<LLMError> for OutputParserError {
+    fn from(error: LLMError) -> Self {
+        OutputParserError::LLMError(Box::new(error))
+    }
+}
+
+impl OutputParserError {
+    pub(crate) fn invalid_output<S: Into<String>>(text: &str, reason: S) -> Self {
+        OutputParserError::InvalidOutput {
+            text: text.to_string(),
+            reason: reason.into(),
+        }
+    }
 }
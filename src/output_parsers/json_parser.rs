@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use regex::Regex;
+use serde::de::IgnoredAny;
+use serde_json::Value;
+
+use super::{FormatInstructions, OutputParser, OutputParserError};
+
+/// Parses JSON out of a model's output: the whole output, the content of a ```json code
+/// block, or the outermost object or array found in surrounding prose.
+#[derive(Default)]
+pub struct JsonParser {}
+
+impl JsonParser {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[async_trait]
+impl OutputParser<Value> for JsonParser {
+    async fn parse(&self, output: &str) -> Result<Value, OutputParserError> {
+        let json = extract_json(output)?.ok_or_else(|| {
+            OutputParserError::invalid_output(output, "no JSON object or array found")
+        })?;
+        serde_json::from_str(json)
+            .map_err(|e| OutputParserError::invalid_output(output, e.to_string()))
+    }
+}
+
+/// Locates the JSON of an output: the whole output if it's valid JSON, else the content of
+/// a code block, else the text from the first `{` or `[` to the last `}` or `]`.
+pub(crate) fn extract_json(output: &str) -> Result<Option<&str>, OutputParserError> {
+    let trimmed = output.trim();
+    if serde_json::from_str::<IgnoredAny>(trimmed).is_ok() {
+        return Ok(Some(trimmed));
+    }
+
+    let re = Regex::new(r"```(?:json)?\s*([\s\S]*?)\s*```")?;
+    if let Some(json) = re.captures(output).and_then(|cap| cap.get(1)) {
+        return Ok(Some(json.as_str()));
+    }
+
+    let start = output.find(['{', '[']);
+    let end = output.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => Ok(Some(&output[start..=end])),
+        _ => Ok(None),
+    }
+}
+
+impl FormatInstructions for JsonParser {
+    fn format_instructions(&self) -> String {
+        "Respond with a valid JSON value, without any other text.".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_json_parser() {
+        let parser = JsonParser::new();
+        assert_eq!(
+            parser.parse(r#" {"answer": 42} "#).await.unwrap(),
+            json!({"answer": 42})
+        );
+        assert_eq!(
+            parser
+                .parse("Here you go:\n```json\n{\"items\": [1, 2]}\n```\nAnything else?")
+                .await
+                .unwrap(),
+            json!({"items": [1, 2]})
+        );
+        assert_eq!(
+            parser
+                .parse(r#"Sure! The result is {"ok": true, "tags": ["a"]}. Hope it helps."#)
+                .await
+                .unwrap(),
+            json!({"ok": true, "tags": ["a"]})
+        );
+
+        match parser.parse("I don't know").await {
+            Err(OutputParserError::InvalidOutput { text, reason }) => {
+                assert_eq!(text, "I don't know");
+                assert_eq!(reason, "no JSON object or array found");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(parser.parse("{\"unclosed\": 1").await.is_err());
+    }
+}
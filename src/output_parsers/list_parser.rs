@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+
+use super::{FormatInstructions, OutputParser, OutputParserError};
+
+/// Parses a comma separated list, such as `red, green, blue`, trimming every item and
+/// skipping empty ones.
+#[derive(Default)]
+pub struct CommaSeparatedListParser {}
+
+impl CommaSeparatedListParser {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[async_trait]
+impl OutputParser<Vec<String>> for CommaSeparatedListParser {
+    async fn parse(&self, output: &str) -> Result<Vec<String>, OutputParserError> {
+        Ok(output
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+impl FormatInstructions for CommaSeparatedListParser {
+    fn format_instructions(&self) -> String {
+        "Your response should be a list of comma separated values, eg: `foo, bar, baz`".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_comma_separated_list_parser() {
+        let parser = CommaSeparatedListParser::new();
+        let result = parser.parse(" red, green ,blue,\n").await.unwrap();
+        assert_eq!(result, vec!["red", "green", "blue"]);
+        assert!(parser.parse("  ").await.unwrap().is_empty());
+    }
+}
@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use regex::Regex;
 
-use super::{OutputParser, OutputParserError};
+use super::{FormatInstructions, OutputParser, OutputParserError};
 
 pub struct MarkdownParser {
     expresion: String,
@@ -31,6 +31,12 @@ impl Default for MarkdownParser {
     }
 }
 
+impl FormatInstructions for MarkdownParser {
+    fn format_instructions(&self) -> String {
+        "Wrap your answer in a markdown code block, starting and ending with ```.".to_string()
+    }
+}
+
 #[async_trait]
 impl OutputParser for MarkdownParser {
     async fn parse(&self, output: &str) -> Result<String, OutputParserError> {
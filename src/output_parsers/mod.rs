@@ -7,5 +7,23 @@ pub use markdown_parser::*;
 mod simple_parser;
 pub use simple_parser::*;
 
+mod list_parser;
+pub use list_parser::*;
+
+mod boolean_parser;
+pub use boolean_parser::*;
+
+mod json_parser;
+pub use json_parser::*;
+
+mod regex_parser;
+pub use regex_parser::*;
+
+mod structured_parser;
+pub use structured_parser::*;
+
+mod output_fixing_parser;
+pub use output_fixing_parser::*;
+
 mod error;
 pub use error::*;
@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+
+use crate::{language_models::llm::LLM, prompt::PromptTemplate, prompt_args, template_fstring};
+
+use super::{FormatInstructions, OutputParser, OutputParserError};
+
+const FIX_TEMPLATE: &str = r#"Instructions:
+--------------
+{instructions}
+--------------
+Completion:
+--------------
+{completion}
+--------------
+
+Above, the Completion did not satisfy the constraints given in the Instructions.
+Error:
+--------------
+{error}
+--------------
+
+Please try again. Please only respond with an answer that satisfies the constraints laid out in the Instructions:"#;
+
+/// Struct `OutputFixingParser` wraps a parser and, when parsing fails, asks an LLM to fix the
+/// output, then parses the fixed output, up to `max_retries` times.
+///
+/// The fixing prompt gets the parser's format instructions as `{instructions}`, the output as
+/// `{completion}` and the parse error as `{error}`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let parser = OutputFixingParser::new(StructuredOutputParser::<Answer>::new(schemas), open_ai)
+///     .with_max_retries(2);
+/// let (answer, attempts) = parser.parse_with_attempts(&output).await?;
+/// ```
+pub struct OutputFixingParser<T> {
+    parser: Box<dyn OutputParser<T>>,
+    instructions: String,
+    llm: Box<dyn LLM>,
+    prompt: PromptTemplate,
+    max_retries: usize,
+}
+
+impl<T> OutputFixingParser<T> {
+    pub fn new<P, L>(parser: P, llm: L) -> Self
+    where
+        P: OutputParser<T> + FormatInstructions + 'static,
+        L: Into<Box<dyn LLM>>,
+    {
+        Self {
+            instructions: parser.format_instructions(),
+            parser: Box::new(parser),
+            llm: llm.into(),
+            prompt: template_fstring!(FIX_TEMPLATE, "instructions", "completion", "error"),
+            max_retries: 1,
+        }
+    }
+
+    /// Sets how many times the output is fixed before giving up. Defaults to 1.
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Replaces the fixing prompt, which takes the `instructions`, `completion` and `error`
+    /// variables.
+    pub fn with_prompt(mut self, prompt: PromptTemplate) -> Self {
+        self.prompt = prompt;
+        self
+    }
+}
+
+impl<T: Send> OutputFixingParser<T> {
+    /// Parses the output, fixing it as needed, and returns the number of fixes it took.
+    ///
+    /// # Errors
+    /// Returns `OutputParserError::FixingFailed` with the last parse error and every fixed
+    /// output if none of them could be parsed.
+    pub async fn parse_with_attempts(&self, output: &str) -> Result<(T, usize), OutputParserError> {
+        let mut completion = output.to_string();
+        let mut outputs = Vec::new();
+        let mut attempts = 0;
+        loop {
+            let error = match self.parser.parse(&completion).await {
+                Ok(value) => return Ok((value, attempts)),
+                Err(error) => error,
+            };
+            if attempts == self.max_retries {
+                return Err(OutputParserError::FixingFailed {
+                    attempts,
+                    outputs,
+                    source: Box::new(error),
+                });
+            }
+
+            attempts += 1;
+            log::debug!("Fixing output, attempt {}: {}", attempts, error);
+            let prompt = self.prompt.format(prompt_args! {
+                "instructions" => self.instructions,
+                "completion" => completion,
+                "error" => error.to_string(),
+            })?;
+            completion = self.llm.invoke(&prompt).await?;
+            outputs.push(completion.clone());
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Send> OutputParser<T> for OutputFixingParser<T> {
+    async fn parse(&self, output: &str) -> Result<T, OutputParserError> {
+        Ok(self.parse_with_attempts(output).await?.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        sync::{Arc, Mutex},
+    };
+
+    use futures::Stream;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        language_models::{GenerateResult, LLMError},
+        output_parsers::JsonParser,
+        schemas::{Message, StreamData},
+    };
+
+    // Answers with its responses in turn, keeping the prompts it got.
+    #[derive(Clone)]
+    struct MockLLM {
+        responses: Arc<Mutex<Vec<String>>>,
+        prompts: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockLLM {
+        fn new(responses: &[&str]) -> Self {
+            Self {
+                responses: Arc::new(Mutex::new(
+                    responses.iter().rev().map(|r| r.to_string()).collect(),
+                )),
+                prompts: Arc::new(Mutex::new(Vec::new())),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl LLM for MockLLM {
+        async fn generate(&self, messages: &[Message]) -> Result<GenerateResult, LLMError> {
+            self.prompts
+                .lock()
+                .unwrap()
+                .push(messages[0].content.clone());
+            let generation = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop()
+                .ok_or_else(|| LLMError::OtherError("no more responses".into()))?;
+            Ok(GenerateResult {
+                tokens: None,
+                generation,
+            })
+        }
+
+        async fn stream(
+            &self,
+            _messages: &[Message],
+        ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamData, LLMError>> + Send>>, LLMError>
+        {
+            Err(LLMError::OtherError("not supported".into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_fixing_parser() {
+        let llm = MockLLM::new(&["```json\n{\"answer\": 42}\n```"]);
+        let parser = OutputFixingParser::<Value>::new(JsonParser::new(), llm.clone());
+        let (value, attempts) = parser
+            .parse_with_attempts("The answer is forty-two")
+            .await
+            .unwrap();
+        assert_eq!(value, json!({"answer": 42}));
+        assert_eq!(attempts, 1);
+
+        let prompts = llm.prompts.lock().unwrap();
+        assert!(prompts[0].contains("Completion:\n--------------\nThe answer is forty-two\n"));
+        assert!(prompts[0].contains("no JSON object or array found"));
+    }
+
+    #[tokio::test]
+    async fn test_output_fixing_parser_gives_up() {
+        let llm = MockLLM::new(&["still not JSON", "nope"]);
+        let parser = OutputFixingParser::<Value>::new(JsonParser::new(), llm).with_max_retries(2);
+        match parser.parse("forty-two").await {
+            Err(OutputParserError::FixingFailed {
+                attempts, outputs, ..
+            }) => {
+                assert_eq!(attempts, 2);
+                assert_eq!(outputs, vec!["still not JSON", "nope"]);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
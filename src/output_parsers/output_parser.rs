@@ -2,9 +2,10 @@ use async_trait::async_trait;
 
 use super::OutputParserError;
 
+/// Parses the output of a model into a `T`, the raw text by default.
 #[async_trait]
-pub trait OutputParser: Send + Sync {
-    async fn parse(&self, output: &str) -> Result<String, OutputParserError>;
+pub trait OutputParser<T = String>: Send + Sync {
+    async fn parse(&self, output: &str) -> Result<T, OutputParserError>;
 }
 
 impl<P> From<P> for Box<dyn OutputParser>
@@ -15,3 +16,9 @@ where
         Box::new(parser)
     }
 }
+
+/// Describes the output a parser expects, for the `{format_instructions}` placeholder of a
+/// prompt, see `PromptTemplate::with_output_parser`.
+pub trait FormatInstructions {
+    fn format_instructions(&self) -> String;
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use regex::Regex;
+
+use super::{FormatInstructions, OutputParser, OutputParserError};
+
+/// How much of the output is quoted in the error of an output that doesn't match.
+const MAX_QUOTED_CHARS: usize = 100;
+
+/// Struct `RegexParser` parses an output with a regex, returning the text captured by each
+/// named group. A single unnamed group can be named with `with_default_output_key`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let parser = RegexParser::new(r"Score: (?<score>\d+)\nReason: (?<reason>.+)")?;
+/// let result = parser.parse("Score: 8\nReason: Clear and concise").await?;
+/// assert_eq!(result["score"], "8");
+/// ```
+pub struct RegexParser {
+    regex: Regex,
+    default_output_key: Option<String>,
+}
+
+impl RegexParser {
+    pub fn new(regex: &str) -> Result<Self, OutputParserError> {
+        Ok(Self {
+            regex: Regex::new(regex)?,
+            default_output_key: None,
+        })
+    }
+
+    /// Sets the key of the text captured by the first group when it has no name, e.g. for
+    /// `Answer: (.+)`.
+    pub fn with_default_output_key<S: Into<String>>(mut self, key: S) -> Self {
+        self.default_output_key = Some(key.into());
+        self
+    }
+
+    // The default output key, if the first group has no name.
+    fn default_group_key(&self) -> Option<&String> {
+        match self.regex.capture_names().nth(1) {
+            Some(None) => self.default_output_key.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .regex
+            .capture_names()
+            .flatten()
+            .map(Into::into)
+            .collect();
+        if let Some(key) = self.default_group_key() {
+            keys.insert(0, key.clone());
+        }
+        keys
+    }
+}
+
+#[async_trait]
+impl OutputParser<HashMap<String, String>> for RegexParser {
+    async fn parse(&self, output: &str) -> Result<HashMap<String, String>, OutputParserError> {
+        let captures = self.regex.captures(output).ok_or_else(|| {
+            let mut quoted: String = output.chars().take(MAX_QUOTED_CHARS).collect();
+            if quoted.len() < output.len() {
+                quoted.push_str("...");
+            }
+            OutputParserError::invalid_output(
+                output,
+                format!("regex `{}` doesn't match {:?}", self.regex, quoted),
+            )
+        })?;
+
+        let mut result = HashMap::new();
+        for name in self.regex.capture_names().flatten() {
+            if let Some(capture) = captures.name(name) {
+                result.insert(name.to_string(), capture.as_str().to_string());
+            }
+        }
+        if let Some(key) = self.default_group_key() {
+            if let Some(capture) = captures.get(1) {
+                result.insert(key.clone(), capture.as_str().to_string());
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl FormatInstructions for RegexParser {
+    /// Describes one `Key: <key>` line per group, e.g. `Score: <score>` for a `score` group.
+    fn format_instructions(&self) -> String {
+        let lines: Vec<String> = self
+            .keys()
+            .iter()
+            .map(|key| {
+                let label = key.replace('_', " ");
+                let mut chars = label.chars();
+                let label = match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => label,
+                };
+                format!("{}: <{}>", label, key)
+            })
+            .collect();
+        format!(
+            "Answer in the following format, one line each:\n{}",
+            lines.join("\n")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_regex_parser() {
+        let parser = RegexParser::new(r"Score: (?<score>\d+)\s+Reason: (?<reason>.+)").unwrap();
+        let result = parser
+            .parse("Sure.\nScore: 8\nReason: Clear and concise")
+            .await
+            .unwrap();
+        assert_eq!(result["score"], "8");
+        assert_eq!(result["reason"], "Clear and concise");
+        assert_eq!(
+            parser.format_instructions(),
+            "Answer in the following format, one line each:\nScore: <score>\nReason: <reason>"
+        );
+
+        let parser = RegexParser::new(r"Answer: (.+)")
+            .unwrap()
+            .with_default_output_key("final_answer");
+        let result = parser.parse("Answer: 42").await.unwrap();
+        assert_eq!(
+            result,
+            HashMap::from([("final_answer".into(), "42".into())])
+        );
+        assert!(parser
+            .format_instructions()
+            .ends_with("Final answer: <final_answer>"));
+    }
+
+    #[tokio::test]
+    async fn test_regex_parser_no_match() {
+        let parser = RegexParser::new(r"Score: (?<score>\d+)").unwrap();
+        let output = "I can't rate this. ".repeat(10);
+        match parser.parse(&output).await {
+            Err(OutputParserError::InvalidOutput { text, reason }) => {
+                assert_eq!(text, output);
+                assert!(reason.starts_with(r"regex `Score: (?<score>\d+)` doesn't match"));
+                assert!(reason.ends_with("...\""));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
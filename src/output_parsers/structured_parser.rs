@@ -0,0 +1,279 @@
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+
+use super::{extract_json, FormatInstructions, OutputParser, OutputParserError};
+
+/// Describes a field of the JSON expected by a `StructuredOutputParser`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseSchema {
+    pub name: String,
+    pub description: String,
+    /// The JSON type of the field, such as `string`, `integer`, `boolean`, `array` or a
+    /// free-form description like `object with "city" and "zip" strings`.
+    pub value_type: String,
+}
+
+impl ResponseSchema {
+    /// Creates the schema of a `string` field.
+    pub fn new<N: Into<String>, D: Into<String>>(name: N, description: D) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            value_type: "string".to_string(),
+        }
+    }
+
+    pub fn with_type<S: Into<String>>(mut self, value_type: S) -> Self {
+        self.value_type = value_type.into();
+        self
+    }
+}
+
+/// Struct `StructuredOutputParser` deserializes the JSON of a model's output into a `T`. The
+/// JSON can be wrapped in a ```json code block or in prose, see `JsonParser`.
+///
+/// # Usage
+/// ```rust,ignore
+/// #[derive(Deserialize)]
+/// struct Answer { answer: String, confidence: f64 }
+///
+/// let parser = StructuredOutputParser::<Answer>::new(vec![
+///     ResponseSchema::new("answer", "the answer to the question"),
+///     ResponseSchema::new("confidence", "between 0 and 1").with_type("number"),
+/// ]);
+/// let prompt = template_fstring!("{question}\n{format_instructions}", "question", "format_instructions")
+///     .with_output_parser(&parser)?;
+/// let answer: Answer = parser.parse(&output).await?;
+/// ```
+pub struct StructuredOutputParser<T> {
+    schemas: Vec<ResponseSchema>,
+    _output: PhantomData<fn() -> T>,
+}
+
+impl<T> StructuredOutputParser<T> {
+    pub fn new(schemas: Vec<ResponseSchema>) -> Self {
+        Self {
+            schemas,
+            _output: PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<T: DeserializeOwned> OutputParser<T> for StructuredOutputParser<T> {
+    async fn parse(&self, output: &str) -> Result<T, OutputParserError> {
+        let json = extract_json(output)?.ok_or_else(|| {
+            OutputParserError::invalid_output(output, "no JSON object or array found")
+        })?;
+        serde_json::from_str(json).map_err(|e| OutputParserError::DeserializationError {
+            path: json_path_at(json, e.line(), e.column()),
+            reason: e.to_string(),
+            json: json.to_string(),
+        })
+    }
+}
+
+impl<T> FormatInstructions for StructuredOutputParser<T> {
+    fn format_instructions(&self) -> String {
+        let fields: Vec<String> = self
+            .schemas
+            .iter()
+            .map(|schema| {
+                format!(
+                    "\t\"{}\": {}  // {}",
+                    schema.name, schema.value_type, schema.description
+                )
+            })
+            .collect();
+        format!(
+            "The output should be a markdown code snippet formatted in the following schema, \
+             including the leading and trailing \"```json\" and \"```\":\n\n```json\n{{\n{}\n}}\n```",
+            fields.join(",\n")
+        )
+    }
+}
+
+enum Container {
+    Object { key: Option<String>, in_key: bool },
+    Array { index: usize },
+}
+
+// Returns the path, like `$.items[1].kind`, of the value at a line and column of a JSON text
+// as reported by serde_json, so errors point at the field that failed.
+fn json_path_at(json: &str, line: usize, column: usize) -> String {
+    let offset: usize = json
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>()
+        + column;
+
+    let mut containers: Vec<Container> = Vec::new();
+    let mut chars = json.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if i >= offset {
+            break;
+        }
+        match c {
+            '{' => containers.push(Container::Object {
+                key: None,
+                in_key: true,
+            }),
+            '[' => containers.push(Container::Array { index: 0 }),
+            '}' | ']' => {
+                containers.pop();
+            }
+            ',' => match containers.last_mut() {
+                Some(Container::Object { key, in_key }) => {
+                    *key = None;
+                    *in_key = true;
+                }
+                Some(Container::Array { index }) => *index += 1,
+                None => {}
+            },
+            '"' => {
+                let mut string = String::new();
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            chars.next();
+                        }
+                        _ => string.push(c),
+                    }
+                }
+                if let Some(Container::Object { key, in_key }) = containers.last_mut() {
+                    if *in_key {
+                        *key = Some(string);
+                        *in_key = false;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut path = "$".to_string();
+    for container in containers {
+        match container {
+            Container::Object { key: Some(key), .. } => {
+                path.push('.');
+                path.push_str(&key);
+            }
+            Container::Array { index } => path.push_str(&format!("[{}]", index)),
+            Container::Object { key: None, .. } => {}
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Sentiment {
+        Positive,
+        Negative,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Author {
+        name: String,
+        email: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Review {
+        sentiment: Sentiment,
+        score: u8,
+        author: Author,
+        tags: Vec<String>,
+        summary: Option<String>,
+    }
+
+    fn parser() -> StructuredOutputParser<Review> {
+        StructuredOutputParser::new(vec![
+            ResponseSchema::new("sentiment", "positive or negative"),
+            ResponseSchema::new("score", "from 1 to 10").with_type("integer"),
+            ResponseSchema::new("author", "the reviewer")
+                .with_type("object with \"name\" and optional \"email\" strings"),
+            ResponseSchema::new("tags", "keywords").with_type("array of strings"),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_structured_output_parser() {
+        let output = r#"Here is the review:
+```json
+{
+    "sentiment": "positive",
+    "score": 9,
+    "author": {"name": "Ana"},
+    "tags": ["fast", "cheap"]
+}
+```"#;
+        let review = parser().parse(output).await.unwrap();
+        assert_eq!(
+            review,
+            Review {
+                sentiment: Sentiment::Positive,
+                score: 9,
+                author: Author {
+                    name: "Ana".into(),
+                    email: None
+                },
+                tags: vec!["fast".into(), "cheap".into()],
+                summary: None,
+            }
+        );
+
+        let output = r#"Sure! {"sentiment": "negative", "score": 2, "author": {"name": "Bo", "email": "bo@example.com"}, "tags": [], "summary": "Slow"}"#;
+        let review = parser().parse(output).await.unwrap();
+        assert_eq!(review.sentiment, Sentiment::Negative);
+        assert_eq!(review.author.email.as_deref(), Some("bo@example.com"));
+        assert_eq!(review.summary.as_deref(), Some("Slow"));
+    }
+
+    #[tokio::test]
+    async fn test_structured_output_parser_errors() {
+        let output = "{\"sentiment\": \"positive\", \"score\": 9,\n\"author\": {\"name\": \"Ana\", \"email\": 3}, \"tags\": []}";
+        match parser().parse(output).await {
+            Err(OutputParserError::DeserializationError { path, json, .. }) => {
+                assert_eq!(path, "$.author.email");
+                assert_eq!(json, output);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let output =
+            r#"{"sentiment": "positive", "score": 9, "author": {"email": "a@b.c"}, "tags": []}"#;
+        match parser().parse(output).await {
+            Err(OutputParserError::DeserializationError { path, reason, .. }) => {
+                assert_eq!(path, "$.author");
+                assert!(reason.starts_with("missing field `name`"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let output = r#"{"sentiment": "neutral"}"#;
+        match parser().parse(output).await {
+            Err(OutputParserError::DeserializationError { path, .. }) => {
+                assert_eq!(path, "$.sentiment")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_structured_output_format_instructions() {
+        let instructions = parser().format_instructions();
+        assert!(instructions.contains("```json\n{\n\t\"sentiment\": string  // positive or negative,\n\t\"score\": integer  // from 1 to 10,"));
+        assert!(instructions.ends_with("\t\"tags\": array of strings  // keywords\n}\n```"));
+    }
+}
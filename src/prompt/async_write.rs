@@ -0,0 +1,224 @@
+use std::{fmt, mem, sync::Arc};
+
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+};
+
+use super::{PromptArgs, PromptError, PromptFromatter, PromptTemplate};
+
+// Bytes rendered before a chunk is sent to the writer, and chunks rendered ahead of it.
+const CHUNK_SIZE: usize = 8 * 1024;
+const CHUNKS_AHEAD: usize = 4;
+
+impl PromptTemplate {
+    /// Formats the prompt like `format_into`, writing it to `writer` as it's rendered instead
+    /// of building it first. The prompt is rendered on tokio's blocking threads, which is why
+    /// it's shared through an `Arc`, and flushed every few KB; rendering waits while `writer`
+    /// is slow, so at most a few chunks are held in memory. Compressed prompts are the
+    /// exception: they're compressed in full before being written.
+    ///
+    /// # Errors
+    /// Returns the errors of `format`, or `PromptError::IoError` if `writer` fails. Part of
+    /// the prompt may have been written by then.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = Arc::new(prompt);
+    /// let mut stream = TcpStream::connect("inspector:9000").await?;
+    /// prompt.format_to_async(prompt_args! { "document" => document }, &mut stream).await?;
+    /// ```
+    pub async fn format_to_async<W: AsyncWrite + Unpin>(
+        self: &Arc<Self>,
+        input_variables: PromptArgs,
+        writer: &mut W,
+    ) -> Result<(), PromptError> {
+        let (sender, mut receiver) = mpsc::channel(CHUNKS_AHEAD);
+        let prompt = Arc::clone(self);
+        let rendering = tokio::task::spawn_blocking(move || {
+            let mut chunks = ChunkSender {
+                buffer: String::with_capacity(CHUNK_SIZE),
+                sender,
+            };
+            PromptFromatter::format_into(&*prompt, input_variables, &mut chunks)?;
+            chunks.send()?;
+            Ok::<_, PromptError>(())
+        });
+
+        let mut written = Ok(());
+        while let Some(chunk) = receiver.recv().await {
+            written = write_chunk(writer, &chunk).await;
+            if written.is_err() {
+                break;
+            }
+        }
+        // stops the rendering if the writer failed
+        drop(receiver);
+        let rendered = rendering
+            .await
+            .map_err(|e| PromptError::OtherError(format!("Failed to render the prompt: {}", e)))?;
+        written?;
+        rendered
+    }
+}
+
+async fn write_chunk<W: AsyncWrite + Unpin>(writer: &mut W, chunk: &str) -> std::io::Result<()> {
+    writer.write_all(chunk.as_bytes()).await?;
+    writer.flush().await
+}
+
+// Sends the rendered prompt in chunks of at least `CHUNK_SIZE` bytes, blocking while the
+// channel is full.
+struct ChunkSender {
+    buffer: String,
+    sender: mpsc::Sender<String>,
+}
+
+impl ChunkSender {
+    fn send(&mut self) -> fmt::Result {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = mem::replace(&mut self.buffer, String::with_capacity(CHUNK_SIZE));
+        self.sender.blocking_send(chunk).map_err(|_| fmt::Error)
+    }
+}
+
+impl fmt::Write for ChunkSender {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.buffer.push_str(s);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use super::*;
+    use crate::{prompt::TemplateFormat, prompt_args, template_fstring};
+
+    // Accepts a few bytes at a time, every other poll, so the prompt can't be written at once.
+    #[derive(Default)]
+    struct SlowWriter {
+        written: Vec<u8>,
+        ready: bool,
+        flushes: usize,
+    }
+
+    impl AsyncWrite for SlowWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let len = buf.len().min(1000);
+            self.written.extend_from_slice(&buf[..len]);
+            Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.flushes += 1;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_format_to_async_with_slow_writer() {
+        let prompt = Arc::new(template_fstring!(
+            "Summarize:\n{document}\nIn {words} words.",
+            "document",
+            "words"
+        ));
+        let args = prompt_args! { "document" => "lorem ipsum ".repeat(10_000), "words" => 50 };
+
+        let mut writer = SlowWriter::default();
+        prompt
+            .format_to_async(args.clone(), &mut writer)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.written).unwrap(),
+            prompt.format(args).unwrap()
+        );
+        assert!(writer.flushes > 1);
+
+        let mut writer = SlowWriter::default();
+        assert!(matches!(
+            prompt.format_to_async(prompt_args! {}, &mut writer).await,
+            Err(PromptError::InvalidInput(_))
+        ));
+        assert!(writer.written.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_format_to_async_matches_format() {
+        let prompts = [
+            template_fstring!("{name|upper}, {{braces}} {n:>5}", "name", "n"),
+            PromptTemplate::new(
+                "Raw {\"json\": {name}}".to_string(),
+                Vec::new(),
+                TemplateFormat::FString,
+            ),
+            template_fstring!("Hi {name}", "name").with_optional_variables(vec!["name"]),
+        ];
+        for prompt in prompts.map(Arc::new) {
+            let args = prompt_args! { "name" => "Ana", "n" => 7 };
+            let mut written = Vec::new();
+            prompt
+                .format_to_async(args.clone(), &mut written)
+                .await
+                .unwrap();
+            assert_eq!(written, prompt.format(args).unwrap().into_bytes());
+        }
+    }
+
+    #[cfg(all(feature = "jinja2", feature = "mustache"))]
+    #[tokio::test]
+    async fn test_format_to_async_streams_engine_templates() {
+        use crate::prompt::TemplateFormat;
+
+        let prompts = [
+            PromptTemplate::new(
+                "{% for line in lines %}{{ line }} é\n{% endfor %}".into(),
+                vec!["lines".into()],
+                TemplateFormat::Jinja2,
+            ),
+            PromptTemplate::new(
+                "{{#lines}}{{.}} é\n{{/lines}}".into(),
+                vec!["lines".into()],
+                TemplateFormat::Mustache,
+            ),
+        ];
+        for prompt in prompts.map(Arc::new) {
+            let args = prompt_args! { "lines" => vec!["lorem ipsum"; 2_000] };
+            let mut writer = SlowWriter::default();
+            prompt
+                .format_to_async(args.clone(), &mut writer)
+                .await
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(writer.written).unwrap(),
+                prompt.format(args).unwrap()
+            );
+            // written in chunks, so not buffered in full
+            assert!(writer.flushes > 1);
+        }
+    }
+}
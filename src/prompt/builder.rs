@@ -0,0 +1,266 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde_json::Value;
+
+use super::{
+    parser, MissingVariableBehavior, PromptArgs, PromptError, PromptMetadata, PromptTemplate,
+    TemplateFormat,
+};
+
+/// A builder for creating a validated `PromptTemplate`.
+///
+/// Variables are either declared with `variable` or `variables`, and checked against the
+/// template's placeholders, or inferred from the placeholders. When none are declared they
+/// are inferred.
+///
+/// ```rust,ignore
+/// let prompt = PromptTemplate::builder()
+///     .template("Today is {date}. Answer in a {tone} tone: {input}")
+///     .format(TemplateFormat::FString)
+///     .infer_variables()
+///     .default("tone", "neutral")
+///     .partial("date", "2024-05-01")
+///     .name("qa/main")
+///     .tag("rag")
+///     .version("1.2.0")
+///     .build()?;
+/// assert_eq!(prompt.required_variables(), vec!["input"]);
+/// ```
+#[derive(Default)]
+pub struct PromptTemplateBuilder {
+    template: Option<String>,
+    format: TemplateFormat,
+    variables: Option<Vec<String>>,
+    infer_variables: bool,
+    defaults: HashMap<String, String>,
+    partial_variables: PromptArgs,
+    optional_variables: Vec<String>,
+    collapse_blank_lines: bool,
+    strip_comment_lines: bool,
+    missing_variable_behavior: MissingVariableBehavior,
+    metadata: PromptMetadata,
+}
+
+impl PromptTemplateBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn template<S: Into<String>>(mut self, template: S) -> Self {
+        self.template = Some(template.into());
+        self
+    }
+
+    /// Sets the template format. Defaults to `TemplateFormat::FString`.
+    pub fn format(mut self, format: TemplateFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Infers the variables from the template's placeholders.
+    pub fn infer_variables(mut self) -> Self {
+        self.infer_variables = true;
+        self
+    }
+
+    /// Declares a variable, which must be used by the template.
+    pub fn variable<S: Into<String>>(mut self, variable: S) -> Self {
+        self.variables
+            .get_or_insert_with(Vec::new)
+            .push(variable.into());
+        self
+    }
+
+    pub fn variables<S: Into<String>>(mut self, variables: Vec<S>) -> Self {
+        self.variables
+            .get_or_insert_with(Vec::new)
+            .extend(variables.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets the value used when the variable is absent from the input.
+    pub fn default<K: Into<String>, V: Into<String>>(mut self, variable: K, value: V) -> Self {
+        self.defaults.insert(variable.into(), value.into());
+        self
+    }
+
+    /// Binds a variable, which is then no longer required by `format`.
+    pub fn partial<K: Into<String>, V: Into<Value>>(mut self, variable: K, value: V) -> Self {
+        self.partial_variables.insert(variable.into(), value.into());
+        self
+    }
+
+    /// Marks a variable optional, rendered as an empty string when absent.
+    pub fn optional<S: Into<String>>(mut self, variable: S) -> Self {
+        self.optional_variables.push(variable.into());
+        self
+    }
+
+    /// Removes the lines left blank by absent optional variables.
+    pub fn collapse_blank_lines(mut self, collapse: bool) -> Self {
+        self.collapse_blank_lines = collapse;
+        self
+    }
+
+    /// Removes the lines holding nothing but a `{# comment #}`.
+    pub fn strip_comment_lines(mut self, strip: bool) -> Self {
+        self.strip_comment_lines = strip;
+        self
+    }
+
+    pub fn missing_variable_behavior(mut self, behavior: MissingVariableBehavior) -> Self {
+        self.missing_variable_behavior = behavior;
+        self
+    }
+
+    /// Names the prompt, like `qa/main`. The name is added to the errors of `format`.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.metadata.name = Some(name.into());
+        self
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> Self {
+        self.metadata.description = Some(description.into());
+        self
+    }
+
+    pub fn owner<S: Into<String>>(mut self, owner: S) -> Self {
+        self.metadata.owner = Some(owner.into());
+        self
+    }
+
+    pub fn tag<S: Into<String>>(mut self, tag: S) -> Self {
+        self.metadata = self.metadata.with_tag(tag);
+        self
+    }
+
+    pub fn version<S: Into<String>>(mut self, version: S) -> Self {
+        self.metadata.version = Some(version.into());
+        self
+    }
+
+    /// Sets all the metadata at once, replacing the name, tags and version set before.
+    pub fn metadata(mut self, metadata: PromptMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Builds the `PromptTemplate`, in an `Arc` so chains can share it.
+    ///
+    /// # Errors
+    /// Returns `PromptError::MissingTemplate` if no template was set,
+    /// `PromptError::ConflictingVariables` if variables were both declared and inferred,
+    /// `PromptError::VariablesMismatch` if the declared variables aren't the template's
+    /// placeholders, and `PromptError::UnknownVariable` for a default, partial or optional
+    /// variable the template doesn't use.
+    pub fn build(self) -> Result<Arc<PromptTemplate>, PromptError> {
+        self.build_template().map(Arc::new)
+    }
+
+    // Builds the template like `build`, without the `Arc`, for the constructors of
+    // `PromptTemplate`.
+    pub(crate) fn build_template(self) -> Result<PromptTemplate, PromptError> {
+        let template = self.template.ok_or(PromptError::MissingTemplate)?;
+        let found = parser::extract_variables(&template, &self.format)?;
+        let variables = match self.variables {
+            Some(variables) if self.infer_variables => {
+                return Err(PromptError::ConflictingVariables(variables))
+            }
+            None => found,
+            Some(variables) => {
+                let unused: Vec<String> = variables
+                    .iter()
+                    .filter(|v| !found.contains(v))
+                    .cloned()
+                    .collect();
+                let undeclared: Vec<String> = found
+                    .into_iter()
+                    .filter(|v| !variables.contains(v))
+                    .collect();
+                if !unused.is_empty() || !undeclared.is_empty() {
+                    return Err(PromptError::VariablesMismatch { unused, undeclared });
+                }
+                variables
+            }
+        };
+
+        if let Some(key) = self
+            .defaults
+            .keys()
+            .chain(self.partial_variables.keys())
+            .chain(self.optional_variables.iter())
+            .find(|key| !variables.contains(key))
+        {
+            return Err(PromptError::UnknownVariable(key.clone()));
+        }
+
+        let prompt = PromptTemplate::new(template, variables, self.format)
+            .with_defaults(self.defaults)
+            .with_optional_variables(self.optional_variables)
+            .with_collapse_blank_lines(self.collapse_blank_lines)
+            .with_strip_comment_lines(self.strip_comment_lines)
+            .with_missing_variable_behavior(self.missing_variable_behavior)
+            .with_metadata(self.metadata);
+        if self.partial_variables.is_empty() {
+            Ok(prompt)
+        } else {
+            Ok(prompt.bind(self.partial_variables))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt::PromptFromatter, prompt_args};
+
+    #[test]
+    fn test_prompt_template_builder() {
+        let prompt = PromptTemplate::builder()
+            .template("Today is {date}. Answer in a {tone} tone: {input}")
+            .infer_variables()
+            .default("tone", "neutral")
+            .partial("date", "Monday")
+            .build()
+            .unwrap();
+        assert_eq!(prompt.variables(), vec!["tone", "input"]);
+        assert_eq!(prompt.required_variables(), vec!["input"]);
+        assert_eq!(
+            prompt.format(prompt_args! { "input" => "Hi" }).unwrap(),
+            "Today is Monday. Answer in a neutral tone: Hi"
+        );
+
+        let prompt = PromptTemplate::builder()
+            .template("Hello {{name}}")
+            .format(TemplateFormat::Jinja2)
+            .variable("name")
+            .build()
+            .unwrap();
+        assert_eq!(prompt.variables(), vec!["name"]);
+    }
+
+    #[test]
+    fn test_prompt_template_builder_misuse() {
+        let result = PromptTemplate::builder().variable("name").build();
+        assert!(matches!(result, Err(PromptError::MissingTemplate)));
+
+        let result = PromptTemplate::builder()
+            .template("Hello {name}")
+            .variable("name")
+            .infer_variables()
+            .build();
+        assert!(matches!(result, Err(PromptError::ConflictingVariables(v)) if v == vec!["name"]));
+
+        let result = PromptTemplate::builder()
+            .template("Hello {name}")
+            .variable("user")
+            .build();
+        assert!(matches!(result, Err(PromptError::VariablesMismatch { .. })));
+
+        let result = PromptTemplate::builder()
+            .template("Hello {name}")
+            .default("tone", "neutral")
+            .build();
+        assert!(matches!(result, Err(PromptError::UnknownVariable(v)) if v == "tone"));
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use super::{PromptArgs, PromptError, PromptFromatter, PromptMetadata, PromptTemplate};
+
+// Identifies each `CachedPrompt`, so prompts sharing a cache never read each other's entries.
+static NEXT_PROMPT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Struct `PromptCache` is an LRU cache of formatted prompts, keyed by the prompt and a hash of
+/// its input variables. It can be shared by several `CachedPrompt`s.
+pub struct PromptCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct Entries {
+    // key to the formatted prompt and the tick of its last use
+    values: HashMap<(u64, u64), (Arc<str>, u64)>,
+    // tick of last use to key, the oldest first
+    recency: BTreeMap<u64, (u64, u64)>,
+    tick: u64,
+}
+
+impl PromptCache {
+    /// Creates a cache holding at most `capacity` prompts, evicting the least recently used.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of cached prompts.
+    pub fn len(&self) -> usize {
+        self.entries().values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every cached prompt. The counters are kept.
+    pub fn clear(&self) {
+        *self.entries() = Entries::default();
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn get(&self, key: (u64, u64)) -> Option<Arc<str>> {
+        let mut entries = self.entries();
+        entries.tick += 1;
+        let tick = entries.tick;
+        let (value, last_used) = entries.values.get_mut(&key)?;
+        let value = value.clone();
+        let previous = std::mem::replace(last_used, tick);
+        entries.recency.remove(&previous);
+        entries.recency.insert(tick, key);
+        Some(value)
+    }
+
+    fn insert(&self, key: (u64, u64), value: Arc<str>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries();
+        entries.tick += 1;
+        let tick = entries.tick;
+        if let Some((_, previous)) = entries.values.insert(key, (value, tick)) {
+            entries.recency.remove(&previous);
+        }
+        entries.recency.insert(tick, key);
+        while entries.values.len() > self.capacity {
+            let Some((_, oldest)) = entries.recency.pop_first() else {
+                break;
+            };
+            entries.values.remove(&oldest);
+        }
+    }
+
+    fn remove_prompt(&self, prompt_id: u64) {
+        let mut entries = self.entries();
+        entries.values.retain(|(id, _), _| *id != prompt_id);
+        entries.recency.retain(|_, (id, _)| *id != prompt_id);
+    }
+}
+
+/// Struct `CachedPrompt` wraps a prompt, caching its formatted output by input variables.
+/// Repeated formats with the same variables return the cached `Arc<str>`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let cache = Arc::new(PromptCache::new(1024));
+/// let system = CachedPrompt::with_cache(Arc::new(system_prompt), cache.clone());
+/// let prompt = system.format_cached(prompt_args! { "tenant" => "acme" })?;
+/// log::debug!("hits: {}, misses: {}", cache.hits(), cache.misses());
+/// ```
+pub struct CachedPrompt {
+    id: u64,
+    prompt: Arc<dyn PromptFromatter>,
+    cache: Arc<PromptCache>,
+}
+
+impl CachedPrompt {
+    /// Wraps the prompt with its own cache of at most `capacity` prompts.
+    pub fn new(prompt: Arc<dyn PromptFromatter>, capacity: usize) -> Self {
+        Self::with_cache(prompt, Arc::new(PromptCache::new(capacity)))
+    }
+
+    /// Wraps the prompt with a cache shared with other prompts.
+    pub fn with_cache(prompt: Arc<dyn PromptFromatter>, cache: Arc<PromptCache>) -> Self {
+        Self {
+            id: NEXT_PROMPT_ID.fetch_add(1, Ordering::Relaxed),
+            prompt,
+            cache,
+        }
+    }
+
+    pub fn cache(&self) -> &Arc<PromptCache> {
+        &self.cache
+    }
+
+    /// Formats the prompt, or returns the cached result for the same input variables.
+    pub fn format_cached(&self, input_variables: PromptArgs) -> Result<Arc<str>, PromptError> {
+        let key = (self.id, hash_args(&input_variables));
+        if let Some(prompt) = self.cache.get(key) {
+            self.cache.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(prompt);
+        }
+        self.cache.misses.fetch_add(1, Ordering::Relaxed);
+        let prompt: Arc<str> = self.prompt.format(input_variables)?.into();
+        self.cache.insert(key, prompt.clone());
+        Ok(prompt)
+    }
+
+    /// Removes the cached results of this prompt, keeping those of other prompts sharing the
+    /// cache.
+    pub fn invalidate(&self) {
+        self.cache.remove_prompt(self.id);
+    }
+
+    /// Removes every cached result, including those of other prompts sharing the cache.
+    pub fn clear(&self) {
+        self.cache.clear();
+    }
+}
+
+// Hashes the variables independently of the map's iteration order.
+fn hash_args(input_variables: &PromptArgs) -> u64 {
+    let mut keys: Vec<&String> = input_variables.keys().collect();
+    keys.sort();
+    let mut hasher = DefaultHasher::new();
+    for key in keys {
+        key.hash(&mut hasher);
+        input_variables[key].to_string().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+impl PromptFromatter for CachedPrompt {
+    fn template(&self) -> String {
+        self.prompt.template()
+    }
+
+    fn variables(&self) -> Vec<String> {
+        self.prompt.variables()
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        Ok(self.format_cached(input_variables)?.to_string())
+    }
+
+    fn metadata(&self) -> &PromptMetadata {
+        self.prompt.metadata()
+    }
+
+    fn as_prompt_template(&self) -> Option<&PromptTemplate> {
+        self.prompt.as_prompt_template()
+    }
+}
+
+impl PromptTemplate {
+    /// Wraps the prompt in a `CachedPrompt` with its own cache of at most `capacity` prompts.
+    pub fn cached(self, capacity: usize) -> CachedPrompt {
+        CachedPrompt::new(Arc::new(self), capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, template_fstring};
+
+    #[test]
+    fn test_cached_prompt() {
+        let prompt = template_fstring!("System prompt of {tenant}", "tenant").cached(2);
+        let first = prompt
+            .format_cached(prompt_args! { "tenant" => "acme" })
+            .unwrap();
+        let second = prompt
+            .format_cached(prompt_args! { "tenant" => "acme" })
+            .unwrap();
+        assert_eq!(&*first, "System prompt of acme");
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!((prompt.cache().hits(), prompt.cache().misses()), (1, 1));
+
+        // "acme" is the most recently used, so "globex" is evicted first
+        prompt
+            .format(prompt_args! { "tenant" => "globex" })
+            .unwrap();
+        prompt.format(prompt_args! { "tenant" => "acme" }).unwrap();
+        prompt
+            .format(prompt_args! { "tenant" => "initech" })
+            .unwrap();
+        assert_eq!(prompt.cache().len(), 2);
+        prompt.format(prompt_args! { "tenant" => "acme" }).unwrap();
+        prompt
+            .format(prompt_args! { "tenant" => "globex" })
+            .unwrap();
+        assert_eq!((prompt.cache().hits(), prompt.cache().misses()), (3, 4));
+
+        prompt.clear();
+        assert!(prompt.cache().is_empty());
+    }
+
+    #[test]
+    fn test_shared_prompt_cache() {
+        let cache = Arc::new(PromptCache::new(10));
+        let greeting = CachedPrompt::with_cache(Arc::new("Hello {name}"), cache.clone());
+        let farewell = CachedPrompt::with_cache(Arc::new("Bye {name}"), cache.clone());
+        let args = || prompt_args! { "name" => "Luis" };
+        assert_eq!(&*greeting.format_cached(args()).unwrap(), "Hello Luis");
+        assert_eq!(&*farewell.format_cached(args()).unwrap(), "Bye Luis");
+        assert_eq!(cache.len(), 2);
+
+        greeting.invalidate();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(&*farewell.format_cached(args()).unwrap(), "Bye Luis");
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+    }
+}
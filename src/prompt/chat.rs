@@ -1,7 +1,19 @@
-use crate::schemas::{messages::Message, prompt::PromptValue};
+use std::{fmt, sync::Arc};
+
+use serde_json::Value;
+
+use crate::{
+    callbacks::{CallbackHandler, Callbacks},
+    schemas::{
+        messages::{ContentPart, ImageDetail, Message, MessageType},
+        prompt::PromptValue,
+    },
+};
 
 use super::{
-    FormatPrompter, MessageFormatter, PromptArgs, PromptError, PromptFromatter, PromptTemplate,
+    display::{debug_variables, write_marked_template, write_slot},
+    DebugOptions, FormatPrompter, MessageFormatter, MessageTrimmer, PromptArgs, PromptError,
+    PromptFromatter, PromptMetadata, PromptTemplate, TemplateFormat,
 };
 
 /// Struct `HumanMessagePromptTemplate` defines a template for creating human (user) messages.
@@ -20,19 +32,36 @@ pub struct HumanMessagePromptTemplate {
 }
 
 impl HumanMessagePromptTemplate {
-    pub fn new(prompt: PromptTemplate) -> Self {
-        Self { prompt }
+    pub fn new<P: Into<PromptTemplate>>(prompt: P) -> Self {
+        Self {
+            prompt: prompt.into(),
+        }
+    }
+
+    /// Formats the template into a single message.
+    pub fn format_message(&self, input_variables: PromptArgs) -> Result<Message, PromptError> {
+        let message = Message::new_human_message(&self.prompt.format(input_variables)?);
+        log::debug!("message: {:?}", message);
+        Ok(message)
     }
 }
 impl MessageFormatter for HumanMessagePromptTemplate {
     fn format_messages(&self, input_variables: PromptArgs) -> Result<Vec<Message>, PromptError> {
-        let message = Message::new_human_message(&self.prompt.format(input_variables)?);
-        log::debug!("message: {:?}", message);
-        Ok(vec![message])
+        Ok(vec![self.format_message(input_variables)?])
     }
     fn input_variables(&self) -> Vec<String> {
         self.prompt.variables().clone()
     }
+    fn message_template(&self) -> Option<String> {
+        Some(format!(
+            "{}: {}",
+            MessageType::HumanMessage.to_string(),
+            self.prompt.template()
+        ))
+    }
+    fn prompt_template(&self) -> Option<&PromptTemplate> {
+        Some(&self.prompt)
+    }
 }
 
 impl FormatPrompter for HumanMessagePromptTemplate {
@@ -62,8 +91,17 @@ pub struct SystemMessagePromptTemplate {
 }
 
 impl SystemMessagePromptTemplate {
-    pub fn new(prompt: PromptTemplate) -> Self {
-        Self { prompt }
+    pub fn new<P: Into<PromptTemplate>>(prompt: P) -> Self {
+        Self {
+            prompt: prompt.into(),
+        }
+    }
+
+    /// Formats the template into a single message.
+    pub fn format_message(&self, input_variables: PromptArgs) -> Result<Message, PromptError> {
+        let message = Message::new_system_message(&self.prompt.format(input_variables)?);
+        log::debug!("message: {:?}", message);
+        Ok(message)
     }
 }
 
@@ -79,13 +117,21 @@ impl FormatPrompter for SystemMessagePromptTemplate {
 
 impl MessageFormatter for SystemMessagePromptTemplate {
     fn format_messages(&self, input_variables: PromptArgs) -> Result<Vec<Message>, PromptError> {
-        let message = Message::new_system_message(&self.prompt.format(input_variables)?);
-        log::debug!("message: {:?}", message);
-        Ok(vec![message])
+        Ok(vec![self.format_message(input_variables)?])
     }
     fn input_variables(&self) -> Vec<String> {
         self.prompt.variables().clone()
     }
+    fn message_template(&self) -> Option<String> {
+        Some(format!(
+            "{}: {}",
+            MessageType::SystemMessage.to_string(),
+            self.prompt.template()
+        ))
+    }
+    fn prompt_template(&self) -> Option<&PromptTemplate> {
+        Some(&self.prompt)
+    }
 }
 
 /// Struct `AIMessagePromptTemplate` defines a template for creating AI (assistant) messages.
@@ -115,18 +161,213 @@ impl FormatPrompter for AIMessagePromptTemplate {
 
 impl MessageFormatter for AIMessagePromptTemplate {
     fn format_messages(&self, input_variables: PromptArgs) -> Result<Vec<Message>, PromptError> {
+        Ok(vec![self.format_message(input_variables)?])
+    }
+    fn input_variables(&self) -> Vec<String> {
+        self.prompt.variables().clone()
+    }
+    fn message_template(&self) -> Option<String> {
+        Some(format!(
+            "{}: {}",
+            MessageType::AIMessage.to_string(),
+            self.prompt.template()
+        ))
+    }
+    fn prompt_template(&self) -> Option<&PromptTemplate> {
+        Some(&self.prompt)
+    }
+}
+
+impl AIMessagePromptTemplate {
+    pub fn new<P: Into<PromptTemplate>>(prompt: P) -> Self {
+        Self {
+            prompt: prompt.into(),
+        }
+    }
+
+    /// Formats the template into a single message.
+    pub fn format_message(&self, input_variables: PromptArgs) -> Result<Message, PromptError> {
         let message = Message::new_ai_message(&self.prompt.format(input_variables)?);
         log::debug!("message: {:?}", message);
-        Ok(vec![message])
+        Ok(message)
+    }
+}
+
+/// Struct `MessagePromptTemplate` defines a template for creating messages of any `MessageType`.
+/// `PromptTemplate` is used to generate the message template.
+///
+/// # Usage
+/// ```rust,ignore
+/// let tool_message_prompt = MessagePromptTemplate::new(
+///     MessageType::ToolMessage,
+///     template_fstring!("Result: {result}", "result"),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct MessagePromptTemplate {
+    message_type: MessageType,
+    prompt: PromptTemplate,
+}
+
+impl MessagePromptTemplate {
+    pub fn new<P: Into<PromptTemplate>>(message_type: MessageType, prompt: P) -> Self {
+        Self {
+            message_type,
+            prompt: prompt.into(),
+        }
+    }
+
+    /// Formats the template into a single message.
+    pub fn format_message(&self, input_variables: PromptArgs) -> Result<Message, PromptError> {
+        let message = Message {
+            content: self.prompt.format(input_variables)?,
+            message_type: self.message_type.clone(),
+            ..Default::default()
+        };
+        log::debug!("message: {:?}", message);
+        Ok(message)
+    }
+}
+
+impl FormatPrompter for MessagePromptTemplate {
+    fn format_prompt(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
+        let messages = self.format_messages(input_variables)?;
+        Ok(PromptValue::from_messages(messages))
+    }
+    fn get_input_variables(&self) -> Vec<String> {
+        self.input_variables()
+    }
+}
+
+impl MessageFormatter for MessagePromptTemplate {
+    fn format_messages(&self, input_variables: PromptArgs) -> Result<Vec<Message>, PromptError> {
+        Ok(vec![self.format_message(input_variables)?])
     }
     fn input_variables(&self) -> Vec<String> {
         self.prompt.variables().clone()
     }
+    fn message_template(&self) -> Option<String> {
+        Some(format!(
+            "{}: {}",
+            self.message_type.to_string(),
+            self.prompt.template()
+        ))
+    }
+    fn prompt_template(&self) -> Option<&PromptTemplate> {
+        Some(&self.prompt)
+    }
 }
 
-impl AIMessagePromptTemplate {
-    pub fn new(prompt: PromptTemplate) -> Self {
-        Self { prompt }
+/// Struct `ImagePlaceholder` defines a multimodal human message holding the image of a
+/// variable, for vision models, optionally preceded by a text template. The variable is the
+/// URL of the image, or its base64 `data:image/...` URI.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = ChatPromptTemplate::new(message_formatter![])
+///     .with_template(
+///         ImagePlaceholder::new("photo")
+///             .with_text(template_fstring!("What is in this {kind}?", "kind"))
+///             .with_detail(ImageDetail::High),
+///     );
+/// let messages = prompt.format_messages(prompt_args! {
+///     "kind" => "photo",
+///     "photo" => "https://example.com/cat.png",
+/// })?;
+/// ```
+#[derive(Clone)]
+pub struct ImagePlaceholder {
+    variable: String,
+    detail: Option<ImageDetail>,
+    text: Option<PromptTemplate>,
+}
+
+impl ImagePlaceholder {
+    pub fn new<S: Into<String>>(variable: S) -> Self {
+        Self {
+            variable: variable.into(),
+            detail: None,
+            text: None,
+        }
+    }
+
+    pub fn with_detail(mut self, detail: ImageDetail) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    /// Sets the text of the message, put before the image.
+    pub fn with_text<P: Into<PromptTemplate>>(mut self, text: P) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    /// Formats the message, with a text part if there's a text template and an image part.
+    ///
+    /// # Errors
+    /// Returns `PromptError::MissingVariable` if the image variable is missing and
+    /// `PromptError::InvalidImage` if it isn't an `http`, `https` or `data:image` URL.
+    pub fn format_message(&self, input_variables: PromptArgs) -> Result<Message, PromptError> {
+        let url = match input_variables.get(&self.variable) {
+            None => return Err(PromptError::MissingVariable(self.variable.clone())),
+            Some(Value::String(url)) => url.clone(),
+            Some(value) => {
+                return Err(PromptError::InvalidImage {
+                    variable: self.variable.clone(),
+                    reason: format!("expected a URL, found {}", value),
+                })
+            }
+        };
+        let is_image_url = ["http://", "https://", "data:image/"]
+            .iter()
+            .any(|scheme| url.starts_with(scheme));
+        if !is_image_url {
+            return Err(PromptError::InvalidImage {
+                variable: self.variable.clone(),
+                reason: "expected an http, https or data:image URL".to_string(),
+            });
+        }
+
+        let mut parts = Vec::new();
+        if let Some(text) = &self.text {
+            parts.push(ContentPart::text(text.format(input_variables)?));
+        }
+        parts.push(ContentPart::ImageUrl {
+            url,
+            detail: self.detail,
+        });
+        let message = Message::new_human_message("").with_parts(parts);
+        log::debug!("message: {:?}", message);
+        Ok(message)
+    }
+}
+
+impl MessageFormatter for ImagePlaceholder {
+    fn format_messages(&self, input_variables: PromptArgs) -> Result<Vec<Message>, PromptError> {
+        Ok(vec![self.format_message(input_variables)?])
+    }
+    fn input_variables(&self) -> Vec<String> {
+        let mut variables = self
+            .text
+            .as_ref()
+            .map(PromptTemplate::variables)
+            .unwrap_or_default();
+        if !variables.contains(&self.variable) {
+            variables.push(self.variable.clone());
+        }
+        variables
+    }
+    fn message_template(&self) -> Option<String> {
+        let image = format!("[image: {{{}}}]", self.variable);
+        Some(match &self.text {
+            Some(text) => format!(
+                "{}: {}\n{}",
+                MessageType::HumanMessage.to_string(),
+                text.template(),
+                image
+            ),
+            None => format!("{}: {}", MessageType::HumanMessage.to_string(), image),
+        })
     }
 }
 
@@ -134,6 +375,10 @@ pub enum MessageOrTemplate {
     Message(Message),
     Template(Box<dyn MessageFormatter>),
     MessagesPlaceholder(String),
+    /// Like `MessagesPlaceholder`, but expands to nothing when its variable is missing.
+    OptionalMessagesPlaceholder(String),
+    /// Like `MessagesPlaceholder`, with the messages trimmed to the trimmer's budget.
+    TrimmedMessagesPlaceholder(String, MessageTrimmer),
 }
 
 /// `fmt_message` is a utility macro used to create a `MessageOrTemplate::Message` variant.
@@ -184,6 +429,21 @@ macro_rules! fmt_placeholder {
     };
 }
 
+/// `fmt_optional_placeholder` is a utility macro used to create a
+/// `MessageOrTemplate::OptionalMessagesPlaceholder` variant.
+///
+/// # Usage
+/// ```rust,ignore
+/// fmt_optional_placeholder!("history")
+/// ```
+/// The placeholder expands to nothing when `history` is missing from the input variables.
+#[macro_export]
+macro_rules! fmt_optional_placeholder {
+    ($placeholder:expr) => {
+        $crate::prompt::MessageOrTemplate::OptionalMessagesPlaceholder($placeholder.into())
+    };
+}
+
 pub struct MessageFormatterStruct {
     items: Vec<MessageOrTemplate>,
 }
@@ -207,8 +467,28 @@ impl MessageFormatterStruct {
         ));
     }
 
-    fn format(&self, input_variables: PromptArgs) -> Result<Vec<Message>, PromptError> {
+    pub fn add_optional_messages_placeholder(&mut self, placeholder: &str) {
+        self.items
+            .push(MessageOrTemplate::OptionalMessagesPlaceholder(
+                placeholder.to_string(),
+            ));
+    }
+
+    pub fn add_trimmed_messages_placeholder(&mut self, placeholder: &str, trimmer: MessageTrimmer) {
+        self.items
+            .push(MessageOrTemplate::TrimmedMessagesPlaceholder(
+                placeholder.to_string(),
+                trimmer,
+            ));
+    }
+
+    /// Formats the messages, also returning how many messages trimmed placeholders dropped.
+    pub fn format_messages_trimmed(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<(Vec<Message>, usize), PromptError> {
         let mut result: Vec<Message> = Vec::new();
+        let mut trimmed = 0;
         for item in &self.items {
             match item {
                 MessageOrTemplate::Message(msg) => result.push(msg.clone()),
@@ -216,18 +496,36 @@ impl MessageFormatterStruct {
                     result.extend(tmpl.format_messages(input_variables.clone())?)
                 }
                 MessageOrTemplate::MessagesPlaceholder(placeholder) => {
-                    let messages = input_variables[placeholder].clone();
-                    result.extend(Message::messages_from_value(&messages)?);
+                    let messages = input_variables
+                        .get(placeholder)
+                        .ok_or_else(|| PromptError::MissingVariable(placeholder.clone()))?;
+                    result.extend(Message::messages_from_value(messages)?);
+                }
+                MessageOrTemplate::OptionalMessagesPlaceholder(placeholder) => {
+                    match input_variables.get(placeholder) {
+                        Some(messages) if !messages.is_null() => {
+                            result.extend(Message::messages_from_value(messages)?)
+                        }
+                        _ => {}
+                    }
+                }
+                MessageOrTemplate::TrimmedMessagesPlaceholder(placeholder, trimmer) => {
+                    let messages = input_variables
+                        .get(placeholder)
+                        .ok_or_else(|| PromptError::MissingVariable(placeholder.clone()))?;
+                    let (messages, dropped) = trimmer.trim(Message::messages_from_value(messages)?);
+                    result.extend(messages);
+                    trimmed += dropped;
                 }
             }
         }
-        Ok(result)
+        Ok((result, trimmed))
     }
 }
 
 impl MessageFormatter for MessageFormatterStruct {
     fn format_messages(&self, input_variables: PromptArgs) -> Result<Vec<Message>, PromptError> {
-        self.format(input_variables)
+        Ok(self.format_messages_trimmed(input_variables)?.0)
     }
     fn input_variables(&self) -> Vec<String> {
         let mut variables = Vec::new();
@@ -237,9 +535,11 @@ impl MessageFormatter for MessageFormatterStruct {
                 MessageOrTemplate::Template(tmpl) => {
                     variables.extend(tmpl.input_variables());
                 }
-                MessageOrTemplate::MessagesPlaceholder(placeholder) => {
+                MessageOrTemplate::MessagesPlaceholder(placeholder)
+                | MessageOrTemplate::TrimmedMessagesPlaceholder(placeholder, _) => {
                     variables.extend(vec![placeholder.clone()]);
                 }
+                MessageOrTemplate::OptionalMessagesPlaceholder(_) => {}
             }
         }
         variables
@@ -248,7 +548,7 @@ impl MessageFormatter for MessageFormatterStruct {
 
 impl FormatPrompter for MessageFormatterStruct {
     fn format_prompt(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
-        let messages = self.format(input_variables)?;
+        let messages = self.format_messages(input_variables)?;
         Ok(PromptValue::from_messages(messages))
     }
     fn get_input_variables(&self) -> Vec<String> {
@@ -256,6 +556,293 @@ impl FormatPrompter for MessageFormatterStruct {
     }
 }
 
+const DEFAULT_CHAT_PROMPT_NAME: &str = "ChatPromptTemplate";
+
+/// Struct `ChatPromptTemplate` renders a list of role-tagged messages from per-role templates,
+/// fixed messages and messages placeholders, all sharing the same input variables.
+///
+/// As a `PromptFromatter`, it joins the rendered messages into a single `Role: content` string
+/// for models that don't take a list of messages.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = ChatPromptTemplate::from_messages(vec![
+///     (MessageType::SystemMessage, "You are {persona}"),
+///     (MessageType::HumanMessage, "{input}"),
+/// ])?;
+/// let messages = prompt.format_messages(prompt_args! {
+///     "persona" => "a pirate",
+///     "input" => "Hello",
+/// })?;
+/// ```
+pub struct ChatPromptTemplate {
+    formatter: MessageFormatterStruct,
+    name: String,
+    callbacks: Callbacks,
+    metadata: PromptMetadata,
+}
+
+impl ChatPromptTemplate {
+    pub fn new(formatter: MessageFormatterStruct) -> Self {
+        Self {
+            formatter,
+            name: DEFAULT_CHAT_PROMPT_NAME.to_string(),
+            callbacks: Callbacks::new(),
+            metadata: PromptMetadata::default(),
+        }
+    }
+
+    /// Sets the name given to callback handlers. Defaults to `ChatPromptTemplate`.
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Sets the metadata of the prompt. Its name, if any, is also given to callback handlers.
+    pub fn with_metadata(mut self, metadata: PromptMetadata) -> Self {
+        if let Some(name) = &metadata.name {
+            self.name = name.clone();
+        }
+        self.metadata = metadata;
+        self
+    }
+
+    /// Adds a handler notified of the formatted messages.
+    pub fn with_callback_handler(mut self, handler: Arc<dyn CallbackHandler>) -> Self {
+        self.callbacks.add_handler(handler);
+        self
+    }
+
+    /// Creates a `ChatPromptTemplate` from `(MessageType, template)` pairs. Each template is an
+    /// FString template whose variables are inferred from its placeholders.
+    pub fn from_messages<S: AsRef<str>>(
+        messages: Vec<(MessageType, S)>,
+    ) -> Result<Self, PromptError> {
+        let mut formatter = MessageFormatterStruct::new();
+        for (message_type, template) in messages {
+            let prompt = PromptTemplate::from_template(template.as_ref(), TemplateFormat::FString)?;
+            formatter.add_template(Box::new(MessagePromptTemplate::new(message_type, prompt)));
+        }
+        Ok(Self::new(formatter))
+    }
+
+    /// Appends a fixed message.
+    pub fn with_message(mut self, message: Message) -> Self {
+        self.formatter.add_message(message);
+        self
+    }
+
+    /// Appends a message template.
+    pub fn with_template<T: Into<Box<dyn MessageFormatter>>>(mut self, template: T) -> Self {
+        self.formatter.add_template(template.into());
+        self
+    }
+
+    /// Appends a human message holding the image of the `variable` variable, see
+    /// `ImagePlaceholder`.
+    pub fn with_image_placeholder<S: Into<String>>(self, variable: S) -> Self {
+        self.with_template(ImagePlaceholder::new(variable))
+    }
+
+    /// Appends a placeholder expanded to the messages of the `placeholder` variable.
+    pub fn with_messages_placeholder(mut self, placeholder: &str) -> Self {
+        self.formatter.add_messages_placeholder(placeholder);
+        self
+    }
+
+    /// Appends a placeholder expanded to the messages of the `placeholder` variable, or to
+    /// nothing when the variable is missing.
+    pub fn with_optional_messages_placeholder(mut self, placeholder: &str) -> Self {
+        self.formatter
+            .add_optional_messages_placeholder(placeholder);
+        self
+    }
+
+    /// Appends a placeholder expanded to the messages of the `placeholder` variable, trimmed
+    /// to the budget of `trimmer`.
+    pub fn with_trimmed_messages_placeholder(
+        mut self,
+        placeholder: &str,
+        trimmer: MessageTrimmer,
+    ) -> Self {
+        self.formatter
+            .add_trimmed_messages_placeholder(placeholder, trimmer);
+        self
+    }
+
+    /// Formats the messages, also returning how many messages trimmed placeholders dropped,
+    /// e.g. to log it.
+    pub fn format_messages_trimmed(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<(Vec<Message>, usize), PromptError> {
+        let (messages, trimmed) = self.formatter.format_messages_trimmed(input_variables)?;
+        self.notify_formatted(&messages);
+        Ok((messages, trimmed))
+    }
+
+    // Returns the role and template of each message, for diffs.
+    pub(crate) fn message_parts(&self) -> Vec<(String, String)> {
+        self.formatter
+            .items
+            .iter()
+            .map(|item| match item {
+                MessageOrTemplate::Message(message) => {
+                    (message.message_type.to_string(), message.content.clone())
+                }
+                MessageOrTemplate::Template(template) => {
+                    let template = template.message_template().unwrap_or_default();
+                    match template.split_once(": ") {
+                        Some((role, template)) => (role.to_string(), template.to_string()),
+                        None => ("template".to_string(), template),
+                    }
+                }
+                MessageOrTemplate::MessagesPlaceholder(placeholder)
+                | MessageOrTemplate::OptionalMessagesPlaceholder(placeholder)
+                | MessageOrTemplate::TrimmedMessagesPlaceholder(placeholder, _) => {
+                    ("placeholder".to_string(), format!("{{{}}}", placeholder))
+                }
+            })
+            .collect()
+    }
+
+    /// Shows each message like `PromptTemplate::render_debug`, under its role, with the
+    /// placeholders of its template annotated with the values from `input_variables`.
+    pub fn render_debug(&self, input_variables: &PromptArgs) -> String {
+        self.render_debug_with(input_variables, &DebugOptions::default())
+    }
+
+    /// Like `render_debug`, with the given options.
+    pub fn render_debug_with(
+        &self,
+        input_variables: &PromptArgs,
+        options: &DebugOptions,
+    ) -> String {
+        let mut out = String::new();
+        self.write_debug(&mut out, Some(input_variables), options)
+            .expect("writing to a String can't fail");
+        out
+    }
+
+    fn write_debug<W: fmt::Write + ?Sized>(
+        &self,
+        out: &mut W,
+        input_variables: Option<&PromptArgs>,
+        options: &DebugOptions,
+    ) -> fmt::Result {
+        for ((role, template), item) in self.message_parts().into_iter().zip(&self.formatter.items)
+        {
+            writeln!(out, "[{}]", role)?;
+            match item {
+                MessageOrTemplate::Template(formatter) => match formatter.prompt_template() {
+                    Some(prompt) => {
+                        let variables = input_variables.map(|args| debug_variables(prompt, args));
+                        write_marked_template(out, prompt, variables.as_ref(), options)?
+                    }
+                    None => out.write_str(&template)?,
+                },
+                MessageOrTemplate::MessagesPlaceholder(placeholder)
+                | MessageOrTemplate::OptionalMessagesPlaceholder(placeholder)
+                | MessageOrTemplate::TrimmedMessagesPlaceholder(placeholder, _) => {
+                    write_slot(out, placeholder, placeholder, input_variables, options)?
+                }
+                MessageOrTemplate::Message(_) => out.write_str(&template)?,
+            }
+            out.write_str("\n")?;
+        }
+        write!(out, "---\nvariables: {}", self.input_variables().join(", "))
+    }
+
+    fn notify_formatted(&self, messages: &[Message]) {
+        self.callbacks.emit("prompt_formatted", |handler| {
+            handler.on_prompt_formatted(
+                &self.name,
+                &PromptValue::from_messages(messages.to_vec()).to_string(),
+            )
+        });
+    }
+}
+
+/// Shows each message under its role, with the placeholders of its template marked like
+/// `⟦name⟧`, followed by the variables.
+impl fmt::Display for ChatPromptTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_debug(f, None, &DebugOptions::default())
+    }
+}
+
+impl From<MessageFormatterStruct> for ChatPromptTemplate {
+    fn from(formatter: MessageFormatterStruct) -> Self {
+        Self::new(formatter)
+    }
+}
+
+impl MessageFormatter for ChatPromptTemplate {
+    fn format_messages(&self, input_variables: PromptArgs) -> Result<Vec<Message>, PromptError> {
+        let messages = self.formatter.format_messages(input_variables)?;
+        self.notify_formatted(&messages);
+        Ok(messages)
+    }
+    fn input_variables(&self) -> Vec<String> {
+        let mut variables: Vec<String> = Vec::new();
+        for variable in self.formatter.input_variables() {
+            if !variables.contains(&variable) {
+                variables.push(variable);
+            }
+        }
+        variables
+    }
+}
+
+impl FormatPrompter for ChatPromptTemplate {
+    fn format_prompt(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
+        self.to_prompt_value(input_variables)
+    }
+    fn get_input_variables(&self) -> Vec<String> {
+        self.input_variables()
+    }
+}
+
+impl PromptFromatter for ChatPromptTemplate {
+    fn template(&self) -> String {
+        self.formatter
+            .items
+            .iter()
+            .map(|item| match item {
+                MessageOrTemplate::Message(message) => {
+                    format!("{}: {}", message.message_type.to_string(), message.content)
+                }
+                MessageOrTemplate::Template(template) => {
+                    template.message_template().unwrap_or_default()
+                }
+                MessageOrTemplate::MessagesPlaceholder(placeholder)
+                | MessageOrTemplate::OptionalMessagesPlaceholder(placeholder)
+                | MessageOrTemplate::TrimmedMessagesPlaceholder(placeholder, _) => {
+                    format!("{{{}}}", placeholder)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn variables(&self) -> Vec<String> {
+        self.input_variables()
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        Ok(self.to_prompt_value(input_variables)?.to_string())
+    }
+
+    fn to_prompt_value(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
+        let messages = self.format_messages(input_variables)?;
+        Ok(PromptValue::from_messages(messages))
+    }
+
+    fn metadata(&self) -> &PromptMetadata {
+        &self.metadata
+    }
+}
+
 #[macro_export]
 // A macro for creating a new MessageFormatterStruct with various types of messages.
 ///
@@ -287,19 +874,90 @@ macro_rules! message_formatter {
             $crate::prompt::MessageOrTemplate::Message(msg) => formatter.add_message(msg),
             $crate::prompt::MessageOrTemplate::Template(tmpl) => formatter.add_template(tmpl),
             $crate::prompt::MessageOrTemplate::MessagesPlaceholder(placeholder) => formatter.add_messages_placeholder(&placeholder.clone()),
+            $crate::prompt::MessageOrTemplate::OptionalMessagesPlaceholder(placeholder) => formatter.add_optional_messages_placeholder(&placeholder.clone()),
+            $crate::prompt::MessageOrTemplate::TrimmedMessagesPlaceholder(placeholder, trimmer) => formatter.add_trimmed_messages_placeholder(&placeholder.clone(), trimmer),
         }
     )*
     formatter
 }};
 }
 
+/// `chat_prompt!` declares a `ChatPromptTemplate` from role templates, with their variables
+/// inferred from their placeholders. The roles are `system`, `human` and `ai`, and a bare
+/// identifier is a messages placeholder named after it. Unknown roles fail to compile.
+///
+/// It returns a `Result`, with the `PromptError` of the first role template that can't be
+/// parsed, like `PromptTemplate::from_template`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = chat_prompt! {
+///     system: "You are {persona}.",
+///     history,
+///     human: "{input}",
+/// }?;
+/// assert_eq!(prompt.variables(), vec!["persona", "history", "input"]);
+/// ```
+#[macro_export]
+macro_rules! chat_prompt {
+    (@build $prompt:expr;) => {
+        $prompt
+    };
+    (@build $prompt:expr; system: $template:expr $(, $($rest:tt)*)?) => {
+        $crate::chat_prompt!(@build $crate::chat_prompt!(
+            @role $prompt, SystemMessagePromptTemplate, $template
+        ); $($($rest)*)?)
+    };
+    (@build $prompt:expr; human: $template:expr $(, $($rest:tt)*)?) => {
+        $crate::chat_prompt!(@build $crate::chat_prompt!(
+            @role $prompt, HumanMessagePromptTemplate, $template
+        ); $($($rest)*)?)
+    };
+    (@build $prompt:expr; ai: $template:expr $(, $($rest:tt)*)?) => {
+        $crate::chat_prompt!(@build $crate::chat_prompt!(
+            @role $prompt, AIMessagePromptTemplate, $template
+        ); $($($rest)*)?)
+    };
+    (@build $prompt:expr; $role:ident: $template:expr $(, $($rest:tt)*)?) => {
+        compile_error!(concat!(
+            "unknown role `",
+            stringify!($role),
+            "`, expected `system`, `human` or `ai`"
+        ))
+    };
+    (@build $prompt:expr; $placeholder:ident $(, $($rest:tt)*)?) => {
+        $crate::chat_prompt!(@build $prompt.map(|prompt| {
+            prompt.with_messages_placeholder(stringify!($placeholder))
+        }); $($($rest)*)?)
+    };
+    (@role $prompt:expr, $role:ident, $template:expr) => {
+        $prompt.and_then(|prompt| {
+            let template = $crate::prompt::PromptTemplate::try_from($template)?;
+            Ok(prompt.with_template($crate::prompt::$role::new(template)))
+        })
+    };
+    ($($body:tt)*) => {
+        $crate::chat_prompt!(@build Ok::<_, $crate::prompt::PromptError>(
+            $crate::prompt::ChatPromptTemplate::new($crate::prompt::MessageFormatterStruct::new())
+        ); $($body)*)
+    };
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use crate::{
+        callbacks::{CallbackEvent, CollectingCallbackHandler},
         message_formatter,
-        prompt::{chat::AIMessagePromptTemplate, FormatPrompter},
+        prompt::{
+            chat::AIMessagePromptTemplate, ChatPromptTemplate, FormatPrompter,
+            HumanMessagePromptTemplate, ImagePlaceholder, MessageFormatter, MessageFormatterStruct,
+            MessageTrimmer, PromptError, PromptFromatter, PromptTemplate,
+            SystemMessagePromptTemplate,
+        },
         prompt_args,
-        schemas::messages::Message,
+        schemas::messages::{ContentPart, ImageDetail, Message, MessageContent, MessageType},
         template_fstring,
     };
 
@@ -352,4 +1010,296 @@ mod tests {
         assert_eq!(formatted_messages[2].content, "Placeholder message 1");
         assert_eq!(formatted_messages[3].content, "Placeholder message 2");
     }
+
+    #[test]
+    fn test_role_message_prompt_templates() {
+        let shared = Arc::new(template_fstring!("Answer as {persona}", "persona"));
+        let system = SystemMessagePromptTemplate::new(shared.clone());
+        let human = HumanMessagePromptTemplate::new(PromptTemplate::try_from("{input}").unwrap());
+        let ai = AIMessagePromptTemplate::new(shared);
+
+        let message = system
+            .format_message(prompt_args! { "persona" => "a pirate" })
+            .unwrap();
+        assert_eq!(message.message_type, MessageType::SystemMessage);
+        assert_eq!(message.content, "Answer as a pirate");
+        assert_eq!(
+            ai.format_message(prompt_args! { "persona" => "a parrot" })
+                .unwrap()
+                .message_type,
+            MessageType::AIMessage
+        );
+
+        let prompt = ChatPromptTemplate::new(MessageFormatterStruct::new())
+            .with_template(system)
+            .with_messages_placeholder("history")
+            .with_message(Message::new_ai_message("Ahoy!"))
+            .with_template(human)
+            .with_template(ai);
+        assert_eq!(prompt.variables(), vec!["persona", "history", "input"]);
+        let messages = prompt
+            .format_messages(prompt_args! {
+                "persona" => "a pirate",
+                "history" => vec![Message::new_human_message("Hi")],
+                "input" => "Where is the treasure?",
+            })
+            .unwrap();
+        let types: Vec<MessageType> = messages.into_iter().map(|m| m.message_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                MessageType::SystemMessage,
+                MessageType::HumanMessage,
+                MessageType::AIMessage,
+                MessageType::HumanMessage,
+                MessageType::AIMessage,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chat_prompt_macro() {
+        let prompt = chat_prompt! {
+            system: "You are {persona}.",
+            history,
+            human: "{input}",
+            ai: "Answer in {language}:",
+        }
+        .unwrap();
+        assert_eq!(
+            prompt.variables(),
+            vec!["persona", "history", "input", "language"]
+        );
+        let messages = prompt
+            .format_messages(prompt_args! {
+                "persona" => "a pirate",
+                "history" => vec![Message::new_ai_message("Ahoy!")],
+                "input" => "Hi",
+                "language" => "English",
+            })
+            .unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].content, "You are a pirate.");
+        assert_eq!(messages[1].content, "Ahoy!");
+        assert_eq!(messages[2].message_type, MessageType::HumanMessage);
+        assert_eq!(messages[3].message_type, MessageType::AIMessage);
+        assert_eq!(messages[3].content, "Answer in English:");
+
+        let prompt = chat_prompt! { history }.unwrap();
+        assert_eq!(prompt.variables(), vec!["history"]);
+
+        let prompt = chat_prompt! {}.unwrap();
+        assert!(prompt.variables().is_empty());
+        assert!(prompt.format_messages(prompt_args! {}).unwrap().is_empty());
+
+        assert!(matches!(
+            chat_prompt! { system: "You are {persona", human: "{input}" },
+            Err(PromptError::InvalidTemplate { .. })
+        ));
+    }
+
+    #[test]
+    fn test_trimmed_messages_placeholder() {
+        let trimmer = MessageTrimmer::new().with_max_messages(2);
+        let prompt = chat_prompt! { system: "You are {persona}." }
+            .unwrap()
+            .with_trimmed_messages_placeholder("history", trimmer)
+            .with_template(HumanMessagePromptTemplate::new(
+                PromptTemplate::try_from("{input}").unwrap(),
+            ));
+        assert_eq!(prompt.variables(), vec!["persona", "history", "input"]);
+
+        let history = vec![
+            Message::new_human_message("Hi"),
+            Message::new_ai_message("Ahoy!"),
+            Message::new_human_message("Where is the treasure?"),
+            Message::new_ai_message("On the island."),
+        ];
+        let (messages, trimmed) = prompt
+            .format_messages_trimmed(prompt_args! {
+                "persona" => "a pirate",
+                "history" => history,
+                "input" => "Which island?",
+            })
+            .unwrap();
+        assert_eq!(trimmed, 2);
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec![
+                "You are a pirate.",
+                "Where is the treasure?",
+                "On the island.",
+                "Which island?"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chat_prompt_template_from_messages() {
+        let prompt = ChatPromptTemplate::from_messages(vec![
+            (MessageType::SystemMessage, "You are {persona}"),
+            (MessageType::HumanMessage, "{input}"),
+            (MessageType::ToolMessage, "{persona} used a tool"),
+        ])
+        .unwrap();
+        assert_eq!(prompt.input_variables(), vec!["persona", "input"]);
+
+        let input_variables = prompt_args! {
+            "persona" => "a pirate",
+            "input" => "Where is the treasure?",
+        };
+        let messages = prompt.format_messages(input_variables.clone()).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].message_type, MessageType::SystemMessage);
+        assert_eq!(messages[0].content, "You are a pirate");
+        assert_eq!(messages[1].message_type, MessageType::HumanMessage);
+        assert_eq!(messages[1].content, "Where is the treasure?");
+        assert_eq!(messages[2].message_type, MessageType::ToolMessage);
+
+        assert_eq!(
+            prompt.template(),
+            "system: You are {persona}\nhuman: {input}\ntool: {persona} used a tool"
+        );
+        assert_eq!(
+            prompt.format(input_variables).unwrap(),
+            "System: You are a pirate\nHuman: Where is the treasure?\nTool: a pirate used a tool"
+        );
+    }
+
+    #[test]
+    fn test_image_placeholder() {
+        let prompt = ChatPromptTemplate::from_messages(vec![(
+            MessageType::SystemMessage,
+            "You describe images",
+        )])
+        .unwrap()
+        .with_template(
+            ImagePlaceholder::new("photo")
+                .with_text(template_fstring!("What is in this {kind}?", "kind"))
+                .with_detail(ImageDetail::High),
+        )
+        .with_image_placeholder("scan");
+        assert_eq!(prompt.input_variables(), vec!["kind", "photo", "scan"]);
+
+        let args = prompt_args! {
+            "kind" => "photo",
+            "photo" => "https://example.com/cat.png",
+            "scan" => "data:image/png;base64,iVBORw0KGgo=",
+        };
+        let messages = prompt.format_messages(args.clone()).unwrap();
+        assert_eq!(
+            messages[1].message_content(),
+            MessageContent::Parts(vec![
+                ContentPart::text("What is in this photo?"),
+                ContentPart::ImageUrl {
+                    url: "https://example.com/cat.png".to_string(),
+                    detail: Some(ImageDetail::High),
+                },
+            ])
+        );
+        assert_eq!(
+            prompt.format(args).unwrap(),
+            "System: You describe images\nHuman: What is in this photo?\n\
+             [image: https://example.com/cat.png]\nHuman: [image: image/png data]"
+        );
+
+        let invalid = prompt_args! { "kind" => "photo", "photo" => "cat.png", "scan" => 1 };
+        assert!(matches!(
+            prompt.format_messages(invalid),
+            Err(PromptError::InvalidImage { variable, .. }) if variable == "photo"
+        ));
+    }
+
+    #[test]
+    fn test_chat_prompt_template_callbacks() {
+        let collector = Arc::new(CollectingCallbackHandler::new());
+        let prompt =
+            ChatPromptTemplate::from_messages(vec![(MessageType::HumanMessage, "{input}")])
+                .unwrap()
+                .with_name("greeting")
+                .with_callback_handler(collector.clone());
+        prompt
+            .format_messages(prompt_args! { "input" => "Hello" })
+            .unwrap();
+        assert!(matches!(
+            &collector.events()[..],
+            [CallbackEvent::PromptFormatted { template_name, rendered }]
+                if template_name == "greeting" && rendered == "Human: Hello"
+        ));
+    }
+
+    #[test]
+    fn test_messages_placeholder() {
+        let prompt = ChatPromptTemplate::from_messages(vec![(
+            MessageType::SystemMessage,
+            "You are {persona}",
+        )])
+        .unwrap()
+        .with_messages_placeholder("history")
+        .with_template(HumanMessagePromptTemplate::new(template_fstring!(
+            "{input}", "input"
+        )));
+        assert_eq!(
+            prompt.input_variables(),
+            vec!["persona", "history", "input"]
+        );
+
+        // empty history
+        let messages = prompt
+            .format_messages(prompt_args! {
+                "persona" => "a pirate",
+                "history" => Vec::<Message>::new(),
+                "input" => "Hello",
+            })
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content, "You are a pirate");
+        assert_eq!(messages[1].content, "Hello");
+
+        // long history, expanded in place preserving order and roles
+        let history: Vec<Message> = (0..50)
+            .map(|i| {
+                if i % 2 == 0 {
+                    Message::new_human_message(format!("question {}", i))
+                } else {
+                    Message::new_ai_message(format!("answer {}", i))
+                }
+            })
+            .collect();
+        let messages = prompt
+            .format_messages(prompt_args! {
+                "persona" => "a pirate",
+                "history" => history,
+                "input" => "Hello",
+            })
+            .unwrap();
+        assert_eq!(messages.len(), 52);
+        assert_eq!(messages[0].message_type, MessageType::SystemMessage);
+        for (i, message) in messages[1..51].iter().enumerate() {
+            if i % 2 == 0 {
+                assert_eq!(message.message_type, MessageType::HumanMessage);
+                assert_eq!(message.content, format!("question {}", i));
+            } else {
+                assert_eq!(message.message_type, MessageType::AIMessage);
+                assert_eq!(message.content, format!("answer {}", i));
+            }
+        }
+        assert_eq!(messages[51].content, "Hello");
+
+        // missing history
+        let result = prompt.format_messages(prompt_args! {
+            "persona" => "a pirate",
+            "input" => "Hello",
+        });
+        assert!(matches!(result, Err(PromptError::MissingVariable(v)) if v == "history"));
+
+        let formatter = message_formatter![
+            fmt_message!(Message::new_system_message("You are a pirate")),
+            fmt_optional_placeholder!("history"),
+        ];
+        assert!(formatter.input_variables().is_empty());
+        let messages = formatter.format_messages(prompt_args! {}).unwrap();
+        assert_eq!(messages.len(), 1);
+    }
 }
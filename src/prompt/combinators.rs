@@ -0,0 +1,219 @@
+use std::sync::Arc;
+
+use crate::schemas::prompt::PromptValue;
+
+use super::{IntoPromptArgs, PromptArgs, PromptError, PromptFromatter, PromptMetadata};
+
+/// Trait `PromptExt` adds combinators to any shared prompt, to tweak a prompt built elsewhere
+/// without writing a wrapper struct. Each one returns a new prompt, so they chain, and the
+/// variables of the result account for the whole chain.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt: Arc<dyn PromptFromatter> = Arc::new(template_fstring!("{question}", "question"));
+/// let prompt = prompt
+///     .then(Arc::new(template_fstring!("Tone: {tone}", "tone")), "\n")
+///     .with_partial([("tone", "formal")])
+///     .map_output(|text| format!("<prompt>{}</prompt>", text));
+/// assert_eq!(prompt.variables(), vec!["question"]);
+/// ```
+pub trait PromptExt {
+    /// Transforms the formatted prompt, e.g. to wrap it in tags.
+    fn map_output<F>(&self, f: F) -> Arc<dyn PromptFromatter>
+    where
+        F: Fn(String) -> String + Send + Sync + 'static;
+
+    /// Binds variables, which are then no longer required. Values of the input take
+    /// precedence over them.
+    fn with_partial<A: IntoPromptArgs>(&self, args: A) -> Arc<dyn PromptFromatter>;
+
+    fn prefix<S: Into<String>>(&self, prefix: S) -> Arc<dyn PromptFromatter>;
+
+    fn suffix<S: Into<String>>(&self, suffix: S) -> Arc<dyn PromptFromatter>;
+
+    /// Formats this prompt then `other` with the same input, joined by `separator`. The
+    /// variables are those of both prompts.
+    fn then<S: Into<String>>(
+        &self,
+        other: Arc<dyn PromptFromatter>,
+        separator: S,
+    ) -> Arc<dyn PromptFromatter>;
+}
+
+impl PromptExt for Arc<dyn PromptFromatter> {
+    fn map_output<F>(&self, f: F) -> Arc<dyn PromptFromatter>
+    where
+        F: Fn(String) -> String + Send + Sync + 'static,
+    {
+        Arc::new(MappedPrompt {
+            prompt: self.clone(),
+            f: Box::new(f),
+        })
+    }
+
+    fn with_partial<A: IntoPromptArgs>(&self, args: A) -> Arc<dyn PromptFromatter> {
+        Arc::new(PartialPrompt {
+            prompt: self.clone(),
+            partial_variables: args.into_prompt_args(),
+        })
+    }
+
+    fn prefix<S: Into<String>>(&self, prefix: S) -> Arc<dyn PromptFromatter> {
+        let prefix = prefix.into();
+        self.map_output(move |text| format!("{}{}", prefix, text))
+    }
+
+    fn suffix<S: Into<String>>(&self, suffix: S) -> Arc<dyn PromptFromatter> {
+        let suffix = suffix.into();
+        self.map_output(move |text| text + &suffix)
+    }
+
+    fn then<S: Into<String>>(
+        &self,
+        other: Arc<dyn PromptFromatter>,
+        separator: S,
+    ) -> Arc<dyn PromptFromatter> {
+        Arc::new(ChainedPrompt {
+            first: self.clone(),
+            second: other,
+            separator: separator.into(),
+        })
+    }
+}
+
+struct MappedPrompt {
+    prompt: Arc<dyn PromptFromatter>,
+    f: Box<dyn Fn(String) -> String + Send + Sync>,
+}
+
+impl PromptFromatter for MappedPrompt {
+    fn template(&self) -> String {
+        self.prompt.template()
+    }
+
+    fn variables(&self) -> Vec<String> {
+        self.prompt.variables()
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        Ok((self.f)(self.prompt.format(input_variables)?))
+    }
+
+    fn metadata(&self) -> &PromptMetadata {
+        self.prompt.metadata()
+    }
+}
+
+struct PartialPrompt {
+    prompt: Arc<dyn PromptFromatter>,
+    partial_variables: PromptArgs,
+}
+
+impl PartialPrompt {
+    fn merge(&self, input_variables: PromptArgs) -> PromptArgs {
+        let mut variables = self.partial_variables.clone();
+        variables.extend(input_variables);
+        variables
+    }
+}
+
+impl PromptFromatter for PartialPrompt {
+    fn template(&self) -> String {
+        self.prompt.template()
+    }
+
+    fn variables(&self) -> Vec<String> {
+        self.prompt
+            .variables()
+            .into_iter()
+            .filter(|variable| !self.partial_variables.contains_key(variable))
+            .collect()
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        self.prompt.format(self.merge(input_variables))
+    }
+
+    /// Keeps the messages of a chat prompt.
+    fn to_prompt_value(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
+        self.prompt.to_prompt_value(self.merge(input_variables))
+    }
+
+    fn metadata(&self) -> &PromptMetadata {
+        self.prompt.metadata()
+    }
+}
+
+struct ChainedPrompt {
+    first: Arc<dyn PromptFromatter>,
+    second: Arc<dyn PromptFromatter>,
+    separator: String,
+}
+
+impl PromptFromatter for ChainedPrompt {
+    fn template(&self) -> String {
+        format!(
+            "{}{}{}",
+            self.first.template(),
+            self.separator,
+            self.second.template()
+        )
+    }
+
+    fn variables(&self) -> Vec<String> {
+        let mut variables = self.first.variables();
+        for variable in self.second.variables() {
+            if !variables.contains(&variable) {
+                variables.push(variable);
+            }
+        }
+        variables
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        let first = self.first.format(input_variables.clone())?;
+        let second = self.second.format(input_variables)?;
+        Ok(format!("{}{}{}", first, self.separator, second))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, template_fstring};
+
+    #[test]
+    fn test_prompt_ext() {
+        let question: Arc<dyn PromptFromatter> = Arc::new(template_fstring!(
+            "Answer {question} as {persona}",
+            "question",
+            "persona"
+        ));
+        let footer: Arc<dyn PromptFromatter> = Arc::new(template_fstring!("Tone: {tone}", "tone"));
+
+        let prompt = question
+            .with_partial([("persona", "a pirate")])
+            .then(footer, "\n")
+            .prefix("<prompt>\n")
+            .suffix("\n</prompt>")
+            .map_output(|text| text.replace("pirate", "PIRATE"));
+        assert_eq!(prompt.variables(), vec!["question", "tone"]);
+        assert_eq!(
+            prompt
+                .format(prompt_args! { "question" => "why?", "tone" => "calm" })
+                .unwrap(),
+            "<prompt>\nAnswer why? as a PIRATE\nTone: calm\n</prompt>"
+        );
+        assert_eq!(
+            question
+                .with_partial([("persona", "a pirate")])
+                .format(prompt_args! { "question" => "why?", "persona" => "a chef" })
+                .unwrap(),
+            "Answer why? as a chef"
+        );
+        assert!(prompt
+            .format(prompt_args! { "question" => "why?" })
+            .unwrap_err()
+            .is_missing_variable());
+    }
+}
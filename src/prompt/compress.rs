@@ -0,0 +1,274 @@
+/// The line starting a region `compress_prompt` leaves as it is, up to a `{/no_compress}` line.
+/// Both lines are removed. In an FString template they are written `{{no_compress}}` and
+/// `{{/no_compress}}`.
+pub const NO_COMPRESS_START: &str = "{no_compress}";
+/// The line ending a region started by `{no_compress}`.
+pub const NO_COMPRESS_END: &str = "{/no_compress}";
+
+const ZERO_WIDTH: [char; 5] = ['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}'];
+
+/// Struct `CompressOptions` sets what `compress_prompt` removes from a prompt, e.g. the runs of
+/// spaces and blank lines of a PDF extraction, which cost tokens. The defaults only change
+/// whitespace a model doesn't read: runs of spaces and tabs within a line, `\r\n` line endings,
+/// trailing whitespace and more than 2 blank lines in a row. Indentation is kept.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = template_fstring!("Context:\n{context}\n\nQuestion: {question}", "context", "question")
+///     .with_compression(CompressOptions::new().with_remove_invisible(true));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressOptions {
+    /// Collapses runs of 2 or more spaces and tabs after the indentation of a line to a space.
+    pub collapse_spaces: bool,
+    /// Turns `\r\n` line endings into `\n`.
+    pub normalize_line_endings: bool,
+    pub trim_trailing_whitespace: bool,
+    /// The number of blank lines kept in a row, `None` to keep them all.
+    pub max_blank_lines: Option<usize>,
+    /// Removes zero-width characters and control characters other than tabs.
+    pub remove_invisible: bool,
+}
+
+impl Default for CompressOptions {
+    fn default() -> Self {
+        Self {
+            collapse_spaces: true,
+            normalize_line_endings: true,
+            trim_trailing_whitespace: true,
+            max_blank_lines: Some(2),
+            remove_invisible: false,
+        }
+    }
+}
+
+impl CompressOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_collapse_spaces(mut self, collapse: bool) -> Self {
+        self.collapse_spaces = collapse;
+        self
+    }
+
+    pub fn with_normalize_line_endings(mut self, normalize: bool) -> Self {
+        self.normalize_line_endings = normalize;
+        self
+    }
+
+    pub fn with_trim_trailing_whitespace(mut self, trim: bool) -> Self {
+        self.trim_trailing_whitespace = trim;
+        self
+    }
+
+    pub fn with_max_blank_lines(mut self, max_blank_lines: Option<usize>) -> Self {
+        self.max_blank_lines = max_blank_lines;
+        self
+    }
+
+    pub fn with_remove_invisible(mut self, remove: bool) -> Self {
+        self.remove_invisible = remove;
+        self
+    }
+}
+
+/// The result of `compress_prompt_with_report`, with the sizes to log the savings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressReport {
+    pub text: String,
+    pub chars_before: usize,
+    pub chars_after: usize,
+}
+
+impl CompressReport {
+    pub fn saved_chars(&self) -> usize {
+        self.chars_before - self.chars_after
+    }
+}
+
+// Where a line is: in the prompt, in a fenced code block opened by the given fence, or in a
+// `{no_compress}` region.
+enum Region<'a> {
+    Text,
+    Fenced(&'a str),
+    Protected,
+}
+
+/// Compresses the whitespace of a prompt as set by `options`. Fenced code blocks and
+/// `{no_compress}` regions are left as they are.
+///
+/// # Usage
+/// ```rust,ignore
+/// let text = compress_prompt("Total:    42  \r\n\n\n\n\nNext", CompressOptions::default());
+/// assert_eq!(text, "Total: 42\n\n\nNext");
+/// ```
+pub fn compress_prompt(text: &str, options: CompressOptions) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut region = Region::Text;
+    let mut blank_lines = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[content.len()..];
+        match region {
+            Region::Protected => {
+                if content.trim() == NO_COMPRESS_END {
+                    region = Region::Text;
+                } else {
+                    out.push_str(line);
+                }
+                blank_lines = 0;
+                continue;
+            }
+            Region::Fenced(fence) => {
+                if closes_fence(content, fence) {
+                    region = Region::Text;
+                }
+                out.push_str(line);
+                blank_lines = 0;
+                continue;
+            }
+            Region::Text => {}
+        }
+        if content.trim() == NO_COMPRESS_START {
+            region = Region::Protected;
+            continue;
+        }
+        if let Some(fence) = opening_fence(content) {
+            region = Region::Fenced(fence);
+            out.push_str(line);
+            blank_lines = 0;
+            continue;
+        }
+
+        let compressed = compress_line(content, &options);
+        if compressed.trim().is_empty() && !ending.is_empty() {
+            blank_lines += 1;
+            if options.max_blank_lines.is_some_and(|max| blank_lines > max) {
+                continue;
+            }
+        } else {
+            blank_lines = 0;
+        }
+        out.push_str(&compressed);
+        out.push_str(match ending {
+            "\r\n" if options.normalize_line_endings => "\n",
+            ending => ending,
+        });
+    }
+    out
+}
+
+/// Compresses a prompt like `compress_prompt`, also returning its size before and after.
+pub fn compress_prompt_with_report(text: &str, options: CompressOptions) -> CompressReport {
+    let compressed = compress_prompt(text, options);
+    CompressReport {
+        chars_before: text.chars().count(),
+        chars_after: compressed.chars().count(),
+        text: compressed,
+    }
+}
+
+fn compress_line(line: &str, options: &CompressOptions) -> String {
+    let mut chars: Vec<char> = line.chars().collect();
+    if options.remove_invisible {
+        chars.retain(|c| !ZERO_WIDTH.contains(c) && (*c == '\t' || !c.is_control()));
+    }
+    let mut out = String::with_capacity(line.len());
+    let indent = chars.iter().take_while(|c| c.is_whitespace()).count();
+    out.extend(&chars[..indent]);
+    let mut rest = chars[indent..].iter().peekable();
+    while let Some(&c) = rest.next() {
+        if options.collapse_spaces
+            && c.is_whitespace()
+            && rest.peek().is_some_and(|c| c.is_whitespace())
+        {
+            while rest.next_if(|c| c.is_whitespace()).is_some() {}
+            out.push(' ');
+        } else {
+            out.push(c);
+        }
+    }
+    if options.trim_trailing_whitespace {
+        out.truncate(out.trim_end().len());
+    }
+    out
+}
+
+// The fence opening a Markdown code block, like ` ``` ` or `~~~~`.
+fn opening_fence(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let fence_char = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.len() - line.trim_start_matches(fence_char).len();
+    (len >= 3).then(|| &line[..len])
+}
+
+fn closes_fence(line: &str, fence: &str) -> bool {
+    opening_fence(line).is_some_and(|closing| {
+        closing.starts_with(fence) && line.trim()[closing.len()..].is_empty()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, template_fstring};
+
+    #[test]
+    fn test_compress_prompt() {
+        let text = "Title   with\t\tgaps   \r\n  indented  line\r\n\n\n\n\n\u{200B}end\u{7}";
+        assert_eq!(
+            compress_prompt(text, CompressOptions::default()),
+            "Title with gaps\n  indented line\n\n\n\u{200B}end\u{7}"
+        );
+        assert_eq!(
+            compress_prompt(
+                text,
+                CompressOptions::new()
+                    .with_remove_invisible(true)
+                    .with_max_blank_lines(Some(1))
+            ),
+            "Title with gaps\n  indented line\n\nend"
+        );
+        let unchanged = CompressOptions::new()
+            .with_collapse_spaces(false)
+            .with_normalize_line_endings(false)
+            .with_trim_trailing_whitespace(false)
+            .with_max_blank_lines(None);
+        assert_eq!(compress_prompt(text, unchanged), text);
+    }
+
+    #[test]
+    fn test_compress_prompt_protected_regions() {
+        let text = "a   b\n```python\nx  =  1   \n\n\n\n\n```\nc   d\n{no_compress}\n| col  |  col |\n{/no_compress}\ne   f";
+        let report = compress_prompt_with_report(text, CompressOptions::default());
+        assert_eq!(
+            report.text,
+            "a b\n```python\nx  =  1   \n\n\n\n\n```\nc d\n| col  |  col |\ne f"
+        );
+        assert_eq!(
+            report.chars_before - report.chars_after,
+            report.saved_chars()
+        );
+        assert_eq!(report.saved_chars(), 35);
+    }
+
+    #[test]
+    fn test_prompt_template_compression() {
+        let prompt = template_fstring!(
+            "Context:\n{context}\n{{no_compress}}\n{table}\n{{/no_compress}}",
+            "context",
+            "table"
+        );
+        let args =
+            prompt_args! { "context" => "page   one\n\n\n\n\npage two  ", "table" => "a    b" };
+        assert!(prompt.format(args.clone()).unwrap().contains("page   one"));
+        let prompt = prompt.with_compression(CompressOptions::default());
+        assert_eq!(
+            prompt.format(args.clone()).unwrap(),
+            "Context:\npage one\n\n\npage two\na    b\n"
+        );
+        let report = prompt.format_compressed(args).unwrap();
+        assert!(report.saved_chars() > 0);
+    }
+}
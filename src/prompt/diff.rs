@@ -0,0 +1,420 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+use serde_json::Value;
+
+use super::{ChatPromptTemplate, MessageFormatter, PromptTemplate, TemplateFormat};
+
+/// A line of a template diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Unchanged(String),
+    Added(String),
+    Removed(String),
+    Changed { old: String, new: String },
+}
+
+/// A default or partial variable whose value was added, removed or changed. The values of
+/// partial variables are shown as JSON, except strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// The differences between two `PromptTemplate`s, see `PromptTemplate::diff`. Its `Display`
+/// is a unified-diff-like text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptDiff {
+    pub lines: Vec<DiffLine>,
+    pub added_variables: Vec<String>,
+    pub removed_variables: Vec<String>,
+    pub format_change: Option<(TemplateFormat, TemplateFormat)>,
+    pub default_changes: Vec<ValueChange>,
+    pub partial_changes: Vec<ValueChange>,
+}
+
+impl PromptDiff {
+    /// Returns true if the prompts are the same.
+    pub fn is_empty(&self) -> bool {
+        self.lines
+            .iter()
+            .all(|line| matches!(line, DiffLine::Unchanged(_)))
+            && self.added_variables.is_empty()
+            && self.removed_variables.is_empty()
+            && self.format_change.is_none()
+            && self.default_changes.is_empty()
+            && self.partial_changes.is_empty()
+    }
+}
+
+impl PromptTemplate {
+    /// Compares the prompt with a newer version: the lines of the template, its variables,
+    /// format, defaults and partial variables.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let diff = current.diff(&updated);
+    /// if !diff.is_empty() {
+    ///     log::info!("Prompt changed:\n{}", diff);
+    /// }
+    /// ```
+    pub fn diff(&self, other: &PromptTemplate) -> PromptDiff {
+        let (added_variables, removed_variables) =
+            diff_names(self.variable_names(), other.variable_names());
+        let format_change = (self.template_format() != other.template_format()).then(|| {
+            (
+                self.template_format().clone(),
+                other.template_format().clone(),
+            )
+        });
+        PromptDiff {
+            lines: diff_lines(self.template_str(), other.template_str()),
+            added_variables,
+            removed_variables,
+            format_change,
+            default_changes: diff_values(
+                self.defaults().iter().map(|(k, v)| (k.clone(), v.clone())),
+                other.defaults().iter().map(|(k, v)| (k.clone(), v.clone())),
+            ),
+            partial_changes: diff_values(
+                self.partial_variables()
+                    .iter()
+                    .map(|(k, v)| (k.clone(), value_string(v))),
+                other
+                    .partial_variables()
+                    .iter()
+                    .map(|(k, v)| (k.clone(), value_string(v))),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for PromptDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((old, new)) = &self.format_change {
+            writeln!(f, "--- format: {:?}", old)?;
+            writeln!(f, "+++ format: {:?}", new)?;
+        }
+        writeln!(f, "@@ template @@")?;
+        write_lines(f, &self.lines)?;
+        write_variables(f, &self.added_variables, &self.removed_variables)?;
+        write_values(f, "default", &self.default_changes)?;
+        write_values(f, "partial", &self.partial_changes)
+    }
+}
+
+/// The differences of a message of a `ChatPromptTemplate`, compared by position. The role is
+/// `None` on the side missing the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageDiff {
+    pub index: usize,
+    pub old_role: Option<String>,
+    pub new_role: Option<String>,
+    pub lines: Vec<DiffLine>,
+}
+
+impl MessageDiff {
+    pub fn is_added(&self) -> bool {
+        self.old_role.is_none()
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.new_role.is_none()
+    }
+
+    pub fn role_changed(&self) -> bool {
+        self.old_role.is_some() && self.new_role.is_some() && self.old_role != self.new_role
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        self.old_role == self.new_role
+            && self
+                .lines
+                .iter()
+                .all(|line| matches!(line, DiffLine::Unchanged(_)))
+    }
+}
+
+/// The differences between two `ChatPromptTemplate`s, see `ChatPromptTemplate::diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatPromptDiff {
+    pub messages: Vec<MessageDiff>,
+    pub added_variables: Vec<String>,
+    pub removed_variables: Vec<String>,
+}
+
+impl ChatPromptDiff {
+    pub fn is_empty(&self) -> bool {
+        self.messages.iter().all(MessageDiff::is_unchanged)
+            && self.added_variables.is_empty()
+            && self.removed_variables.is_empty()
+    }
+}
+
+impl ChatPromptTemplate {
+    /// Compares the prompt with a newer version, message by message. Messages placeholders
+    /// are compared as `{name}` lines.
+    pub fn diff(&self, other: &ChatPromptTemplate) -> ChatPromptDiff {
+        let old = self.message_parts();
+        let new = other.message_parts();
+        let messages = (0..old.len().max(new.len()))
+            .map(|index| {
+                let old = old.get(index);
+                let new = new.get(index);
+                let text = |part: Option<&(String, String)>| {
+                    part.map(|(_, text)| text.clone()).unwrap_or_default()
+                };
+                MessageDiff {
+                    index,
+                    old_role: old.map(|(role, _)| role.clone()),
+                    new_role: new.map(|(role, _)| role.clone()),
+                    lines: match (old, new) {
+                        (Some(_), Some(_)) => diff_lines(&text(old), &text(new)),
+                        (None, _) => lines(&text(new)).map(DiffLine::Added).collect(),
+                        (_, None) => lines(&text(old)).map(DiffLine::Removed).collect(),
+                    },
+                }
+            })
+            .collect();
+        let (added_variables, removed_variables) =
+            diff_names(&self.input_variables(), &other.input_variables());
+        ChatPromptDiff {
+            messages,
+            added_variables,
+            removed_variables,
+        }
+    }
+}
+
+impl fmt::Display for ChatPromptDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for message in &self.messages {
+            let role = match (&message.old_role, &message.new_role) {
+                (Some(old), Some(new)) if old != new => format!("{} -> {}, role changed", old, new),
+                (Some(role), Some(_)) => role.clone(),
+                (None, Some(role)) => format!("{}, added", role),
+                (Some(role), None) => format!("{}, removed", role),
+                (None, None) => String::new(),
+            };
+            writeln!(f, "@@ message {} ({}) @@", message.index, role)?;
+            write_lines(f, &message.lines)?;
+        }
+        write_variables(f, &self.added_variables, &self.removed_variables)
+    }
+}
+
+fn write_lines(f: &mut fmt::Formatter<'_>, lines: &[DiffLine]) -> fmt::Result {
+    for line in lines {
+        match line {
+            DiffLine::Unchanged(line) => writeln!(f, " {}", line)?,
+            DiffLine::Added(line) => writeln!(f, "+{}", line)?,
+            DiffLine::Removed(line) => writeln!(f, "-{}", line)?,
+            DiffLine::Changed { old, new } => {
+                writeln!(f, "-{}", old)?;
+                writeln!(f, "+{}", new)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_variables(
+    f: &mut fmt::Formatter<'_>,
+    added: &[String],
+    removed: &[String],
+) -> fmt::Result {
+    if added.is_empty() && removed.is_empty() {
+        return Ok(());
+    }
+    let changes: Vec<String> = added
+        .iter()
+        .map(|v| format!("+{}", v))
+        .chain(removed.iter().map(|v| format!("-{}", v)))
+        .collect();
+    writeln!(f, "variables: {}", changes.join(" "))
+}
+
+fn write_values(f: &mut fmt::Formatter<'_>, kind: &str, changes: &[ValueChange]) -> fmt::Result {
+    let show = |value: &Option<String>| match value {
+        Some(value) => format!("{:?}", value),
+        None => "(none)".to_string(),
+    };
+    for change in changes {
+        writeln!(
+            f,
+            "{} {}: {} -> {}",
+            kind,
+            change.name,
+            show(&change.old),
+            show(&change.new)
+        )?;
+    }
+    Ok(())
+}
+
+fn lines(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.lines().map(str::to_string)
+}
+
+// Diffs the lines with their longest common subsequence. A run of removed lines followed by
+// added lines is paired into changed lines, the rest staying added or removed.
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lengths[i][j] is the length of the common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            flush_changes(&mut result, &mut removed, &mut added);
+            result.push(DiffLine::Unchanged(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            added.push(new[j].to_string());
+            j += 1;
+        } else {
+            removed.push(old[i].to_string());
+            i += 1;
+        }
+    }
+    flush_changes(&mut result, &mut removed, &mut added);
+    result
+}
+
+fn flush_changes(result: &mut Vec<DiffLine>, removed: &mut Vec<String>, added: &mut Vec<String>) {
+    let paired = removed.len().min(added.len());
+    let mut removed = removed.drain(..);
+    let mut added = added.drain(..);
+    for (old, new) in removed.by_ref().zip(added.by_ref()).take(paired) {
+        result.push(DiffLine::Changed { old, new });
+    }
+    result.extend(removed.map(DiffLine::Removed));
+    result.extend(added.map(DiffLine::Added));
+}
+
+fn diff_names(old: &[String], new: &[String]) -> (Vec<String>, Vec<String>) {
+    let added = new.iter().filter(|v| !old.contains(v)).cloned().collect();
+    let removed = old.iter().filter(|v| !new.contains(v)).cloned().collect();
+    (added, removed)
+}
+
+// Compares the values by name, sorted by name.
+fn diff_values<O, N>(old: O, new: N) -> Vec<ValueChange>
+where
+    O: Iterator<Item = (String, String)>,
+    N: Iterator<Item = (String, String)>,
+{
+    let old: HashMap<String, String> = old.collect();
+    let new: HashMap<String, String> = new.collect();
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    names
+        .into_iter()
+        .filter(|name| old.get(*name) != new.get(*name))
+        .map(|name| ValueChange {
+            name: name.clone(),
+            old: old.get(name).cloned(),
+            new: new.get(name).cloned(),
+        })
+        .collect()
+}
+
+fn value_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, schemas::MessageType};
+
+    fn fstring(template: &str) -> PromptTemplate {
+        PromptTemplate::from_template(template, TemplateFormat::FString).unwrap()
+    }
+
+    #[test]
+    fn test_prompt_diff() {
+        let old = fstring("You are {persona}.\nBe brief.\nQuestion: {question}").with_defaults(
+            HashMap::from([("persona".to_string(), "helpful".to_string())]),
+        );
+        let new = fstring("You are {persona}.\nCite {source}.\nBe brief.\nQ: {input}")
+            .partial(prompt_args! { "source" => "the docs" });
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.lines,
+            vec![
+                DiffLine::Unchanged("You are {persona}.".into()),
+                DiffLine::Added("Cite {source}.".into()),
+                DiffLine::Unchanged("Be brief.".into()),
+                DiffLine::Changed {
+                    old: "Question: {question}".into(),
+                    new: "Q: {input}".into()
+                },
+            ]
+        );
+        assert_eq!(diff.added_variables, vec!["input"]);
+        assert_eq!(diff.removed_variables, vec!["question"]);
+        assert_eq!(diff.format_change, None);
+        assert_eq!(diff.default_changes[0].old.as_deref(), Some("helpful"));
+        assert_eq!(diff.partial_changes[0].new.as_deref(), Some("the docs"));
+        assert_eq!(
+            diff.to_string(),
+            "@@ template @@\n You are {persona}.\n+Cite {source}.\n Be brief.\n-Question: {question}\n+Q: {input}\nvariables: +input -question\ndefault persona: \"helpful\" -> (none)\npartial source: (none) -> \"the docs\"\n"
+        );
+
+        assert!(old.diff(&old).is_empty());
+        let jinja = PromptTemplate::from_template("Be brief.", TemplateFormat::Jinja2).unwrap();
+        let diff = fstring("Be brief.").diff(&jinja);
+        assert!(diff
+            .to_string()
+            .starts_with("--- format: FString\n+++ format: Jinja2\n"));
+    }
+
+    #[test]
+    fn test_chat_prompt_diff() {
+        let old = ChatPromptTemplate::from_messages(vec![
+            (MessageType::SystemMessage, "You are {persona}"),
+            (MessageType::HumanMessage, "{input}"),
+        ])
+        .unwrap();
+        let new = ChatPromptTemplate::from_messages(vec![
+            (MessageType::SystemMessage, "You are {persona}"),
+            (MessageType::AIMessage, "{input}"),
+            (MessageType::HumanMessage, "Answer in {language}"),
+        ])
+        .unwrap();
+
+        let diff = old.diff(&new);
+        assert!(diff.messages[0].is_unchanged());
+        assert!(diff.messages[1].role_changed());
+        assert!(diff.messages[2].is_added());
+        assert_eq!(diff.added_variables, vec!["language"]);
+        assert_eq!(
+            diff.to_string(),
+            "@@ message 0 (system) @@\n You are {persona}\n@@ message 1 (human -> ai, role changed) @@\n {input}\n@@ message 2 (human, added) @@\n+Answer in {language}\nvariables: +language\n"
+        );
+        assert!(old.diff(&old).is_empty());
+    }
+}
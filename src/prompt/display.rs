@@ -0,0 +1,298 @@
+use std::fmt::{self, Write};
+
+use super::{
+    parser::{self, Segment},
+    prompt::{resolve_value, value_to_string},
+    PromptArgs, PromptTemplate, TemplateFormat,
+};
+
+const OPEN: &str = "⟦";
+const CLOSE: &str = "⟧";
+const CYAN: &str = "\x1b[36m";
+const RED: &str = "\x1b[31m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// How `PromptTemplate::render_debug_with` shows a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugOptions {
+    /// Colors the placeholders with ANSI escapes, for terminals.
+    pub color: bool,
+    /// The number of chars shown of each value, longer values are cut with `…`.
+    pub max_value_len: usize,
+}
+
+impl Default for DebugOptions {
+    fn default() -> Self {
+        Self {
+            color: false,
+            max_value_len: 40,
+        }
+    }
+}
+
+impl DebugOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_max_value_len(mut self, max_value_len: usize) -> Self {
+        self.max_value_len = max_value_len;
+        self
+    }
+}
+
+impl PromptTemplate {
+    /// Shows the template with its placeholders marked like `⟦name = "Ana"⟧`, annotated with
+    /// the value each one would get from `input_variables`, the partial variables and the
+    /// defaults, and followed by the variables and the format. Values are cut to 40 chars and
+    /// never substituted, so it's fit for logging a prompt that misrenders.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// log::debug!("{}", prompt.render_debug(&args));
+    /// ```
+    pub fn render_debug(&self, input_variables: &PromptArgs) -> String {
+        self.render_debug_with(input_variables, &DebugOptions::default())
+    }
+
+    /// Like `render_debug`, with the given options.
+    pub fn render_debug_with(
+        &self,
+        input_variables: &PromptArgs,
+        options: &DebugOptions,
+    ) -> String {
+        let variables = debug_variables(self, input_variables);
+        let mut out = String::new();
+        write_marked_template(&mut out, self, Some(&variables), options)
+            .and_then(|_| write_footer(&mut out, self, options))
+            .expect("writing to a String can't fail");
+        out
+    }
+}
+
+/// Shows the template with its placeholders marked like `⟦name⟧`, followed by its variables,
+/// defaults, partial variables and format.
+impl fmt::Display for PromptTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = DebugOptions::default();
+        write_marked_template(f, self, None, &options)?;
+        write_footer(f, self, &options)
+    }
+}
+
+// The values a placeholder would get: the input variables, the defaults and the partials.
+pub(crate) fn debug_variables(prompt: &PromptTemplate, input_variables: &PromptArgs) -> PromptArgs {
+    let mut variables = prompt.partial_variables().clone();
+    for (key, value) in prompt.defaults() {
+        variables.insert(key.clone(), value.clone().into());
+    }
+    variables.extend(input_variables.clone());
+    variables
+}
+
+// Writes the template with its placeholders marked, and annotated with their values when
+// `variables` is set. Templates that don't parse are written as they are.
+pub(crate) fn write_marked_template<W: Write + ?Sized>(
+    out: &mut W,
+    prompt: &PromptTemplate,
+    variables: Option<&PromptArgs>,
+    options: &DebugOptions,
+) -> fmt::Result {
+    let template = prompt.template_str();
+    let segments = match prompt.template_format() {
+        TemplateFormat::FString => parser::parse_fstring(template).ok(),
+        TemplateFormat::Jinja2 | TemplateFormat::Mustache => Some(parser::parse_jinja2(template)),
+        TemplateFormat::Custom { open, close } => parser::parse_custom(template, open, close).ok(),
+    };
+    let Some(segments) = segments else {
+        return out.write_str(template);
+    };
+
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => out.write_str(text)?,
+            Segment::Variable(path) => write_slot(out, path, path, variables, options)?,
+            Segment::Formatted(path, spec) => write_slot(
+                out,
+                &format!("{}:{}", path, spec.raw),
+                path,
+                variables,
+                options,
+            )?,
+            Segment::Filtered(path, filters) => write_slot(
+                out,
+                &format!("{}|{}", path, filters.raw),
+                path,
+                variables,
+                options,
+            )?,
+            Segment::DateTime(path, format) => write_slot(
+                out,
+                &format!("{}:{}", path, format),
+                path,
+                variables,
+                options,
+            )?,
+            // never show the values of environment variables
+            Segment::Env(name) => write_slot(out, &format!("env:{}", name), "", None, options)?,
+            Segment::If(path) => write!(out, "{{#if {}}}", path)?,
+            Segment::Else => out.write_str("{#else}")?,
+            Segment::EndIf => out.write_str("{/if}")?,
+            Segment::Comment(comment) => out.write_str(comment)?,
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn write_slot<W: Write + ?Sized>(
+    out: &mut W,
+    label: &str,
+    path: &str,
+    variables: Option<&PromptArgs>,
+    options: &DebugOptions,
+) -> fmt::Result {
+    let (color, annotation) = match variables.map(|variables| resolve_value(variables, path)) {
+        None => (CYAN, String::new()),
+        Some(Ok(Some(value))) => (
+            CYAN,
+            format!(" = {:?}", cut(&value_to_string(value), options)),
+        ),
+        Some(_) => (RED, " = <missing>".to_string()),
+    };
+    if options.color {
+        write!(
+            out,
+            "{}{}{}{}{}{}",
+            color, OPEN, label, annotation, CLOSE, RESET
+        )
+    } else {
+        write!(out, "{}{}{}{}", OPEN, label, annotation, CLOSE)
+    }
+}
+
+fn write_footer<W: Write + ?Sized>(
+    out: &mut W,
+    prompt: &PromptTemplate,
+    options: &DebugOptions,
+) -> fmt::Result {
+    if options.color {
+        out.write_str(DIM)?;
+    }
+    write!(out, "\n---\nformat: {:?}", prompt.template_format())?;
+    write!(out, "\nvariables: {}", prompt.variable_names().join(", "))?;
+    let mut defaults: Vec<_> = prompt.defaults().iter().collect();
+    defaults.sort();
+    if !defaults.is_empty() {
+        out.write_str("\ndefaults: ")?;
+        for (i, (key, value)) in defaults.into_iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            write!(out, "{}{} = {:?}", separator, key, cut(value, options))?;
+        }
+    }
+    let mut partials: Vec<_> = prompt.partial_variables().iter().collect();
+    partials.sort_by(|a, b| a.0.cmp(b.0));
+    if !partials.is_empty() {
+        out.write_str("\npartials: ")?;
+        for (i, (key, value)) in partials.into_iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            write!(
+                out,
+                "{}{} = {:?}",
+                separator,
+                key,
+                cut(&value_to_string(value), options)
+            )?;
+        }
+    }
+    if options.color {
+        out.write_str(RESET)?;
+    }
+    Ok(())
+}
+
+fn cut(value: &str, options: &DebugOptions) -> String {
+    match value.char_indices().nth(options.max_value_len) {
+        Some((end, _)) => format!("{}…", &value[..end]),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        prompt::{ChatPromptTemplate, HumanMessagePromptTemplate},
+        prompt_args,
+        schemas::messages::MessageType,
+        template_fstring,
+    };
+
+    #[test]
+    fn test_display_prompt_template() {
+        let prompt = template_fstring!(
+            "{persona}: summarize {text} in {words:>3} words{{!}}",
+            "persona",
+            "text",
+            "words"
+        )
+        .with_defaults(HashMap::from([("words".to_string(), "50".to_string())]))
+        .partial(prompt_args! { "persona" => "a pirate" });
+        assert_eq!(
+            prompt.to_string(),
+            "⟦persona⟧: summarize ⟦text⟧ in ⟦words:>3⟧ words{!}\n---\nformat: FString\n\
+             variables: text, words\ndefaults: words = \"50\"\npartials: persona = \"a pirate\""
+        );
+
+        let debug = prompt.render_debug_with(
+            &prompt_args! { "text" => "a very long article\nabout ships" },
+            &DebugOptions::new().with_max_value_len(10),
+        );
+        assert!(
+            debug.starts_with(
+                "⟦persona = \"a pirate\"⟧: summarize ⟦text = \"a very lon…\"⟧ in ⟦words:>3 = \"50\"⟧"
+            ),
+            "{}",
+            debug
+        );
+
+        let debug =
+            prompt.render_debug_with(&PromptArgs::new(), &DebugOptions::new().with_color(true));
+        assert!(
+            debug.contains("\x1b[31m⟦text = <missing>⟧\x1b[0m"),
+            "{}",
+            debug
+        );
+    }
+
+    #[test]
+    fn test_display_chat_prompt_template() {
+        let prompt = ChatPromptTemplate::from_messages(vec![
+            (MessageType::SystemMessage, "You are {persona}"),
+            (MessageType::HumanMessage, "{input}"),
+        ])
+        .unwrap()
+        .with_template(HumanMessagePromptTemplate::new(template_fstring!(
+            "Also {extra}",
+            "extra"
+        )))
+        .with_messages_placeholder("history");
+        assert_eq!(
+            prompt.to_string(),
+            "[system]\nYou are ⟦persona⟧\n[human]\n⟦input⟧\n[human]\nAlso ⟦extra⟧\n\
+             [placeholder]\n⟦history⟧\n---\nvariables: persona, input, extra, history"
+        );
+        let debug = prompt.render_debug(&prompt_args! { "persona" => "Bot", "input" => "Hi" });
+        assert!(
+            debug.starts_with("[system]\nYou are ⟦persona = \"Bot\"⟧\n[human]\n⟦input = \"Hi\"⟧")
+        );
+        assert!(debug.contains("⟦history = <missing>⟧"));
+    }
+}
@@ -1,14 +1,270 @@
+use std::{collections::BTreeMap, ops::Range};
+
 use serde_json::Error as SerdeJsonError;
 use thiserror::Error;
 
+use super::Span;
+
 #[derive(Error, Debug)]
 pub enum PromptError {
     #[error("Variable {0} is missing from input variables")]
     MissingVariable(String),
 
+    #[error(transparent)]
+    InvalidInput(#[from] PromptInputError),
+
+    #[error("Variable {0} is not used by the template")]
+    UnknownVariable(String),
+
+    #[error("Alias {alias} of variable {variable} is invalid: {reason}")]
+    InvalidAlias {
+        alias: String,
+        variable: String,
+        reason: String,
+    },
+
+    #[error("Variable {variable} is looped over but it's {found}, not a list")]
+    NotIterable { variable: String, found: String },
+
+    /// The snippet is the line of the template the span starts on, underlined.
+    #[error("Invalid template: {reason} at {span}\n{snippet}")]
+    InvalidTemplate {
+        reason: String,
+        span: Span,
+        snippet: String,
+    },
+
+    #[error("Template is missing the required placeholders {0:?}")]
+    MissingPlaceholders(Vec<String>),
+
+    #[error("No template was set")]
+    MissingTemplate,
+
+    #[error("Variables {0:?} are declared but variables are also inferred from the template")]
+    ConflictingVariables(Vec<String>),
+
+    #[error("Template variables mismatch: declared but unused {unused:?}, used but undeclared {undeclared:?}")]
+    VariablesMismatch {
+        unused: Vec<String>,
+        undeclared: Vec<String>,
+    },
+
+    #[error("Prompt {name} failed: {source}")]
+    NamedPromptError {
+        name: String,
+        #[source]
+        source: Box<PromptError>,
+    },
+
+    #[error("Prompt stage {stage} failed: {source}")]
+    StageError {
+        stage: String,
+        #[source]
+        source: Box<PromptError>,
+    },
+
+    #[error("Example {index} failed to render: {source}")]
+    ExampleError {
+        index: usize,
+        #[source]
+        source: Box<PromptError>,
+    },
+
+    #[error("Arguments {index} of the batch failed to format: {source}")]
+    BatchError {
+        index: usize,
+        #[source]
+        source: Box<PromptError>,
+    },
+
+    #[error("Prompt file {} failed to load: {source}", .path.display())]
+    FileError {
+        path: std::path::PathBuf,
+        #[source]
+        source: Box<PromptError>,
+    },
+
+    #[error("Unsupported prompt type {found}, expected one of {expected:?}")]
+    UnsupportedPromptType {
+        found: String,
+        expected: Vec<String>,
+    },
+
+    #[error("Unsupported fields {fields:?} in a {prompt_type} prompt")]
+    UnsupportedPromptFields {
+        prompt_type: String,
+        fields: Vec<String>,
+    },
+
+    #[error("Environment variable {0} is not allowed by the template")]
+    EnvNotAllowed(String),
+
+    #[error("Environment variable {0} is not set")]
+    MissingEnvVariable(String),
+
+    #[error("Invalid delimiters `{open}` and `{close}`: {reason}")]
+    InvalidDelimiters {
+        open: String,
+        close: String,
+        reason: String,
+    },
+
+    #[error("Variable {variable} can't be formatted with `{spec}`: {reason}")]
+    FormatSpecError {
+        variable: String,
+        spec: String,
+        reason: String,
+    },
+
+    #[error("Filter `{filter}` can't be applied to variable {variable}: {reason}")]
+    FilterError {
+        variable: String,
+        filter: String,
+        reason: String,
+    },
+
+    #[error("Positional placeholder {{{index}}} is out of range of the {len} arguments")]
+    PositionOutOfRange { index: usize, len: usize },
+
+    #[error("Value of {variable} is {length} long, over its limit of {max}")]
+    ValueTooLong {
+        variable: String,
+        length: usize,
+        max: usize,
+    },
+
+    #[error("Value of {variable} contains the template syntax `{syntax}`")]
+    TemplateSyntaxInValue { variable: String, syntax: String },
+
+    #[error("Template lint failed: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    LintFailed(Vec<super::LintWarning>),
+
+    #[error("Template {name} included by {included_from} was not found")]
+    MissingInclude { name: String, included_from: String },
+
+    #[error("Cyclic variable reference: {}", .0.join(" -> "))]
+    CyclicVariable(Vec<String>),
+
+    #[error("Expanding variable {variable} goes deeper than {max_depth} levels")]
+    RecursionLimit { variable: String, max_depth: usize },
+
+    #[error("Prompt has {tokens} tokens, {} over the limit of {max_tokens}", .tokens - .max_tokens)]
+    TokenLimitExceeded { tokens: usize, max_tokens: usize },
+
+    #[error(
+        "Few-shot prompt has {fixed_tokens} tokens without examples, over the budget of {max_tokens}"
+    )]
+    FewShotBudgetExceeded {
+        fixed_tokens: usize,
+        max_tokens: usize,
+    },
+
+    #[error("No prompt is registered as {0}")]
+    PromptNotFound(String),
+
+    #[error("A prompt is already registered as {0}")]
+    DuplicatePrompt(String),
+
+    #[error("Version {version} of prompt {name} is already registered")]
+    DuplicateVersion { name: String, version: String },
+
+    #[error("The fallback prompt uses variables the primary prompt doesn't: {0:?}")]
+    InvalidFallback(Vec<String>),
+
+    #[error("Variable {variable} is not an image: {reason}")]
+    InvalidImage { variable: String, reason: String },
+
+    #[error("Invalid variant weights: {0}")]
+    InvalidWeights(String),
+
+    #[error("Invalid prompt version {version}: {reason}")]
+    InvalidVersion { version: String, reason: String },
+
+    #[error("Resolver of variable {variable} failed: {source}")]
+    ResolverError {
+        variable: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[error("Examples failed to be selected: {0}")]
+    ExampleSelectionError(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Render error: {0}")]
+    RenderError(String),
+
     #[error("Serialization error: {0}")]
     SerializationError(#[from] SerdeJsonError),
 
+    #[error("YAML error: {0}")]
+    YamlError(#[from] serde_yaml::Error),
+
+    #[error("Write error: {0}")]
+    WriteError(#[from] std::fmt::Error),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
     #[error("Error: {0}")]
     OtherError(String),
 }
+
+/// All the problems found in the input variables of a prompt, reported at once, with where
+/// each missing variable is used in the template.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "Invalid input variables: missing {missing:?}, unexpected {unexpected:?}{}",
+    format_locations(.locations)
+)]
+pub struct PromptInputError {
+    pub missing: Vec<String>,
+    pub unexpected: Vec<String>,
+    pub locations: BTreeMap<String, Vec<Span>>,
+}
+
+// Like `; input at 1:7 and 3:2, topic at 2:1`, as lines and columns.
+fn format_locations(locations: &BTreeMap<String, Vec<Span>>) -> String {
+    let locations: Vec<String> = locations
+        .iter()
+        .filter(|(_, spans)| !spans.is_empty())
+        .map(|(variable, spans)| {
+            let spans: Vec<String> = spans
+                .iter()
+                .map(|span| format!("{}:{}", span.line, span.column))
+                .collect();
+            format!("{} at {}", variable, spans.join(" and "))
+        })
+        .collect();
+    if locations.is_empty() {
+        String::new()
+    } else {
+        format!("; {}", locations.join(", "))
+    }
+}
+
+impl PromptError {
+    /// Whether the error is caused by a variable, or an environment variable, missing from the
+    /// input, including when it's wrapped with the name of a prompt or a stage.
+    pub fn is_missing_variable(&self) -> bool {
+        match self {
+            PromptError::MissingVariable(_) | PromptError::MissingEnvVariable(_) => true,
+            PromptError::InvalidInput(e) => !e.missing.is_empty(),
+            PromptError::NamedPromptError { source, .. }
+            | PromptError::StageError { source, .. } => source.is_missing_variable(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn invalid_template<S: Into<String>>(
+        template: &str,
+        reason: S,
+        range: Range<usize>,
+    ) -> Self {
+        let span = Span::new(template, range);
+        PromptError::InvalidTemplate {
+            reason: reason.into(),
+            snippet: span.snippet(template),
+            span,
+        }
+    }
+}
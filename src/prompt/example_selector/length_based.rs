@@ -0,0 +1,220 @@
+use crate::prompt::{value_to_string, PromptArgs, PromptError, PromptFromatter};
+
+use super::ExampleSelector;
+
+/// Struct `LengthBasedExampleSelector` greedily selects examples, in order, until their rendered
+/// length, with the separators between them and the length of the input, would exceed
+/// `max_length`.
+///
+/// Lengths are measured in characters unless a custom function is set with
+/// `with_length_function`, e.g. to count words or tokens.
+///
+/// # Usage
+/// ```rust,ignore
+/// let selector = LengthBasedExampleSelector::new(
+///     examples,
+///     template_fstring!("Input: {question}\nOutput: {answer}", "question", "answer"),
+///     200,
+/// )
+/// .with_surrounding_prompt(template_fstring!("Input: {input}\nOutput:", "input"));
+/// let prompt = FewShotPromptTemplate::new(example_prompt, suffix).with_example_selector(selector);
+/// ```
+pub struct LengthBasedExampleSelector {
+    examples: Vec<PromptArgs>,
+    example_prompt: Box<dyn PromptFromatter>,
+    max_length: usize,
+    length_function: Box<dyn Fn(&str) -> usize + Send + Sync>,
+    surrounding_prompt: Option<Box<dyn PromptFromatter>>,
+    example_separator: String,
+}
+
+impl LengthBasedExampleSelector {
+    pub fn new<P: Into<Box<dyn PromptFromatter>>>(
+        examples: Vec<PromptArgs>,
+        example_prompt: P,
+        max_length: usize,
+    ) -> Self {
+        Self {
+            examples,
+            example_prompt: example_prompt.into(),
+            max_length,
+            length_function: Box::new(|text| text.chars().count()),
+            surrounding_prompt: None,
+            example_separator: "\n\n".to_string(),
+        }
+    }
+
+    pub fn with_length_function<F: Fn(&str) -> usize + Send + Sync + 'static>(
+        mut self,
+        length_function: F,
+    ) -> Self {
+        self.length_function = Box::new(length_function);
+        self
+    }
+
+    /// Sets the prompt rendered around the examples, usually the prefix and suffix of the
+    /// few-shot prompt. Its length, rendered with the input, counts against `max_length`
+    /// instead of the raw length of the input values.
+    pub fn with_surrounding_prompt<P: Into<Box<dyn PromptFromatter>>>(mut self, prompt: P) -> Self {
+        self.surrounding_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Sets the string the examples are joined with, whose length counts against
+    /// `max_length` between every two examples. Defaults to a blank line, like
+    /// `FewShotPromptTemplate`.
+    pub fn with_example_separator<S: Into<String>>(mut self, example_separator: S) -> Self {
+        self.example_separator = example_separator.into();
+        self
+    }
+
+    fn input_length(&self, input: &PromptArgs) -> Result<usize, PromptError> {
+        let text = match &self.surrounding_prompt {
+            Some(prompt) => prompt.format(input.clone())?,
+            None => input
+                .values()
+                .map(value_to_string)
+                .collect::<Vec<String>>()
+                .join(" "),
+        };
+        Ok((self.length_function)(&text))
+    }
+}
+
+impl ExampleSelector for LengthBasedExampleSelector {
+    /// Returns no examples if they can't be selected, see `try_select_examples`.
+    fn select_examples(&self, input: &PromptArgs) -> Vec<PromptArgs> {
+        self.try_select_examples(input).unwrap_or_else(|e| {
+            log::warn!("{}", e);
+            Vec::new()
+        })
+    }
+
+    /// # Errors
+    /// Returns the error of the surrounding prompt if it can't be formatted with the input,
+    /// and of the example prompt if an example can't be rendered.
+    fn try_select_examples(&self, input: &PromptArgs) -> Result<Vec<PromptArgs>, PromptError> {
+        let separator_length = (self.length_function)(&self.example_separator);
+        let mut remaining = self.max_length.saturating_sub(self.input_length(input)?);
+        let mut selected = Vec::new();
+        for example in &self.examples {
+            let text = self.example_prompt.format(example.clone())?;
+            let mut length = (self.length_function)(&text);
+            if !selected.is_empty() {
+                length += separator_length;
+            }
+            if length > remaining {
+                break;
+            }
+            remaining -= length;
+            selected.push(example.clone());
+        }
+        Ok(selected)
+    }
+
+    fn add_example(&mut self, example: PromptArgs) {
+        self.examples.push(example);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt::FewShotPromptTemplate, prompt_args, template_fstring};
+
+    fn examples() -> Vec<PromptArgs> {
+        vec![
+            prompt_args! { "question" => "happy", "answer" => "sad" },
+            prompt_args! { "question" => "tall", "answer" => "short" },
+            prompt_args! { "question" => "energetic", "answer" => "lethargic" },
+            prompt_args! { "question" => "sunny", "answer" => "gloomy" },
+        ]
+    }
+
+    fn example_prompt() -> crate::prompt::PromptTemplate {
+        template_fstring!("Input: {question}\nOutput: {answer}", "question", "answer")
+    }
+
+    #[test]
+    fn test_length_based_example_selector() {
+        let words = |text: &str| text.split_whitespace().count();
+        let selector = LengthBasedExampleSelector::new(examples(), example_prompt(), 20)
+            .with_length_function(words);
+
+        // every example is four words long
+        let selected = selector.select_examples(&prompt_args! { "input" => "big" });
+        assert_eq!(selected.len(), 4);
+
+        let selected =
+            selector.select_examples(&prompt_args! { "input" => "a big and tall mountain" });
+        assert_eq!(selected.len(), 3);
+
+        let long_input = "a very big and tall mountain covered with snow all year long today";
+        let selected = selector.select_examples(&prompt_args! { "input" => long_input });
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0]["question"], "happy");
+
+        let selector = LengthBasedExampleSelector::new(examples(), example_prompt(), 20)
+            .with_length_function(words)
+            .with_surrounding_prompt(template_fstring!("Input: {input}\nOutput:", "input"));
+        let selected = selector
+            .select_examples(&prompt_args! { "input" => "a big and tall snowy mountain range" });
+        assert_eq!(selected.len(), 2);
+    }
+
+    #[test]
+    fn test_length_based_example_selector_separator() {
+        // the first examples render to 24, 25 and 34 characters, plus 2 for each blank line
+        let selector = LengthBasedExampleSelector::new(examples(), example_prompt(), 85);
+        let selected = selector.select_examples(&prompt_args! { "input" => "" });
+        assert_eq!(selected.len(), 2);
+        let joined: Vec<String> = selected
+            .into_iter()
+            .map(|example| example_prompt().format(example).unwrap())
+            .collect();
+        assert!(joined.join("\n\n").chars().count() <= 85);
+
+        let selector = LengthBasedExampleSelector::new(examples(), example_prompt(), 85)
+            .with_example_separator("");
+        assert_eq!(
+            selector
+                .select_examples(&prompt_args! { "input" => "" })
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_length_based_example_selector_errors() {
+        let mut selector = LengthBasedExampleSelector::new(examples(), example_prompt(), 1000);
+        selector.add_example(prompt_args! { "question" => "cold" });
+        assert!(matches!(
+            selector.try_select_examples(&prompt_args! { "input" => "big" }),
+            Err(PromptError::InvalidInput(_))
+        ));
+        assert!(selector
+            .select_examples(&prompt_args! { "input" => "big" })
+            .is_empty());
+
+        let selector = LengthBasedExampleSelector::new(examples(), example_prompt(), 1000)
+            .with_surrounding_prompt(template_fstring!("Input: {input}\nOutput:", "input"));
+        assert!(selector
+            .try_select_examples(&prompt_args! { "question" => "big" })
+            .is_err());
+    }
+
+    #[test]
+    fn test_few_shot_prompt_with_example_selector() {
+        let suffix = template_fstring!("Input: {input}\nOutput:", "input");
+        let selector = LengthBasedExampleSelector::new(examples(), example_prompt(), 70)
+            .with_surrounding_prompt(suffix.clone());
+        let prompt =
+            FewShotPromptTemplate::new(example_prompt(), suffix).with_example_selector(selector);
+
+        let result = prompt.format(prompt_args! { "input" => "big" }).unwrap();
+        assert_eq!(
+            result,
+            "Input: happy\nOutput: sad\n\nInput: tall\nOutput: short\n\nInput: big\nOutput:"
+        );
+    }
+}
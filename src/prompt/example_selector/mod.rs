@@ -0,0 +1,41 @@
+mod length_based;
+pub use length_based::*;
+mod semantic_similarity;
+pub use semantic_similarity::*;
+
+use futures::future::{self, BoxFuture};
+
+use super::{PromptArgs, PromptError};
+
+/// Chooses which examples of a few-shot prompt to include for a given input.
+pub trait ExampleSelector: Send + Sync {
+    fn select_examples(&self, input: &PromptArgs) -> Vec<PromptArgs>;
+
+    /// Selects the examples like `select_examples`, failing when they can't be selected
+    /// instead of selecting none. `FewShotPromptTemplate` selects its examples with it.
+    /// Defaults to `select_examples`.
+    fn try_select_examples(&self, input: &PromptArgs) -> Result<Vec<PromptArgs>, PromptError> {
+        Ok(self.select_examples(input))
+    }
+
+    /// Selects the examples without blocking, for `FewShotPromptTemplate`'s `AsyncPrompt`
+    /// implementation. Defaults to `try_select_examples`.
+    fn aselect_examples<'a>(
+        &'a self,
+        input: &'a PromptArgs,
+    ) -> BoxFuture<'a, Result<Vec<PromptArgs>, PromptError>> {
+        Box::pin(future::ready(self.try_select_examples(input)))
+    }
+
+    /// Adds an example to the pool the selector chooses from.
+    fn add_example(&mut self, example: PromptArgs);
+}
+
+impl<ES> From<ES> for Box<dyn ExampleSelector>
+where
+    ES: ExampleSelector + 'static,
+{
+    fn from(selector: ES) -> Self {
+        Box::new(selector)
+    }
+}
@@ -0,0 +1,341 @@
+use std::{future::Future, sync::Arc};
+
+use futures::future::BoxFuture;
+
+use tokio::{runtime::RuntimeFlavor, sync::Mutex};
+
+use crate::{
+    embedding::{Embedder, EmbedderError},
+    prompt::{value_to_string, PromptArgs, PromptError},
+    semantic_router::utils::cosine_similarity,
+};
+
+use super::ExampleSelector;
+
+type SimilarityFn = Box<dyn Fn(&[f64], &[f64]) -> f64 + Send + Sync>;
+
+/// Struct `SemanticSimilarityExampleSelector` selects the `k` examples most similar to the input,
+/// comparing their embeddings with a similarity metric, cosine similarity by default.
+///
+/// Example embeddings are computed once and cached, examples added with `add_example` are
+/// embedded the next time examples are selected.
+///
+/// # Usage
+/// ```rust,ignore
+/// let selector = SemanticSimilarityExampleSelector::new(examples, OpenAiEmbedder::default())
+///     .with_k(2)
+///     .with_input_keys(vec!["question".to_string()]);
+/// let examples = selector.select_examples_async(&prompt_args! { "question" => "big" }).await?;
+/// ```
+pub struct SemanticSimilarityExampleSelector {
+    examples: Vec<PromptArgs>,
+    embedder: Arc<dyn Embedder>,
+    k: usize,
+    input_keys: Option<Vec<String>>,
+    similarity: SimilarityFn,
+    embeddings: Mutex<Vec<Vec<f64>>>,
+}
+
+impl SemanticSimilarityExampleSelector {
+    pub fn new<E: Embedder + 'static>(examples: Vec<PromptArgs>, embedder: E) -> Self {
+        Self {
+            examples,
+            embedder: Arc::new(embedder),
+            k: 4,
+            input_keys: None,
+            similarity: Box::new(cosine_similarity),
+            embeddings: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Sets the number of examples to select. Defaults to 4.
+    pub fn with_k(mut self, k: usize) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// Sets the variables used to build the text embedded for the input and the examples.
+    /// Defaults to all of them.
+    pub fn with_input_keys(mut self, input_keys: Vec<String>) -> Self {
+        self.input_keys = Some(input_keys);
+        self
+    }
+
+    /// Sets the similarity metric, where a higher score means more similar.
+    pub fn with_similarity<F: Fn(&[f64], &[f64]) -> f64 + Send + Sync + 'static>(
+        mut self,
+        similarity: F,
+    ) -> Self {
+        self.similarity = Box::new(similarity);
+        self
+    }
+
+    /// Selects the examples most similar to the input, most similar first.
+    pub async fn select_examples_async(
+        &self,
+        input: &PromptArgs,
+    ) -> Result<Vec<PromptArgs>, EmbedderError> {
+        let mut embeddings = self.embeddings.lock().await;
+        if embeddings.len() < self.examples.len() {
+            let texts: Vec<String> = self.examples[embeddings.len()..]
+                .iter()
+                .map(|example| self.text_to_embed(example))
+                .collect();
+            embeddings.extend(self.embedder.embed_documents(&texts).await?);
+        }
+
+        let query = self
+            .embedder
+            .embed_query(&self.text_to_embed(input))
+            .await?;
+        let mut scores: Vec<(usize, f64)> = embeddings
+            .iter()
+            .enumerate()
+            .map(|(i, embedding)| (i, (self.similarity)(&query, embedding)))
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        Ok(scores
+            .into_iter()
+            .take(self.k)
+            .map(|(i, _)| self.examples[i].clone())
+            .collect())
+    }
+
+    fn text_to_embed(&self, args: &PromptArgs) -> String {
+        let mut keys: Vec<&String> = match &self.input_keys {
+            Some(input_keys) => input_keys
+                .iter()
+                .filter(|k| args.contains_key(*k))
+                .collect(),
+            None => args.keys().collect(),
+        };
+        if self.input_keys.is_none() {
+            keys.sort();
+        }
+        keys.into_iter()
+            .map(|key| value_to_string(&args[key]))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+impl ExampleSelector for SemanticSimilarityExampleSelector {
+    /// Returns no examples if they can't be selected, see `try_select_examples`.
+    fn select_examples(&self, input: &PromptArgs) -> Vec<PromptArgs> {
+        self.try_select_examples(input).unwrap_or_else(|e| {
+            log::warn!("{}", e);
+            Vec::new()
+        })
+    }
+
+    /// Blocks on `select_examples_async`. Prefer `select_examples_async`, or formatting the
+    /// prompt with `AsyncPrompt::aformat`, from async code.
+    ///
+    /// # Errors
+    /// Returns `PromptError::ExampleSelectionError` if the embedder fails, or on a current
+    /// thread runtime, which can't be blocked.
+    fn try_select_examples(&self, input: &PromptArgs) -> Result<Vec<PromptArgs>, PromptError> {
+        block_on(self.select_examples_async(input))?
+            .map_err(|e| PromptError::ExampleSelectionError(Box::new(e)))
+    }
+
+    fn aselect_examples<'a>(
+        &'a self,
+        input: &'a PromptArgs,
+    ) -> BoxFuture<'a, Result<Vec<PromptArgs>, PromptError>> {
+        Box::pin(async move {
+            self.select_examples_async(input)
+                .await
+                .map_err(|e| PromptError::ExampleSelectionError(Box::new(e)))
+        })
+    }
+
+    fn add_example(&mut self, example: PromptArgs) {
+        self.examples.push(example);
+    }
+}
+
+// Blocks on `future` on the current runtime, or on a runtime of its own outside of one.
+fn block_on<F: Future>(future: F) -> Result<F::Output, PromptError> {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::CurrentThread => {
+            Err(PromptError::ExampleSelectionError(
+                "a current thread runtime can't be blocked, select the examples asynchronously"
+                    .into(),
+            ))
+        }
+        Ok(handle) => Ok(tokio::task::block_in_place(|| handle.block_on(future))),
+        Err(_) => Ok(tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| PromptError::ExampleSelectionError(Box::new(e)))?
+            .block_on(future)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{
+        prompt::{AsyncPrompt, FewShotPromptTemplate, PromptFromatter},
+        prompt_args, template_fstring,
+    };
+
+    // Embeds a text as the count of each letter, so similar words get similar vectors.
+    struct LetterEmbedder {
+        calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Embedder for LetterEmbedder {
+        async fn embed_documents(
+            &self,
+            documents: &[String],
+        ) -> Result<Vec<Vec<f64>>, EmbedderError> {
+            self.calls
+                .fetch_add(documents.len(), std::sync::atomic::Ordering::SeqCst);
+            let mut embeddings = Vec::new();
+            for document in documents {
+                embeddings.push(self.embed_query(document).await?);
+            }
+            Ok(embeddings)
+        }
+
+        async fn embed_query(&self, text: &str) -> Result<Vec<f64>, EmbedderError> {
+            let mut embedding = vec![0.0; 26];
+            for c in text
+                .to_lowercase()
+                .chars()
+                .filter(|c| c.is_ascii_lowercase())
+            {
+                embedding[(c as u8 - b'a') as usize] += 1.0;
+            }
+            Ok(embedding)
+        }
+    }
+
+    struct FailingEmbedder;
+
+    #[async_trait]
+    impl Embedder for FailingEmbedder {
+        async fn embed_documents(&self, _: &[String]) -> Result<Vec<Vec<f64>>, EmbedderError> {
+            Err(EmbedderError::FastEmbedError("model not loaded".into()))
+        }
+
+        async fn embed_query(&self, _: &str) -> Result<Vec<f64>, EmbedderError> {
+            Err(EmbedderError::FastEmbedError("model not loaded".into()))
+        }
+    }
+
+    fn examples() -> Vec<PromptArgs> {
+        vec![
+            prompt_args! { "question" => "cat", "answer" => "animal" },
+            prompt_args! { "question" => "zzz", "answer" => "sleep" },
+            prompt_args! { "question" => "act", "answer" => "verb" },
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_semantic_similarity_example_selector() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut selector = SemanticSimilarityExampleSelector::new(
+            examples(),
+            LetterEmbedder {
+                calls: calls.clone(),
+            },
+        )
+        .with_k(2)
+        .with_input_keys(vec!["question".to_string()]);
+
+        let selected = selector
+            .select_examples_async(&prompt_args! { "question" => "tac" })
+            .await
+            .unwrap();
+        assert_eq!(selected.len(), 2);
+        assert!(selected.iter().all(|e| e["question"] != "zzz"));
+
+        // examples are embedded once
+        selector
+            .select_examples_async(&prompt_args! { "question" => "zz" })
+            .await
+            .unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        selector.add_example(prompt_args! { "question" => "zzzz", "answer" => "snore" });
+        let selected = selector
+            .select_examples_async(&prompt_args! { "question" => "zz" })
+            .await
+            .unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 4);
+        assert!(selected
+            .iter()
+            .all(|e| e["question"].as_str().unwrap().starts_with('z')));
+    }
+
+    fn few_shot_prompt() -> FewShotPromptTemplate {
+        few_shot_prompt_with(LetterEmbedder {
+            calls: Arc::default(),
+        })
+    }
+
+    fn few_shot_prompt_with<E: Embedder + 'static>(embedder: E) -> FewShotPromptTemplate {
+        let selector = SemanticSimilarityExampleSelector::new(examples(), embedder)
+            .with_k(1)
+            .with_input_keys(vec!["input".to_string(), "question".to_string()]);
+        FewShotPromptTemplate::new(
+            template_fstring!("Input: {question}\nOutput: {answer}", "question", "answer"),
+            template_fstring!("Input: {input}\nOutput:", "input"),
+        )
+        .with_example_selector(selector)
+    }
+
+    #[test]
+    fn test_few_shot_prompt_with_semantic_similarity_selector() {
+        let result = few_shot_prompt()
+            .format(prompt_args! { "input" => "zzzz" })
+            .unwrap();
+        assert_eq!(result, "Input: zzz\nOutput: sleep\n\nInput: zzzz\nOutput:");
+    }
+
+    // a current thread runtime, which can't be blocked on
+    #[tokio::test]
+    async fn test_few_shot_prompt_aformat() {
+        let prompt = few_shot_prompt();
+        let result = prompt
+            .aformat(prompt_args! { "input" => "zzzz" })
+            .await
+            .unwrap();
+        assert_eq!(result, "Input: zzz\nOutput: sleep\n\nInput: zzzz\nOutput:");
+
+        assert!(matches!(
+            prompt.format(prompt_args! { "input" => "zzzz" }),
+            Err(PromptError::ExampleSelectionError(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_few_shot_prompt_format_in_multi_thread_runtime() {
+        let result = few_shot_prompt()
+            .format(prompt_args! { "input" => "zzzz" })
+            .unwrap();
+        assert_eq!(result, "Input: zzz\nOutput: sleep\n\nInput: zzzz\nOutput:");
+    }
+
+    #[test]
+    fn test_few_shot_prompt_with_failing_embedder() {
+        let prompt = few_shot_prompt_with(FailingEmbedder);
+        let args = prompt_args! { "input" => "zzzz" };
+        let error = prompt.format(args.clone()).unwrap_err();
+        assert!(matches!(error, PromptError::ExampleSelectionError(_)));
+        assert!(error.to_string().contains("model not loaded"));
+
+        let error = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(prompt.aformat(args))
+            .unwrap_err();
+        assert!(error.to_string().contains("model not loaded"));
+    }
+}
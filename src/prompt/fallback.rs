@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use super::{PromptArgs, PromptError, PromptFromatter};
+
+/// The result of `FallbackPrompt::format_with_report`.
+#[derive(Debug)]
+pub struct FallbackReport {
+    pub prompt: String,
+    /// The error of the primary prompt, when the fallback was used instead.
+    pub primary_error: Option<PromptError>,
+}
+
+impl FallbackReport {
+    pub fn used_fallback(&self) -> bool {
+        self.primary_error.is_some()
+    }
+}
+
+/// Struct `FallbackPrompt` formats a generic fallback prompt when its primary prompt fails on
+/// a missing variable, so a request gets a degraded prompt rather than an error. Which errors
+/// fall back is set with `with_fallback_on`. The fallback can itself be a `FallbackPrompt`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = FallbackPrompt::new(
+///     Arc::new(template_fstring!("As {persona}, answer {question}", "persona", "question")),
+///     Arc::new(template_fstring!("Answer {question}", "question")),
+/// )?;
+/// let report = prompt.format_with_report(prompt_args! { "question" => "Why?" })?;
+/// assert!(report.used_fallback());
+/// ```
+pub struct FallbackPrompt {
+    primary: Arc<dyn PromptFromatter>,
+    fallback: Arc<dyn PromptFromatter>,
+    fallback_on: Box<dyn Fn(&PromptError) -> bool + Send + Sync>,
+}
+
+impl FallbackPrompt {
+    /// Creates a fallback prompt, which falls back on `PromptError::is_missing_variable` errors.
+    ///
+    /// # Errors
+    /// Returns `PromptError::InvalidFallback` if the fallback uses variables the primary
+    /// doesn't, since the input of the primary may lack them.
+    pub fn new(
+        primary: Arc<dyn PromptFromatter>,
+        fallback: Arc<dyn PromptFromatter>,
+    ) -> Result<Self, PromptError> {
+        let variables = primary.variables();
+        let extra: Vec<String> = fallback
+            .variables()
+            .into_iter()
+            .filter(|variable| !variables.contains(variable))
+            .collect();
+        if !extra.is_empty() {
+            return Err(PromptError::InvalidFallback(extra));
+        }
+        Ok(Self {
+            primary,
+            fallback,
+            fallback_on: Box::new(PromptError::is_missing_variable),
+        })
+    }
+
+    /// Sets the errors of the primary prompt that fall back, e.g. `|_| true` for all of them.
+    pub fn with_fallback_on<F>(mut self, fallback_on: F) -> Self
+    where
+        F: Fn(&PromptError) -> bool + Send + Sync + 'static,
+    {
+        self.fallback_on = Box::new(fallback_on);
+        self
+    }
+
+    /// Formats the primary prompt, without falling back, e.g. to test it.
+    pub fn format_primary_only(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        self.primary.format(input_variables)
+    }
+
+    /// Formats the primary prompt, or the fallback, returning the error of the primary when
+    /// the fallback was used.
+    pub fn format_with_report(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<FallbackReport, PromptError> {
+        match self.primary.format(input_variables.clone()) {
+            Ok(prompt) => Ok(FallbackReport {
+                prompt,
+                primary_error: None,
+            }),
+            Err(e) if (self.fallback_on)(&e) => {
+                log::warn!("Prompt falls back after: {}", e);
+                Ok(FallbackReport {
+                    prompt: self.fallback.format(input_variables)?,
+                    primary_error: Some(e),
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl PromptFromatter for FallbackPrompt {
+    /// Returns the template of the primary prompt.
+    fn template(&self) -> String {
+        self.primary.template()
+    }
+
+    fn variables(&self) -> Vec<String> {
+        self.primary.variables()
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        self.format_with_report(input_variables)
+            .map(|report| report.prompt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, template_fstring};
+
+    #[test]
+    fn test_fallback_prompt() {
+        let detailed = template_fstring!(
+            "As {persona}, answer {question} about {topic}",
+            "persona",
+            "question",
+            "topic"
+        );
+        let focused = template_fstring!("Answer {question} about {topic}", "question", "topic");
+        let generic = template_fstring!("Answer {question}", "question");
+        let inner = FallbackPrompt::new(Arc::new(detailed), Arc::new(focused)).unwrap();
+        let prompt = FallbackPrompt::new(Arc::new(inner), Arc::new(generic)).unwrap();
+
+        let full = prompt_args! { "persona" => "a chef", "question" => "why?", "topic" => "salt" };
+        let report = prompt.format_with_report(full).unwrap();
+        assert_eq!(report.prompt, "As a chef, answer why? about salt");
+        assert!(!report.used_fallback());
+        assert_eq!(
+            prompt
+                .format(prompt_args! { "question" => "why?", "topic" => "salt" })
+                .unwrap(),
+            "Answer why? about salt"
+        );
+        let report = prompt
+            .format_with_report(prompt_args! { "question" => "why?" })
+            .unwrap();
+        assert_eq!(report.prompt, "Answer why?");
+        assert!(report.primary_error.unwrap().is_missing_variable());
+
+        assert!(prompt
+            .format_primary_only(prompt_args! { "question" => "why?" })
+            .unwrap_err()
+            .is_missing_variable());
+        let strict = FallbackPrompt::new(
+            Arc::new(template_fstring!("{question}", "question")),
+            Arc::new("Answer"),
+        )
+        .unwrap()
+        .with_fallback_on(|_| false);
+        assert!(strict.format(prompt_args! {}).is_err());
+    }
+
+    #[test]
+    fn test_fallback_prompt_variables() {
+        let result = FallbackPrompt::new(
+            Arc::new(template_fstring!("Answer {question}", "question")),
+            Arc::new(template_fstring!(
+                "As {persona}: {question}",
+                "persona",
+                "question"
+            )),
+        );
+        assert!(matches!(
+            result,
+            Err(PromptError::InvalidFallback(extra)) if extra == vec!["persona"]
+        ));
+    }
+}
@@ -0,0 +1,367 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+use crate::schemas::prompt::PromptValue;
+
+use super::{
+    ExampleSelector, FormatPrompter, PromptArgs, PromptError, PromptFromatter, PromptTemplate,
+    TokenCounter,
+};
+
+/// The result of `FewShotPromptTemplate::format_with_report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FewShotReport {
+    pub prompt: String,
+    /// The number of examples in the prompt.
+    pub included: usize,
+    /// The number of examples left out to fit the token budget.
+    pub excluded: usize,
+}
+
+/// Struct `FewShotPromptTemplate` renders a prefix, a list of examples and a suffix, joined by
+/// a separator. Each example is rendered through the shared `example_prompt`, while the prefix
+/// and suffix are rendered with the input variables.
+///
+/// The examples are either a fixed list, set with `with_examples`, or chosen for each input by
+/// an `ExampleSelector`, set with `with_example_selector`. With a token budget, set with
+/// `with_token_budget`, the examples that don't fit are left out.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = FewShotPromptTemplate::new(
+///     template_fstring!("Input: {question}\nOutput: {answer}", "question", "answer"),
+///     template_fstring!("Input: {input}\nOutput:", "input"),
+/// )
+/// .with_prefix(template_fstring!("Give the antonym of every input.",))
+/// .with_examples(vec![
+///     prompt_args! { "question" => "happy", "answer" => "sad" },
+///     prompt_args! { "question" => "tall", "answer" => "short" },
+/// ]);
+/// let result = prompt.format(prompt_args! { "input" => "big" })?;
+/// ```
+pub struct FewShotPromptTemplate {
+    examples: Vec<PromptArgs>,
+    example_selector: Option<Box<dyn ExampleSelector>>,
+    example_prompt: Box<dyn PromptFromatter>,
+    prefix: Option<PromptTemplate>,
+    suffix: PromptTemplate,
+    example_separator: String,
+    token_budget: Option<(Arc<dyn TokenCounter>, usize)>,
+}
+
+impl FewShotPromptTemplate {
+    pub fn new<P: Into<Box<dyn PromptFromatter>>, S: Into<PromptTemplate>>(
+        example_prompt: P,
+        suffix: S,
+    ) -> Self {
+        Self {
+            examples: Vec::new(),
+            example_selector: None,
+            example_prompt: example_prompt.into(),
+            prefix: None,
+            suffix: suffix.into(),
+            example_separator: "\n\n".to_string(),
+            token_budget: None,
+        }
+    }
+
+    pub fn with_examples(mut self, examples: Vec<PromptArgs>) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    /// Sets a selector choosing the examples for each input. It takes precedence over the
+    /// examples set with `with_examples`.
+    pub fn with_example_selector<ES: Into<Box<dyn ExampleSelector>>>(
+        mut self,
+        example_selector: ES,
+    ) -> Self {
+        self.example_selector = Some(example_selector.into());
+        self
+    }
+
+    pub fn with_prefix<S: Into<PromptTemplate>>(mut self, prefix: S) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Sets the string placed between the prefix, each example and the suffix. Defaults to a
+    /// blank line.
+    pub fn with_example_separator<S: Into<String>>(mut self, example_separator: S) -> Self {
+        self.example_separator = example_separator.into();
+        self
+    }
+
+    /// Keeps the formatted prompt within `max_tokens`, as counted by `counter`. The prefix,
+    /// suffix and input always go in, then every example that still fits, in the order of the
+    /// list or of the selector, so the most relevant examples go first.
+    pub fn with_token_budget<C: TokenCounter + 'static>(
+        mut self,
+        counter: C,
+        max_tokens: usize,
+    ) -> Self {
+        self.token_budget = Some((Arc::new(counter), max_tokens));
+        self
+    }
+
+    pub fn examples(&self) -> &[PromptArgs] {
+        &self.examples
+    }
+
+    /// Formats the prompt, also returning how many examples went in.
+    ///
+    /// # Errors
+    /// Returns `PromptError::FewShotBudgetExceeded` if the prompt is over the token budget
+    /// without any example.
+    pub fn format_with_report(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<FewShotReport, PromptError> {
+        let examples = match &self.example_selector {
+            Some(selector) => selector.try_select_examples(&input_variables)?,
+            None => self.examples.clone(),
+        };
+        self.render(input_variables, examples)
+    }
+
+    /// Formats the prompt like `format_with_report`, selecting the examples without blocking.
+    pub async fn aformat_with_report(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<FewShotReport, PromptError> {
+        let examples = match &self.example_selector {
+            Some(selector) => selector.aselect_examples(&input_variables).await?,
+            None => self.examples.clone(),
+        };
+        self.render(input_variables, examples)
+    }
+
+    fn render(
+        &self,
+        input_variables: PromptArgs,
+        examples: Vec<PromptArgs>,
+    ) -> Result<FewShotReport, PromptError> {
+        let prefix = match &self.prefix {
+            Some(prefix) => prefix.format(input_variables.clone())?,
+            None => String::new(),
+        };
+        let suffix = self.suffix.format(input_variables)?;
+
+        // an empty prefix or suffix must not leave a dangling separator
+        let join = |examples: &[String]| {
+            let mut pieces = vec![prefix.as_str()];
+            pieces.extend(examples.iter().map(String::as_str));
+            pieces.push(suffix.as_str());
+            pieces.retain(|piece| !piece.is_empty());
+            pieces.join(&self.example_separator)
+        };
+
+        let mut rendered = Vec::new();
+        for (index, example) in examples.iter().enumerate() {
+            let example = self.example_prompt.format(example.clone()).map_err(|e| {
+                PromptError::ExampleError {
+                    index,
+                    source: Box::new(e),
+                }
+            })?;
+            rendered.push(example);
+        }
+        let Some((counter, max_tokens)) = &self.token_budget else {
+            return Ok(FewShotReport {
+                prompt: join(&rendered),
+                included: rendered.len(),
+                excluded: 0,
+            });
+        };
+
+        // tokens don't add up across pieces, so each candidate prompt is counted whole
+        let mut prompt = join(&[]);
+        let fixed_tokens = counter.count_tokens(&prompt);
+        if fixed_tokens > *max_tokens {
+            return Err(PromptError::FewShotBudgetExceeded {
+                fixed_tokens,
+                max_tokens: *max_tokens,
+            });
+        }
+        let mut included = Vec::new();
+        for example in rendered.iter() {
+            included.push(example.clone());
+            let candidate = join(&included);
+            if counter.count_tokens(&candidate) <= *max_tokens {
+                prompt = candidate;
+            } else {
+                included.pop();
+            }
+        }
+        let excluded = rendered.len() - included.len();
+        if excluded > 0 {
+            log::debug!(
+                "Few-shot prompt left out {} of {} examples to fit {} tokens",
+                excluded,
+                rendered.len(),
+                max_tokens
+            );
+        }
+        Ok(FewShotReport {
+            prompt,
+            included: included.len(),
+            excluded,
+        })
+    }
+}
+
+impl PromptFromatter for FewShotPromptTemplate {
+    fn template(&self) -> String {
+        let mut pieces = Vec::new();
+        if let Some(prefix) = &self.prefix {
+            pieces.push(prefix.template());
+        }
+        pieces.push(self.example_prompt.template());
+        pieces.push(self.suffix.template());
+        pieces.join(&self.example_separator)
+    }
+
+    fn variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        let prefix_variables = self.prefix.iter().flat_map(|prefix| prefix.variables());
+        for variable in prefix_variables.chain(self.suffix.variables()) {
+            if !variables.contains(&variable) {
+                variables.push(variable);
+            }
+        }
+        variables
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        self.format_with_report(input_variables)
+            .map(|report| report.prompt)
+    }
+}
+
+impl FormatPrompter for FewShotPromptTemplate {
+    fn format_prompt(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
+        self.to_prompt_value(input_variables)
+    }
+    fn get_input_variables(&self) -> Vec<String> {
+        self.variables()
+    }
+
+    /// Selects the examples without blocking, see `aformat_with_report`.
+    fn format_prompt_async(
+        &self,
+        input_variables: PromptArgs,
+    ) -> BoxFuture<'_, Result<PromptValue, PromptError>> {
+        Box::pin(async move {
+            let report = self.aformat_with_report(input_variables).await?;
+            Ok(PromptValue::from_string(&report.prompt))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt::HeuristicTokenCounter, prompt_args, template_fstring};
+
+    fn antonyms_prompt() -> FewShotPromptTemplate {
+        FewShotPromptTemplate::new(
+            template_fstring!("Input: {question}\nOutput: {answer}", "question", "answer"),
+            template_fstring!("Input: {input}\nOutput:", "input"),
+        )
+        .with_prefix(template_fstring!("Give the {kind} of every input.", "kind"))
+    }
+
+    #[test]
+    fn test_few_shot_prompt_template() {
+        let prompt = antonyms_prompt().with_examples(vec![
+            prompt_args! { "question" => "happy", "answer" => "sad" },
+            prompt_args! { "question" => "tall", "answer" => "short" },
+        ]);
+        assert_eq!(prompt.variables(), vec!["kind", "input"]);
+
+        let result = prompt
+            .format(prompt_args! { "kind" => "antonym", "input" => "big" })
+            .unwrap();
+        assert_eq!(
+            result,
+            "Give the antonym of every input.\n\nInput: happy\nOutput: sad\n\nInput: tall\nOutput: short\n\nInput: big\nOutput:"
+        );
+    }
+
+    #[test]
+    fn test_few_shot_prompt_template_from_strings() {
+        let suffix = PromptTemplate::try_from("Q: {input}\nA:").unwrap();
+        let prompt = FewShotPromptTemplate::new("Q: {question}\nA: {answer}", suffix)
+            .with_prefix(PromptTemplate::try_from("Answer {{briefly}}.").unwrap())
+            .with_examples(vec![prompt_args! { "question" => "2+2", "answer" => "4" }]);
+        assert_eq!(prompt.variables(), vec!["input"]);
+
+        let result = prompt.format(prompt_args! { "input" => "3+3" }).unwrap();
+        assert_eq!(result, "Answer {briefly}.\n\nQ: 2+2\nA: 4\n\nQ: 3+3\nA:");
+    }
+
+    #[test]
+    fn test_few_shot_prompt_template_edge_cases() {
+        let prompt = antonyms_prompt().with_example_separator("\n---\n");
+        let result = prompt
+            .format(prompt_args! { "kind" => "antonym", "input" => "big" })
+            .unwrap();
+        assert_eq!(
+            result,
+            "Give the antonym of every input.\n---\nInput: big\nOutput:"
+        );
+
+        let prompt = antonyms_prompt().with_examples(vec![
+            prompt_args! { "question" => "happy", "answer" => "sad" },
+            prompt_args! { "question" => "tall" },
+        ]);
+        match prompt.format(prompt_args! { "kind" => "antonym", "input" => "big" }) {
+            Err(PromptError::ExampleError { index, source }) => {
+                assert_eq!(index, 1);
+                assert!(
+                    matches!(*source, PromptError::InvalidInput(e) if e.missing == vec!["answer"])
+                );
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_few_shot_prompt_template_token_budget() {
+        let examples = vec![
+            prompt_args! { "question" => "happy", "answer" => "sad" },
+            prompt_args! { "question" => "a very tall tower", "answer" => "a small hut" },
+            prompt_args! { "question" => "hot", "answer" => "cold" },
+        ];
+        let input = prompt_args! { "kind" => "antonym", "input" => "big" };
+        // the prefix and suffix take 9 words, each example 4 words but the second one 10
+        let prompt = antonyms_prompt()
+            .with_examples(examples.clone())
+            .with_token_budget(HeuristicTokenCounter::Words, 18);
+        let report = prompt.format_with_report(input.clone()).unwrap();
+        assert_eq!(
+            report.prompt,
+            "Give the antonym of every input.\n\nInput: happy\nOutput: sad\n\nInput: hot\nOutput: cold\n\nInput: big\nOutput:"
+        );
+        assert_eq!((report.included, report.excluded), (2, 1));
+        assert!(HeuristicTokenCounter::Words.count_tokens(&report.prompt) <= 18);
+
+        let prompt = antonyms_prompt()
+            .with_examples(examples.clone())
+            .with_token_budget(HeuristicTokenCounter::Words, 9);
+        let report = prompt.format_with_report(input.clone()).unwrap();
+        assert_eq!((report.included, report.excluded), (0, 3));
+
+        let prompt = antonyms_prompt()
+            .with_examples(examples)
+            .with_token_budget(HeuristicTokenCounter::Words, 8);
+        assert!(matches!(
+            prompt.format(input),
+            Err(PromptError::FewShotBudgetExceeded {
+                fixed_tokens: 9,
+                max_tokens: 8
+            })
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+use serde_json::Value;
+
+use super::{prompt::value_to_string, PromptError};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Join(String),
+    Lower,
+    Trim,
+    Truncate(usize),
+    Upper,
+}
+
+impl Filter {
+    fn name(&self) -> &'static str {
+        match self {
+            Filter::Join(_) => "join",
+            Filter::Lower => "lower",
+            Filter::Trim => "trim",
+            Filter::Truncate(_) => "truncate",
+            Filter::Upper => "upper",
+        }
+    }
+}
+
+/// The filters of an FString placeholder, after its first pipe, like `join:", "|upper` in
+/// `{points|join:", "|upper}`. They are applied left to right:
+///
+/// - `join:SEP` joins the items of a list with `SEP`, nothing by default.
+/// - `upper`, `lower` and `trim` change the text of the value.
+/// - `truncate:N` keeps the first `N` characters.
+///
+/// An argument is a bare word or a double-quoted string, which can hold pipes and braces and
+/// the escapes `\n`, `\t`, `\"` and `\\`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FilterChain<'a> {
+    pub(crate) raw: &'a str,
+    filters: Vec<Filter>,
+}
+
+impl<'a> FilterChain<'a> {
+    /// Parses the filters, returning the reason they're invalid otherwise.
+    pub(crate) fn parse(raw: &'a str) -> Result<Self, String> {
+        let mut filters = Vec::new();
+        let mut rest = raw;
+        loop {
+            let (name, argument, after) = split_filter(rest)?;
+            let filter = match (name, argument) {
+                ("join", separator) => Filter::Join(separator.unwrap_or_default()),
+                ("truncate", Some(length)) => Filter::Truncate(
+                    length
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("`truncate` expects a length, got `{}`", length))?,
+                ),
+                ("truncate", None) => return Err("`truncate` expects a length".into()),
+                ("lower" | "trim" | "upper", Some(_)) => {
+                    return Err(format!("`{}` takes no argument", name))
+                }
+                ("lower", None) => Filter::Lower,
+                ("trim", None) => Filter::Trim,
+                ("upper", None) => Filter::Upper,
+                ("", _) => return Err("empty filter".into()),
+                _ => return Err(format!("unknown filter `{}`", name)),
+            };
+            filters.push(filter);
+            match after {
+                Some(after) => rest = after,
+                None => break,
+            }
+        }
+        Ok(Self { raw, filters })
+    }
+
+    /// Filters the value of `variable`.
+    ///
+    /// # Errors
+    /// Returns `PromptError::FilterError` if `join` is applied to a value that isn't a list.
+    pub(crate) fn apply(&self, variable: &str, value: &Value) -> Result<String, PromptError> {
+        let mut value = value.clone();
+        for filter in &self.filters {
+            let text = match (filter, &value) {
+                (Filter::Join(separator), Value::Array(items)) => items
+                    .iter()
+                    .map(value_to_string)
+                    .collect::<Vec<String>>()
+                    .join(separator),
+                (Filter::Join(_), _) => {
+                    return Err(PromptError::FilterError {
+                        variable: variable.to_string(),
+                        filter: filter.name().to_string(),
+                        reason: format!("expected a list, got {}", value),
+                    })
+                }
+                (Filter::Lower, _) => value_to_string(&value).to_lowercase(),
+                (Filter::Trim, _) => value_to_string(&value).trim().to_string(),
+                (Filter::Truncate(length), _) => {
+                    value_to_string(&value).chars().take(*length).collect()
+                }
+                (Filter::Upper, _) => value_to_string(&value).to_uppercase(),
+            };
+            value = Value::String(text);
+        }
+        Ok(value_to_string(&value))
+    }
+}
+
+// Splits the first filter of `raw` into its name, its unescaped argument, and the filters
+// after its pipe.
+fn split_filter(raw: &str) -> Result<(&str, Option<String>, Option<&str>), String> {
+    let end = raw.find(['|', ':']).unwrap_or(raw.len());
+    let name = raw[..end].trim();
+    if !raw[end..].starts_with(':') {
+        return Ok((name, None, raw.get(end + 1..)));
+    }
+    let argument = raw[end + 1..].trim_start();
+    if let Some(quoted) = argument.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    let after = quoted[i + 1..].trim_start();
+                    return match after.strip_prefix('|') {
+                        Some(after) => Ok((name, Some(value), Some(after))),
+                        None if after.is_empty() => Ok((name, Some(value), None)),
+                        None => Err(format!(
+                            "unexpected `{}` after the argument of `{}`",
+                            after, name
+                        )),
+                    };
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ ('"' | '\\')) => value.push(c),
+                    Some(c) => return Err(format!("unknown escape `\\{}`", c)),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        return Err(format!("unclosed argument of `{}`", name));
+    }
+    let (argument, after) = match argument.split_once('|') {
+        Some((argument, after)) => (argument, Some(after)),
+        None => (argument, None),
+    };
+    Ok((name, Some(argument.trim().to_string()), after))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_filter_chain() {
+        let chain = FilterChain::parse(r#"join:"\n- "|upper"#).unwrap();
+        assert_eq!(
+            chain.apply("points", &json!(["a", "b | c"])).unwrap(),
+            "A\n- B | C"
+        );
+        let chain = FilterChain::parse(" trim | truncate:5 | lower ").unwrap();
+        assert_eq!(
+            chain.apply("name", &json!("  HELLO WORLD ")).unwrap(),
+            "hello"
+        );
+
+        assert!(matches!(
+            FilterChain::parse("join").unwrap().apply("points", &json!("a")),
+            Err(PromptError::FilterError { variable, .. }) if variable == "points"
+        ));
+        for (raw, reason) in [
+            ("shout", "unknown filter `shout`"),
+            ("truncate:many", "`truncate` expects a length, got `many`"),
+            ("upper:1", "`upper` takes no argument"),
+            (r#"join:"a"#, "unclosed argument of `join`"),
+            ("trim|", "empty filter"),
+        ] {
+            assert_eq!(FilterChain::parse(raw), Err(reason.to_string()));
+        }
+    }
+}
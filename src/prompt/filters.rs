@@ -0,0 +1,135 @@
+use std::error::Error;
+
+use serde_json::Value;
+
+/// The Jinja2 filters every `PromptTemplate` can use, whatever the version of the engine. More
+/// may be available, but only these are checked by the tests.
+pub const BUILTIN_FILTERS: &[&str] = &[
+    "abs",
+    "capitalize",
+    "default",
+    "first",
+    "float",
+    "indent",
+    "int",
+    "join",
+    "last",
+    "length",
+    "list",
+    "lower",
+    "max",
+    "min",
+    "replace",
+    "reverse",
+    "round",
+    "sort",
+    "string",
+    "sum",
+    "title",
+    "trim",
+    "unique",
+    "upper",
+];
+
+/// A custom Jinja2 filter, see `PromptTemplate::with_filter`. It gets the filtered value and
+/// the arguments of the filter call, like `", "` in `{{ items | join(", ") }}`. It's
+/// implemented for closures.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = template_jinja2!("Contact: {{ email | redact_email }}", "email")
+///     .with_filter("redact_email", |value: &Value, _args: &[Value]| {
+///         let email = value.as_str().ok_or("expected a string")?;
+///         let domain = email.split('@').nth(1).unwrap_or_default();
+///         Ok::<_, Box<dyn Error + Send + Sync>>(Value::from(format!("***@{}", domain)))
+///     });
+/// ```
+pub trait TemplateFilter: Send + Sync {
+    fn apply(&self, value: &Value, args: &[Value]) -> Result<Value, Box<dyn Error + Send + Sync>>;
+}
+
+impl<F, V, E> TemplateFilter for F
+where
+    F: Fn(&Value, &[Value]) -> Result<V, E> + Send + Sync,
+    V: Into<Value>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    fn apply(&self, value: &Value, args: &[Value]) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        self(value, args).map(Into::into).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prompt::{PromptError, PromptFromatter},
+        prompt_args, template_jinja2,
+    };
+
+    #[test]
+    fn test_builtin_filters() {
+        // the filters may reject the value, but they must exist
+        let unknown = |filter: &str| {
+            let prompt = template_jinja2!(format!("{{{{ items | {} }}}}", filter), "items");
+            match prompt.format(prompt_args! { "items" => vec![3, 1, 2] }) {
+                Err(PromptError::RenderError(message)) => message.contains("unknown filter"),
+                _ => false,
+            }
+        };
+        assert!(unknown("not_a_filter"));
+        for filter in BUILTIN_FILTERS {
+            assert!(!unknown(filter), "filter {} is missing", filter);
+        }
+
+        let prompt = template_jinja2!(
+            "{{ name | upper }}: {{ items | join(\", \") }}",
+            "name",
+            "items"
+        );
+        assert_eq!(
+            prompt
+                .format(prompt_args! { "name" => "luis", "items" => vec!["a", "b"] })
+                .unwrap(),
+            "LUIS: a, b"
+        );
+    }
+
+    #[test]
+    fn test_custom_filters() {
+        let mut prompt = template_jinja2!(
+            "Contact {{ email|redact_email }}\n{{ steps | to_bullets(\"*\") }}",
+            "email",
+            "steps"
+        )
+        .with_filter("redact_email", |value: &Value, _: &[Value]| {
+            let email = value.as_str().ok_or("expected a string")?;
+            let domain = email.split('@').nth(1).ok_or("expected an email")?;
+            Ok::<_, Box<dyn Error + Send + Sync>>(format!("***@{}", domain))
+        });
+        prompt.add_filter("to_bullets", |value: &Value, args: &[Value]| {
+            let bullet = args.first().and_then(Value::as_str).unwrap_or("-");
+            let items = value.as_array().ok_or("expected a list")?;
+            let lines: Vec<String> = items
+                .iter()
+                .map(|item| format!("{} {}", bullet, item.as_str().unwrap_or_default()))
+                .collect();
+            Ok::<_, &str>(lines.join("\n"))
+        });
+        assert_eq!(prompt.variables(), vec!["email", "steps"]);
+
+        let result = prompt
+            .format(prompt_args! { "email" => "ana@example.com", "steps" => vec!["a", "b"] })
+            .unwrap();
+        assert_eq!(result, "Contact ***@example.com\n* a\n* b");
+
+        match prompt.format(prompt_args! { "email" => "nobody", "steps" => vec!["a"] }) {
+            Err(PromptError::RenderError(message)) => {
+                assert!(message.contains("redact_email"), "{}", message);
+                assert!(message.contains("expected an email"), "{}", message);
+                assert!(message.contains("line 1"), "{}", message);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
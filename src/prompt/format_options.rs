@@ -0,0 +1,195 @@
+/// Struct `FormatOptions` controls the whitespace of a prompt formatted with
+/// `PromptTemplate::format_with_options`, so templates can be indented for readability in
+/// source code. Every option is off by default, leaving the output unchanged.
+///
+/// # Usage
+/// ```rust,ignore
+/// let options = FormatOptions::new()
+///     .with_dedent(true)
+///     .with_trim_trailing_whitespace(true)
+///     .with_collapse_newlines(true)
+///     .with_trim(true);
+/// let result = prompt.format_with_options(prompt_args! { "question" => "Why?" }, &options)?;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatOptions {
+    pub dedent: bool,
+    pub trim_trailing_whitespace: bool,
+    pub collapse_newlines: bool,
+    pub trim: bool,
+}
+
+impl FormatOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Strips the leading indentation common to every non-blank line of the template. It
+    /// applies to the template before rendering, so multi-line values don't affect it.
+    pub fn with_dedent(mut self, dedent: bool) -> Self {
+        self.dedent = dedent;
+        self
+    }
+
+    /// Removes the whitespace at the end of every line.
+    pub fn with_trim_trailing_whitespace(mut self, trim: bool) -> Self {
+        self.trim_trailing_whitespace = trim;
+        self
+    }
+
+    /// Collapses runs of 3 or more newlines into 2, leaving a single blank line.
+    pub fn with_collapse_newlines(mut self, collapse: bool) -> Self {
+        self.collapse_newlines = collapse;
+        self
+    }
+
+    /// Removes the whitespace at the start and end of the result.
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    // Applies the options other than `dedent` to a rendered prompt.
+    pub(crate) fn apply(&self, text: String) -> String {
+        let mut text = text;
+        if self.trim_trailing_whitespace {
+            text = text
+                .split_inclusive('\n')
+                .map(|line| {
+                    let content = line.strip_suffix('\n').unwrap_or(line);
+                    let trimmed = content.trim_end();
+                    if content.len() == line.len() {
+                        trimmed.to_string()
+                    } else {
+                        format!("{}\n", trimmed)
+                    }
+                })
+                .collect();
+        }
+        if self.collapse_newlines {
+            let mut collapsed = String::with_capacity(text.len());
+            let mut newlines = 0;
+            for c in text.chars() {
+                newlines = if c == '\n' { newlines + 1 } else { 0 };
+                if newlines <= 2 {
+                    collapsed.push(c);
+                }
+            }
+            text = collapsed;
+        }
+        if self.trim {
+            text = text.trim().to_string();
+        }
+        text
+    }
+}
+
+/// Strips the leading spaces and tabs common to every non-blank line. Blank lines don't count
+/// and are left with their newline only.
+pub(crate) fn dedent(text: &str) -> String {
+    let indent = text
+        .split('\n')
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| &line[..line.len() - line.trim_start_matches([' ', '\t']).len()])
+        .reduce(|common, indent| {
+            let len = common
+                .bytes()
+                .zip(indent.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            &common[..len]
+        })
+        .unwrap_or_default();
+
+    text.split_inclusive('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                if line.ends_with('\n') {
+                    "\n"
+                } else {
+                    ""
+                }
+            } else {
+                line.strip_prefix(indent).unwrap_or(line)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prompt::{PromptTemplate, TemplateFormat},
+        prompt_args,
+    };
+
+    fn prompt() -> PromptTemplate {
+        PromptTemplate::from_template(
+            r#"
+            You are a helpful assistant.   
+
+                - Be concise.
+                - Cite your sources.
+
+
+
+            Context:
+            {context}
+
+            Question: {question}
+        "#,
+            TemplateFormat::FString,
+        )
+        .unwrap()
+    }
+
+    fn args() -> crate::prompt::PromptArgs {
+        prompt_args! { "context" => "line one\nline two", "question" => "Why?" }
+    }
+
+    #[test]
+    fn test_format_options_default_is_unchanged() {
+        let prompt = prompt();
+        assert_eq!(
+            prompt
+                .format_with_options(args(), &FormatOptions::default())
+                .unwrap(),
+            prompt.format(args()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_format_options() {
+        let options = FormatOptions::new()
+            .with_dedent(true)
+            .with_trim_trailing_whitespace(true)
+            .with_collapse_newlines(true)
+            .with_trim(true);
+        let result = prompt().format_with_options(args(), &options).unwrap();
+        assert_eq!(
+            result,
+            "You are a helpful assistant.\n\n    - Be concise.\n    - Cite your sources.\n\nContext:\nline one\nline two\n\nQuestion: Why?"
+        );
+
+        let options = FormatOptions::new().with_dedent(true);
+        let result = prompt().format_with_options(args(), &options).unwrap();
+        assert!(result.starts_with("\nYou are a helpful assistant.   \n\n    - Be concise."));
+        assert!(result.ends_with("Question: Why?\n"));
+    }
+
+    #[test]
+    fn test_format_options_individually() {
+        let trailing = FormatOptions::new().with_trim_trailing_whitespace(true);
+        assert_eq!(trailing.apply("a  \n b\t\n\n".to_string()), "a\n b\n\n");
+
+        let collapse = FormatOptions::new().with_collapse_newlines(true);
+        assert_eq!(collapse.apply("a\n\n\n\nb\n\nc".to_string()), "a\n\nb\n\nc");
+
+        let trim = FormatOptions::new().with_trim(true);
+        assert_eq!(trim.apply("\n  a \n".to_string()), "a");
+
+        assert_eq!(dedent("    a\n      b\n\n    c"), "a\n  b\n\nc");
+        assert_eq!(dedent("\ta\n  b"), "\ta\n  b");
+    }
+}
@@ -0,0 +1,186 @@
+use serde_json::Value;
+
+use super::{prompt::value_to_string, PromptError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// The format spec of an FString placeholder, after its colon, like `>8` in `{score:>8}`. It's
+/// the `[[fill]align][0][width][.precision]` subset of Rust and Python format specs:
+///
+/// - `fill` is any character but a brace, and `align` is `<`, `^` or `>`. Numbers are aligned
+///   right by default, other values left.
+/// - `0` pads numbers with zeros after their sign.
+/// - `.precision` formats numbers with that many decimals.
+///
+/// `0` and `.precision` need a number, or a string holding one.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FormatSpec<'a> {
+    pub(crate) raw: &'a str,
+    fill: char,
+    align: Option<Align>,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl<'a> FormatSpec<'a> {
+    /// Parses the spec, returning the reason it's invalid otherwise.
+    pub(crate) fn parse(raw: &'a str) -> Result<Self, String> {
+        let align_of = |c: char| match c {
+            '<' => Some(Align::Left),
+            '^' => Some(Align::Center),
+            '>' => Some(Align::Right),
+            _ => None,
+        };
+        let mut chars = raw.chars().peekable();
+        let mut spec = FormatSpec {
+            raw,
+            fill: ' ',
+            align: None,
+            zero: false,
+            width: 0,
+            precision: None,
+        };
+
+        let mut lookahead = raw.chars();
+        match (lookahead.next(), lookahead.next().and_then(align_of)) {
+            (Some(fill), Some(align)) => {
+                if fill == '{' || fill == '}' {
+                    return Err("braces can't be used as fill".into());
+                }
+                spec.fill = fill;
+                spec.align = Some(align);
+                chars.nth(1);
+            }
+            (Some(c), None) if align_of(c).is_some() => {
+                spec.align = align_of(c);
+                chars.next();
+            }
+            _ => {}
+        }
+        if chars.peek() == Some(&'0') {
+            spec.zero = true;
+            chars.next();
+        }
+        spec.width = take_number(&mut chars).unwrap_or(0);
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            spec.precision =
+                Some(take_number(&mut chars).ok_or("`.` must be followed by a precision")?);
+        }
+        match chars.next() {
+            Some(c) => Err(format!("unexpected `{}`", c)),
+            None => Ok(spec),
+        }
+    }
+
+    /// Formats the value of `variable` as set by the spec.
+    ///
+    /// # Errors
+    /// Returns `PromptError::FormatSpecError` if the spec needs a number and the value isn't one.
+    pub(crate) fn apply(&self, variable: &str, value: &Value) -> Result<String, PromptError> {
+        let number = match value {
+            Value::Number(number) => number.as_f64(),
+            Value::String(text) => text.trim().parse::<f64>().ok(),
+            _ => None,
+        };
+        let text = if self.zero || self.precision.is_some() {
+            let number = number.ok_or_else(|| PromptError::FormatSpecError {
+                variable: variable.to_string(),
+                spec: self.raw.to_string(),
+                reason: format!("{} is not a number", value_to_string(value)),
+            })?;
+            match self.precision {
+                Some(precision) => format!("{:.*}", precision, number),
+                None => value_to_string(value).trim().to_string(),
+            }
+        } else {
+            value_to_string(value)
+        };
+
+        let len = text.chars().count();
+        if len >= self.width {
+            return Ok(text);
+        }
+        let padding = self.width - len;
+        if self.zero && self.align.is_none() {
+            let digits = text.trim_start_matches(['-', '+']);
+            let sign = &text[..text.len() - digits.len()];
+            return Ok(format!("{}{}{}", sign, "0".repeat(padding), digits));
+        }
+
+        let default_align = match value {
+            Value::Number(_) => Align::Right,
+            _ if self.zero || self.precision.is_some() => Align::Right,
+            _ => Align::Left,
+        };
+        let (before, after) = match self.align.unwrap_or(default_align) {
+            Align::Left => (0, padding),
+            Align::Center => (padding / 2, padding - padding / 2),
+            Align::Right => (padding, 0),
+        };
+        let fill = |count| self.fill.to_string().repeat(count);
+        Ok(format!("{}{}{}", fill(before), text, fill(after)))
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<usize> {
+    let mut number: Option<usize> = None;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        number = Some(number.unwrap_or(0) * 10 + digit as usize);
+        chars.next();
+    }
+    number
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn format(spec: &str, value: Value) -> String {
+        FormatSpec::parse(spec)
+            .unwrap()
+            .apply("value", &value)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_format_spec() {
+        assert_eq!(format(">8", json!(42)), "      42");
+        assert_eq!(format("8", json!(42)), "      42");
+        assert_eq!(format("8", json!("ab")), "ab      ");
+        assert_eq!(format("*^7", json!("ab")), "**ab***");
+        assert_eq!(format("-<4", json!(7)), "7---");
+        assert_eq!(format("03", json!(7)), "007");
+        assert_eq!(format("05", json!(-7)), "-0007");
+        assert_eq!(format(".2", json!(1.23456)), "1.23");
+        assert_eq!(format("08.3", json!(-2.5)), "-002.500");
+        assert_eq!(format(">6.1", json!("0.25")), "   0.2");
+        assert_eq!(format("2", json!("long")), "long");
+    }
+
+    #[test]
+    fn test_invalid_format_spec() {
+        assert!(FormatSpec::parse("x").is_err());
+        assert!(FormatSpec::parse(".").is_err());
+        assert!(FormatSpec::parse("{>3").is_err());
+        assert!(FormatSpec::parse("3x").is_err());
+
+        match FormatSpec::parse(".2")
+            .unwrap()
+            .apply("score", &json!("high"))
+        {
+            Err(PromptError::FormatSpecError { variable, spec, .. }) => {
+                assert_eq!((variable.as_str(), spec.as_str()), ("score", ".2"))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use super::{
+    loading::PromptTemplateData, PromptArgs, PromptError, PromptFromatter, PromptTemplate,
+};
+
+// Prefixes the canonical form hashed by `content_hash`, bumped whenever it changes.
+const CONTENT_HASH_VERSION: &[u8] = b"langchain-rust/prompt-template/v1\n";
+
+/// Struct `PromptHash` is a SHA-256 digest identifying a template or a rendered prompt, e.g.
+/// as a cache key or in audit logs. It's displayed and serialized as 64 lowercase hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PromptHash([u8; 32]);
+
+impl PromptHash {
+    /// Hashes `text` as `hash_rendered` hashes a prompt, that is, the SHA-256 of its UTF-8
+    /// bytes.
+    pub fn of(text: &str) -> Self {
+        Self(Sha256::digest(text.as_bytes()).into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl fmt::Display for PromptHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl FromStr for PromptHash {
+    type Err = PromptError;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        let invalid = || PromptError::OtherError(format!("Invalid prompt hash: {}", hex));
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl Serialize for PromptHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for PromptHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// Feeds the rendered prompt to the digest as it's written.
+struct HashWriter(Sha256);
+
+impl fmt::Write for HashWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.update(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl PromptTemplate {
+    /// Returns a hash of what the template renders: its text, format, variables, partial
+    /// variables, defaults, optional variables, aliases and allowed environment variables.
+    /// The metadata, the resolvers, the sanitizer and the other settings not kept by the
+    /// serialized form are left out.
+    ///
+    /// The hash is the SHA-256 of a versioned prefix followed by the serialized form of the
+    /// template as JSON, without whitespace and with the keys of objects sorted. It's the same
+    /// across runs and platforms, and stays the same across crate versions unless the release
+    /// notes say otherwise: a setting added later is only part of the hash once it's used, so
+    /// existing hashes stay valid, and a change of the canonical form bumps the prefix.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let key = format!("{}:{}", prompt.content_hash(), prompt.hash_rendered(args.clone())?);
+    /// ```
+    pub fn content_hash(&self) -> PromptHash {
+        let mut data =
+            serde_json::to_value(PromptTemplateData::from(self.clone())).unwrap_or(Value::Null);
+        if let Value::Object(fields) = &mut data {
+            fields.remove("metadata");
+        }
+        let mut canonical = String::new();
+        write_canonical(&data, &mut canonical);
+        let mut hasher = Sha256::new();
+        hasher.update(CONTENT_HASH_VERSION);
+        hasher.update(canonical.as_bytes());
+        PromptHash(hasher.finalize().into())
+    }
+
+    /// Returns the hash of the prompt `format` would return, `PromptHash::of` the prompt,
+    /// which is stable across crate versions. The prompt is hashed while it's rendered,
+    /// without being built, unless the template compresses it.
+    pub fn hash_rendered(&self, input_variables: PromptArgs) -> Result<PromptHash, PromptError> {
+        let mut writer = HashWriter(Sha256::new());
+        self.format_into(input_variables, &mut writer)?;
+        Ok(PromptHash(writer.0.finalize().into()))
+    }
+}
+
+// Writes `value` as JSON without whitespace, with the keys of objects sorted whatever the
+// order of the map.
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(fields) => {
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(&fields[key], out);
+            }
+            out.push('}');
+        }
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{prompt::TemplateFormat, prompt_args, template_fstring};
+
+    #[test]
+    fn test_content_hash() {
+        let prompt = template_fstring!("{persona}: {question}", "persona", "question");
+        let hash = prompt.content_hash();
+        assert_eq!(hash, prompt.clone().content_hash());
+        assert_eq!(
+            hash,
+            prompt
+                .clone()
+                .with_metadata(crate::prompt::PromptMetadata::new().with_name("qa"))
+                .content_hash()
+        );
+
+        let changed = [
+            template_fstring!("{persona} - {question}", "persona", "question"),
+            prompt
+                .partial(prompt_args! { "persona" => "a pirate" })
+                .into(),
+            prompt
+                .clone()
+                .with_defaults(HashMap::from([("persona".into(), "a chef".into())])),
+            PromptTemplate::new_unchecked(
+                prompt.template(),
+                prompt.variables(),
+                TemplateFormat::Jinja2,
+            ),
+        ];
+        for other in changed {
+            assert_ne!(other.content_hash(), hash);
+        }
+
+        let serialized = serde_json::to_string(&hash).unwrap();
+        assert_eq!(serialized.len(), 66);
+        assert_eq!(
+            serde_json::from_str::<PromptHash>(&serialized).unwrap(),
+            hash
+        );
+        assert!("abc".parse::<PromptHash>().is_err());
+    }
+
+    #[test]
+    fn test_hash_rendered() {
+        let prompt = template_fstring!("Hello {name}", "name");
+        assert_eq!(
+            prompt
+                .hash_rendered(prompt_args! { "name" => "Ana" })
+                .unwrap(),
+            PromptHash::of("Hello Ana")
+        );
+        assert_eq!(
+            PromptHash::of("").to_string(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert!(prompt.hash_rendered(prompt_args! {}).is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! Pulls prompts from the LangChain Hub, like `hub.pull` in Python LangChain. Requires the
+//! `hub` feature.
+//!
+//! # Usage
+//! ```rust,ignore
+//! let prompt = hub::pull("rlm/rag-prompt").await?;
+//! let messages = prompt.to_prompt_value(prompt_args! {
+//!     "context" => context,
+//!     "question" => question,
+//! })?;
+//! ```
+
+use std::sync::Arc;
+
+use reqwest::Client;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::schemas::messages::{Message, MessageType};
+
+use super::{
+    loading::PromptTemplateData, ChatPromptTemplate, MessageFormatterStruct, MessagePromptTemplate,
+    PromptError, PromptFromatter, PromptTemplate,
+};
+
+const DEFAULT_API_URL: &str = "https://api.hub.langchain.com";
+
+#[derive(Error, Debug)]
+pub enum HubError {
+    #[error("Invalid hub handle {0}, expected owner/repo or owner/repo:commit")]
+    InvalidHandle(String),
+
+    #[error("Request error: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    #[error("Hub API error {status}: {message}")]
+    ApiError { status: u16, message: String },
+
+    #[error("Unsupported object type: {0}")]
+    UnsupportedObjectType(String),
+
+    #[error("Invalid manifest: {0}")]
+    InvalidManifest(String),
+
+    #[error(transparent)]
+    PromptError(#[from] PromptError),
+}
+
+/// Pulls the prompt of a `owner/repo` or `owner/repo:commit` handle, the latest commit if
+/// none is given. The API URL and key are read from the `LANGCHAIN_HUB_API_URL` and
+/// `LANGCHAIN_API_KEY` environment variables.
+pub async fn pull(handle: &str) -> Result<Arc<dyn PromptFromatter>, HubError> {
+    HubClient::new().pull(handle).await
+}
+
+/// Like `pull`, sending the request with a preconfigured client, e.g. with a proxy.
+pub async fn pull_with_client(
+    client: Client,
+    handle: &str,
+) -> Result<Arc<dyn PromptFromatter>, HubError> {
+    HubClient::new().with_client(client).pull(handle).await
+}
+
+/// Struct `HubClient` pulls prompts from a LangChain Hub API.
+#[derive(Clone)]
+pub struct HubClient {
+    client: Client,
+    api_url: String,
+    api_key: Option<String>,
+}
+
+impl Default for HubClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HubClient {
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+            api_url: std::env::var("LANGCHAIN_HUB_API_URL")
+                .unwrap_or_else(|_| DEFAULT_API_URL.to_string()),
+            api_key: std::env::var("LANGCHAIN_API_KEY").ok(),
+        }
+    }
+
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_api_url<S: Into<String>>(mut self, api_url: S) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    pub fn with_api_key<S: Into<String>>(mut self, api_key: S) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Pulls the prompt of a `owner/repo` or `owner/repo:commit` handle.
+    pub async fn pull(&self, handle: &str) -> Result<Arc<dyn PromptFromatter>, HubError> {
+        let (owner, repo, commit) = parse_handle(handle)?;
+        let url = format!(
+            "{}/commits/{}/{}/{}",
+            self.api_url.trim_end_matches('/'),
+            owner,
+            repo,
+            commit.unwrap_or("latest")
+        );
+
+        let mut request = self.client.get(url);
+        if let Some(api_key) = &self.api_key {
+            request = request.header("x-api-key", api_key);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(HubError::ApiError {
+                status: status.as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+
+        let body: Value = response.json().await?;
+        let manifest = body
+            .get("manifest")
+            .ok_or_else(|| HubError::InvalidManifest("missing manifest".to_string()))?;
+        from_manifest(manifest)
+    }
+}
+
+fn parse_handle(handle: &str) -> Result<(&str, &str, Option<&str>), HubError> {
+    let (path, commit) = match handle.split_once(':') {
+        Some((path, commit)) => (path, Some(commit)),
+        None => (handle, None),
+    };
+    match path.split_once('/') {
+        Some((owner, repo))
+            if !owner.is_empty()
+                && !repo.is_empty()
+                && !repo.contains('/')
+                && commit != Some("") =>
+        {
+            Ok((owner, repo, commit))
+        }
+        _ => Err(HubError::InvalidHandle(handle.to_string())),
+    }
+}
+
+/// Maps a prompt in the LangChain serialization format onto a `PromptTemplate` or a
+/// `ChatPromptTemplate`.
+///
+/// # Errors
+/// Returns `HubError::UnsupportedObjectType` for prompts of any other kind.
+pub fn from_manifest(manifest: &Value) -> Result<Arc<dyn PromptFromatter>, HubError> {
+    match object_type(manifest)?.as_str() {
+        "PromptTemplate" => Ok(Arc::new(prompt_template(manifest)?)),
+        "ChatPromptTemplate" => Ok(Arc::new(chat_prompt_template(manifest)?)),
+        _ => Err(HubError::UnsupportedObjectType(object_path(manifest))),
+    }
+}
+
+fn prompt_template(object: &Value) -> Result<PromptTemplate, HubError> {
+    let data: PromptTemplateData = serde_json::from_value(kwargs(object)?.clone())
+        .map_err(|e| HubError::InvalidManifest(e.to_string()))?;
+    Ok(data.try_into()?)
+}
+
+fn chat_prompt_template(object: &Value) -> Result<ChatPromptTemplate, HubError> {
+    let messages = kwargs(object)?
+        .get("messages")
+        .and_then(Value::as_array)
+        .ok_or_else(|| HubError::InvalidManifest("missing messages".to_string()))?;
+
+    let mut prompt = ChatPromptTemplate::new(MessageFormatterStruct::new());
+    for message in messages {
+        let kwargs = kwargs(message)?;
+        prompt = match object_type(message)?.as_str() {
+            "SystemMessagePromptTemplate" => prompt.with_template(MessagePromptTemplate::new(
+                MessageType::SystemMessage,
+                nested_prompt(kwargs)?,
+            )),
+            "HumanMessagePromptTemplate" => prompt.with_template(MessagePromptTemplate::new(
+                MessageType::HumanMessage,
+                nested_prompt(kwargs)?,
+            )),
+            "AIMessagePromptTemplate" => prompt.with_template(MessagePromptTemplate::new(
+                MessageType::AIMessage,
+                nested_prompt(kwargs)?,
+            )),
+            "SystemMessage" => prompt.with_message(Message::new_system_message(content(kwargs)?)),
+            "HumanMessage" => prompt.with_message(Message::new_human_message(content(kwargs)?)),
+            "AIMessage" => prompt.with_message(Message::new_ai_message(content(kwargs)?)),
+            "MessagesPlaceholder" => {
+                let variable_name = kwargs
+                    .get("variable_name")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| HubError::InvalidManifest("missing variable_name".into()))?;
+                if kwargs.get("optional").and_then(Value::as_bool) == Some(true) {
+                    prompt.with_optional_messages_placeholder(variable_name)
+                } else {
+                    prompt.with_messages_placeholder(variable_name)
+                }
+            }
+            _ => return Err(HubError::UnsupportedObjectType(object_path(message))),
+        };
+    }
+    Ok(prompt)
+}
+
+// The `prompt` of a message template, which must be a single text `PromptTemplate`.
+fn nested_prompt(kwargs: &Value) -> Result<PromptTemplate, HubError> {
+    let prompt = kwargs
+        .get("prompt")
+        .ok_or_else(|| HubError::InvalidManifest("missing prompt".to_string()))?;
+    if object_type(prompt)? != "PromptTemplate" {
+        return Err(HubError::UnsupportedObjectType(object_path(prompt)));
+    }
+    prompt_template(prompt)
+}
+
+fn content(kwargs: &Value) -> Result<&str, HubError> {
+    kwargs
+        .get("content")
+        .and_then(Value::as_str)
+        .ok_or_else(|| HubError::InvalidManifest("missing text content".to_string()))
+}
+
+// The class name of a serialized constructor, e.g. `PromptTemplate`.
+fn object_type(object: &Value) -> Result<String, HubError> {
+    if object.get("type").and_then(Value::as_str) != Some("constructor") {
+        return Err(HubError::UnsupportedObjectType(object_path(object)));
+    }
+    object
+        .get("id")
+        .and_then(Value::as_array)
+        .and_then(|id| id.last())
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| HubError::InvalidManifest("missing object id".to_string()))
+}
+
+fn object_path(object: &Value) -> String {
+    match object.get("id").and_then(Value::as_array) {
+        Some(id) => id
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<&str>>()
+            .join("."),
+        None => object
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown")
+            .to_string(),
+    }
+}
+
+fn kwargs(object: &Value) -> Result<&Value, HubError> {
+    object
+        .get("kwargs")
+        .ok_or_else(|| HubError::InvalidManifest("missing kwargs".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::prompt_args;
+
+    fn prompt_manifest(template: &str, input_variables: &[&str]) -> Value {
+        json!({
+            "lc": 1,
+            "type": "constructor",
+            "id": ["langchain", "prompts", "prompt", "PromptTemplate"],
+            "kwargs": {
+                "input_variables": input_variables,
+                "template": template,
+                "template_format": "f-string",
+            }
+        })
+    }
+
+    fn rag_manifest() -> Value {
+        json!({
+            "lc": 1,
+            "type": "constructor",
+            "id": ["langchain", "prompts", "chat", "ChatPromptTemplate"],
+            "kwargs": {
+                "input_variables": ["context", "question"],
+                "messages": [
+                    {
+                        "lc": 1,
+                        "type": "constructor",
+                        "id": ["langchain", "prompts", "chat", "SystemMessagePromptTemplate"],
+                        "kwargs": { "prompt": prompt_manifest("Use this context: {context}", &["context"]) }
+                    },
+                    {
+                        "lc": 1,
+                        "type": "constructor",
+                        "id": ["langchain_core", "prompts", "chat", "MessagesPlaceholder"],
+                        "kwargs": { "variable_name": "history", "optional": true }
+                    },
+                    {
+                        "lc": 1,
+                        "type": "constructor",
+                        "id": ["langchain", "prompts", "chat", "HumanMessagePromptTemplate"],
+                        "kwargs": { "prompt": prompt_manifest("{question}", &["question"]) }
+                    }
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_handle() {
+        assert_eq!(
+            parse_handle("rlm/rag-prompt").unwrap(),
+            ("rlm", "rag-prompt", None)
+        );
+        assert_eq!(
+            parse_handle("rlm/rag-prompt:50442af1").unwrap(),
+            ("rlm", "rag-prompt", Some("50442af1"))
+        );
+        for handle in ["rag-prompt", "rlm/", "rlm/rag/prompt", "rlm/rag-prompt:"] {
+            assert!(matches!(
+                parse_handle(handle),
+                Err(HubError::InvalidHandle(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_from_manifest() {
+        let prompt = from_manifest(&rag_manifest()).unwrap();
+        assert_eq!(prompt.variables(), vec!["context", "question"]);
+        let messages = prompt
+            .to_prompt_value(
+                prompt_args! { "context" => "Paris is in France", "question" => "Where is Paris?" },
+            )
+            .unwrap()
+            .to_chat_messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].message_type, MessageType::SystemMessage);
+        assert_eq!(messages[0].content, "Use this context: Paris is in France");
+        assert_eq!(messages[1].content, "Where is Paris?");
+
+        let prompt = from_manifest(&prompt_manifest("Hello {name}", &["name"])).unwrap();
+        assert_eq!(
+            prompt.format(prompt_args! { "name" => "Luis" }).unwrap(),
+            "Hello Luis"
+        );
+
+        let mut manifest = prompt_manifest("Hello {name}", &["name"]);
+        manifest["id"] = json!(["langchain", "prompts", "few_shot", "FewShotPromptTemplate"]);
+        match from_manifest(&manifest) {
+            Err(e @ HubError::UnsupportedObjectType(_)) => assert_eq!(
+                e.to_string(),
+                "Unsupported object type: langchain.prompts.few_shot.FewShotPromptTemplate"
+            ),
+            _ => panic!("expected an unsupported object type error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pull_commit() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/commits/rlm/rag-prompt/50442af1")
+            .match_header("x-api-key", "key")
+            .with_status(200)
+            .with_body(json!({ "commit_hash": "50442af1", "manifest": rag_manifest() }).to_string())
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/commits/rlm/missing/latest")
+            .with_status(404)
+            .with_body("not found")
+            .create_async()
+            .await;
+
+        let client = HubClient::new()
+            .with_api_url(server.url())
+            .with_api_key("key");
+        let prompt = client.pull("rlm/rag-prompt:50442af1").await.unwrap();
+        assert_eq!(prompt.variables(), vec!["context", "question"]);
+        mock.assert_async().await;
+
+        let result = client.pull("rlm/missing").await;
+        assert!(matches!(
+            result,
+            Err(HubError::ApiError { status: 404, .. })
+        ));
+    }
+}
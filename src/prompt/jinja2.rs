@@ -0,0 +1,348 @@
+use std::{fmt, sync::Arc};
+
+use minijinja::{value::Rest, Environment, ErrorKind, UndefinedBehavior, Value};
+
+use serde_json::Value as JsonValue;
+
+use super::{
+    parser, prompt::resolve_value, text_writer::TextWriter, PromptArgs, PromptError,
+    TemplateFilter, TemplateLoader,
+};
+
+/// Renders a Jinja2 template with minijinja into `writer`, as it's rendered, evaluating control
+/// flow and filters. Included templates are loaded from `loader`, and the custom `filters` are
+/// added to the built-in ones.
+pub(crate) fn render(
+    template: &str,
+    input_variables: &PromptArgs,
+    loader: Option<&Arc<dyn TemplateLoader>>,
+    filters: &[(String, Arc<dyn TemplateFilter>)],
+    writer: &mut dyn fmt::Write,
+) -> Result<(), PromptError> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_keep_trailing_newline(true);
+    for (name, filter) in filters {
+        let (name, filter) = (name.clone(), filter.clone());
+        env.add_filter(
+            name.clone(),
+            move |value: Value, args: Rest<Value>| -> Result<Value, minijinja::Error> {
+                let to_json = |value: &Value| {
+                    serde_json::to_value(value).map_err(|e| {
+                        minijinja::Error::new(ErrorKind::BadSerialization, e.to_string())
+                    })
+                };
+                let args = args.iter().map(to_json).collect::<Result<Vec<_>, _>>()?;
+                let result = filter.apply(&to_json(&value)?, &args).map_err(|e| {
+                    minijinja::Error::new(
+                        ErrorKind::InvalidOperation,
+                        format!("filter `{}` failed: {}", name, e),
+                    )
+                })?;
+                Ok(Value::from_serialize(&result))
+            },
+        );
+    }
+    if let Some(loader) = loader {
+        let loader = loader.clone();
+        env.set_loader(move |name| {
+            loader.load(name).map_err(|e| {
+                minijinja::Error::new(ErrorKind::InvalidOperation, "failed to load template")
+                    .with_source(e)
+            })
+        });
+    }
+
+    let tmpl = env
+        .template_from_str(template)
+        .map_err(|e| to_prompt_error(template, e))?;
+    check_loops(template, input_variables)?;
+
+    tmpl.render_captured_to(input_variables, TextWriter::new(writer))
+        .map(|_| ())
+        .map_err(|e| {
+            if e.kind() == ErrorKind::WriteFailure {
+                return PromptError::WriteError(fmt::Error);
+            }
+            if e.kind() == ErrorKind::UndefinedError {
+                let mut undeclared = tmpl
+                    .undeclared_variables(false)
+                    .into_iter()
+                    .filter(|v| !input_variables.contains_key(v))
+                    .collect::<Vec<_>>();
+                undeclared.sort();
+                if let Some(variable) = undeclared.into_iter().next() {
+                    return PromptError::MissingVariable(variable);
+                }
+            }
+            to_prompt_error(template, e)
+        })
+}
+
+// Checks the sequences of the loops over input variables, so looping over a scalar names
+// the variable rather than failing in the engine. Sequences of nested loops, like
+// `doc.sections` in `{% for doc in docs %}{% for section in doc.sections %}`, are left to it.
+fn check_loops(template: &str, input_variables: &PromptArgs) -> Result<(), PromptError> {
+    let mut loops: Vec<Vec<&str>> = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{%") {
+        let Some(end) = rest[start..].find("%}").map(|end| end + start) else {
+            break;
+        };
+        let tag =
+            rest[start + 2..end].trim_matches(|c: char| c.is_whitespace() || c == '-' || c == '+');
+        rest = &rest[end + 2..];
+
+        if tag == "endfor" {
+            loops.pop();
+            continue;
+        }
+        let Some((targets, sequence)) = parser::parse_jinja2_loop(tag) else {
+            continue;
+        };
+        let root = sequence.split('.').next().unwrap_or_default();
+        if !loops.iter().flatten().any(|target| *target == root) {
+            let found = match resolve_value(input_variables, sequence)? {
+                Some(JsonValue::Null) => Some("null"),
+                Some(JsonValue::Bool(_)) => Some("a boolean"),
+                Some(JsonValue::Number(_)) => Some("a number"),
+                _ => None,
+            };
+            if let Some(found) = found {
+                return Err(PromptError::NotIterable {
+                    variable: sequence.to_string(),
+                    found: found.to_string(),
+                });
+            }
+        }
+        loops.push(targets);
+    }
+    Ok(())
+}
+
+/// Returns the variables of the templates included by `template`, directly or not.
+///
+/// # Errors
+/// Returns `PromptError::MissingInclude` if the loader has no template for a referenced name.
+pub(crate) fn included_variables(
+    template: &str,
+    loader: &dyn TemplateLoader,
+) -> Result<Vec<String>, PromptError> {
+    let mut variables = Vec::new();
+    let mut visited = Vec::new();
+    let mut queue: Vec<(String, String)> = referenced_templates(template)
+        .into_iter()
+        .map(|name| (name, "the prompt".to_string()))
+        .collect();
+    while let Some((name, included_from)) = queue.pop() {
+        if visited.contains(&name) {
+            continue;
+        }
+        let source = loader
+            .load(&name)?
+            .ok_or_else(|| PromptError::MissingInclude {
+                name: name.clone(),
+                included_from,
+            })?;
+        for variable in parser::parse_jinja2_variables(&source)? {
+            if !variables.contains(&variable) {
+                variables.push(variable);
+            }
+        }
+        queue.extend(
+            referenced_templates(&source)
+                .into_iter()
+                .map(|included| (included, name.clone())),
+        );
+        visited.push(name);
+    }
+    Ok(variables)
+}
+
+// Finds the names given as string literals to `include`, `extends`, `import` and `from`
+// tags. Includes marked `ignore missing` are skipped.
+fn referenced_templates(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{%") {
+        let Some(end) = rest[start..].find("%}").map(|end| end + start) else {
+            break;
+        };
+        let tag =
+            rest[start + 2..end].trim_matches(|c: char| c.is_whitespace() || c == '-' || c == '+');
+        rest = &rest[end + 2..];
+
+        let keyword = tag.split_whitespace().next().unwrap_or_default();
+        if !["include", "extends", "import", "from"].contains(&keyword)
+            || tag.contains("ignore missing")
+        {
+            continue;
+        }
+        let Some(quote_start) = tag.find(['"', '\'']) else {
+            continue;
+        };
+        let quote = &tag[quote_start..quote_start + 1];
+        if let Some(length) = tag[quote_start + 1..].find(quote) {
+            names.push(tag[quote_start + 1..quote_start + 1 + length].to_string());
+        }
+    }
+    names
+}
+
+fn to_prompt_error(template: &str, error: minijinja::Error) -> PromptError {
+    let message = error.detail().map(str::to_string).unwrap_or_default();
+    if error.kind() == ErrorKind::SyntaxError {
+        let range = error.range().unwrap_or(0..0);
+        return PromptError::invalid_template(
+            template,
+            format!("{}: {}", error.kind(), message),
+            range,
+        );
+    }
+    let location = match (error.line(), error.range()) {
+        (Some(line), Some(range)) => {
+            let line_start = template[..range.start].rfind('\n').map_or(0, |p| p + 1);
+            format!(" (line {}, column {})", line, range.start - line_start + 1)
+        }
+        (Some(line), None) => format!(" (line {})", line),
+        _ => String::new(),
+    };
+    if message.is_empty() {
+        PromptError::RenderError(format!("{}{}", error.kind(), location))
+    } else {
+        PromptError::RenderError(format!("{}: {}{}", error.kind(), message, location))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        prompt::{PromptError, PromptFromatter, PromptTemplate, TemplateFormat},
+        prompt_args, template_jinja2,
+    };
+
+    #[test]
+    fn test_jinja2_control_flow() {
+        let template = template_jinja2!(
+            "{% if formal %}Dear {{ name | upper }}{% else %}Hi {{ name }}{% endif %}\n{% for item in items %}- {{ item }}\n{% endfor %}",
+            "formal",
+            "name",
+            "items"
+        );
+
+        let result = template
+            .format(prompt_args! {
+                "formal" => true,
+                "name" => "luis",
+                "items" => vec!["a", "b"],
+            })
+            .unwrap();
+        assert_eq!(result, "Dear LUIS\n- a\n- b\n");
+    }
+
+    #[test]
+    fn test_jinja2_loops() {
+        let template = PromptTemplate::from_template(
+            "{% for doc in documents %}{{ loop.index }}. {{ doc.title }}\n\
+             {% for tag in doc.tags %}  #{{ tag }}\n{% endfor %}\
+             {% else %}No documents\n{% endfor %}",
+            TemplateFormat::Jinja2,
+        )
+        .unwrap();
+        assert_eq!(template.variables(), vec!["documents"]);
+
+        let documents = json!([
+            {"title": "Intro", "tags": ["a", "b"]},
+            {"title": "Usage", "tags": []},
+        ]);
+        assert_eq!(
+            template
+                .format(prompt_args! { "documents" => documents })
+                .unwrap(),
+            "1. Intro\n  #a\n  #b\n2. Usage\n"
+        );
+        assert_eq!(
+            template
+                .format(prompt_args! { "documents" => json!([]) })
+                .unwrap(),
+            "No documents\n"
+        );
+
+        match template.format(prompt_args! { "documents" => 3 }) {
+            Err(PromptError::NotIterable { variable, found }) => {
+                assert_eq!(
+                    (variable.as_str(), found.as_str()),
+                    ("documents", "a number")
+                )
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(matches!(
+            template.format(prompt_args! {}),
+            Err(PromptError::InvalidInput(e)) if e.missing == vec!["documents"]
+        ));
+
+        let template = template_jinja2!("{% for doc in docs.items %}{{ doc }}{% endfor %}", "docs");
+        assert!(matches!(
+            template.format(prompt_args! { "docs" => json!({"items": null}) }),
+            Err(PromptError::NotIterable { variable, .. }) if variable == "docs.items"
+        ));
+    }
+
+    #[test]
+    fn test_jinja2_undefined_variable() {
+        let template = PromptTemplate::new_unchecked(
+            "Hello {{ name }} from {{ place }}".into(),
+            vec!["name".into()],
+            TemplateFormat::Jinja2,
+        );
+        let result = template.format(prompt_args! { "name" => "luis" });
+        assert!(matches!(result, Err(PromptError::MissingVariable(v)) if v == "place"));
+    }
+
+    #[test]
+    fn test_jinja2_syntax_error_location() {
+        let template = template_jinja2!("Hello\n{% if name %}{{ name }}", "name");
+        let result = template.format(prompt_args! { "name" => "luis" });
+        assert!(matches!(result, Err(PromptError::InvalidTemplate { .. })));
+
+        let template = template_jinja2!("Hello\n{{ name + 1 }}", "name");
+        let result = template.format(prompt_args! { "name" => "luis" });
+        match result {
+            Err(PromptError::RenderError(message)) => {
+                assert!(message.contains("line 2, column 4"), "{}", message)
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_jinja2_format_partial() {
+        let template = template_jinja2!(
+            "{{ context }}{% if question %} / {{ question | upper }}{% endif %}",
+            "context",
+            "question"
+        );
+        let partial = template
+            .format_partial(prompt_args! { "context" => "{{ not_a_var }}" })
+            .unwrap();
+        assert_eq!(partial.variables(), vec!["question"]);
+        assert_eq!(
+            partial
+                .format(prompt_args! { "question" => "why?" })
+                .unwrap(),
+            "{{ not_a_var }} / WHY?"
+        );
+
+        let partial = template
+            .format_partial(prompt_args! { "question" => "why?" })
+            .unwrap();
+        assert_eq!(partial.variables(), vec!["context"]);
+        assert_eq!(
+            partial.format(prompt_args! { "context" => "ctx" }).unwrap(),
+            "ctx / WHY?"
+        );
+    }
+}
@@ -0,0 +1,474 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde_json::{Map, Value};
+
+use crate::schemas::messages::{Message, MessageType};
+
+use super::{
+    loading::{file_error, value_into_prompt},
+    ChatPromptTemplate, FewShotPromptTemplate, MessageFormatterStruct, MessagePromptTemplate,
+    PromptArgs, PromptError, PromptFromatter, PromptMetadata, PromptTemplate, TemplateFormat,
+};
+
+const PROMPT_TYPES: [&str; 3] = ["prompt", "few_shot", "chat"];
+
+// Fields Python LangChain writes for every prompt, which have no counterpart here or are
+// derived from the template, accepted whatever their value.
+const IGNORED_FIELDS: [&str; 4] = [
+    "_type",
+    "input_types",
+    "input_variables",
+    "validate_template",
+];
+
+/// Enum `LangChainPrompt` is a prompt saved by Python LangChain, of the kind named by its
+/// `_type`.
+pub enum LangChainPrompt {
+    Prompt(Box<PromptTemplate>),
+    FewShot(Box<FewShotPromptTemplate>),
+    Chat(ChatPromptTemplate),
+}
+
+impl LangChainPrompt {
+    pub fn into_formatter(self) -> Arc<dyn PromptFromatter> {
+        match self {
+            LangChainPrompt::Prompt(prompt) => Arc::new(*prompt),
+            LangChainPrompt::FewShot(prompt) => Arc::new(*prompt),
+            LangChainPrompt::Chat(prompt) => Arc::new(prompt),
+        }
+    }
+}
+
+/// Loads a prompt saved by Python LangChain's `prompt.save()`, from the path of a `.json`,
+/// `.yaml` or `.yml` file, or from JSON content starting with `{`. Its `_type` picks the
+/// prompt built:
+///
+/// - `prompt` builds a `PromptTemplate`, whose template can be kept in a `template_path`
+///   file.
+/// - `few_shot` builds a `FewShotPromptTemplate`. Its `examples` can be a path to a JSON or
+///   YAML list, and its `example_prompt`, `prefix` and `suffix` can be kept in files too,
+///   through `example_prompt_path`, `prefix_path` and `suffix_path`.
+/// - `chat` builds a `ChatPromptTemplate`. Python LangChain doesn't save the role of message
+///   templates, so a message template without a `role` is only accepted alone, as a human
+///   message, like Python LangChain loads it.
+///
+/// Paths are relative to the prompt file, or to the working directory for JSON content.
+///
+/// # Errors
+/// Returns `PromptError::UnsupportedPromptType` for any other `_type`, and
+/// `PromptError::UnsupportedPromptFields` listing the fields set to a value that can't be
+/// mapped, like an `output_parser` or an `example_selector`. Errors of a file are wrapped in
+/// `PromptError::FileError`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = load_langchain_prompt("prompts/few_shot.json")?.into_formatter();
+/// let result = prompt.format(prompt_args! { "adjective" => "big" })?;
+/// ```
+pub fn load_langchain_prompt<S: AsRef<str>>(
+    path_or_json: S,
+) -> Result<LangChainPrompt, PromptError> {
+    let source = path_or_json.as_ref();
+    if source.trim_start().starts_with('{') {
+        return prompt_from_config(serde_json::from_str(source)?, None);
+    }
+    let path = Path::new(source);
+    read_config(path)
+        .and_then(|config| prompt_from_config(config, path.parent()))
+        .map_err(|e| file_error(path, e))
+}
+
+fn read_config(path: &Path) -> Result<Value, PromptError> {
+    let content = fs::read_to_string(path)?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => Ok(serde_json::from_str(&content)?),
+        Some("yaml" | "yml") => Ok(serde_yaml::from_str(&content)?),
+        _ => Err(PromptError::OtherError(format!(
+            "Unsupported prompt file: {}, expected a json or yaml file",
+            path.display()
+        ))),
+    }
+}
+
+fn prompt_from_config(config: Value, dir: Option<&Path>) -> Result<LangChainPrompt, PromptError> {
+    let Value::Object(config) = config else {
+        return Err(PromptError::OtherError(
+            "A prompt must be a JSON or YAML object".to_string(),
+        ));
+    };
+    let prompt_type = config
+        .get("_type")
+        .and_then(Value::as_str)
+        .unwrap_or("prompt");
+    match prompt_type {
+        "prompt" => Ok(LangChainPrompt::Prompt(Box::new(prompt_template(
+            config, dir,
+        )?))),
+        "few_shot" => Ok(LangChainPrompt::FewShot(Box::new(few_shot_template(
+            config, dir,
+        )?))),
+        "chat" => Ok(LangChainPrompt::Chat(chat_template(config, dir)?)),
+        _ => Err(PromptError::UnsupportedPromptType {
+            found: prompt_type.to_string(),
+            expected: PROMPT_TYPES.iter().map(|t| t.to_string()).collect(),
+        }),
+    }
+}
+
+fn prompt_template(
+    mut config: Map<String, Value>,
+    dir: Option<&Path>,
+) -> Result<PromptTemplate, PromptError> {
+    check_fields(
+        &config,
+        "prompt",
+        &[
+            "template",
+            "template_path",
+            "template_format",
+            "partial_variables",
+            "optional_variables",
+            "name",
+            "tags",
+            "metadata",
+        ],
+    )?;
+    let template = template_text(&mut config, "template", dir)?
+        .ok_or_else(|| missing_field("prompt", "template"))?;
+    let metadata = metadata(&config)?;
+    let mut data = Map::new();
+    data.insert("template".to_string(), template.into());
+    for field in [
+        "input_variables",
+        "template_format",
+        "partial_variables",
+        "optional_variables",
+    ] {
+        if let Some(value) = config.remove(field).filter(|value| !value.is_null()) {
+            data.insert(field.to_string(), value);
+        }
+    }
+    data.insert("metadata".to_string(), serde_json::to_value(metadata)?);
+    value_into_prompt(Value::Object(data))
+}
+
+fn few_shot_template(
+    mut config: Map<String, Value>,
+    dir: Option<&Path>,
+) -> Result<FewShotPromptTemplate, PromptError> {
+    check_fields(
+        &config,
+        "few_shot",
+        &[
+            "examples",
+            "example_prompt",
+            "example_prompt_path",
+            "prefix",
+            "prefix_path",
+            "suffix",
+            "suffix_path",
+            "example_separator",
+            "template_format",
+        ],
+    )?;
+    let format: TemplateFormat = match config.get("template_format") {
+        Some(format) => serde_json::from_value(format.clone())?,
+        None => TemplateFormat::FString,
+    };
+    let example_prompt = match config.remove("example_prompt_path") {
+        Some(Value::String(path)) => {
+            let path = resolve(dir, &path);
+            read_config(&path)
+                .and_then(|config| nested_prompt(config, path.parent(), "example_prompt"))
+                .map_err(|e| file_error(&path, e))?
+        }
+        _ => match config.remove("example_prompt") {
+            Some(example_prompt) => nested_prompt(example_prompt, dir, "example_prompt")?,
+            None => return Err(missing_field("few_shot", "example_prompt")),
+        },
+    };
+    let suffix = template_text(&mut config, "suffix", dir)?
+        .ok_or_else(|| missing_field("few_shot", "suffix"))?;
+    let mut prompt = FewShotPromptTemplate::new(
+        example_prompt,
+        PromptTemplate::from_template(&suffix, format.clone())?,
+    )
+    .with_examples(examples(config.remove("examples"), dir)?);
+    if let Some(prefix) = template_text(&mut config, "prefix", dir)?.filter(|p| !p.is_empty()) {
+        prompt = prompt.with_prefix(PromptTemplate::from_template(&prefix, format)?);
+    }
+    if let Some(separator) = config.get("example_separator").and_then(Value::as_str) {
+        prompt = prompt.with_example_separator(separator);
+    }
+    Ok(prompt)
+}
+
+fn chat_template(
+    config: Map<String, Value>,
+    dir: Option<&Path>,
+) -> Result<ChatPromptTemplate, PromptError> {
+    check_fields(
+        &config,
+        "chat",
+        &["messages", "optional_variables", "name", "tags", "metadata"],
+    )?;
+    let messages = config
+        .get("messages")
+        .and_then(Value::as_array)
+        .ok_or_else(|| missing_field("chat", "messages"))?;
+    let mut prompt =
+        ChatPromptTemplate::new(MessageFormatterStruct::new()).with_metadata(metadata(&config)?);
+    for (index, message) in messages.iter().enumerate() {
+        let field = |name: &str| message.get(name).filter(|value| !value.is_null());
+        prompt = if let Some(variable_name) = field("variable_name").and_then(Value::as_str) {
+            if field("optional").and_then(Value::as_bool) == Some(true) {
+                prompt.with_optional_messages_placeholder(variable_name)
+            } else {
+                prompt.with_messages_placeholder(variable_name)
+            }
+        } else if let Some(nested) = field("prompt") {
+            let message_type = match field("role").and_then(Value::as_str) {
+                Some(role) => message_type(role)?,
+                None if messages.len() == 1 => MessageType::HumanMessage,
+                None => {
+                    return Err(PromptError::OtherError(format!(
+                        "Message {} of the chat prompt has no role, which Python LangChain \
+                         doesn't save for message templates, so it can't be told apart from \
+                         the others",
+                        index
+                    )))
+                }
+            };
+            let nested = nested_prompt(nested.clone(), dir, "prompt of a message")?;
+            prompt.with_template(MessagePromptTemplate::new(message_type, nested))
+        } else if let Some(content) = field("content").and_then(Value::as_str) {
+            let message = match message_type(field("type").and_then(Value::as_str).unwrap_or(""))? {
+                MessageType::SystemMessage => Message::new_system_message(content),
+                MessageType::AIMessage => Message::new_ai_message(content),
+                _ => Message::new_human_message(content),
+            };
+            prompt.with_message(message)
+        } else {
+            return Err(PromptError::OtherError(format!(
+                "Message {} of the chat prompt has neither a prompt, a content nor a \
+                 variable_name",
+                index
+            )));
+        };
+    }
+    Ok(prompt)
+}
+
+// Reports the fields outside of `supported` that are set, that is, neither null, false nor
+// empty.
+fn check_fields(
+    config: &Map<String, Value>,
+    prompt_type: &str,
+    supported: &[&str],
+) -> Result<(), PromptError> {
+    let mut fields: Vec<String> = config
+        .iter()
+        .filter(|(field, value)| {
+            !supported.contains(&field.as_str())
+                && !IGNORED_FIELDS.contains(&field.as_str())
+                && !matches!(value, Value::Null | Value::Bool(false))
+                && !value.as_str().is_some_and(str::is_empty)
+                && !value.as_array().is_some_and(Vec::is_empty)
+                && !value.as_object().is_some_and(Map::is_empty)
+        })
+        .map(|(field, _)| field.clone())
+        .collect();
+    if fields.is_empty() {
+        return Ok(());
+    }
+    fields.sort();
+    Err(PromptError::UnsupportedPromptFields {
+        prompt_type: prompt_type.to_string(),
+        fields,
+    })
+}
+
+fn missing_field(prompt_type: &str, field: &str) -> PromptError {
+    PromptError::OtherError(format!("Missing {} in a {} prompt", field, prompt_type))
+}
+
+// The text of `field`, or of the file its `_path` field points to.
+fn template_text(
+    config: &mut Map<String, Value>,
+    field: &str,
+    dir: Option<&Path>,
+) -> Result<Option<String>, PromptError> {
+    if let Some(Value::String(path)) = config.remove(&format!("{}_path", field)) {
+        return Ok(Some(fs::read_to_string(resolve(dir, &path))?));
+    }
+    Ok(config
+        .remove(field)
+        .and_then(|value| value.as_str().map(str::to_string)))
+}
+
+// A prompt nested in `field` of another one, which must be of the `prompt` type.
+fn nested_prompt(
+    config: Value,
+    dir: Option<&Path>,
+    field: &str,
+) -> Result<PromptTemplate, PromptError> {
+    match prompt_from_config(config, dir)? {
+        LangChainPrompt::Prompt(prompt) => Ok(*prompt),
+        _ => Err(PromptError::OtherError(format!(
+            "The {} must be a prompt, not a few_shot or chat prompt",
+            field
+        ))),
+    }
+}
+
+// The examples of a few-shot prompt, listed or in a JSON or YAML file.
+fn examples(examples: Option<Value>, dir: Option<&Path>) -> Result<Vec<PromptArgs>, PromptError> {
+    let examples = match examples {
+        Some(Value::String(path)) => {
+            let path = resolve(dir, &path);
+            read_config(&path).map_err(|e| file_error(&path, e))?
+        }
+        Some(examples) => examples,
+        None => return Ok(Vec::new()),
+    };
+    Ok(serde_json::from_value(examples)?)
+}
+
+fn metadata(config: &Map<String, Value>) -> Result<PromptMetadata, PromptError> {
+    let mut metadata: PromptMetadata = match config.get("metadata") {
+        Some(metadata @ Value::Object(_)) => serde_json::from_value(metadata.clone())?,
+        _ => PromptMetadata::default(),
+    };
+    if let Some(name) = config.get("name").and_then(Value::as_str) {
+        metadata.name.get_or_insert_with(|| name.to_string());
+    }
+    if let Some(tags) = config.get("tags").and_then(Value::as_array) {
+        for tag in tags.iter().filter_map(Value::as_str) {
+            if !metadata.tags.iter().any(|t| t == tag) {
+                metadata.tags.push(tag.to_string());
+            }
+        }
+    }
+    Ok(metadata)
+}
+
+fn message_type(role: &str) -> Result<MessageType, PromptError> {
+    match role {
+        "system" => Ok(MessageType::SystemMessage),
+        "human" | "user" => Ok(MessageType::HumanMessage),
+        "ai" | "assistant" => Ok(MessageType::AIMessage),
+        _ => Err(PromptError::OtherError(format!(
+            "Unsupported message role {}, expected system, human, user, ai or assistant",
+            role
+        ))),
+    }
+}
+
+fn resolve(dir: Option<&Path>, path: &str) -> PathBuf {
+    match dir {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt::MessageFormatter, prompt_args};
+
+    const FIXTURES: &str = "./src/prompt/test_data/langchain";
+
+    fn load_fixture(name: &str) -> LangChainPrompt {
+        load_langchain_prompt(format!("{}/{}", FIXTURES, name)).unwrap()
+    }
+
+    #[test]
+    fn test_load_langchain_prompt() {
+        let LangChainPrompt::Prompt(prompt) = load_fixture("prompt.json") else {
+            panic!("expected a prompt");
+        };
+        assert_eq!(prompt.variables(), vec!["adjective", "content"]);
+        assert_eq!(
+            prompt
+                .format(prompt_args! { "adjective" => "funny", "content" => "chickens" })
+                .unwrap(),
+            "Tell me a funny joke about chickens."
+        );
+
+        let LangChainPrompt::Prompt(prompt) = load_fixture("jinja2_prompt.yaml") else {
+            panic!("expected a prompt");
+        };
+        assert_eq!(prompt.template_format(), &TemplateFormat::Jinja2);
+        assert_eq!(prompt.metadata().name.as_deref(), Some("greeting"));
+        assert_eq!(
+            prompt.format(prompt_args! { "name" => "Ana" }).unwrap(),
+            "Hello Ana!"
+        );
+
+        for fixture in ["few_shot.json", "few_shot_examples_file.yaml"] {
+            let prompt = load_fixture(fixture).into_formatter();
+            assert_eq!(
+                prompt.format(prompt_args! { "adjective" => "big" }).unwrap(),
+                "Give the antonym of every input\n\nInput: happy\nOutput: sad\n\nInput: tall\nOutput: short\n\nInput: big\nOutput:",
+                "{}",
+                fixture
+            );
+        }
+
+        let LangChainPrompt::Chat(prompt) = load_fixture("chat.json") else {
+            panic!("expected a chat prompt");
+        };
+        let messages = prompt
+            .format_messages(prompt_args! { "question" => "Why?" })
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_type, MessageType::HumanMessage);
+        assert_eq!(messages[0].content, "Answer this question: Why?");
+    }
+
+    #[test]
+    fn test_load_langchain_prompt_errors() {
+        assert!(matches!(
+            load_langchain_prompt(r#"{"_type": "llm_chain", "llm": {}}"#),
+            Err(PromptError::UnsupportedPromptType { found, expected })
+                if found == "llm_chain" && expected.len() == 3
+        ));
+        let result = load_langchain_prompt(
+            r#"{
+                "_type": "prompt",
+                "template": "{a}",
+                "output_parser": {"_type": "regex_parser"},
+                "template_engine": "custom",
+                "name": null,
+                "validate_template": true
+            }"#,
+        );
+        match result {
+            Err(PromptError::UnsupportedPromptFields {
+                prompt_type,
+                fields,
+            }) => {
+                assert_eq!(prompt_type, "prompt");
+                assert_eq!(fields, vec!["output_parser", "template_engine"]);
+            }
+            _ => panic!("expected unsupported fields"),
+        }
+        let result = load_langchain_prompt(
+            r#"{"_type": "chat", "messages": [
+                {"prompt": {"_type": "prompt", "template": "You are a bot"}},
+                {"prompt": {"_type": "prompt", "template": "{question}"}}
+            ]}"#,
+        );
+        assert!(result.is_err());
+
+        let missing = format!("{}/missing.json", FIXTURES);
+        assert!(matches!(
+            load_langchain_prompt(&missing),
+            Err(PromptError::FileError { .. })
+        ));
+    }
+}
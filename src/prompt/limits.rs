@@ -0,0 +1,293 @@
+use std::{fmt, sync::Arc};
+
+use super::{HeuristicTokenCounter, PromptError, TokenCounter};
+
+/// What a `VarLimit` measures.
+#[derive(Clone)]
+pub enum LimitUnit {
+    Chars,
+    Tokens(Arc<dyn TokenCounter>),
+}
+
+impl fmt::Debug for LimitUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitUnit::Chars => write!(f, "Chars"),
+            LimitUnit::Tokens(_) => write!(f, "Tokens"),
+        }
+    }
+}
+
+/// How a `VarLimit` shortens a value over its maximum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TruncateStrategy {
+    /// Keeps the start of the value.
+    #[default]
+    End,
+    /// Keeps the end of the value.
+    Start,
+    /// Keeps the start and the end of the value, cutting its middle.
+    Middle,
+    /// Fails with `PromptError::ValueTooLong` instead of truncating.
+    Error,
+}
+
+/// Struct `VarLimit` caps the length of a variable's value, in chars or tokens, see
+/// `PromptTemplate::limit`. The marker, if any, replaces the cut text and counts towards the
+/// maximum.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = template_fstring!("{context}\n\nQuestion: {question}", "context", "question")
+///     .limit("context", VarLimit::chars(8000).truncate_end().marker("…[truncated]"))
+///     .limit("question", VarLimit::tokens(500).with_token_counter(counter).error());
+/// let (prompt, truncations) = prompt.format_with_report(args)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct VarLimit {
+    max: usize,
+    unit: LimitUnit,
+    strategy: TruncateStrategy,
+    marker: Option<String>,
+}
+
+impl VarLimit {
+    /// Limits the value to `max` chars, cutting its end by default.
+    pub fn chars(max: usize) -> Self {
+        Self {
+            max,
+            unit: LimitUnit::Chars,
+            strategy: TruncateStrategy::default(),
+            marker: None,
+        }
+    }
+
+    /// Limits the value to `max` tokens, counted by the default `HeuristicTokenCounter` unless
+    /// another counter is set.
+    pub fn tokens(max: usize) -> Self {
+        Self {
+            unit: LimitUnit::Tokens(Arc::new(HeuristicTokenCounter::default())),
+            ..Self::chars(max)
+        }
+    }
+
+    pub fn with_token_counter<C: TokenCounter + 'static>(mut self, counter: C) -> Self {
+        self.unit = LimitUnit::Tokens(Arc::new(counter));
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: TruncateStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn truncate_end(self) -> Self {
+        self.with_strategy(TruncateStrategy::End)
+    }
+
+    pub fn truncate_start(self) -> Self {
+        self.with_strategy(TruncateStrategy::Start)
+    }
+
+    pub fn truncate_middle(self) -> Self {
+        self.with_strategy(TruncateStrategy::Middle)
+    }
+
+    /// Fails instead of truncating, for values that must never be silently cut.
+    pub fn error(self) -> Self {
+        self.with_strategy(TruncateStrategy::Error)
+    }
+
+    /// Sets the text put in place of the cut part, like `…[truncated]`.
+    pub fn marker<S: Into<String>>(mut self, marker: S) -> Self {
+        self.marker = Some(marker.into());
+        self
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn unit(&self) -> &LimitUnit {
+        &self.unit
+    }
+
+    fn measure(&self, text: &str) -> usize {
+        match &self.unit {
+            LimitUnit::Chars => text.chars().count(),
+            LimitUnit::Tokens(counter) => counter.count_tokens(text),
+        }
+    }
+
+    /// Applies the limit to the value of `variable`, returning the value to substitute and
+    /// the truncation made, if any.
+    ///
+    /// # Errors
+    /// Returns `PromptError::ValueTooLong` if the value is over the limit and the strategy is
+    /// `TruncateStrategy::Error`.
+    pub fn apply(
+        &self,
+        variable: &str,
+        value: &str,
+    ) -> Result<(String, Option<Truncation>), PromptError> {
+        let length = self.measure(value);
+        if length <= self.max {
+            return Ok((value.to_string(), None));
+        }
+
+        let marker = self.marker.as_deref().unwrap_or_default();
+        let budget = self.max.saturating_sub(self.measure(marker));
+        // char boundaries of the value, including its end
+        let boundaries: Vec<usize> = value
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([value.len()])
+            .collect();
+        let truncated = match self.strategy {
+            TruncateStrategy::Error => {
+                return Err(PromptError::ValueTooLong {
+                    variable: variable.to_string(),
+                    length,
+                    max: self.max,
+                })
+            }
+            TruncateStrategy::End => {
+                let end = self.longest(&boundaries, budget, |b| &value[..b]);
+                format!("{}{}", &value[..end], marker)
+            }
+            TruncateStrategy::Start => {
+                let reversed: Vec<usize> = boundaries.iter().rev().copied().collect();
+                let start = self.longest(&reversed, budget, |b| &value[b..]);
+                format!("{}{}", marker, &value[start..])
+            }
+            TruncateStrategy::Middle => {
+                let end = self.longest(&boundaries, budget - budget / 2, |b| &value[..b]);
+                let reversed: Vec<usize> = boundaries
+                    .iter()
+                    .rev()
+                    .copied()
+                    .take_while(|b| *b >= end)
+                    .collect();
+                let start = self.longest(&reversed, budget / 2, |b| &value[b..]);
+                format!("{}{}{}", &value[..end], marker, &value[start..])
+            }
+        };
+        let truncation = Truncation {
+            variable: variable.to_string(),
+            original: length,
+            kept: self.measure(&truncated),
+        };
+        Ok((truncated, Some(truncation)))
+    }
+
+    // Returns the last of the candidate boundaries whose part is within the budget, by
+    // binary search since a part only grows along them.
+    fn longest<'a, F>(&self, candidates: &[usize], budget: usize, part: F) -> usize
+    where
+        F: Fn(usize) -> &'a str,
+    {
+        let within = candidates.partition_point(|b| self.measure(part(*b)) <= budget);
+        candidates[within.saturating_sub(1)]
+    }
+}
+
+/// A value shortened by a `VarLimit`, as reported by `PromptTemplate::format_with_report`.
+/// The lengths are in the unit of the limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncation {
+    pub variable: String,
+    pub original: usize,
+    pub kept: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, template_fstring};
+
+    #[test]
+    fn test_var_limit_strategies() {
+        let value = "abcdefghij";
+        let apply = |limit: VarLimit| limit.apply("context", value).unwrap().0;
+        assert_eq!(apply(VarLimit::chars(10)), value);
+        assert_eq!(apply(VarLimit::chars(4)), "abcd");
+        assert_eq!(apply(VarLimit::chars(6).marker("..")), "abcd..");
+        assert_eq!(
+            apply(VarLimit::chars(6).truncate_start().marker("..")),
+            "..ghij"
+        );
+        assert_eq!(
+            apply(VarLimit::chars(7).truncate_middle().marker("..")),
+            "abc..ij"
+        );
+        assert_eq!(apply(VarLimit::chars(1).marker("...")), "...");
+
+        let (truncated, truncation) = VarLimit::chars(3).apply("context", "ñandú").unwrap();
+        assert_eq!(truncated, "ñan");
+        assert_eq!(
+            truncation,
+            Some(Truncation {
+                variable: "context".into(),
+                original: 5,
+                kept: 3
+            })
+        );
+
+        let words = VarLimit::tokens(3)
+            .with_token_counter(HeuristicTokenCounter::Words)
+            .truncate_middle()
+            .marker("…");
+        assert_eq!(
+            words.apply("context", "one two three four five").unwrap().0,
+            "one … five"
+        );
+
+        match VarLimit::chars(4).error().apply("question", value) {
+            Err(PromptError::ValueTooLong {
+                variable,
+                length,
+                max,
+            }) => assert_eq!((variable.as_str(), length, max), ("question", 10, 4)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_format_with_limits() {
+        let prompt = template_fstring!("{context} | {question}", "context", "question")
+            .limit("context", VarLimit::chars(8).marker("…"))
+            .limit("question", VarLimit::chars(20).error());
+        let args = prompt_args! {
+            "context" => "The quick brown fox",
+            "question" => "What does it do?",
+        };
+        assert_eq!(
+            prompt.format(args.clone()).unwrap(),
+            "The qui… | What does it do?"
+        );
+        let (_, truncations) = prompt.format_with_report(args).unwrap();
+        assert_eq!(
+            truncations,
+            vec![Truncation {
+                variable: "context".into(),
+                original: 19,
+                kept: 8
+            }]
+        );
+
+        let args = prompt_args! { "context" => "", "question" => "x".repeat(21) };
+        assert!(matches!(
+            prompt.format(args),
+            Err(PromptError::ValueTooLong { .. })
+        ));
+
+        // partial values are limited too
+        let partial = prompt.partial(prompt_args! { "context" => "The quick brown fox" });
+        assert_eq!(
+            partial
+                .format(prompt_args! { "question" => "Why?" })
+                .unwrap(),
+            "The qui… | Why?"
+        );
+    }
+}
@@ -0,0 +1,635 @@
+use std::{fmt, ops::Range};
+
+use super::{PromptError, PromptTemplate, Span, TemplateFormat};
+
+/// The kinds of issue `PromptTemplate::lint` reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    /// A placeholder opened but never closed, like `{name`.
+    UnclosedPlaceholder,
+    /// A placeholder without a name, like `{}` or `{{ }}`.
+    EmptyPlaceholder,
+    /// A declared variable that the template never uses.
+    UnusedVariable,
+    /// A placeholder whose variable is neither declared nor bound as a partial variable.
+    UndeclaredVariable,
+    /// Two variable names that differ only by case, `_` or `-`, or by a single character,
+    /// like `user_name` and `username`.
+    SimilarVariables,
+    /// A run of two or more spaces or tabs inside a line, or of more than one blank line.
+    RepeatedWhitespace,
+    /// Syntax of another template format, like `{{ name }}` in an FString template, which
+    /// renders as literal braces.
+    MixedSyntax,
+}
+
+impl LintKind {
+    pub const ALL: [LintKind; 7] = [
+        LintKind::UnclosedPlaceholder,
+        LintKind::EmptyPlaceholder,
+        LintKind::UnusedVariable,
+        LintKind::UndeclaredVariable,
+        LintKind::SimilarVariables,
+        LintKind::RepeatedWhitespace,
+        LintKind::MixedSyntax,
+    ];
+}
+
+/// An issue found by `PromptTemplate::lint`. The span is the part of the template it's
+/// about, empty at the end for declared variables that aren't in the template, and the
+/// snippet its line, underlined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    pub kind: LintKind,
+    pub span: Span,
+    pub message: String,
+    pub snippet: String,
+}
+
+impl LintWarning {
+    fn new<S: Into<String>>(
+        template: &str,
+        kind: LintKind,
+        range: Range<usize>,
+        message: S,
+    ) -> Self {
+        let span = Span::new(template, range);
+        Self {
+            kind,
+            span,
+            message: message.into(),
+            snippet: span.snippet(template),
+        }
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+impl PromptTemplate {
+    /// Checks the template for likely mistakes, without formatting it. Unlike the
+    /// constructors, it keeps going after malformed placeholders, and returns every warning
+    /// sorted by position.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// for warning in prompt.lint() {
+    ///     log::warn!("{:?}: {}", warning.kind, warning);
+    /// }
+    /// ```
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let template = self.template_str();
+        let mut scan = self.scan();
+
+        let known = |name: &str| {
+            self.variable_names().iter().any(|v| v == name)
+                || self.partial_variables().contains_key(name)
+                || self.defaults().contains_key(name)
+        };
+        for (name, span) in &scan.placeholders {
+            if !known(name) {
+                scan.warnings.push(LintWarning::new(
+                    template,
+                    LintKind::UndeclaredVariable,
+                    span.clone(),
+                    format!("Variable `{}` is used but not declared", name),
+                ));
+            }
+        }
+        for variable in self.variable_names() {
+            let used = scan.placeholders.iter().any(|(name, _)| name == variable)
+                || scan.referenced.contains(&variable.as_str());
+            if !used {
+                let span = template.len()..template.len();
+                scan.warnings.push(LintWarning::new(
+                    template,
+                    LintKind::UnusedVariable,
+                    span,
+                    format!("Variable `{}` is declared but never used", variable),
+                ));
+            }
+        }
+
+        let mut names: Vec<&str> = self.variable_names().iter().map(String::as_str).collect();
+        for (name, _) in &scan.placeholders {
+            if !names.contains(name) {
+                names.push(name);
+            }
+        }
+        for (i, first) in names.iter().enumerate() {
+            for second in &names[i + 1..] {
+                if are_similar(first, second) {
+                    let span = scan
+                        .placeholders
+                        .iter()
+                        .find(|(name, _)| name == second)
+                        .map_or(template.len()..template.len(), |(_, span)| span.clone());
+                    scan.warnings.push(LintWarning::new(
+                        template,
+                        LintKind::SimilarVariables,
+                        span,
+                        format!("Variables `{}` and `{}` look alike", first, second),
+                    ));
+                }
+            }
+        }
+
+        let mut text_start = 0;
+        for tag in scan.tags.iter().chain([&(template.len()..template.len())]) {
+            repeated_whitespace(template, text_start..tag.start, &mut scan.warnings);
+            text_start = tag.end;
+        }
+
+        scan.warnings.sort_by_key(|warning| warning.span.start);
+        scan.warnings
+    }
+
+    /// Returns where the variable is used in the template, one span per placeholder.
+    pub fn variable_locations(&self, variable: &str) -> Vec<Span> {
+        let template = self.template_str();
+        self.scan()
+            .placeholders
+            .into_iter()
+            .filter(|(name, _)| *name == variable)
+            .map(|(_, range)| Span::new(template, range))
+            .collect()
+    }
+
+    fn scan(&self) -> Scan<'_> {
+        let template = self.template_str();
+        match self.template_format() {
+            TemplateFormat::FString => scan_fstring(template),
+            TemplateFormat::Custom { open, close } => scan_custom(template, open, close),
+            format => scan_braces(template, format),
+        }
+    }
+
+    /// Lints the template, failing if any warning is of one of `kinds`. The other warnings
+    /// are returned, e.g. to log them.
+    ///
+    /// # Errors
+    /// Returns `PromptError::LintFailed` with the warnings of the denied kinds.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// #[test]
+    /// fn test_prompts_are_clean() {
+    ///     support_prompt().deny(&LintKind::ALL).unwrap();
+    /// }
+    /// ```
+    pub fn deny(&self, kinds: &[LintKind]) -> Result<Vec<LintWarning>, PromptError> {
+        let (denied, allowed): (Vec<_>, Vec<_>) = self
+            .lint()
+            .into_iter()
+            .partition(|warning| kinds.contains(&warning.kind));
+        if denied.is_empty() {
+            Ok(allowed)
+        } else {
+            Err(PromptError::LintFailed(denied))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Scan<'a> {
+    // the top-level variables used, with the span of their tag
+    placeholders: Vec<(&'a str, Range<usize>)>,
+    // the variables only referenced by Jinja2 blocks, like `items` in `{% for x in items %}`,
+    // which aren't inferred as variables but are used
+    referenced: Vec<&'a str>,
+    // the spans of every tag, in order, the text being in between
+    tags: Vec<Range<usize>>,
+    warnings: Vec<LintWarning>,
+}
+
+fn scan_fstring(template: &str) -> Scan<'_> {
+    let mut scan = Scan::default();
+    let mut i = 0;
+    while i < template.len() {
+        let rest = &template[i..];
+        if let Some(body) = rest.strip_prefix("{{") {
+            // `{{ name }}` is escaped braces in an FString template
+            if let Some(end) = body.find("}}").map(|p| i + 2 + p) {
+                if is_variable_path(template[i + 2..end].trim()) {
+                    scan.warnings.push(LintWarning::new(
+                        template,
+                        LintKind::MixedSyntax,
+                        i..end + 2,
+                        "Jinja2 placeholder in an FString template renders as literal braces",
+                    ));
+                    scan.tags.push(i..end + 2);
+                    i = end + 2;
+                    continue;
+                }
+            }
+            i += 2;
+        } else if rest.starts_with("}}") {
+            i += 2;
+        } else if rest.starts_with("{#") && !super::parser::is_block_tag(&rest[2..]) {
+            let Some(end) = rest[2..].find("#}").map(|p| i + 2 + p + 2) else {
+                scan.warnings.push(LintWarning::new(
+                    template,
+                    LintKind::UnclosedPlaceholder,
+                    i..i + 2,
+                    "Comment is never closed",
+                ));
+                break;
+            };
+            scan.tags.push(i..end);
+            i = end;
+        } else if rest.starts_with("{%") {
+            let end = rest.find("%}").map_or(template.len(), |p| i + p + 2);
+            scan.warnings.push(LintWarning::new(
+                template,
+                LintKind::MixedSyntax,
+                i..end,
+                "Jinja2 block in an FString template",
+            ));
+            scan.tags.push(i..end);
+            i = end;
+        } else if let Some(body) = rest.strip_prefix('{') {
+            let close = langchain_rust_fstring::placeholder_end(template, i);
+            let next_open = body.find('{').map(|p| i + 1 + p);
+            let close = match (close, next_open) {
+                // braces in the quoted arguments of filters don't count
+                (Some(close), Some(open)) if open < close && !template[i..close].contains('|') => {
+                    None
+                }
+                (close, _) => close,
+            };
+            let Some(close) = close else {
+                scan.warnings.push(LintWarning::new(
+                    template,
+                    LintKind::UnclosedPlaceholder,
+                    i..i + 1,
+                    "Placeholder is never closed",
+                ));
+                i += 1;
+                continue;
+            };
+            let name = template[i + 1..close].trim();
+            if name
+                .strip_prefix("env:")
+                .is_some_and(super::parser::is_env_name)
+            {
+                scan.tags.push(i..close + 1);
+                i = close + 1;
+                continue;
+            }
+            let name = name.strip_prefix("#if ").map_or(name, str::trim);
+            // drop the format spec or the filters, like `>8` in `{score:>8}`
+            let name = name.split([':', '|']).next().unwrap_or_default().trim_end();
+            if name.is_empty() {
+                scan.warnings.push(LintWarning::new(
+                    template,
+                    LintKind::EmptyPlaceholder,
+                    i..close + 1,
+                    "Placeholder has no name",
+                ));
+            } else if is_variable_path(name) || super::parser::is_index(name) {
+                scan.placeholders.push((root(name), i..close + 1));
+            }
+            scan.tags.push(i..close + 1);
+            i = close + 1;
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    scan
+}
+
+fn scan_custom<'a>(template: &'a str, open: &str, close: &str) -> Scan<'a> {
+    let mut scan = Scan::default();
+    let mut i = 0;
+    while let Some(start) = template[i..].find(open).map(|p| p + i) {
+        let body = start + open.len();
+        if template[body..].starts_with(open) {
+            i = body + open.len();
+            continue;
+        }
+        let Some(end) = template[body..].find(close).map(|p| p + body) else {
+            scan.warnings.push(LintWarning::new(
+                template,
+                LintKind::UnclosedPlaceholder,
+                start..body,
+                "Placeholder is never closed",
+            ));
+            break;
+        };
+        let name = template[body..end].trim();
+        let span = start..end + close.len();
+        if name.is_empty() {
+            scan.warnings.push(LintWarning::new(
+                template,
+                LintKind::EmptyPlaceholder,
+                span.clone(),
+                "Placeholder has no name",
+            ));
+        } else if is_variable_path(name) {
+            scan.placeholders.push((root(name), span.clone()));
+        }
+        i = span.end;
+        scan.tags.push(span);
+    }
+    scan
+}
+
+// Scans Jinja2 and Mustache templates, reporting the variables as their parsers infer them.
+fn scan_braces<'a>(template: &'a str, format: &TemplateFormat) -> Scan<'a> {
+    let mut scan = Scan::default();
+    // names bound by `{% for %}` and `{% set %}` in Jinja2, and the depth of Mustache sections
+    let mut bound: Vec<&str> = Vec::new();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while let Some(start) = template[i..].find('{').map(|p| p + i) {
+        let rest = &template[start..];
+        let (open, close) = match rest.get(..2) {
+            Some("{{") if *format == TemplateFormat::Mustache && rest.starts_with("{{{") => {
+                ("{{{", "}}}")
+            }
+            Some("{{") => ("{{", "}}"),
+            Some("{%") if *format == TemplateFormat::Jinja2 => ("{%", "%}"),
+            Some("{#") if *format == TemplateFormat::Jinja2 => ("{#", "#}"),
+            _ => {
+                // a lone `{name}` is an FString placeholder, which renders as it is
+                if let Some(end) = rest[1..].find('}').map(|p| start + 1 + p) {
+                    if is_variable_path(template[start + 1..end].trim()) {
+                        scan.warnings.push(LintWarning::new(
+                            template,
+                            LintKind::MixedSyntax,
+                            start..end + 1,
+                            "FString placeholder renders as literal braces",
+                        ));
+                    }
+                }
+                i = start + 1;
+                continue;
+            }
+        };
+        let body_start = start + open.len();
+        let Some(end) = template[body_start..].find(close).map(|p| p + body_start) else {
+            scan.warnings.push(LintWarning::new(
+                template,
+                LintKind::UnclosedPlaceholder,
+                start..body_start,
+                format!("`{}` is never closed", open),
+            ));
+            break;
+        };
+        let span = start..end + close.len();
+        let body = template[body_start..end].trim();
+        scan.tags.push(span.clone());
+        i = span.end;
+
+        if body.is_empty() && open != "{%" && open != "{#" {
+            scan.warnings.push(LintWarning::new(
+                template,
+                LintKind::EmptyPlaceholder,
+                span,
+                "Placeholder has no name",
+            ));
+            continue;
+        }
+        match (format, open) {
+            (TemplateFormat::Jinja2, "{%") => {
+                let mut words = body.split_whitespace();
+                match words.next() {
+                    Some("for") => {
+                        bound.extend(
+                            words
+                                .by_ref()
+                                .take_while(|word| *word != "in")
+                                .flat_map(|word| word.split(','))
+                                .filter(|name| !name.is_empty()),
+                        );
+                        scan.referenced.extend(words.next().map(root));
+                    }
+                    Some("if") | Some("elif") => scan.referenced.extend(words.next().map(root)),
+                    Some("set") => bound.extend(words.next().map(root)),
+                    _ => {}
+                }
+            }
+            (TemplateFormat::Jinja2, "{{") => {
+                let name = body
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .next()
+                    .unwrap_or_default();
+                if is_variable_path(name) && !bound.contains(&name) {
+                    scan.placeholders.push((name, span));
+                }
+            }
+            (TemplateFormat::Mustache, _) => {
+                let (sigil, name) = match body.strip_prefix(['#', '^', '/', '!', '>', '&']) {
+                    Some(name) => (body.chars().next(), name.trim()),
+                    None => (None, body),
+                };
+                let name = match sigil {
+                    Some('#') | Some('/') => name.split_whitespace().last().unwrap_or_default(),
+                    _ => name,
+                };
+                let name = root(name);
+                let top_level = depth == 0 && is_variable_path(name);
+                match sigil {
+                    Some('!') | Some('>') => {}
+                    Some('/') => depth = depth.saturating_sub(1),
+                    Some('#') | Some('^') => {
+                        if top_level {
+                            scan.placeholders.push((name, span));
+                        }
+                        depth += 1;
+                    }
+                    _ => {
+                        if top_level && name != "this" && name != "else" {
+                            scan.placeholders.push((name, span));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    scan
+}
+
+fn repeated_whitespace(template: &str, text: Range<usize>, warnings: &mut Vec<LintWarning>) {
+    let bytes = template.as_bytes();
+    let mut i = text.start;
+    while i < text.end {
+        let start = i;
+        if bytes[i] == b'\n' {
+            // count the blank lines following this newline
+            let mut newlines = 0;
+            while i < text.end && matches!(bytes[i], b'\n' | b' ' | b'\t' | b'\r') {
+                newlines += usize::from(bytes[i] == b'\n');
+                i += 1;
+            }
+            if newlines > 2 && i < bytes.len() {
+                warnings.push(LintWarning::new(
+                    template,
+                    LintKind::RepeatedWhitespace,
+                    start..i,
+                    format!("{} blank lines in a row", newlines - 1),
+                ));
+            }
+        } else if matches!(bytes[i], b' ' | b'\t') {
+            while i < text.end && matches!(bytes[i], b' ' | b'\t') {
+                i += 1;
+            }
+            // leading indentation and trailing whitespace are left to `FormatOptions`
+            let inside_line = start > 0
+                && bytes[start - 1] != b'\n'
+                && i < bytes.len()
+                && !matches!(bytes[i], b'\n' | b'\r');
+            if i - start > 1 && inside_line {
+                warnings.push(LintWarning::new(
+                    template,
+                    LintKind::RepeatedWhitespace,
+                    start..i,
+                    format!("{} spaces or tabs in a row", i - start),
+                ));
+            }
+        } else {
+            i += 1;
+        }
+    }
+}
+
+// Whether the names differ only by case, `_` and `-`, or by one character for names long
+// enough that it's unlikely to be on purpose.
+fn are_similar(first: &str, second: &str) -> bool {
+    let normalize = |name: &str| -> Vec<char> {
+        name.chars()
+            .filter(|c| *c != '_' && *c != '-')
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let (first, second) = (normalize(first), normalize(second));
+    first == second || (first.len().min(second.len()) >= 6 && edit_distance(&first, &second) == 1)
+}
+
+fn edit_distance(first: &[char], second: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=second.len()).collect();
+    for (i, a) in first.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in second.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[second.len()]
+}
+
+fn is_variable_path(name: &str) -> bool {
+    super::parser::is_path(name)
+}
+
+fn root(path: &str) -> &str {
+    path.split('.').next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::PromptTemplateBuilder;
+
+    fn prompt(template: &str, variables: &[&str], format: TemplateFormat) -> PromptTemplate {
+        PromptTemplate::new_unchecked(
+            template.to_string(),
+            variables.iter().map(|v| v.to_string()).collect(),
+            format,
+        )
+    }
+
+    fn kinds(warnings: &[LintWarning]) -> Vec<LintKind> {
+        warnings.iter().map(|warning| warning.kind).collect()
+    }
+
+    #[test]
+    fn test_lint_fstring() {
+        let template = "Hi {user_name}, {}  you are {age}. {{ username }} {name";
+        let warnings = prompt(
+            template,
+            &["user_name", "age", "mood"],
+            TemplateFormat::FString,
+        )
+        .lint();
+        assert_eq!(
+            kinds(&warnings),
+            vec![
+                LintKind::EmptyPlaceholder,
+                LintKind::RepeatedWhitespace,
+                LintKind::MixedSyntax,
+                LintKind::UnclosedPlaceholder,
+                LintKind::UnusedVariable,
+            ]
+        );
+        assert_eq!(&template[warnings[0].span.range()], "{}");
+        assert_eq!(&template[warnings[2].span.range()], "{{ username }}");
+        assert_eq!(warnings[3].span.range(), 50..51);
+        assert!(warnings[4].message.contains("`mood`"));
+
+        let warnings = prompt(
+            "{user_name} {username} {userName}",
+            &[],
+            TemplateFormat::FString,
+        )
+        .lint();
+        assert_eq!(
+            kinds(&warnings),
+            vec![
+                LintKind::UndeclaredVariable,
+                LintKind::UndeclaredVariable,
+                LintKind::SimilarVariables,
+                LintKind::UndeclaredVariable,
+                LintKind::SimilarVariables,
+                LintKind::SimilarVariables,
+            ]
+        );
+        assert_eq!(warnings[2].span.range(), 12..22);
+
+        let clean = PromptTemplateBuilder::new()
+            .template("{#if context}Context:\n\n{context}{/if}\n{# {question}  is raw #}Question: {question}")
+            .infer_variables()
+            .build()
+            .unwrap();
+        assert!(clean.lint().is_empty());
+    }
+
+    #[test]
+    fn test_lint_jinja2_and_mustache() {
+        let template =
+            "{% for item in items %}{{ item }}, {{ items|length }}{% endfor %}\n\n\n\n{name} {{ }}";
+        let warnings = prompt(template, &["items"], TemplateFormat::Jinja2).lint();
+        assert_eq!(
+            kinds(&warnings),
+            vec![
+                LintKind::RepeatedWhitespace,
+                LintKind::MixedSyntax,
+                LintKind::EmptyPlaceholder,
+            ]
+        );
+        assert_eq!(&template[warnings[1].span.range()], "{name}");
+
+        let template = "{{#items}}{{label}}{{/items}} {{user.name}} {{ missing";
+        let warnings = prompt(template, &["items", "user"], TemplateFormat::Mustache).lint();
+        assert_eq!(kinds(&warnings), vec![LintKind::UnclosedPlaceholder]);
+    }
+
+    #[test]
+    fn test_deny() {
+        let prompt = prompt("Hi  {name}", &["name"], TemplateFormat::FString);
+        let allowed = prompt.deny(&[LintKind::UndeclaredVariable]).unwrap();
+        assert_eq!(kinds(&allowed), vec![LintKind::RepeatedWhitespace]);
+
+        match prompt.deny(&LintKind::ALL) {
+            Err(PromptError::LintFailed(denied)) => {
+                assert_eq!(kinds(&denied), vec![LintKind::RepeatedWhitespace])
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,446 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    parser, PromptArgs, PromptError, PromptFromatter, PromptMetadata, PromptTemplate,
+    TemplateFormat,
+};
+
+/// The serialized form of a `PromptTemplate`. It follows the layout written by Python
+/// LangChain's `prompt.save()`, so prompt files saved there can be loaded as they are.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PromptTemplateData {
+    #[serde(rename = "_type", default = "prompt_type")]
+    prompt_type: String,
+    input_variables: Vec<String>,
+    template: String,
+    #[serde(default = "default_template_format")]
+    template_format: TemplateFormat,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    partial_variables: PromptArgs,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    defaults: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    optional_variables: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    allowed_env: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    env_fallbacks: HashMap<String, String>,
+    /// The names of the input, keyed by alias, not those of the template.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    aliases: HashMap<String, String>,
+    // Python LangChain writes `"metadata": null` for prompts without any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<PromptMetadata>,
+}
+
+fn prompt_type() -> String {
+    "prompt".to_string()
+}
+
+fn default_template_format() -> TemplateFormat {
+    TemplateFormat::FString
+}
+
+impl From<PromptTemplate> for PromptTemplateData {
+    fn from(prompt: PromptTemplate) -> Self {
+        Self {
+            prompt_type: prompt_type(),
+            input_variables: prompt.variable_names().to_vec(),
+            template: prompt.template(),
+            template_format: prompt.template_format().clone(),
+            partial_variables: prompt.partial_variables().clone(),
+            defaults: prompt.defaults().clone(),
+            optional_variables: prompt.optional_variables().to_vec(),
+            allowed_env: prompt.allowed_env().to_vec(),
+            env_fallbacks: prompt.env_fallbacks().clone(),
+            aliases: prompt.aliases().clone(),
+            metadata: Some(prompt.metadata().clone()).filter(|m| !m.is_empty()),
+        }
+    }
+}
+
+impl TryFrom<PromptTemplateData> for PromptTemplate {
+    type Error = PromptError;
+
+    fn try_from(data: PromptTemplateData) -> Result<Self, Self::Error> {
+        if data.prompt_type != "prompt" {
+            return Err(PromptError::OtherError(format!(
+                "Unsupported prompt type: {}",
+                data.prompt_type
+            )));
+        }
+        let mut variables = data.input_variables;
+        variables.extend(data.partial_variables.keys().cloned());
+        // Python LangChain lists optional variables apart from the input variables
+        for variable in &data.optional_variables {
+            if !variables.contains(variable) {
+                variables.push(variable.clone());
+            }
+        }
+        let prompt = PromptTemplate::try_new(data.template, variables, data.template_format)?
+            .with_defaults(data.defaults)
+            .with_optional_variables(data.optional_variables)
+            .allow_env(data.allowed_env)
+            .with_metadata(data.metadata.unwrap_or_default());
+        let prompt = data
+            .env_fallbacks
+            .into_iter()
+            .fold(prompt, |prompt, (name, value)| {
+                prompt.with_env_fallback(name, value)
+            });
+        let prompt = if data.partial_variables.is_empty() {
+            prompt
+        } else {
+            prompt.bind(data.partial_variables)
+        };
+        prompt.with_aliases(data.aliases)
+    }
+}
+
+impl PromptTemplate {
+    /// Parses a `PromptTemplate` from JSON, validating its declared variables.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = PromptTemplate::from_json(
+    ///     r#"{"input_variables": ["name"], "template": "Hello {name}", "template_format": "f-string"}"#,
+    /// )?;
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, PromptError> {
+        let data: PromptTemplateData = serde_json::from_str(json)?;
+        data.try_into()
+    }
+
+    /// Serializes the `PromptTemplate` to pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, PromptError> {
+        Ok(serde_json::to_string_pretty(&PromptTemplateData::from(
+            self.clone(),
+        ))?)
+    }
+
+    /// Parses a `PromptTemplate` from YAML, validating its declared variables.
+    pub fn from_yaml(yaml: &str) -> Result<Self, PromptError> {
+        let data: PromptTemplateData = serde_yaml::from_str(yaml)?;
+        data.try_into()
+    }
+
+    /// Serializes the `PromptTemplate` to YAML.
+    pub fn to_yaml(&self) -> Result<String, PromptError> {
+        Ok(serde_yaml::to_string(&PromptTemplateData::from(
+            self.clone(),
+        ))?)
+    }
+}
+
+impl PromptTemplate {
+    /// Loads a `PromptTemplate` from a prompt file, by its extension:
+    ///
+    /// - `.json`, `.yaml` and `.yml` files hold the serialized form of the prompt, as read by
+    ///   `load_prompt`.
+    /// - `.md` files are the template, with an optional YAML front matter fenced by `---`
+    ///   lines setting its `template_format` (or `format`), `input_variables`, `defaults`,
+    ///   `partial_variables`, `optional_variables`, `allowed_env`, `env_fallbacks` and
+    ///   `metadata`.
+    /// - Any other file, like a `.txt`, is an FString template.
+    ///
+    /// The variables are inferred from the template unless they are declared.
+    ///
+    /// # Errors
+    /// Returns `PromptError::FileError` with the path of the file on any I/O or parse error.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = PromptTemplate::from_file("prompts/summarize.md")?;
+    /// let result = prompt.format(prompt_args! { "text" => text })?;
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PromptError> {
+        let path = path.as_ref();
+        let load = || {
+            let mut value = parse_prompt_file(path, &fs::read_to_string(path)?)?;
+            if let Some(template_path) = take_template_path(path, &mut value) {
+                set_template(&mut value, fs::read_to_string(template_path)?);
+            }
+            value_into_prompt(value)
+        };
+        load().map_err(|e| file_error(path, e))
+    }
+
+    /// Loads a `PromptTemplate` from a prompt file like `from_file`, without blocking, for
+    /// servers loading prompts at request time.
+    pub async fn from_file_async<P: AsRef<Path>>(path: P) -> Result<Self, PromptError> {
+        let path = path.as_ref();
+        let load = async {
+            let mut value = parse_prompt_file(path, &tokio::fs::read_to_string(path).await?)?;
+            if let Some(template_path) = take_template_path(path, &mut value) {
+                set_template(&mut value, tokio::fs::read_to_string(template_path).await?);
+            }
+            value_into_prompt(value)
+        };
+        load.await.map_err(|e| file_error(path, e))
+    }
+}
+
+/// Loads a `PromptTemplate` from a `.json`, `.yaml` or `.yml` file.
+///
+/// Like in Python LangChain, the template can also be kept in a separate text file referenced
+/// by a `template_path` key, relative to the prompt file.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = load_prompt("prompts/summarize.yaml")?;
+/// let result = prompt.format(prompt_args! { "text" => text })?;
+/// ```
+pub fn load_prompt<P: AsRef<Path>>(path: P) -> Result<PromptTemplate, PromptError> {
+    let path = path.as_ref();
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json" | "yaml" | "yml") => PromptTemplate::from_file(path),
+        _ => Err(PromptError::OtherError(format!(
+            "Unsupported prompt file: {}, expected a json or yaml file",
+            path.display()
+        ))),
+    }
+}
+
+pub(super) fn file_error(path: &Path, error: PromptError) -> PromptError {
+    PromptError::FileError {
+        path: path.to_path_buf(),
+        source: Box::new(error),
+    }
+}
+
+// Parses the content of a prompt file into the serialized form of a prompt, whose variables
+// may still have to be inferred.
+fn parse_prompt_file(path: &Path, content: &str) -> Result<Value, PromptError> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => Ok(serde_json::from_str(content)?),
+        Some("yaml" | "yml") => Ok(serde_yaml::from_str(content)?),
+        Some("md") => {
+            let (front_matter, template) = split_front_matter(content);
+            let mut value = match front_matter {
+                Some(yaml) => serde_yaml::from_str::<Value>(yaml)?,
+                None => Value::Null,
+            };
+            if value.is_null() {
+                value = Value::Object(Default::default());
+            }
+            let object = value.as_object_mut().ok_or_else(|| {
+                PromptError::OtherError("Front matter must be a YAML mapping".to_string())
+            })?;
+            if let Some(format) = object.remove("format") {
+                object.entry("template_format").or_insert(format);
+            }
+            set_template(&mut value, template.to_string());
+            Ok(value)
+        }
+        _ => Ok(serde_json::json!({ "template": content })),
+    }
+}
+
+// Splits a `---` fenced front matter from the rest of a markdown file.
+fn split_front_matter(content: &str) -> (Option<&str>, &str) {
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (None, content);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, content)
+}
+
+// Removes the `template_path` key, returning the path of the template relative to the prompt
+// file.
+fn take_template_path(path: &Path, value: &mut Value) -> Option<PathBuf> {
+    let template_path = value.as_object_mut()?.remove("template_path")?;
+    let template_path = Path::new(template_path.as_str().unwrap_or_default());
+    Some(
+        path.parent()
+            .map_or(template_path.to_path_buf(), |dir| dir.join(template_path)),
+    )
+}
+
+fn set_template(value: &mut Value, template: String) {
+    if let Some(object) = value.as_object_mut() {
+        object.insert("template".to_string(), template.into());
+    }
+}
+
+pub(super) fn value_into_prompt(mut value: Value) -> Result<PromptTemplate, PromptError> {
+    if let Some(object) = value.as_object_mut() {
+        if !object.contains_key("input_variables") {
+            let template = object
+                .get("template")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let format: TemplateFormat = match object.get("template_format") {
+                Some(format) => serde_json::from_value(format.clone())?,
+                None => default_template_format(),
+            };
+            let partials: Vec<&String> = object
+                .get("partial_variables")
+                .and_then(Value::as_object)
+                .map(|partials| partials.keys().collect())
+                .unwrap_or_default();
+            let variables: Vec<String> = parser::extract_variables(template, &format)?
+                .into_iter()
+                .filter(|variable| !partials.contains(&variable))
+                .collect();
+            object.insert("input_variables".to_string(), variables.into());
+        }
+    }
+    let data: PromptTemplateData = serde_json::from_value(value)?;
+    data.try_into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, template_fstring};
+
+    #[test]
+    fn test_prompt_template_json_roundtrip() {
+        let prompt = template_fstring!("{persona}: tell me about {topic}", "persona", "topic")
+            .partial(prompt_args! { "persona" => "a pirate" });
+        let json = prompt.to_json().unwrap();
+
+        let loaded = PromptTemplate::from_json(&json).unwrap();
+        assert_eq!(loaded.template(), prompt.template());
+        assert_eq!(loaded.variables(), vec!["topic"]);
+        assert_eq!(
+            loaded.format(prompt_args! { "topic" => "ships" }).unwrap(),
+            "a pirate: tell me about ships"
+        );
+
+        let loaded: PromptTemplate = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.partial_variables()["persona"], "a pirate");
+
+        let aliased = PromptTemplate::from(prompt)
+            .with_aliases(HashMap::from([(
+                "subject".to_string(),
+                "topic".to_string(),
+            )]))
+            .unwrap();
+        let loaded = PromptTemplate::from_json(&aliased.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.variables(), vec!["subject"]);
+        assert_eq!(loaded.aliases(), aliased.aliases());
+
+        let result = PromptTemplate::from_json(
+            r#"{"input_variables": ["name"], "template": "Hello {user}"}"#,
+        );
+        assert!(matches!(result, Err(PromptError::VariablesMismatch { .. })));
+    }
+
+    #[test]
+    fn test_load_python_langchain_prompt_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+
+        // as written by `PromptTemplate.save("prompt.json")` in Python LangChain
+        let json_path = dir.join("prompt.json");
+        fs::write(
+            &json_path,
+            r#"{
+    "name": null,
+    "input_variables": ["adjective", "content"],
+    "output_parser": null,
+    "partial_variables": {},
+    "metadata": null,
+    "tags": null,
+    "template": "Tell me a {adjective} joke about {content}.",
+    "template_format": "f-string",
+    "validate_template": false,
+    "_type": "prompt"
+}"#,
+        )
+        .unwrap();
+        let prompt = load_prompt(&json_path).unwrap();
+        assert_eq!(prompt.variables(), vec!["adjective", "content"]);
+
+        let yaml_path = dir.join("prompt.yaml");
+        fs::write(
+            &yaml_path,
+            "_type: prompt\ninput_variables:\n  - name\ntemplate_path: template.txt\ntemplate_format: jinja2\n",
+        )
+        .unwrap();
+        fs::write(dir.join("template.txt"), "Hello {{name}}!").unwrap();
+        let prompt = load_prompt(&yaml_path).unwrap();
+        assert_eq!(
+            prompt.format(prompt_args! { "name" => "Luis" }).unwrap(),
+            "Hello Luis!"
+        );
+
+        let yaml = prompt.to_yaml().unwrap();
+        assert!(yaml.contains("template_format: jinja2"));
+        assert_eq!(
+            PromptTemplate::from_yaml(&yaml).unwrap().template(),
+            "Hello {{name}}!"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prompt_template_from_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+
+        let txt_path = dir.join("greeting.txt");
+        fs::write(&txt_path, "Hello {name}, welcome to {place}!").unwrap();
+        let prompt = PromptTemplate::from_file(&txt_path).unwrap();
+        assert_eq!(prompt.variables(), vec!["name", "place"]);
+
+        let md_path = dir.join("summarize.md");
+        fs::write(
+            &md_path,
+            "---\nformat: jinja2\ndefaults:\n  style: brief\n---\n# Summary\n\nSummarize {{ text }} in a {{ style }} way.\n",
+        )
+        .unwrap();
+        let prompt = PromptTemplate::from_file_async(&md_path).await.unwrap();
+        assert_eq!(prompt.template_format(), &TemplateFormat::Jinja2);
+        assert_eq!(prompt.variables(), vec!["text", "style"]);
+        assert_eq!(
+            prompt.template(),
+            "# Summary\n\nSummarize {{ text }} in a {{ style }} way.\n"
+        );
+
+        let plain_md = dir.join("plain.md");
+        fs::write(&plain_md, "--- not front matter\n{topic}").unwrap();
+        let prompt = PromptTemplate::from_file(&plain_md).unwrap();
+        assert_eq!(prompt.variables(), vec!["topic"]);
+
+        let yaml_path = dir.join("partial.yaml");
+        fs::write(
+            &yaml_path,
+            "template: \"{persona}: {question}\"\npartial_variables:\n  persona: Bot\n",
+        )
+        .unwrap();
+        let prompt = PromptTemplate::from_file(&yaml_path).unwrap();
+        assert_eq!(prompt.variables(), vec!["question"]);
+
+        let missing = dir.join("missing.txt");
+        match PromptTemplate::from_file_async(&missing).await {
+            Err(PromptError::FileError { path, source }) => {
+                assert_eq!(path, missing);
+                assert!(matches!(*source, PromptError::IoError(_)));
+            }
+            _ => panic!("expected a file error"),
+        }
+        let invalid = dir.join("invalid.json");
+        fs::write(&invalid, "{ not json").unwrap();
+        match PromptTemplate::from_file(&invalid) {
+            Err(error) => assert!(error.to_string().contains("invalid.json"), "{}", error),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+}
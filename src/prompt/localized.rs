@@ -0,0 +1,186 @@
+use crate::schemas::prompt::PromptValue;
+
+use super::{FormatPrompter, PromptArgs, PromptError, PromptFromatter};
+
+/// Struct `LocalizedPrompt` holds one prompt per locale tag, such as `en` or `de-AT`, and a
+/// fallback prompt. Each `format` picks the prompt of the `locale` input variable, or of the
+/// default locale when it's absent.
+///
+/// Locales fall back to their parent, e.g. `de-AT` to `de`, then to the default locale and
+/// finally to the fallback prompt. Tags are compared case-insensitively, `_` and `-` alike.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = LocalizedPrompt::new("Answer the question: {question}")
+///     .with_locale("de", "Beantworte die Frage: {question}")
+///     .with_locale("ja", "質問に答えてください: {question}");
+/// let result = prompt.format(prompt_args! { "locale" => "de-AT", "question" => "Warum?" })?;
+/// ```
+pub struct LocalizedPrompt {
+    locales: Vec<(String, Box<dyn PromptFromatter>)>,
+    fallback: Box<dyn PromptFromatter>,
+    default_locale: Option<String>,
+}
+
+impl LocalizedPrompt {
+    pub fn new<P: Into<Box<dyn PromptFromatter>>>(fallback: P) -> Self {
+        Self {
+            locales: Vec::new(),
+            fallback: fallback.into(),
+            default_locale: None,
+        }
+    }
+
+    /// Sets the prompt of a locale, replacing any prompt already set for it.
+    pub fn with_locale<S: AsRef<str>, P: Into<Box<dyn PromptFromatter>>>(
+        mut self,
+        locale: S,
+        prompt: P,
+    ) -> Self {
+        let locale = normalize(locale.as_ref());
+        self.locales.retain(|(tag, _)| *tag != locale);
+        self.locales.push((locale, prompt.into()));
+        self
+    }
+
+    /// Sets the locale used when the input has no `locale` variable.
+    pub fn with_default_locale<S: AsRef<str>>(mut self, locale: S) -> Self {
+        self.default_locale = Some(normalize(locale.as_ref()));
+        self
+    }
+
+    /// Returns the prompt used for `locale`, following the fallback chain.
+    pub fn resolve(&self, locale: Option<&str>) -> &dyn PromptFromatter {
+        let candidates = locale
+            .map(normalize)
+            .into_iter()
+            .chain(self.default_locale.clone());
+        for mut locale in candidates {
+            loop {
+                if let Some((_, prompt)) = self.locales.iter().find(|(tag, _)| *tag == locale) {
+                    return prompt.as_ref();
+                }
+                match locale.rfind('-') {
+                    Some(index) => locale.truncate(index),
+                    None => break,
+                }
+            }
+        }
+        self.fallback.as_ref()
+    }
+
+    fn resolve_input(&self, input_variables: &PromptArgs) -> &dyn PromptFromatter {
+        self.resolve(
+            input_variables
+                .get("locale")
+                .and_then(|locale| locale.as_str()),
+        )
+    }
+}
+
+fn normalize(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_lowercase()
+}
+
+impl PromptFromatter for LocalizedPrompt {
+    /// Returns the template of the default locale.
+    fn template(&self) -> String {
+        self.resolve(None).template()
+    }
+
+    /// Returns the variables of every locale, so the input can be validated whatever the locale.
+    fn variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        let prompts = self
+            .locales
+            .iter()
+            .map(|(_, prompt)| prompt)
+            .chain(Some(&self.fallback));
+        for variable in prompts.flat_map(|prompt| prompt.variables()) {
+            if !variables.contains(&variable) {
+                variables.push(variable);
+            }
+        }
+        variables
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        self.resolve_input(&input_variables).format(input_variables)
+    }
+
+    fn format_into(
+        &self,
+        input_variables: PromptArgs,
+        writer: &mut dyn std::fmt::Write,
+    ) -> Result<(), PromptError> {
+        self.resolve_input(&input_variables)
+            .format_into(input_variables, writer)
+    }
+
+    fn to_prompt_value(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
+        self.resolve_input(&input_variables)
+            .to_prompt_value(input_variables)
+    }
+}
+
+impl FormatPrompter for LocalizedPrompt {
+    fn format_prompt(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
+        self.to_prompt_value(input_variables)
+    }
+    fn get_input_variables(&self) -> Vec<String> {
+        self.variables()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, template_fstring};
+
+    fn prompt() -> LocalizedPrompt {
+        LocalizedPrompt::new("Answer: {question}")
+            .with_locale("de", "Antwort: {question}")
+            .with_locale("de-CH", "Antwort (CH): {question}")
+            .with_locale(
+                "ja",
+                template_fstring!("{name}さん、答え: {question}", "name", "question"),
+            )
+    }
+
+    #[test]
+    fn test_localized_prompt_fallback() {
+        let prompt = prompt();
+        let format = |locale: &str| {
+            prompt
+                .format(prompt_args! { "locale" => locale, "question" => "?", "name" => "Luis" })
+                .unwrap()
+        };
+        assert_eq!(format("de"), "Antwort: ?");
+        assert_eq!(format("de-AT"), "Antwort: ?");
+        assert_eq!(format("de_ch"), "Antwort (CH): ?");
+        assert_eq!(format("ja-JP"), "Luisさん、答え: ?");
+        assert_eq!(format("fr"), "Answer: ?");
+        assert_eq!(
+            prompt.format(prompt_args! { "question" => "?" }).unwrap(),
+            "Answer: ?"
+        );
+
+        let prompt = prompt.with_default_locale("de-AT");
+        assert_eq!(
+            prompt.format(prompt_args! { "question" => "?" }).unwrap(),
+            "Antwort: ?"
+        );
+        assert_eq!(
+            prompt
+                .format(prompt_args! { "locale" => "fr", "question" => "?" })
+                .unwrap(),
+            "Antwort: ?"
+        );
+        assert_eq!(prompt.template(), "Antwort: {question}");
+    }
+
+    #[test]
+    fn test_localized_prompt_variables() {
+        assert_eq!(prompt().variables(), vec!["question", "name"]);
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+/// Operational metadata of a prompt: a stable name, like `qa/main`, a description, an owner,
+/// tags and a version. It's serialized with the prompt, and the name is added to its errors.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// The metadata of prompts without any.
+pub(crate) static EMPTY_METADATA: PromptMetadata = PromptMetadata {
+    name: None,
+    description: None,
+    owner: None,
+    tags: Vec::new(),
+    version: None,
+};
+
+impl PromptMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_owner<S: Into<String>>(mut self, owner: S) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    /// Adds a tag, unless the metadata already has it.
+    pub fn with_tag<S: Into<String>>(mut self, tag: S) -> Self {
+        let tag = tag.into();
+        if !self.has_tag(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+
+    pub fn with_version<S: Into<String>>(mut self, version: S) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == EMPTY_METADATA
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::{PromptError, PromptFromatter, PromptTemplate};
+
+    #[test]
+    fn test_prompt_metadata() {
+        let prompt = PromptTemplate::builder()
+            .template("Answer {question} from {context}")
+            .name("qa/main")
+            .tag("rag")
+            .tag("rag")
+            .version("1.2.0")
+            .build()
+            .unwrap();
+        assert_eq!(
+            prompt.metadata(),
+            &PromptMetadata::new()
+                .with_name("qa/main")
+                .with_tag("rag")
+                .with_version("1.2.0")
+        );
+
+        let json = prompt.to_json().unwrap();
+        assert!(json.contains(r#""tags": ["#), "{}", json);
+        let loaded = PromptTemplate::from_json(&json).unwrap();
+        assert_eq!(loaded.metadata(), prompt.metadata());
+
+        let python = r#"{"input_variables": ["x"], "template": "{x}", "metadata": null}"#;
+        assert!(PromptTemplate::from_json(python)
+            .unwrap()
+            .metadata()
+            .is_empty());
+        assert!("{x}".metadata().is_empty());
+
+        match prompt.format(crate::prompt_args! { "question" => "why?" }) {
+            Err(PromptError::NamedPromptError { name, source }) => {
+                assert_eq!(name, "qa/main");
+                assert!(source.to_string().contains("context"), "{}", source);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
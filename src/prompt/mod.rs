@@ -1,22 +1,264 @@
+mod async_write;
+mod builder;
+mod cached;
 mod chat;
+mod clock;
+mod combinators;
+mod compress;
+mod diff;
+mod display;
 mod error;
+mod example_selector;
+mod fallback;
+mod few_shot;
+mod filter_chain;
+#[cfg(feature = "jinja2")]
+mod filters;
+mod format_options;
+mod format_spec;
+mod hash;
+#[cfg(feature = "hub")]
+pub mod hub;
+#[cfg(feature = "jinja2")]
+mod jinja2;
+mod langchain_import;
+mod limits;
+mod lint;
+mod loading;
+mod localized;
+mod metadata;
+#[cfg(feature = "mustache")]
+mod mustache;
+mod parser;
+mod pipeline;
 mod prompt;
+pub mod prompt_eval;
+mod registry;
+mod resolver;
+mod sanitizer;
+mod span;
+#[cfg(feature = "jinja2")]
+mod template_loader;
+#[cfg(any(feature = "jinja2", feature = "mustache"))]
+mod text_writer;
+mod token_counter;
+mod trimming;
+mod weighted;
 
 use std::collections::HashMap;
 
+use async_trait::async_trait;
+use futures::future::{self, BoxFuture};
+
+pub use builder::*;
+pub use cached::*;
 pub use chat::*;
+pub use clock::*;
+pub use combinators::*;
+pub use compress::*;
+pub use diff::*;
+pub use display::*;
 pub use error::*;
+pub use example_selector::*;
+pub use fallback::*;
+pub use few_shot::*;
+#[cfg(feature = "jinja2")]
+pub use filters::*;
+pub use format_options::*;
+pub use hash::PromptHash;
+pub use langchain_import::*;
+pub use langchain_rust_derive::{prompt_template, IntoPromptArgs};
+pub use limits::*;
+pub use lint::*;
+pub use loading::load_prompt;
+pub use localized::*;
+pub use metadata::*;
+pub use pipeline::*;
 pub use prompt::*;
+pub use registry::*;
+pub use resolver::*;
+pub use sanitizer::*;
 use serde_json::Value;
+pub use span::*;
+#[cfg(feature = "jinja2")]
+pub use template_loader::*;
+pub use token_counter::*;
+pub use trimming::*;
+pub use weighted::*;
 
 use crate::schemas::{messages::Message, prompt::PromptValue};
 
-// pub type PromptArgs<'a> = HashMap<&'a str, &'a str>;
+/// The owned arguments passed to a prompt when formatting it.
+///
+/// String values are substituted as-is, numbers and booleans through their natural
+/// string form, and structured values as JSON.
 pub type PromptArgs = HashMap<String, Value>;
+
+/// Conversion into `PromptArgs` from any collection of key/value pairs, such as a
+/// `HashMap<&str, &str>`, a `BTreeMap<String, String>`, a `Vec<(&str, i32)>`, an array or a
+/// slice of pairs. Structs can derive a conversion with `#[derive(IntoPromptArgs)]` instead.
+///
+/// # Usage
+/// ```rust,ignore
+/// let mut args = HashMap::new();
+/// args.insert("name", "Luis");
+/// let result = prompt.format(args.into_prompt_args())?;
+/// let result = prompt.format_from([("name", "Luis")])?;
+/// ```
+pub trait IntoPromptArgs {
+    /// Converts into `PromptArgs`, also returning the keys given more than once, which keep
+    /// their last value.
+    fn into_prompt_args_checked(self) -> (PromptArgs, Vec<String>);
+
+    /// Converts into `PromptArgs`. A key given more than once keeps its last value, and a
+    /// warning is logged.
+    fn into_prompt_args(self) -> PromptArgs
+    where
+        Self: Sized,
+    {
+        let (args, duplicates) = self.into_prompt_args_checked();
+        if !duplicates.is_empty() {
+            log::warn!(
+                "Prompt arguments {:?} are given more than once, the last values are kept",
+                duplicates
+            );
+        }
+        args
+    }
+}
+
+impl<I, P> IntoPromptArgs for I
+where
+    I: IntoIterator<Item = P>,
+    P: PromptArgPair,
+{
+    fn into_prompt_args_checked(self) -> (PromptArgs, Vec<String>) {
+        let mut args = PromptArgs::new();
+        let mut duplicates = Vec::new();
+        for pair in self {
+            let (key, value) = pair.into_pair();
+            if args.contains_key(&key) && !duplicates.contains(&key) {
+                duplicates.push(key.clone());
+            }
+            args.insert(key, value);
+        }
+        (args, duplicates)
+    }
+}
+
+/// A key/value pair of `IntoPromptArgs`, owned or borrowed from a slice of pairs.
+pub trait PromptArgPair {
+    fn into_pair(self) -> (String, Value);
+}
+
+impl<K: Into<String>, V: Into<Value>> PromptArgPair for (K, V) {
+    fn into_pair(self) -> (String, Value) {
+        (self.0.into(), self.1.into())
+    }
+}
+
+impl<K: Clone + Into<String>, V: Clone + Into<Value>> PromptArgPair for &(K, V) {
+    fn into_pair(self) -> (String, Value) {
+        self.clone().into_pair()
+    }
+}
+
+// Serializes a field of a struct deriving `IntoPromptArgs`, leaving it out with a warning if
+// it fails, so the prompt reports it as missing rather than formatting a null.
+#[doc(hidden)]
+pub fn __serialize_prompt_value<T: serde::Serialize + ?Sized>(
+    name: &str,
+    value: &T,
+) -> Option<Value> {
+    serde_json::to_value(value)
+        .map_err(|e| log::warn!("Prompt argument `{}` failed to be serialized: {}", name, e))
+        .ok()
+}
+
+#[doc(hidden)]
+pub fn __display_prompt_value<T: std::fmt::Display + ?Sized>(
+    _name: &str,
+    value: &T,
+) -> Option<Value> {
+    Some(Value::String(value.to_string()))
+}
+
+#[doc(hidden)]
+pub fn __prompt_args_object(args: PromptArgs) -> Value {
+    Value::Object(args.into_iter().collect())
+}
+
+#[doc(hidden)]
+pub fn __prompt_args_array(values: Vec<Value>) -> Value {
+    Value::Array(values)
+}
+
 pub trait PromptFromatter: Send + Sync {
     fn template(&self) -> String;
     fn variables(&self) -> Vec<String>;
     fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError>;
+
+    /// Returns the metadata of the prompt, empty by default.
+    fn metadata(&self) -> &PromptMetadata {
+        &metadata::EMPTY_METADATA
+    }
+
+    /// Returns the prompt as a `PromptTemplate` if it's one, e.g. to serialize it.
+    fn as_prompt_template(&self) -> Option<&PromptTemplate> {
+        None
+    }
+
+    /// Formats the prompt from any collection of key/value pairs, see `IntoPromptArgs`.
+    fn format_from<A: IntoPromptArgs>(&self, input_variables: A) -> Result<String, PromptError>
+    where
+        Self: Sized,
+    {
+        self.format(input_variables.into_prompt_args())
+    }
+
+    /// Formats the prompt into `writer`, e.g. a reused `String` buffer. `PromptTemplate` streams
+    /// the template and the values straight into it, without building the prompt first.
+    fn format_into(
+        &self,
+        input_variables: PromptArgs,
+        writer: &mut dyn std::fmt::Write,
+    ) -> Result<(), PromptError> {
+        writer.write_str(&self.format(input_variables)?)?;
+        Ok(())
+    }
+
+    /// Formats the prompt as a `PromptValue`, usable by both completion and chat models.
+    /// Defaults to a string value, chat prompts return their messages.
+    fn to_prompt_value(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
+        Ok(PromptValue::from_string(&self.format(input_variables)?))
+    }
+
+    /// Formats the prompt with each arguments of the batch, in order. A failure doesn't stop
+    /// the batch: the results are index-aligned with the batch.
+    fn format_batch(&self, batch: &[PromptArgs]) -> Vec<Result<String, PromptError>> {
+        batch
+            .iter()
+            .map(|input_variables| self.format(input_variables.clone()))
+            .collect()
+    }
+
+    /// Formats the prompt with each arguments of the batch, stopping at the first failure.
+    ///
+    /// # Errors
+    /// Returns `PromptError::BatchError` with the index of the failed arguments.
+    fn format_batch_strict(&self, batch: &[PromptArgs]) -> Result<Vec<String>, PromptError> {
+        batch
+            .iter()
+            .enumerate()
+            .map(|(index, input_variables)| {
+                self.format(input_variables.clone())
+                    .map_err(|e| PromptError::BatchError {
+                        index,
+                        source: Box::new(e),
+                    })
+            })
+            .collect()
+    }
 }
 impl<PA> From<PA> for Box<dyn PromptFromatter>
 where
@@ -33,6 +275,17 @@ pub trait MessageFormatter: Send + Sync {
 
     /// Returns a list of required input variable names for the template.
     fn input_variables(&self) -> Vec<String>;
+
+    /// Returns the raw template of the message, prefixed with its role, if it has one.
+    fn message_template(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the `PromptTemplate` of the message, if it has one, e.g. to show it with
+    /// `ChatPromptTemplate::render_debug`.
+    fn prompt_template(&self) -> Option<&PromptTemplate> {
+        None
+    }
 }
 impl<MF> From<MF> for Box<dyn MessageFormatter>
 where
@@ -46,6 +299,22 @@ where
 pub trait FormatPrompter: Send + Sync {
     fn format_prompt(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError>;
     fn get_input_variables(&self) -> Vec<String>;
+
+    /// Returns the input variables that must be given to format the prompt, leaving out the
+    /// ones with a default value or that are optional. Defaults to `get_input_variables`.
+    fn get_required_variables(&self) -> Vec<String> {
+        self.get_input_variables()
+    }
+
+    /// Formats the prompt like `format_prompt`, doing its async work, like running resolvers
+    /// or embedding the input to select examples, without blocking. This is how `AsyncPrompt`
+    /// formats sync prompts, defaulting to `format_prompt`.
+    fn format_prompt_async(
+        &self,
+        input_variables: PromptArgs,
+    ) -> BoxFuture<'_, Result<PromptValue, PromptError>> {
+        Box::pin(future::ready(self.format_prompt(input_variables)))
+    }
 }
 impl<FP> From<FP> for Box<dyn FormatPrompter>
 where
@@ -55,3 +324,71 @@ where
         Box::new(prompt)
     }
 }
+
+/// Trait `AsyncPrompt` formats prompts whose formatting does async work, like
+/// `PromptTemplate`s with resolvers or `FewShotPromptTemplate`s selecting examples by
+/// embeddings, without blocking the runtime. Chains accept it as an `Arc<dyn AsyncPrompt>`,
+/// which can be shared across `tokio::spawn`.
+///
+/// Every `FormatPrompter` implements it through `FormatPrompter::format_prompt_async`, which
+/// prompts with async work override.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt: Arc<dyn AsyncPrompt> = Arc::new(template.with_resolver("profile", load_profile));
+/// let chain = LLMChainBuilder::new().async_prompt(prompt).llm(llm).build()?;
+/// ```
+#[async_trait]
+pub trait AsyncPrompt: Send + Sync {
+    async fn aformat_prompt(&self, input_variables: PromptArgs)
+        -> Result<PromptValue, PromptError>;
+
+    async fn aformat(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        Ok(self.aformat_prompt(input_variables).await?.to_string())
+    }
+
+    /// Returns the variables the prompt takes.
+    fn input_variables(&self) -> Vec<String>;
+
+    /// Returns the variables that must be given to format the prompt, see
+    /// `FormatPrompter::get_required_variables`.
+    fn required_input_variables(&self) -> Vec<String> {
+        self.input_variables()
+    }
+}
+
+#[async_trait]
+impl<P: FormatPrompter + ?Sized> AsyncPrompt for P {
+    async fn aformat_prompt(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<PromptValue, PromptError> {
+        self.format_prompt_async(input_variables).await
+    }
+
+    fn input_variables(&self) -> Vec<String> {
+        self.get_input_variables()
+    }
+
+    fn required_input_variables(&self) -> Vec<String> {
+        self.get_required_variables()
+    }
+}
+
+#[async_trait]
+impl AsyncPrompt for Box<dyn FormatPrompter> {
+    async fn aformat_prompt(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<PromptValue, PromptError> {
+        self.as_ref().format_prompt_async(input_variables).await
+    }
+
+    fn input_variables(&self) -> Vec<String> {
+        self.get_input_variables()
+    }
+
+    fn required_input_variables(&self) -> Vec<String> {
+        self.get_required_variables()
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+
+use super::{parser, text_writer::TextWriter, PromptArgs, PromptError};
+
+/// Renders a Mustache template into `writer`, as it's rendered, evaluating sections and
+/// inverted sections. Values are not HTML-escaped, since prompts are not HTML.
+pub(crate) fn render(
+    template: &str,
+    input_variables: &PromptArgs,
+    writer: &mut dyn fmt::Write,
+) -> Result<(), PromptError> {
+    // reports unbalanced sections and unclosed tags with their position
+    parser::parse_mustache_variables(template)?;
+
+    let compiled = mustache::compile_str(&unescape_tags(template)).map_err(|e| match e {
+        mustache::Error::Parser(error) => PromptError::invalid_template(
+            template,
+            error.to_string(),
+            template.len()..template.len(),
+        ),
+        error => PromptError::RenderError(error.to_string()),
+    })?;
+    compiled
+        .render(&mut TextWriter::new(writer), input_variables)
+        .map_err(|e| match e {
+            mustache::Error::Io(_) => PromptError::WriteError(fmt::Error),
+            error => PromptError::RenderError(error.to_string()),
+        })
+}
+
+/// Turns every escaped `{{var}}` tag into an unescaped `{{&var}}` tag.
+fn unescape_tags(template: &str) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start + 2]);
+        rest = &rest[start + 2..];
+        let is_variable = !rest
+            .trim_start()
+            .starts_with(['{', '&', '#', '^', '/', '!', '>', '=']);
+        if is_variable {
+            result.push('&');
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        prompt::{PromptError, PromptFromatter, PromptTemplate, TemplateFormat},
+        prompt_args,
+    };
+
+    #[test]
+    fn test_mustache_sections() {
+        let template = PromptTemplate::from_template(
+            "Items for {{name}}:\n{{#items}}- {{label}}\n{{/items}}{{^empty}}nothing else{{/empty}}",
+            TemplateFormat::Mustache,
+        )
+        .unwrap();
+        assert_eq!(template.variables(), vec!["name", "items", "empty"]);
+
+        let result = template
+            .format(prompt_args! {
+                "name" => "Luis",
+                "items" => vec![
+                    serde_json::json!({"label": "a"}),
+                    serde_json::json!({"label": "b"}),
+                ],
+                "empty" => Vec::<String>::new(),
+            })
+            .unwrap();
+        assert_eq!(result, "Items for Luis:\n- a\n- b\nnothing else");
+    }
+
+    #[test]
+    fn test_mustache_does_not_escape_html() {
+        let template =
+            PromptTemplate::from_template("Code: {{code}}", TemplateFormat::Mustache).unwrap();
+        let result = template
+            .format(prompt_args! { "code" => "<b>\"a\" & 'b'</b>" })
+            .unwrap();
+        assert_eq!(result, "Code: <b>\"a\" & 'b'</b>");
+    }
+
+    #[test]
+    fn test_mustache_missing_variable() {
+        let template =
+            PromptTemplate::from_template("Hello {{name}}", TemplateFormat::Mustache).unwrap();
+        let result = template.format(prompt_args! {});
+        assert!(matches!(result, Err(PromptError::InvalidInput(e)) if e.missing == vec!["name"]));
+
+        // like in any Mustache implementation, names inside sections may be missing
+        let template = PromptTemplate::new(
+            "{{#user}}Hello {{user.name}}{{/user}}".to_string(),
+            vec!["user".to_string()],
+            TemplateFormat::Mustache,
+        );
+        let result = template.format(prompt_args! { "user" => serde_json::json!({"age": 3}) });
+        assert_eq!(result.unwrap(), "Hello ");
+
+        let template = PromptTemplate::new(
+            "{{#user}}Hello".to_string(),
+            vec!["user".to_string()],
+            TemplateFormat::Mustache,
+        );
+        let result = template.format(prompt_args! { "user" => true });
+        assert!(matches!(result, Err(PromptError::InvalidTemplate { .. })));
+    }
+}
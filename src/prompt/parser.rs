@@ -0,0 +1,896 @@
+use chrono::format::{Item, StrftimeItems};
+pub(crate) use langchain_rust_fstring::{is_block_tag, is_env_name, is_index, is_path};
+use langchain_rust_fstring::{is_identifier, Scanner, Tag, MAX_BLOCK_DEPTH};
+
+use super::{filter_chain::FilterChain, format_spec::FormatSpec, PromptError, TemplateFormat};
+
+/// A piece of a parsed template: literal text, a placeholder or, in FString templates, a
+/// placeholder with a format spec, filters or a tag of a conditional block.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Segment<'a> {
+    Text(&'a str),
+    Variable(&'a str),
+    Formatted(&'a str, FormatSpec<'a>),
+    Filtered(&'a str, FilterChain<'a>),
+    Env(&'a str),
+    DateTime(&'a str, &'a str),
+    If(&'a str),
+    Else,
+    EndIf,
+    /// A `{# comment #}`, with its line when it's alone on it and comment lines are stripped.
+    Comment(&'a str),
+}
+
+/// Splits an FString template into text and placeholder segments.
+///
+/// `{{` and `}}` are escaped braces: they render as a single `{` or `}` and never
+/// start or end a placeholder. Placeholders may be dot paths like `{user.name}` or `{items.0}`,
+/// or positions like `{0}`, but a template can't mix positional and named placeholders. A
+/// format spec can follow a colon, like `{score:>8}` or `{pi:.2}`, see `FormatSpec`, or
+/// filters a pipe, like `{points|join:", "}`, see `FilterChain`.
+///
+/// `{#if name}`, `{#else}` and `{/if}` delimit conditional blocks, which are checked to be
+/// closed and nested one level deep at most.
+///
+/// `{env:NAME}` is an environment variable, see `PromptTemplate::allow_env`, and a chrono
+/// format string can follow the colon instead of a format spec, like `{now:%Y-%m-%d}`, see
+/// `PromptTemplate::with_clock`.
+///
+/// `{# ... #}` is a comment, which can span lines and hold braces, unless it starts like a
+/// block tag, with `{#if ` or `{#else}`.
+pub(crate) fn parse_fstring(template: &str) -> Result<Vec<Segment<'_>>, PromptError> {
+    parse_fstring_with(template, false)
+}
+
+/// Parses an FString template like `parse_fstring`. With `strip_comment_lines`, a comment
+/// alone on its line takes the whole line, line break included.
+pub(crate) fn parse_fstring_with(
+    template: &str,
+    strip_comment_lines: bool,
+) -> Result<Vec<Segment<'_>>, PromptError> {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    // position of each open block and whether its `{#else}` was seen
+    let mut blocks: Vec<(usize, bool)> = Vec::new();
+    // whether the placeholders seen so far are positional
+    let mut positional: Option<bool> = None;
+
+    for tag in Scanner::new(template) {
+        let (span, tag) =
+            tag.map_err(|e| PromptError::invalid_template(template, e.reason, e.span))?;
+        let i = span.start;
+        match tag {
+            Tag::EscapedBrace => {
+                // keep one brace of the pair and skip the other
+                segments.push(Segment::Text(&template[text_start..i + 1]));
+                text_start = span.end;
+            }
+            Tag::Comment => {
+                let end = span.end;
+                let (start, end) = if strip_comment_lines {
+                    comment_line(template, i, end).unwrap_or((i, end))
+                } else {
+                    (i, end)
+                };
+                if text_start < start {
+                    segments.push(Segment::Text(&template[text_start..start]));
+                }
+                segments.push(Segment::Comment(&template[start..end]));
+                text_start = end;
+            }
+            Tag::Placeholder(_) => {
+                let close = span.end - 1;
+                if let Some((name, filters)) = template[i + 1..close].split_once('|') {
+                    let name = name.trim();
+                    if !is_path(name) && !is_index(name) {
+                        return Err(PromptError::invalid_template(
+                            template,
+                            format!("invalid placeholder `{{{}}}`", &template[i + 1..close]),
+                            i..close + 1,
+                        ));
+                    }
+                    let filters = FilterChain::parse(filters).map_err(|reason| {
+                        PromptError::invalid_template(
+                            template,
+                            format!("invalid filters `{}`: {}", filters, reason),
+                            i..close + 1,
+                        )
+                    })?;
+                    let is_positional = is_index(name);
+                    if *positional.get_or_insert(is_positional) != is_positional {
+                        return Err(PromptError::invalid_template(
+                            template,
+                            "positional and named placeholders can't be mixed",
+                            i..close + 1,
+                        ));
+                    }
+                    if text_start < i {
+                        segments.push(Segment::Text(&template[text_start..i]));
+                    }
+                    segments.push(Segment::Filtered(name, filters));
+                    text_start = close + 1;
+                    continue;
+                }
+                if let Some(name) = template[i + 1..close].trim().strip_prefix("env:") {
+                    if is_env_name(name) {
+                        if text_start < i {
+                            segments.push(Segment::Text(&template[text_start..i]));
+                        }
+                        segments.push(Segment::Env(name));
+                        text_start = close + 1;
+                        continue;
+                    }
+                }
+                let (name, spec, date_format) = match template[i + 1..close].split_once(':') {
+                    Some((name, format)) if format.starts_with('%') && is_path(name.trim()) => {
+                        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                            return Err(PromptError::invalid_template(
+                                template,
+                                format!("invalid date format `{}`", format),
+                                i..close + 1,
+                            ));
+                        }
+                        (name.trim(), None, Some(format))
+                    }
+                    Some((name, spec)) if is_path(name.trim()) || is_index(name.trim()) => {
+                        let spec = FormatSpec::parse(spec).map_err(|reason| {
+                            PromptError::invalid_template(
+                                template,
+                                format!("invalid format spec `{}`: {}", spec, reason),
+                                i..close + 1,
+                            )
+                        })?;
+                        (name.trim(), Some(spec), None)
+                    }
+                    _ => (template[i + 1..close].trim(), None, None),
+                };
+                let segment = match name {
+                    "#else" => match blocks.last_mut() {
+                        Some((_, has_else @ false)) => {
+                            *has_else = true;
+                            Segment::Else
+                        }
+                        Some(_) => {
+                            return Err(PromptError::invalid_template(
+                                template,
+                                "duplicate `{#else}`",
+                                i..close + 1,
+                            ))
+                        }
+                        None => {
+                            return Err(PromptError::invalid_template(
+                                template,
+                                "`{#else}` outside of an `{#if}` block",
+                                i..close + 1,
+                            ))
+                        }
+                    },
+                    "/if" => {
+                        blocks.pop().ok_or_else(|| {
+                            PromptError::invalid_template(
+                                template,
+                                "`{/if}` without an `{#if}` block",
+                                i..close + 1,
+                            )
+                        })?;
+                        Segment::EndIf
+                    }
+                    _ => match name.strip_prefix("#if ").map(str::trim) {
+                        Some(condition) if is_path(condition) => {
+                            if blocks.len() == MAX_BLOCK_DEPTH {
+                                return Err(PromptError::invalid_template(
+                                    template,
+                                    "conditional blocks can only be nested one level deep",
+                                    i..close + 1,
+                                ));
+                            }
+                            blocks.push((i, false));
+                            Segment::If(condition)
+                        }
+                        None if is_path(name) || is_index(name) => match (spec, date_format) {
+                            (Some(spec), _) => Segment::Formatted(name, spec),
+                            (None, Some(format)) => Segment::DateTime(name, format),
+                            (None, None) => Segment::Variable(name),
+                        },
+                        _ => {
+                            return Err(PromptError::invalid_template(
+                                template,
+                                format!("invalid placeholder `{{{}}}`", &template[i + 1..close]),
+                                i..close + 1,
+                            ))
+                        }
+                    },
+                };
+                if let Segment::Variable(path)
+                | Segment::Formatted(path, _)
+                | Segment::DateTime(path, _)
+                | Segment::If(path) = segment
+                {
+                    let is_positional = is_index(path);
+                    if *positional.get_or_insert(is_positional) != is_positional {
+                        return Err(PromptError::invalid_template(
+                            template,
+                            "positional and named placeholders can't be mixed",
+                            i..close + 1,
+                        ));
+                    }
+                }
+                if text_start < i {
+                    segments.push(Segment::Text(&template[text_start..i]));
+                }
+                segments.push(segment);
+                text_start = close + 1;
+            }
+        }
+    }
+
+    if let Some((position, _)) = blocks.pop() {
+        let tag_end = template[position..]
+            .find('}')
+            .map_or(position + 1, |p| position + p + 1);
+        return Err(PromptError::invalid_template(
+            template,
+            "unclosed `{#if}` block",
+            position..tag_end,
+        ));
+    }
+    if text_start < template.len() {
+        segments.push(Segment::Text(&template[text_start..]));
+    }
+    Ok(segments)
+}
+
+// The line of the comment between `start` and `end`, line break included, if there's only
+// whitespace around the comment on it.
+fn comment_line(template: &str, start: usize, end: usize) -> Option<(usize, usize)> {
+    let line_start = template[..start].rfind('\n').map_or(0, |p| p + 1);
+    let line_end = template[end..]
+        .find('\n')
+        .map_or(template.len(), |p| end + p + 1);
+    let is_blank = |text: &str| text.chars().all(char::is_whitespace);
+    (is_blank(&template[line_start..start]) && is_blank(&template[end..line_end]))
+        .then_some((line_start, line_end))
+}
+
+/// Checks the delimiters of a `TemplateFormat::Custom` template: they can't be empty, and
+/// neither can be a prefix of the other, or placeholders would be ambiguous.
+pub(crate) fn validate_delimiters(open: &str, close: &str) -> Result<(), PromptError> {
+    let reason = if open.is_empty() || close.is_empty() {
+        "delimiters can't be empty"
+    } else if open.starts_with(close) || close.starts_with(open) {
+        "a delimiter can't be a prefix of the other"
+    } else {
+        return Ok(());
+    };
+    Err(PromptError::InvalidDelimiters {
+        open: open.to_string(),
+        close: close.to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+/// Splits a template with custom delimiters into text and placeholder segments. A doubled
+/// open delimiter is an escape rendering as a single one, while a lone close delimiter is
+/// plain text.
+pub(crate) fn parse_custom<'a>(
+    template: &'a str,
+    open: &str,
+    close: &str,
+) -> Result<Vec<Segment<'a>>, PromptError> {
+    validate_delimiters(open, close)?;
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < template.len() {
+        let rest = &template[i..];
+        if !rest.starts_with(open) {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        }
+        let body_start = i + open.len();
+        if template[body_start..].starts_with(open) {
+            // keep one delimiter of the pair and skip the other
+            segments.push(Segment::Text(&template[text_start..body_start]));
+            i = body_start + open.len();
+            text_start = i;
+            continue;
+        }
+        let end = template[body_start..]
+            .find(close)
+            .map(|p| p + body_start)
+            .ok_or_else(|| {
+                PromptError::invalid_template(template, "unclosed placeholder", i..template.len())
+            })?;
+        let name = template[body_start..end].trim();
+        if !is_path(name) {
+            return Err(PromptError::invalid_template(
+                template,
+                format!("invalid placeholder `{}`", &template[i..end + close.len()]),
+                i..end + close.len(),
+            ));
+        }
+        if text_start < i {
+            segments.push(Segment::Text(&template[text_start..i]));
+        }
+        segments.push(Segment::Variable(name));
+        i = end + close.len();
+        text_start = i;
+    }
+    if text_start < template.len() {
+        segments.push(Segment::Text(&template[text_start..]));
+    }
+    Ok(segments)
+}
+
+/// Returns the roots of the variables used only inside conditional blocks, including the
+/// conditions, which `format` treats as optional.
+pub(crate) fn conditional_variables(segments: &[Segment]) -> Vec<String> {
+    let mut inside = Vec::new();
+    let mut outside = Vec::new();
+    let mut depth = 0usize;
+    for segment in segments {
+        match segment {
+            Segment::If(path) => {
+                push_unique(&mut inside, root(path));
+                depth += 1;
+            }
+            Segment::EndIf => depth = depth.saturating_sub(1),
+            Segment::Variable(path)
+            | Segment::Formatted(path, _)
+            | Segment::Filtered(path, _)
+            | Segment::DateTime(path, _)
+                if depth > 0 =>
+            {
+                push_unique(&mut inside, root(path))
+            }
+            Segment::Variable(path)
+            | Segment::Formatted(path, _)
+            | Segment::Filtered(path, _)
+            | Segment::DateTime(path, _) => push_unique(&mut outside, root(path)),
+            Segment::Text(_) | Segment::Env(_) | Segment::Else | Segment::Comment(_) => {}
+        }
+    }
+    inside.retain(|variable| !outside.contains(variable));
+    inside
+}
+
+fn root(path: &str) -> &str {
+    path.split('.').next().unwrap_or_default()
+}
+
+/// Splits a Jinja2 template on its simple `{{ var }}` expressions. Everything else,
+/// including `{% %}` blocks, is kept as text.
+pub(crate) fn parse_jinja2(template: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut search_from = 0;
+
+    while let Some(start) = template[search_from..].find("{{").map(|p| p + search_from) {
+        let Some(end) = template[start + 2..].find("}}").map(|p| p + start + 2) else {
+            break;
+        };
+        let name = template[start + 2..end].trim();
+        if is_identifier(name) {
+            if text_start < start {
+                segments.push(Segment::Text(&template[text_start..start]));
+            }
+            segments.push(Segment::Variable(name));
+            text_start = end + 2;
+        }
+        search_from = end + 2;
+    }
+
+    if text_start < template.len() {
+        segments.push(Segment::Text(&template[text_start..]));
+    }
+    segments
+}
+
+/// Finds every `{{ var }}` expression in a Jinja2 template, and the sequences of the
+/// `{% for %}` loops. Loop targets and `loop` are local to their loops, so they're not
+/// variables of the template.
+pub(crate) fn parse_jinja2_variables(template: &str) -> Result<Vec<String>, PromptError> {
+    let mut variables = Vec::new();
+    let mut rest = template;
+    let mut offset = 0;
+    // the targets of each open loop
+    let mut loops: Vec<Vec<&str>> = Vec::new();
+    let is_local = |loops: &[Vec<&str>], name: &str| {
+        !loops.is_empty() && (name == "loop" || loops.iter().flatten().any(|t| *t == name))
+    };
+
+    while let Some(start) = rest.find('{') {
+        let (close, is_expression) = match rest[start..].get(..2) {
+            Some("{{") => ("}}", true),
+            Some("{%") => ("%}", false),
+            Some("{#") => ("#}", false),
+            _ => {
+                offset += start + 1;
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+        let body_start = start + 2;
+        let end = rest[body_start..].find(close).ok_or_else(|| {
+            PromptError::invalid_template(
+                template,
+                format!("unclosed `{}`", &rest[start..body_start]),
+                offset + start..template.len(),
+            )
+        })? + body_start;
+
+        if is_expression {
+            let expression = rest[body_start..end].trim();
+            let name = expression
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or_default();
+            if !is_identifier(name) {
+                return Err(PromptError::invalid_template(
+                    template,
+                    format!("invalid expression `{{{{{}}}}}`", &rest[body_start..end]),
+                    offset + start..offset + end + 2,
+                ));
+            }
+            if !is_local(&loops, name) {
+                push_unique(&mut variables, name);
+            }
+        } else if close == "%}" {
+            let tag = rest[body_start..end].trim_matches(|c: char| c.is_whitespace() || c == '-');
+            if let Some((targets, sequence)) = parse_jinja2_loop(tag) {
+                if is_identifier(root(sequence)) && !is_local(&loops, root(sequence)) {
+                    push_unique(&mut variables, root(sequence));
+                }
+                loops.push(targets);
+            } else if tag == "endfor" {
+                loops.pop();
+            } else if let Some(condition) = tag
+                .strip_prefix("if ")
+                .or_else(|| tag.strip_prefix("elif "))
+            {
+                for name in jinja2_expression_names(condition) {
+                    if !is_local(&loops, name) {
+                        push_unique(&mut variables, name);
+                    }
+                }
+            }
+        }
+
+        offset += end + 2;
+        rest = &rest[end + 2..];
+    }
+
+    Ok(variables)
+}
+
+/// Finds the names an expression like `user.admin and not items|length > 0` reads.
+/// Attributes, filters, tests, calls, literals and keywords are skipped.
+fn jinja2_expression_names(expression: &str) -> Vec<&str> {
+    const KEYWORDS: [&str; 13] = [
+        "and", "or", "not", "in", "is", "if", "else", "true", "false", "none", "True", "False",
+        "None",
+    ];
+    let mut names = Vec::new();
+    // the last character before the current token that isn't whitespace
+    let mut previous = ' ';
+    let mut after_is = false;
+    let mut chars = expression.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c == '"' || c == '\'' {
+            chars.by_ref().find(|(_, next)| *next == c);
+            previous = c;
+            after_is = false;
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut end = expression.len();
+            while let Some(&(i, next)) = chars.peek() {
+                if !(next.is_alphanumeric() || next == '_') {
+                    end = i;
+                    break;
+                }
+                chars.next();
+            }
+            let name = &expression[start..end];
+            let is_call = expression[end..].trim_start().starts_with('(');
+            if is_identifier(name)
+                && !KEYWORDS.contains(&name)
+                && !matches!(previous, '.' | '|')
+                && !after_is
+                && !is_call
+            {
+                names.push(name);
+            }
+            after_is = name == "is" || (after_is && name == "not");
+            previous = 'a';
+        } else if !c.is_whitespace() {
+            previous = c;
+            after_is = false;
+        }
+    }
+    names
+}
+
+/// Splits the tag of a `{% for %}` loop, like `for key, value in items.pairs`, into its
+/// targets and the path of its sequence.
+pub(crate) fn parse_jinja2_loop(tag: &str) -> Option<(Vec<&str>, &str)> {
+    let (targets, sequence) = tag.strip_prefix("for ")?.split_once(" in ")?;
+    let sequence = sequence.trim_start();
+    let end = sequence
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+        .unwrap_or(sequence.len());
+    let targets = targets
+        .split(',')
+        .map(|target| target.trim().trim_matches(['(', ')']))
+        .collect();
+    Some((targets, &sequence[..end]))
+}
+
+/// Finds the top-level names of a Mustache template: plain `{{var}}` tags and the names of
+/// `{{#section}}` and `{{^inverted}}` sections. Tags inside sections refer to the section's
+/// context and are not reported.
+pub(crate) fn parse_mustache_variables(template: &str) -> Result<Vec<String>, PromptError> {
+    let mut variables = Vec::new();
+    let mut depth = 0usize;
+    let mut offset = 0;
+
+    while let Some(start) = template[offset..].find("{{").map(|p| p + offset) {
+        let (body_start, close) = if template[start..].starts_with("{{{") {
+            (start + 3, "}}}")
+        } else {
+            (start + 2, "}}")
+        };
+        let end = template[body_start..]
+            .find(close)
+            .map(|p| p + body_start)
+            .ok_or_else(|| {
+                PromptError::invalid_template(template, "unclosed `{{`", start..template.len())
+            })?;
+        let tag = template[body_start..end].trim();
+        offset = end + close.len();
+
+        let (sigil, name) = match tag.chars().next() {
+            Some(c @ ('#' | '^' | '/' | '!' | '>' | '&')) => (Some(c), tag[1..].trim()),
+            _ => (None, tag),
+        };
+        // `{{#each items}}`-style helpers name the variable after the helper
+        let name = match sigil {
+            Some('#') | Some('/') => name.split_whitespace().last().unwrap_or_default(),
+            _ => name,
+        };
+        let name = name.split('.').next().unwrap_or_default();
+
+        match sigil {
+            Some('!') | Some('>') => {}
+            Some('/') => {
+                depth = depth.checked_sub(1).ok_or_else(|| {
+                    PromptError::invalid_template(
+                        template,
+                        format!("unopened section `{}`", tag),
+                        start..offset,
+                    )
+                })?;
+            }
+            Some('#') | Some('^') => {
+                if depth == 0 && is_identifier(name) {
+                    push_unique(&mut variables, name);
+                }
+                depth += 1;
+            }
+            _ => {
+                if depth == 0 && is_identifier(name) && name != "this" && name != "else" {
+                    push_unique(&mut variables, name);
+                }
+            }
+        }
+    }
+
+    if depth > 0 {
+        return Err(PromptError::invalid_template(
+            template,
+            "unclosed section",
+            template.len()..template.len(),
+        ));
+    }
+    Ok(variables)
+}
+
+/// Returns the deduplicated placeholder names of a template, in order of first appearance.
+pub(crate) fn extract_variables(
+    template: &str,
+    format: &TemplateFormat,
+) -> Result<Vec<String>, PromptError> {
+    match format {
+        TemplateFormat::FString => {
+            let mut variables = Vec::new();
+            for segment in parse_fstring(template)? {
+                if let Segment::Variable(path)
+                | Segment::Formatted(path, _)
+                | Segment::Filtered(path, _)
+                | Segment::DateTime(path, _)
+                | Segment::If(path) = segment
+                {
+                    push_unique(&mut variables, root(path));
+                }
+            }
+            Ok(variables)
+        }
+        TemplateFormat::Jinja2 => parse_jinja2_variables(template),
+        TemplateFormat::Mustache => parse_mustache_variables(template),
+        TemplateFormat::Custom { open, close } => {
+            let mut variables = Vec::new();
+            for segment in parse_custom(template, open, close)? {
+                if let Segment::Variable(path) = segment {
+                    push_unique(&mut variables, root(path));
+                }
+            }
+            Ok(variables)
+        }
+    }
+}
+
+fn push_unique(variables: &mut Vec<String>, name: &str) {
+    if !variables.iter().any(|v| v == name) {
+        variables.push(name.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_fstring_variables() {
+        let variables = extract_variables(
+            "Hi {name}, {{not_a_var}} {name} {age}",
+            &TemplateFormat::FString,
+        )
+        .unwrap();
+        assert_eq!(variables, vec!["name", "age"]);
+    }
+
+    #[test]
+    fn test_extract_fstring_dot_paths() {
+        let variables = extract_variables(
+            "{user.name} has {items.0} and {user.address.city}",
+            &TemplateFormat::FString,
+        )
+        .unwrap();
+        assert_eq!(variables, vec!["user", "items"]);
+
+        assert!(extract_variables("{user.}", &TemplateFormat::FString).is_err());
+        assert!(extract_variables("{0.user}", &TemplateFormat::FString).is_err());
+    }
+
+    #[test]
+    fn test_extract_positional_variables() {
+        let variables = extract_variables("{0} is {1}, {0}!", &TemplateFormat::FString).unwrap();
+        assert_eq!(variables, vec!["0", "1"]);
+
+        match extract_variables("{0} is {name}", &TemplateFormat::FString) {
+            Err(PromptError::InvalidTemplate { span, .. }) => assert_eq!(span.start, 7),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(extract_variables("{#if name}{0}{/if}", &TemplateFormat::FString).is_err());
+    }
+
+    #[test]
+    fn test_parse_fstring_format_specs() {
+        let segments = parse_fstring("{{{score:>8}}} {pi:.2}").unwrap();
+        assert_eq!(segments[0], Segment::Text("{"));
+        assert!(matches!(&segments[1], Segment::Formatted("score", spec) if spec.raw == ">8"));
+        assert_eq!(segments[2], Segment::Text("}"));
+        assert!(matches!(&segments[4], Segment::Formatted("pi", spec) if spec.raw == ".2"));
+
+        let variables =
+            extract_variables("{score:>8} {score} {n:03}", &TemplateFormat::FString).unwrap();
+        assert_eq!(variables, vec!["score", "n"]);
+
+        match extract_variables("Score: {score:x}", &TemplateFormat::FString) {
+            Err(PromptError::InvalidTemplate { reason, span, .. }) => {
+                assert!(reason.contains("`x`"));
+                assert_eq!(span.range(), 7..16);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_fstring_escaped_braces() {
+        let segments = parse_fstring("{{{name}}} {{x}}").unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Text("{"),
+                Segment::Variable("name"),
+                Segment::Text("}"),
+                Segment::Text(" {"),
+                Segment::Text("x}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fstring_conditional_blocks() {
+        let segments =
+            parse_fstring("{#if context}Use: {context}{#else}None{/if}{#if a.b}{#if c}x{/if}{/if}")
+                .unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                Segment::If("context"),
+                Segment::Text("Use: "),
+                Segment::Variable("context"),
+                Segment::Else,
+                Segment::Text("None"),
+                Segment::EndIf,
+                Segment::If("a.b"),
+                Segment::If("c"),
+                Segment::Text("x"),
+                Segment::EndIf,
+                Segment::EndIf,
+            ]
+        );
+
+        let variables = extract_variables(
+            "{#if context}{context} {extra}{/if} {extra}",
+            &TemplateFormat::FString,
+        )
+        .unwrap();
+        assert_eq!(variables, vec!["context", "extra"]);
+        assert_eq!(
+            conditional_variables(
+                &parse_fstring("{#if context}{context} {extra}{/if} {extra}").unwrap()
+            ),
+            vec!["context"]
+        );
+    }
+
+    #[test]
+    fn test_malformed_conditional_blocks() {
+        let position = |template| match parse_fstring(template) {
+            Err(PromptError::InvalidTemplate { span, .. }) => span.start,
+            other => panic!("unexpected result: {:?}", other),
+        };
+        assert_eq!(position("Hi {#if name}{name}"), 3);
+        assert_eq!(position("Hi {#if a}{#if b}{/if}"), 3);
+        assert_eq!(position("{/if}"), 0);
+        assert_eq!(position("x {#else}"), 2);
+        assert_eq!(position("{#if a}{#else}{#else}{/if}"), 14);
+        assert_eq!(position("{#if a}{#if b}{#if c}{/if}{/if}{/if}"), 14);
+        assert_eq!(position("{#if }{/if}"), 0);
+    }
+
+    #[test]
+    fn test_parse_fstring_comments() {
+        let template = "Hi {name}{# tone: see {style},\n PROMPT-123 #}!\n  {#note #}  \nBye";
+        assert_eq!(
+            parse_fstring(template).unwrap(),
+            vec![
+                Segment::Text("Hi "),
+                Segment::Variable("name"),
+                Segment::Comment("{# tone: see {style},\n PROMPT-123 #}"),
+                Segment::Text("!\n  "),
+                Segment::Comment("{#note #}"),
+                Segment::Text("  \nBye"),
+            ]
+        );
+        assert_eq!(
+            parse_fstring_with(template, true).unwrap()[3..],
+            [
+                Segment::Text("!\n"),
+                Segment::Comment("  {#note #}  \n"),
+                Segment::Text("Bye")
+            ]
+        );
+        assert_eq!(
+            extract_variables(template, &TemplateFormat::FString).unwrap(),
+            vec!["name"]
+        );
+        assert_eq!(
+            parse_fstring("{#if a}x{#else }y{/if}").unwrap()[2],
+            Segment::Else
+        );
+
+        match parse_fstring("Hi {name} {# unclosed }") {
+            Err(PromptError::InvalidTemplate { span, reason, .. }) => {
+                assert_eq!(span.start, 10);
+                assert_eq!(reason, "unclosed comment");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_extract_jinja2_variables() {
+        let variables = extract_variables(
+            "{# comment #}{% if x %}Hi {{ name }}{% endif %} {{name}} {{ age }}",
+            &TemplateFormat::Jinja2,
+        )
+        .unwrap();
+        assert_eq!(variables, vec!["x", "name", "age"]);
+
+        let variables = extract_variables(
+            "{% if user.admin and not items|length > 0 %}{% elif \"x\" in tags is not none %}\
+             {% endif %}{% for item in items %}{% if item.done or range(n) %}{% endif %}{% endfor %}",
+            &TemplateFormat::Jinja2,
+        )
+        .unwrap();
+        assert_eq!(variables, vec!["user", "items", "tags", "n"]);
+
+        let variables = extract_variables(
+            "{% for doc in docs %}{{ loop.index }}. {{ doc.title }}\
+             {%- for key, value in doc.meta|items %}{{ key }}{{ value }}{% endfor %}\
+             {% endfor %}{{ doc }} {% for tag in tags if tag %}{% endfor %}",
+            &TemplateFormat::Jinja2,
+        )
+        .unwrap();
+        assert_eq!(variables, vec!["docs", "doc", "tags"]);
+    }
+
+    #[test]
+    fn test_extract_mustache_variables() {
+        let variables = extract_variables(
+            "{{! comment }}Hi {{name}}, {{{raw}}} {{#items}}- {{label}}{{/items}}{{^items}}none{{/items}} {{user.age}}",
+            &TemplateFormat::Mustache,
+        )
+        .unwrap();
+        assert_eq!(variables, vec!["name", "raw", "items", "user"]);
+
+        assert!(extract_variables("{{#items}}open", &TemplateFormat::Mustache).is_err());
+        assert!(extract_variables("{{/items}}", &TemplateFormat::Mustache).is_err());
+    }
+
+    #[test]
+    fn test_malformed_placeholders() {
+        assert!(extract_variables("Hi {name", &TemplateFormat::FString).is_err());
+        assert!(extract_variables("Hi {}", &TemplateFormat::FString).is_err());
+        assert!(extract_variables("Hi name}", &TemplateFormat::FString).is_err());
+        assert!(extract_variables("Hi {{ name", &TemplateFormat::Jinja2).is_err());
+
+        match extract_variables("Hello {name", &TemplateFormat::FString) {
+            Err(PromptError::InvalidTemplate { span, .. }) => assert_eq!(span.start, 6),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_custom() {
+        assert_eq!(
+            parse_custom("Hi <<name>>, <<<<not>> >> <<user.id>>", "<<", ">>").unwrap(),
+            vec![
+                Segment::Text("Hi "),
+                Segment::Variable("name"),
+                Segment::Text(", <<"),
+                Segment::Text("not>> >> "),
+                Segment::Variable("user.id"),
+            ]
+        );
+        let custom = |open: &str, close: &str| TemplateFormat::Custom {
+            open: open.into(),
+            close: close.into(),
+        };
+        assert_eq!(
+            extract_variables("[% a %] and [%b%] [% a %]", &custom("[%", "%]")).unwrap(),
+            vec!["a", "b"]
+        );
+        match extract_variables("Hi <%name", &custom("<%", "%>")) {
+            Err(PromptError::InvalidTemplate { span, .. }) => assert_eq!(span.start, 3),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(extract_variables("Hi <%%>", &custom("<%", "%>")).is_err());
+        assert!(extract_variables("Hi <% a b %>", &custom("<%", "%>")).is_err());
+
+        for (open, close) in [("", ">"), ("<", ""), ("<<", "<"), ("$", "$$"), ("@", "@")] {
+            assert!(
+                matches!(
+                    TemplateFormat::custom(open, close),
+                    Err(PromptError::InvalidDelimiters { .. })
+                ),
+                "{} {}",
+                open,
+                close
+            );
+        }
+        assert!(TemplateFormat::custom("${", "}").is_ok());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::schemas::prompt::PromptValue;
+
+use super::{FormatPrompter, PromptArgs, PromptError, PromptFromatter, PromptTemplate};
+
+/// Struct `PipelinePromptTemplate` composes a prompt out of reusable sub-prompts.
+/// Each stage is formatted with the input variables, and its result is injected into the
+/// final prompt as the variable named after the stage. Stages can also use the results of
+/// the stages before them.
+///
+/// # Usage
+/// ```rust,ignore
+/// let pipeline = PipelinePromptTemplate::new(
+///     template_fstring!("{persona}\n{instructions}\n{input}", "persona", "instructions", "input"),
+///     vec![
+///         ("persona".to_string(), template_fstring!("You are {name}.", "name").into()),
+///         ("instructions".to_string(), template_fstring!("Answer in {language}.", "language").into()),
+///     ],
+/// );
+/// assert_eq!(pipeline.variables(), vec!["input", "name", "language"]);
+/// ```
+pub struct PipelinePromptTemplate {
+    final_prompt: PromptTemplate,
+    pipeline_prompts: Vec<(String, Box<dyn PromptFromatter>)>,
+}
+
+impl PipelinePromptTemplate {
+    pub fn new(
+        final_prompt: PromptTemplate,
+        pipeline_prompts: Vec<(String, Box<dyn PromptFromatter>)>,
+    ) -> Self {
+        Self {
+            final_prompt,
+            pipeline_prompts,
+        }
+    }
+
+    /// Appends a stage whose result is injected into the final prompt as `name`.
+    pub fn with_stage<S: Into<String>, P: Into<Box<dyn PromptFromatter>>>(
+        mut self,
+        name: S,
+        prompt: P,
+    ) -> Self {
+        self.pipeline_prompts.push((name.into(), prompt.into()));
+        self
+    }
+
+    fn stage_names(&self) -> Vec<&str> {
+        self.pipeline_prompts
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
+
+impl PromptFromatter for PipelinePromptTemplate {
+    fn template(&self) -> String {
+        self.final_prompt.template()
+    }
+
+    fn variables(&self) -> Vec<String> {
+        let injected = self.stage_names();
+        let mut variables = Vec::new();
+        let stage_variables = self
+            .pipeline_prompts
+            .iter()
+            .flat_map(|(_, prompt)| prompt.variables());
+        for variable in self
+            .final_prompt
+            .variables()
+            .into_iter()
+            .chain(stage_variables)
+        {
+            if !injected.contains(&variable.as_str()) && !variables.contains(&variable) {
+                variables.push(variable);
+            }
+        }
+        variables
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        let mut variables = input_variables;
+        for (name, prompt) in &self.pipeline_prompts {
+            let result = prompt
+                .format(variables.clone())
+                .map_err(|e| PromptError::StageError {
+                    stage: name.clone(),
+                    source: Box::new(e),
+                })?;
+            variables.insert(name.clone(), result.into());
+        }
+        self.final_prompt.format(variables)
+    }
+}
+
+impl FormatPrompter for PipelinePromptTemplate {
+    fn format_prompt(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
+        self.to_prompt_value(input_variables)
+    }
+    fn get_input_variables(&self) -> Vec<String> {
+        self.variables()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, template_fstring};
+
+    #[test]
+    fn test_pipeline_prompt_template() {
+        let pipeline = PipelinePromptTemplate::new(
+            template_fstring!(
+                "{persona}\n{instructions}\nQuestion: {input}",
+                "persona",
+                "instructions",
+                "input"
+            ),
+            vec![(
+                "persona".to_string(),
+                template_fstring!("You are {name}.", "name").into(),
+            )],
+        )
+        .with_stage(
+            "instructions",
+            template_fstring!("{persona} Answer in {language}.", "persona", "language"),
+        );
+
+        assert_eq!(pipeline.variables(), vec!["input", "name", "language"]);
+
+        let result = pipeline
+            .format(prompt_args! {
+                "name" => "a pirate",
+                "language" => "English",
+                "input" => "Where is the treasure?",
+            })
+            .unwrap();
+        assert_eq!(
+            result,
+            "You are a pirate.\nYou are a pirate. Answer in English.\nQuestion: Where is the treasure?"
+        );
+
+        match pipeline.format(prompt_args! { "name" => "a pirate", "input" => "hi" }) {
+            Err(PromptError::StageError { stage, source }) => {
+                assert_eq!(stage, "instructions");
+                assert!(
+                    matches!(*source, PromptError::InvalidInput(e) if e.missing == vec!["language"])
+                );
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -1,201 +1,2867 @@
-use crate::schemas::{messages::Message, prompt::PromptValue};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Arc,
+};
 
-use super::{FormatPrompter, PromptArgs, PromptError, PromptFromatter};
+use chrono::{DateTime, FixedOffset};
+use futures::future::{join_all, BoxFuture};
 
-#[derive(Clone)]
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{output_parsers::FormatInstructions, schemas::prompt::PromptValue};
+
+#[cfg(feature = "jinja2")]
+use super::jinja2;
+#[cfg(feature = "mustache")]
+use super::mustache;
+use super::{
+    compress_prompt_with_report,
+    format_options::dedent,
+    loading::PromptTemplateData,
+    parser::{self, Segment},
+    Clock, CompressOptions, CompressReport, FormatOptions, FormatPrompter, PromptArgs, PromptError,
+    PromptFromatter, PromptInputError, PromptMetadata, PromptTemplateBuilder, Span, SystemClock,
+    Truncation, ValueSanitizer, VarLimit, VariableResolver,
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum TemplateFormat {
+    #[default]
+    #[serde(rename = "f-string")]
     FString,
+    /// Jinja2 templates. With the `jinja2` feature enabled they are rendered by
+    /// minijinja, so `{% if %}`, `{% for %}` and filters are evaluated. Without it,
+    /// only plain `{{var}}` placeholders are substituted.
+    #[serde(rename = "jinja2")]
     Jinja2,
+    /// Mustache templates. With the `mustache` feature enabled `{{#section}}` and
+    /// `{{^inverted}}` sections are evaluated, and values are never HTML-escaped.
+    /// Without it, only plain `{{var}}` placeholders are substituted.
+    #[serde(rename = "mustache")]
+    Mustache,
+    /// Templates with placeholders between custom delimiters, like `<<var>>` or Go's
+    /// `{{.Var}}`, with the same dot paths as FString. A doubled open delimiter renders as a
+    /// single one. Create it with `TemplateFormat::custom` to check the delimiters.
+    #[serde(rename = "custom")]
+    Custom { open: String, close: String },
+}
+
+impl TemplateFormat {
+    /// Creates a `Custom` format.
+    ///
+    /// # Errors
+    /// Returns `PromptError::InvalidDelimiters` if a delimiter is empty, or a prefix of the
+    /// other.
+    pub fn custom<O: Into<String>, C: Into<String>>(
+        open: O,
+        close: C,
+    ) -> Result<Self, PromptError> {
+        let (open, close) = (open.into(), close.into());
+        parser::validate_delimiters(&open, &close)?;
+        Ok(TemplateFormat::Custom { open, close })
+    }
+}
+
+/// What `format` does with a variable missing from the input.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum MissingVariableBehavior {
+    /// Fail with `PromptError::MissingVariable`.
+    #[default]
+    Error,
+    /// Leave the placeholder as it is in the template, e.g. `{var}`.
+    LeaveAsIs,
+    /// Replace the placeholder with an empty string.
+    Empty,
+    /// Replace the placeholder with a marker, where `{}` is replaced by the variable name,
+    /// e.g. `Marker("<MISSING:{}>".into())`.
+    Marker(String),
 }
 
-#[derive(Clone)]
+/// A template rendered to a single string. It (de)serializes in the prompt file layout used by
+/// Python LangChain, see `load_prompt`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "PromptTemplateData", try_from = "PromptTemplateData")]
 pub struct PromptTemplate {
     template: String,
     variables: Vec<String>,
     format: TemplateFormat,
+    partial_variables: PromptArgs,
+    defaults: HashMap<String, String>,
+    // the defaults as values, so formatting can look them up by reference
+    default_values: PromptArgs,
+    optional_variables: Vec<String>,
+    collapse_blank_lines: bool,
+    strip_comment_lines: bool,
+    missing_variable_behavior: MissingVariableBehavior,
+    resolvers: Vec<(String, Arc<dyn VariableResolver>)>,
+    sanitizer: Option<Arc<dyn ValueSanitizer>>,
+    compression: Option<CompressOptions>,
+    trusted_variables: Vec<String>,
+    limits: Vec<(String, VarLimit)>,
+    aliases: HashMap<String, String>,
+    allowed_env: Vec<String>,
+    env_fallbacks: HashMap<String, String>,
+    clock: Option<Arc<dyn Clock>>,
+    timezone: FixedOffset,
+    metadata: PromptMetadata,
+    #[cfg(feature = "jinja2")]
+    loader: Option<Arc<dyn super::TemplateLoader>>,
+    #[cfg(feature = "jinja2")]
+    filters: Vec<(String, Arc<dyn super::TemplateFilter>)>,
 }
 
 impl PromptTemplate {
+    /// Creates a new `PromptTemplate` without validating the template against the declared
+    /// variables. This is the lenient constructor, useful for templates that intentionally
+    /// contain unescaped braces; use `try_new` to catch mismatches early.
+    ///
+    /// # Panics
+    /// In debug builds, if the template parses and its placeholders differ from `variables`.
     pub fn new(template: String, variables: Vec<String>, format: TemplateFormat) -> Self {
+        #[cfg(debug_assertions)]
+        if let Ok(found) = parser::extract_variables(&template, &format) {
+            debug_assert!(
+                found.iter().all(|v| variables.contains(v))
+                    && variables.iter().all(|v| found.contains(v)),
+                "Declared variables {:?} do not match the template placeholders {:?}",
+                variables,
+                found
+            );
+        }
+        Self::new_unchecked(template, variables, format)
+    }
+
+    // `new` without the debug assertion, for templates that are meant not to match.
+    pub(crate) fn new_unchecked(
+        template: String,
+        variables: Vec<String>,
+        format: TemplateFormat,
+    ) -> Self {
         Self {
             template,
             variables,
             format,
+            partial_variables: PromptArgs::new(),
+            defaults: HashMap::new(),
+            default_values: PromptArgs::new(),
+            optional_variables: Vec::new(),
+            collapse_blank_lines: false,
+            strip_comment_lines: false,
+            missing_variable_behavior: MissingVariableBehavior::default(),
+            resolvers: Vec::new(),
+            sanitizer: None,
+            compression: None,
+            trusted_variables: Vec::new(),
+            limits: Vec::new(),
+            aliases: HashMap::new(),
+            allowed_env: Vec::new(),
+            env_fallbacks: HashMap::new(),
+            clock: None,
+            timezone: FixedOffset::east_opt(0).expect("UTC is a valid offset"),
+            metadata: PromptMetadata::default(),
+            #[cfg(feature = "jinja2")]
+            loader: None,
+            #[cfg(feature = "jinja2")]
+            filters: Vec::new(),
+        }
+    }
+
+    /// Creates a new `PromptTemplate`, checking that the declared variables are exactly the
+    /// placeholders used in the template.
+    ///
+    /// # Errors
+    /// Returns `PromptError::InvalidTemplate` if the template can't be parsed, and
+    /// `PromptError::VariablesMismatch` listing the declared but unused variables and the
+    /// used but undeclared ones.
+    pub fn try_new(
+        template: String,
+        variables: Vec<String>,
+        format: TemplateFormat,
+    ) -> Result<Self, PromptError> {
+        PromptTemplateBuilder::new()
+            .template(template)
+            .variables(variables)
+            .format(format)
+            .build_template()
+    }
+
+    /// Creates a new `PromptTemplate` inferring the variables from the placeholders
+    /// found in the template (`{var}` for FString, `{{var}}` for Jinja2 and Mustache).
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = PromptTemplate::from_template("Hello {name}", TemplateFormat::FString)?;
+    /// assert_eq!(prompt.variables(), vec!["name"]);
+    /// ```
+    pub fn from_template(template: &str, format: TemplateFormat) -> Result<Self, PromptError> {
+        PromptTemplateBuilder::new()
+            .template(template)
+            .format(format)
+            .infer_variables()
+            .build_template()
+    }
+
+    /// Formats the template like `PromptFromatter::format`, from anything convertible into
+    /// `PromptArgs`, such as a struct deriving `IntoPromptArgs`.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// #[derive(IntoPromptArgs)]
+    /// struct Args {
+    ///     name: String,
+    ///     age: u32,
+    /// }
+    ///
+    /// let result = prompt.format(Args { name: "Ana".into(), age: 30 })?;
+    /// ```
+    pub fn format<A: Into<PromptArgs>>(&self, input_variables: A) -> Result<String, PromptError> {
+        let (prompt, _) = self
+            .format_with_behavior(input_variables.into(), &self.missing_variable_behavior)
+            .map_err(|e| self.named_error(e))?;
+        let prompt = match self.compression {
+            Some(options) => {
+                let report = compress_prompt_with_report(&prompt, options);
+                log::debug!(
+                    "Compressed prompt from {} to {} chars",
+                    report.chars_before,
+                    report.chars_after
+                );
+                report.text
+            }
+            None => prompt,
+        };
+
+        log::debug!("Formatted prompt: {}", prompt);
+        Ok(prompt)
+    }
+
+    /// Sets the name, tags, version and other metadata of the prompt.
+    pub fn with_metadata(mut self, metadata: PromptMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    // Adds the name of the prompt, when it has one, to the errors of `format`.
+    fn named_error(&self, error: PromptError) -> PromptError {
+        match &self.metadata.name {
+            Some(name) => PromptError::NamedPromptError {
+                name: name.clone(),
+                source: Box::new(error),
+            },
+            None => error,
+        }
+    }
+
+    /// Returns a `PromptTemplateBuilder`, to set the template, its variables, defaults and
+    /// partial variables at once.
+    pub fn builder() -> PromptTemplateBuilder {
+        PromptTemplateBuilder::new()
+    }
+
+    /// Returns a new `PromptTemplate` with some of its variables already bound, in an `Arc`
+    /// so chains can share it. The bound variables are no longer required by `format` and are
+    /// not listed in `variables`.
+    ///
+    /// To keep configuring it with the `with_*` methods, take it out of the `Arc` with
+    /// `PromptTemplate::from`, which doesn't clone it while it isn't shared.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = template_fstring!("{persona}: {input}", "persona", "input")
+    ///     .partial(prompt_args! { "persona" => "pirate" });
+    /// assert_eq!(prompt.variables(), vec!["input"]);
+    /// ```
+    pub fn partial(&self, partial_variables: PromptArgs) -> Arc<PromptTemplate> {
+        Arc::new(self.clone().bind(partial_variables))
+    }
+
+    // Binds some of the variables, like `partial`, without cloning the template.
+    pub(crate) fn bind(mut self, partial_variables: PromptArgs) -> Self {
+        let partial_variables = self.unalias(partial_variables);
+        self.variables
+            .retain(|variable| !partial_variables.contains_key(variable));
+        self.partial_variables.extend(partial_variables);
+        self
+    }
+
+    /// Binds the format instructions of an output parser to the `format_instructions`
+    /// variable, so the prompt stays in sync with the parser. Like every variable value, the
+    /// instructions are inserted as they are, never scanned for placeholders.
+    ///
+    /// # Errors
+    /// Returns `PromptError::UnknownVariable` if the template has no `format_instructions`
+    /// variable.
+    pub fn with_output_parser<P: FormatInstructions + ?Sized>(
+        self,
+        parser: &P,
+    ) -> Result<Self, PromptError> {
+        const VARIABLE: &str = "format_instructions";
+        if !self.variables.iter().any(|v| v == VARIABLE)
+            && !self.partial_variables.contains_key(VARIABLE)
+        {
+            return Err(PromptError::UnknownVariable(VARIABLE.to_string()));
+        }
+        Ok(self.bind(PromptArgs::from([(
+            VARIABLE.to_string(),
+            Value::String(parser.format_instructions()),
+        )])))
+    }
+
+    /// Returns the template without cloning it, unlike `PromptFromatter::template`.
+    pub fn template_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Returns the variables without cloning them, unlike `PromptFromatter::variables`.
+    pub fn variable_names(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn template_format(&self) -> &TemplateFormat {
+        &self.format
+    }
+
+    /// Returns the variables bound through `partial`.
+    pub fn partial_variables(&self) -> &PromptArgs {
+        &self.partial_variables
+    }
+
+    /// Returns a new `PromptTemplate` whose template has the given variables substituted, leaving
+    /// the other placeholders intact. Unlike `format`, missing variables are not an error.
+    /// Substituted values are escaped, so braces in them never become new placeholders.
+    ///
+    /// Jinja2 variables used in expressions other than a plain `{{var}}`, such as filters or
+    /// `{% if %}` blocks, are bound as partial variables instead.
+    ///
+    /// # Errors
+    /// Returns `PromptError::UnknownVariable` if a variable isn't used by the template.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = template_fstring!("Context: {context}\nQuestion: {question}", "context", "question")
+    ///     .format_partial(prompt_args! { "context" => retrieved_context })?;
+    /// assert_eq!(prompt.variables(), vec!["question"]);
+    /// ```
+    pub fn format_partial(&self, input_variables: PromptArgs) -> Result<Self, PromptError> {
+        if let Some(key) = input_variables
+            .keys()
+            .find(|key| !self.variables.iter().any(|v| v == self.internal_name(key)))
+        {
+            return Err(PromptError::UnknownVariable(key.clone()));
+        }
+        let input_variables = self.unalias(input_variables);
+
+        let mut template = String::with_capacity(self.template.len());
+        match self.format {
+            TemplateFormat::FString => {
+                for segment in parser::parse_fstring(&self.template)? {
+                    match segment {
+                        Segment::Text(text) => template.push_str(&escape_fstring(text)),
+                        Segment::Variable(path) => {
+                            match resolve_path(&input_variables, &HashMap::new(), path)? {
+                                Some(value) => template.push_str(&escape_fstring(&value)),
+                                None => template.push_str(&format!("{{{}}}", path)),
+                            }
+                        }
+                        Segment::Formatted(path, spec) => {
+                            match resolve_value(&input_variables, path)? {
+                                Some(value) => {
+                                    template.push_str(&escape_fstring(&spec.apply(path, value)?))
+                                }
+                                None => template.push_str(&format!("{{{}:{}}}", path, spec.raw)),
+                            }
+                        }
+                        Segment::Filtered(path, filters) => {
+                            match resolve_value(&input_variables, path)? {
+                                Some(value) => {
+                                    template.push_str(&escape_fstring(&filters.apply(path, value)?))
+                                }
+                                None => template.push_str(&format!("{{{}|{}}}", path, filters.raw)),
+                            }
+                        }
+                        Segment::DateTime(path, format) => {
+                            match resolve_value(&input_variables, path)? {
+                                Some(value) => {
+                                    template.push_str(&escape_fstring(&format_date(value, format)))
+                                }
+                                None => template.push_str(&format!("{{{}:{}}}", path, format)),
+                            }
+                        }
+                        Segment::Env(name) => template.push_str(&format!("{{env:{}}}", name)),
+                        Segment::If(path) => template.push_str(&format!("{{#if {}}}", path)),
+                        Segment::Else => template.push_str("{#else}"),
+                        Segment::EndIf => template.push_str("{/if}"),
+                        Segment::Comment(comment) => template.push_str(comment),
+                    }
+                }
+            }
+            TemplateFormat::Jinja2 | TemplateFormat::Mustache => {
+                for segment in parser::parse_jinja2(&self.template) {
+                    match segment {
+                        Segment::Text(text) => template.push_str(text),
+                        Segment::Variable(name) => match input_variables.get(name) {
+                            Some(value) => template.push_str(
+                                &self.escape_double_braces(name, &value_to_string(value))?,
+                            ),
+                            None => template.push_str(&format!("{{{{{}}}}}", name)),
+                        },
+                        Segment::Formatted(..)
+                        | Segment::Filtered(..)
+                        | Segment::Env(_)
+                        | Segment::DateTime(..)
+                        | Segment::If(_)
+                        | Segment::Else
+                        | Segment::EndIf
+                        | Segment::Comment(_) => {
+                            unreachable!(
+                                "only FString templates have format strings, filters, environment variables, conditional blocks and comments"
+                            )
+                        }
+                    }
+                }
+            }
+            TemplateFormat::Custom {
+                ref open,
+                ref close,
+            } => {
+                let escape = |text: &str| text.replace(open.as_str(), &open.repeat(2));
+                for segment in parser::parse_custom(&self.template, open, close)? {
+                    match segment {
+                        Segment::Text(text) => template.push_str(&escape(text)),
+                        Segment::Variable(path) => {
+                            match resolve_path(&input_variables, &HashMap::new(), path)? {
+                                Some(value) => template.push_str(&escape(&value)),
+                                None => template.push_str(&format!("{}{}{}", open, path, close)),
+                            }
+                        }
+                        Segment::Formatted(..)
+                        | Segment::Filtered(..)
+                        | Segment::Env(_)
+                        | Segment::DateTime(..)
+                        | Segment::If(_)
+                        | Segment::Else
+                        | Segment::EndIf
+                        | Segment::Comment(_) => {
+                            unreachable!(
+                                "only FString templates have format strings, filters, environment variables, conditional blocks and comments"
+                            )
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut prompt = self.clone();
+        prompt
+            .variables
+            .retain(|variable| !input_variables.contains_key(variable));
+        let still_used = parser::extract_variables(&template, &self.format).unwrap_or_default();
+        prompt.partial_variables.extend(
+            input_variables
+                .into_iter()
+                .filter(|(key, _)| still_used.contains(key)),
+        );
+        prompt.template = template;
+        Ok(prompt)
+    }
+
+    fn escape_double_braces(&self, name: &str, value: &str) -> Result<String, PromptError> {
+        let has_syntax = ["{{", "{%", "{#"].iter().any(|s| value.contains(s));
+        match self.format {
+            TemplateFormat::Jinja2 if has_syntax => {
+                Ok(format!("{{% raw %}}{}{{% endraw %}}", value))
+            }
+            TemplateFormat::Mustache if value.contains("{{") => Err(PromptError::OtherError(
+                format!("Value of {} contains `{{{{` and can't be substituted", name),
+            )),
+            _ => Ok(value.to_string()),
+        }
+    }
+
+    /// Registers default values for some of the variables. `format` falls back to them when
+    /// a variable is absent from the input; a value passed explicitly, even an empty string,
+    /// always wins.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = template_fstring!("Answer in {language}: {input}", "language", "input")
+    ///     .with_defaults(HashMap::from([("language".to_string(), "English".to_string())]));
+    /// assert_eq!(prompt.required_variables(), vec!["input"]);
+    /// ```
+    pub fn with_defaults(mut self, defaults: HashMap<String, String>) -> Self {
+        self.default_values.extend(
+            defaults
+                .iter()
+                .map(|(key, value)| (key.clone(), Value::String(value.clone()))),
+        );
+        self.defaults.extend(defaults);
+        self
+    }
+
+    /// Returns the default values registered through `with_defaults`.
+    pub fn defaults(&self) -> &HashMap<String, String> {
+        &self.defaults
+    }
+
+    /// Marks some of the variables as optional. `format` substitutes an empty string for an
+    /// optional variable absent from the input, instead of failing.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = template_fstring!("Summarize:\n{extra_instructions}\n{text}", "extra_instructions", "text")
+    ///     .with_optional_variables(["extra_instructions"])
+    ///     .with_collapse_blank_lines(true);
+    /// assert_eq!(prompt.format(prompt_args! { "text" => "..." })?, "Summarize:\n...");
+    /// ```
+    pub fn with_optional_variables<I, S>(mut self, optional_variables: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for variable in optional_variables {
+            let variable = variable.into();
+            if !self.optional_variables.contains(&variable) {
+                self.optional_variables.push(variable);
+            }
+        }
+        self
+    }
+
+    /// Returns the variables marked optional through `with_optional_variables`.
+    pub fn optional_variables(&self) -> &[String] {
+        &self.optional_variables
+    }
+
+    /// Sets whether a line holding nothing but absent optional variables is removed entirely,
+    /// instead of being left blank. Defaults to `false`.
+    pub fn with_collapse_blank_lines(mut self, collapse: bool) -> Self {
+        self.collapse_blank_lines = collapse;
+        self
+    }
+
+    /// Sets whether a `{# comment #}` alone on its line of an FString template is removed with
+    /// its line, instead of leaving it blank. Comments are never rendered, but are kept in the
+    /// template, and so when it's saved. Defaults to `false`.
+    pub fn with_strip_comment_lines(mut self, strip: bool) -> Self {
+        self.strip_comment_lines = strip;
+        self
+    }
+
+    /// Returns the variables that must be provided to `format`, that is, the variables
+    /// without a default value that aren't optional. Variables used only inside the
+    /// conditional blocks of an FString template are optional, and so are `now` and `today`
+    /// once a clock is set.
+    pub fn required_variables(&self) -> Vec<String> {
+        let conditional_variables = self.conditional_variables();
+        self.variables
+            .iter()
+            .filter(|variable| {
+                !self.defaults.contains_key(*variable)
+                    && !self.optional_variables.contains(variable)
+                    && !conditional_variables.contains(variable)
+                    && (self.clock.is_none()
+                        || !BUILTIN_TIME_VARIABLES.contains(&variable.as_str()))
+            })
+            .map(|variable| self.external_name(variable).to_string())
+            .collect()
+    }
+
+    /// Lets the input name variables differently from the template: each alias maps a
+    /// name of the input to the variable of the template it stands for. `variables`, the
+    /// required and missing variables and the validation of the input then report the
+    /// aliases, and aliased variables must be passed by their alias.
+    ///
+    /// # Errors
+    /// Returns `PromptError::UnknownVariable` if an alias maps to a variable the template
+    /// doesn't use, and `PromptError::InvalidAlias` if an alias is also a variable of the
+    /// template or two aliases map to the same variable.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = template_fstring!("Answer {q}", "q")
+    ///     .with_aliases(HashMap::from([("question".to_string(), "q".to_string())]))?;
+    /// assert_eq!(prompt.variables(), vec!["question"]);
+    /// prompt.format(prompt_args! { "question" => "Why?" })?;
+    /// ```
+    pub fn with_aliases(mut self, aliases: HashMap<String, String>) -> Result<Self, PromptError> {
+        for (alias, variable) in aliases {
+            let is_variable = |name: &String| {
+                self.variables.contains(name) || self.partial_variables.contains_key(name)
+            };
+            if !is_variable(&variable) {
+                return Err(PromptError::UnknownVariable(variable));
+            }
+            let reason = if is_variable(&alias) {
+                Some("it's a variable of the template".to_string())
+            } else if let Some((other, _)) = self
+                .aliases
+                .iter()
+                .find(|(other, target)| **target == variable && **other != alias)
+            {
+                Some(format!("the variable is already aliased as {}", other))
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                return Err(PromptError::InvalidAlias {
+                    alias,
+                    variable,
+                    reason,
+                });
+            }
+            self.aliases.insert(alias, variable);
+        }
+        Ok(self)
+    }
+
+    /// Returns the aliases set through `with_aliases`, from the name of the input to the
+    /// variable of the template.
+    pub fn aliases(&self) -> &HashMap<String, String> {
+        &self.aliases
+    }
+
+    fn internal_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, String::as_str)
+    }
+
+    fn external_name<'a>(&'a self, variable: &'a str) -> &'a str {
+        self.aliases
+            .iter()
+            .find(|(_, target)| *target == variable)
+            .map_or(variable, |(alias, _)| alias.as_str())
+    }
+
+    // Renames the aliases of `input_variables` to the variables they stand for.
+    fn unalias(&self, input_variables: PromptArgs) -> PromptArgs {
+        if self.aliases.is_empty() {
+            return input_variables;
+        }
+        input_variables
+            .into_iter()
+            .map(|(key, value)| (self.internal_name(&key).to_string(), value))
+            .collect()
+    }
+
+    fn conditional_variables(&self) -> Vec<String> {
+        match self.format {
+            TemplateFormat::FString => parser::parse_fstring(&self.template)
+                .map(|segments| parser::conditional_variables(&segments))
+                .unwrap_or_default(),
+            TemplateFormat::Jinja2 | TemplateFormat::Mustache | TemplateFormat::Custom { .. } => {
+                Vec::new()
+            }
+        }
+    }
+
+    /// Returns the variables absent from `input_variables` that have no default value.
+    pub fn missing_variables(&self, input_variables: &PromptArgs) -> Vec<String> {
+        self.required_variables()
+            .into_iter()
+            .filter(|key| !input_variables.contains_key(key))
+            .collect()
+    }
+
+    /// Checks `input_variables` against the template, reporting every missing variable and
+    /// every key the template doesn't use at once. Keys of partial variables are expected,
+    /// since they override the bound values.
+    ///
+    /// `format` fails on missing variables only, extra keys are ignored.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// if let Err(e) = template.validate_input(&args) {
+    ///     eprintln!("missing {:?}, unexpected {:?}", e.missing, e.unexpected);
+    /// }
+    /// ```
+    pub fn validate_input(&self, input_variables: &PromptArgs) -> Result<(), PromptInputError> {
+        let missing = self.missing_variables(input_variables);
+        let variables = PromptFromatter::variables(self);
+        let mut unexpected: Vec<String> = input_variables
+            .keys()
+            .filter(|key| {
+                !variables.contains(key)
+                    && !self.partial_variables.contains_key(self.internal_name(key))
+            })
+            .cloned()
+            .collect();
+        unexpected.sort();
+        if missing.is_empty() && unexpected.is_empty() {
+            Ok(())
+        } else {
+            Err(PromptInputError {
+                locations: self.missing_locations(&missing),
+                missing,
+                unexpected,
+            })
+        }
+    }
+
+    /// Sets the loader resolving the templates referenced by `{% include %}`, `{% extends %}`,
+    /// `{% import %}` and `{% from %}` in a Jinja2 template. The variables of the referenced
+    /// templates are added to the variables of the prompt.
+    ///
+    /// # Errors
+    /// Returns `PromptError::MissingInclude` naming a referenced template the loader doesn't
+    /// have and the template referencing it.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = template_jinja2!("{% include \"safety.j2\" %}\n{{ input }}", "input")
+    ///     .with_loader(DirectoryTemplateLoader::new("prompts/shared"))?;
+    /// ```
+    #[cfg(feature = "jinja2")]
+    pub fn with_loader<L: super::TemplateLoader + 'static>(
+        mut self,
+        loader: L,
+    ) -> Result<Self, PromptError> {
+        let loader: Arc<dyn super::TemplateLoader> = Arc::new(loader);
+        for variable in jinja2::included_variables(&self.template, loader.as_ref())? {
+            if !self.variables.contains(&variable)
+                && !self.partial_variables.contains_key(&variable)
+            {
+                self.variables.push(variable);
+            }
+        }
+        self.loader = Some(loader);
+        Ok(self)
+    }
+
+    /// Registers a custom filter for a Jinja2 template, usable as `{{ value | name(args) }}`
+    /// alongside the `BUILTIN_FILTERS`. Replaces any filter already registered with the name.
+    /// An error of the filter fails the format with a `PromptError::RenderError` naming it and
+    /// its position.
+    #[cfg(feature = "jinja2")]
+    pub fn with_filter<S: Into<String>, F: super::TemplateFilter + 'static>(
+        mut self,
+        name: S,
+        filter: F,
+    ) -> Self {
+        self.add_filter(name, filter);
+        self
+    }
+
+    /// Registers a custom filter like `with_filter`, in place.
+    #[cfg(feature = "jinja2")]
+    pub fn add_filter<S: Into<String>, F: super::TemplateFilter + 'static>(
+        &mut self,
+        name: S,
+        filter: F,
+    ) {
+        let name = name.into();
+        self.filters.retain(|(existing, _)| *existing != name);
+        self.filters.push((name, Arc::new(filter)));
+    }
+
+    /// Sets what `format` does with missing variables. Defaults to
+    /// `MissingVariableBehavior::Error`.
+    pub fn with_missing_variable_behavior(mut self, behavior: MissingVariableBehavior) -> Self {
+        self.missing_variable_behavior = behavior;
+        self
+    }
+
+    /// Formats the template without failing on missing variables, returning the prompt and the
+    /// variables that were skipped. Missing placeholders are handled by the configured
+    /// `MissingVariableBehavior`, or left as they are if it is `Error`.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let (prompt, skipped) = template.format_lenient(prompt_args! { "name" => "Luis" })?;
+    /// if !skipped.is_empty() {
+    ///     log::warn!("Missing prompt variables: {:?}", skipped);
+    /// }
+    /// ```
+    pub fn format_lenient(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<(String, Vec<String>), PromptError> {
+        let behavior = match &self.missing_variable_behavior {
+            MissingVariableBehavior::Error => &MissingVariableBehavior::LeaveAsIs,
+            behavior => behavior,
+        };
+        self.format_with_behavior(input_variables, behavior)
+    }
+
+    /// Registers an async resolver for a variable, used by `format_async` when the variable is
+    /// neither passed nor bound as a partial variable. Replaces any resolver already set for it.
+    pub fn with_resolver<S: Into<String>, R: VariableResolver + 'static>(
+        mut self,
+        variable: S,
+        resolver: R,
+    ) -> Self {
+        let variable = variable.into();
+        self.resolvers.retain(|(name, _)| *name != variable);
+        self.resolvers.push((variable, Arc::new(resolver)));
+        self
+    }
+
+    /// Sanitizes the string values of the input variables with `sanitizer` before they are
+    /// substituted, e.g. a `DelimiterSanitizer` to neutralize template syntax typed by end
+    /// users. Nested strings of arrays and objects are sanitized too.
+    pub fn with_sanitizer<S: ValueSanitizer + 'static>(mut self, sanitizer: S) -> Self {
+        self.sanitizer = Some(Arc::new(sanitizer));
+        self
+    }
+
+    /// Compresses the whitespace of each formatted prompt with `compress_prompt`, e.g. to drop
+    /// the runs of spaces and blank lines of extracted documents. It's off by default.
+    pub fn with_compression(mut self, options: CompressOptions) -> Self {
+        self.compression = Some(options);
+        self
+    }
+
+    /// Formats the prompt and compresses it with the options of `with_compression`, or the
+    /// default ones, returning its size before and after so the savings can be logged.
+    pub fn format_compressed<A: Into<PromptArgs>>(
+        &self,
+        input_variables: A,
+    ) -> Result<CompressReport, PromptError> {
+        let (prompt, _) = self
+            .format_with_behavior(input_variables.into(), &self.missing_variable_behavior)
+            .map_err(|e| self.named_error(e))?;
+        Ok(compress_prompt_with_report(
+            &prompt,
+            self.compression.unwrap_or_default(),
+        ))
+    }
+
+    /// Exempts a variable from the sanitizer, for values that are known to be safe or meant
+    /// to contain template syntax.
+    pub fn mark_trusted<S: Into<String>>(mut self, variable: S) -> Self {
+        let variable = variable.into();
+        if !self.trusted_variables.contains(&variable) {
+            self.trusted_variables.push(variable);
+        }
+        self
+    }
+
+    fn sanitize(&self, input_variables: PromptArgs) -> Result<PromptArgs, PromptError> {
+        let Some(sanitizer) = &self.sanitizer else {
+            return Ok(input_variables);
+        };
+        input_variables
+            .into_iter()
+            .map(|(key, value)| {
+                if self.trusted_variables.contains(&key) {
+                    return Ok((key, value));
+                }
+                let value = sanitize_value(sanitizer.as_ref(), &key, value, &self.format)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    /// Caps the length of a variable's value, which `format` truncates, or fails on, before
+    /// substituting it. Replaces any limit already set for the variable.
+    pub fn limit<S: Into<String>>(mut self, variable: S, limit: VarLimit) -> Self {
+        let variable = variable.into();
+        self.limits.retain(|(name, _)| *name != variable);
+        self.limits.push((variable, limit));
+        self
+    }
+
+    /// Formats the prompt like `format`, also returning the values truncated by the limits
+    /// set with `limit`.
+    pub fn format_with_report(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<(String, Vec<Truncation>), PromptError> {
+        let mut prompt = String::with_capacity(self.template.len());
+        let (_, truncations) = self.format_with_behavior_into(
+            input_variables,
+            &self.missing_variable_behavior,
+            &mut prompt,
+        )?;
+        Ok((prompt, truncations))
+    }
+
+    // Limits the inputs, and the partial and default values, a truncated one being replaced
+    // by an input.
+    fn apply_limits(
+        &self,
+        input_variables: &mut PromptArgs,
+    ) -> Result<Vec<Truncation>, PromptError> {
+        let mut truncations = Vec::new();
+        for (variable, limit) in &self.limits {
+            let Some(value) = input_variables
+                .get(variable)
+                .or_else(|| self.partial_variables.get(variable))
+                .or_else(|| self.default_values.get(variable))
+            else {
+                continue;
+            };
+            let limited = match value {
+                Value::String(text) => limit.apply(variable, text)?,
+                value => limit.apply(variable, &value_to_string(value))?,
+            };
+            if let (limited, Some(truncation)) = limited {
+                input_variables.insert(variable.clone(), Value::String(limited));
+                truncations.push(truncation);
+            }
+        }
+        Ok(truncations)
+    }
+
+    /// Allows `{env:NAME}` placeholders of FString templates to read the listed environment
+    /// variables, when formatting. Environment variables aren't readable by default, and
+    /// formatting a template using one that isn't allowed fails.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = template_fstring!("You are the assistant of {env:PRODUCT_NAME}. {input}", "input")
+    ///     .allow_env(["PRODUCT_NAME"])
+    ///     .with_env_fallback("PRODUCT_NAME", "our product");
+    /// ```
+    pub fn allow_env<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for name in names {
+            let name = name.into();
+            if !self.allowed_env.contains(&name) {
+                self.allowed_env.push(name);
+            }
+        }
+        self
+    }
+
+    /// Sets the value of an allowed environment variable when it isn't set.
+    pub fn with_env_fallback<K: Into<String>, V: Into<String>>(
+        mut self,
+        name: K,
+        value: V,
+    ) -> Self {
+        self.env_fallbacks.insert(name.into(), value.into());
+        self
+    }
+
+    /// Returns the environment variables allowed through `allow_env`.
+    pub fn allowed_env(&self) -> &[String] {
+        &self.allowed_env
+    }
+
+    /// Returns the fallbacks of the environment variables set through `with_env_fallback`.
+    pub fn env_fallbacks(&self) -> &HashMap<String, String> {
+        &self.env_fallbacks
+    }
+
+    fn env_value(&self, name: &str) -> Result<String, PromptError> {
+        if !self.allowed_env.iter().any(|allowed| allowed == name) {
+            return Err(PromptError::EnvNotAllowed(name.to_string()));
+        }
+        std::env::var(name)
+            .ok()
+            .or_else(|| self.env_fallbacks.get(name).cloned())
+            .ok_or_else(|| PromptError::MissingEnvVariable(name.to_string()))
+    }
+
+    /// Enables the built-in `now` and `today` variables, read from the system clock, see
+    /// `with_clock`.
+    pub fn with_datetime_variables(self) -> Self {
+        self.with_clock(SystemClock)
+    }
+
+    /// Enables the built-in `now` and `today` variables, read from `clock` when they aren't
+    /// passed to `format`. They're formatted as `2024-05-01 13:45:00 +00:00` and `2024-05-01`,
+    /// and an FString placeholder can set a chrono format string, like
+    /// `{now:%A %-d %B at %H:%M}`. A `FixedClock` makes formatting deterministic in tests.
+    ///
+    /// Templates without a clock treat `now` and `today` as ordinary variables.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = template_fstring!("Today's date is {today}. {input}", "today", "input")
+    ///     .with_clock(SystemClock)
+    ///     .with_timezone(FixedOffset::east_opt(2 * 3600).unwrap());
+    /// ```
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Sets the timezone of the `now` and `today` variables, UTC by default.
+    pub fn with_timezone(mut self, timezone: FixedOffset) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    fn current_time(&self) -> Option<DateTime<FixedOffset>> {
+        self.clock
+            .as_ref()
+            .map(|clock| clock.now().with_timezone(&self.timezone))
+    }
+
+    /// Formats the prompt after awaiting, concurrently, the resolvers of the variables that
+    /// weren't passed. Each resolver gets the passed variables. `format` doesn't run resolvers,
+    /// so their variables must be passed to it.
+    ///
+    /// # Errors
+    /// Returns `PromptError::ResolverError` naming the variable of a failed resolver.
+    pub async fn format_async(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        let pending: Vec<_> = self
+            .resolvers
+            .iter()
+            .filter(|(variable, _)| {
+                !input_variables.contains_key(self.external_name(variable))
+                    && !self.partial_variables.contains_key(variable)
+            })
+            .collect();
+        let values = join_all(
+            pending
+                .iter()
+                .map(|(_, resolver)| resolver.resolve(input_variables.clone())),
+        )
+        .await;
+
+        let mut input_variables = input_variables;
+        for ((variable, _), value) in pending.into_iter().zip(values) {
+            let value = value.map_err(|source| PromptError::ResolverError {
+                variable: variable.clone(),
+                source,
+            })?;
+            input_variables.insert(self.external_name(variable).to_string(), value);
+        }
+        self.format(input_variables)
+    }
+
+    /// Formats an FString template of positional placeholders, like `{0} is {1}`, with the
+    /// argument at each position.
+    ///
+    /// # Errors
+    /// Returns `PromptError::PositionOutOfRange` if a placeholder's position is past the
+    /// arguments.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let prompt = PromptTemplate::from_template("{0} was released in {1}", TemplateFormat::FString)?;
+    /// let result = prompt.format_positional(&prompt_pos_args!["Rust 1.0", 2015])?;
+    /// ```
+    pub fn format_positional<S: AsRef<str>>(&self, args: &[S]) -> Result<String, PromptError> {
+        let placeholders = parser::extract_variables(&self.template, &self.format)
+            .unwrap_or_else(|_| self.variables.clone());
+        if let Some(index) = placeholders
+            .iter()
+            .filter_map(|name| name.parse::<usize>().ok())
+            .filter(|index| *index >= args.len())
+            .min()
+        {
+            return Err(PromptError::PositionOutOfRange {
+                index,
+                len: args.len(),
+            });
+        }
+        self.format(
+            args.iter()
+                .enumerate()
+                .map(|(index, arg)| (index.to_string(), Value::String(arg.as_ref().to_string())))
+                .collect::<PromptArgs>(),
+        )
+    }
+
+    /// Lazily formats the prompt with each arguments of `iter`, e.g. rows read from a CSV file
+    /// or a database cursor, without collecting them. Every prompt is rendered into a reused
+    /// buffer, so each one is allocated once at its final size.
+    ///
+    /// The iterator borrows the template for `'a`, and the arguments source for as long as it
+    /// lives, so both must outlive it. The arguments themselves are owned.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let rows = reader.records().map(|record| record_to_args(record?));
+    /// for prompt in template.format_iter(rows).take(100) {
+    ///     send(prompt?).await;
+    /// }
+    /// ```
+    pub fn format_iter<'a, I>(
+        &'a self,
+        iter: I,
+    ) -> impl Iterator<Item = Result<String, PromptError>> + 'a
+    where
+        I: IntoIterator<Item = PromptArgs>,
+        I::IntoIter: 'a,
+    {
+        let mut buffer = String::new();
+        iter.into_iter().map(move |input_variables| {
+            buffer.clear();
+            self.format_into(input_variables, &mut buffer)?;
+            Ok(buffer.as_str().to_owned())
+        })
+    }
+
+    /// Formats the prompt, then adjusts its whitespace as set by `options`.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let options = FormatOptions::new().with_dedent(true).with_trim(true);
+    /// let result = prompt.format_with_options(prompt_args! { "question" => "Why?" }, &options)?;
+    /// ```
+    pub fn format_with_options(
+        &self,
+        input_variables: PromptArgs,
+        options: &FormatOptions,
+    ) -> Result<String, PromptError> {
+        let result = if options.dedent {
+            let mut prompt = self.clone();
+            prompt.template = dedent(&self.template);
+            prompt.format(input_variables)?
+        } else {
+            self.format(input_variables)?
+        };
+        Ok(options.apply(result))
+    }
+
+    fn missing_locations(&self, missing: &[String]) -> BTreeMap<String, Vec<Span>> {
+        missing
+            .iter()
+            .map(|variable| {
+                let locations = self.variable_locations(self.internal_name(variable));
+                (variable.clone(), locations)
+            })
+            .collect()
+    }
+
+    fn format_with_behavior(
+        &self,
+        input_variables: PromptArgs,
+        behavior: &MissingVariableBehavior,
+    ) -> Result<(String, Vec<String>), PromptError> {
+        let mut prompt = String::with_capacity(self.template.len());
+        let (missing, _) =
+            self.format_with_behavior_into(input_variables, behavior, &mut prompt)?;
+        Ok((prompt, missing))
+    }
+
+    fn format_with_behavior_into(
+        &self,
+        input_variables: PromptArgs,
+        behavior: &MissingVariableBehavior,
+        writer: &mut dyn fmt::Write,
+    ) -> Result<(Vec<String>, Vec<Truncation>), PromptError> {
+        let missing = self.missing_variables(&input_variables);
+        if !missing.is_empty() && *behavior == MissingVariableBehavior::Error {
+            return Err(PromptInputError {
+                locations: self.missing_locations(&missing),
+                missing,
+                unexpected: Vec::new(),
+            }
+            .into());
+        }
+        let mut input_variables = self.sanitize(self.unalias(input_variables))?;
+        let truncations = self.apply_limits(&mut input_variables)?;
+        let is_bound = |key: &str| {
+            input_variables.contains_key(key)
+                || self.partial_variables.contains_key(key)
+                || self.default_values.contains_key(key)
+        };
+
+        // the values only the template engines are given, FString and custom templates
+        // handle them while rendering, see `render_into`
+        let mut engine_variables = PromptArgs::new();
+        if let TemplateFormat::Jinja2 | TemplateFormat::Mustache = self.format {
+            let now = self.current_time();
+            for name in BUILTIN_TIME_VARIABLES {
+                if is_bound(name) {
+                    continue;
+                }
+                if let Some(time) = builtin_time(name, now, None) {
+                    engine_variables.insert(name.to_string(), Value::String(time));
+                }
+            }
+        }
+
+        // replacement of each missing variable, `None` to leave its placeholders as they are
+        let mut replacements = HashMap::new();
+        for name in &missing {
+            let replacement = match behavior {
+                MissingVariableBehavior::Error | MissingVariableBehavior::LeaveAsIs => None,
+                MissingVariableBehavior::Empty => Some(String::new()),
+                MissingVariableBehavior::Marker(marker) => Some(marker.replace("{}", name)),
+            };
+            let key = self.internal_name(name).to_string();
+            match self.format {
+                TemplateFormat::FString | TemplateFormat::Custom { .. } => {
+                    replacements.insert(key.clone(), replacement);
+                }
+                TemplateFormat::Jinja2 | TemplateFormat::Mustache => {
+                    let replacement = replacement.unwrap_or_else(|| format!("{{{{{}}}}}", key));
+                    engine_variables.insert(key.clone(), Value::String(replacement));
+                }
+            }
         }
+
+        let conditional_variables = self.conditional_variables();
+        let absent_optional: Vec<&String> = self
+            .optional_variables
+            .iter()
+            .chain(&conditional_variables)
+            .filter(|key| !is_bound(key))
+            .collect();
+        for key in &absent_optional {
+            match self.format {
+                TemplateFormat::FString | TemplateFormat::Custom { .. } => {
+                    replacements.insert(key.to_string(), Some(String::new()));
+                }
+                TemplateFormat::Jinja2 | TemplateFormat::Mustache => {
+                    engine_variables.insert(key.to_string(), Value::String(String::new()));
+                }
+            }
+        }
+
+        let variables = Scope([
+            &input_variables,
+            &self.partial_variables,
+            &self.default_values,
+            &engine_variables,
+        ]);
+        if self.collapse_blank_lines && !absent_optional.is_empty() {
+            let mut prompt = self.clone();
+            prompt.template = remove_optional_lines(&self.template, &self.format, &absent_optional);
+            prompt.render_into(&variables, &replacements, writer)?;
+        } else {
+            self.render_into(&variables, &replacements, writer)?;
+        }
+        Ok((missing, truncations))
+    }
+
+    // Streams the rendered template into `writer`, writing the template text and the values
+    // directly instead of building the prompt first.
+    fn render_into(
+        &self,
+        input_variables: &Scope<'_>,
+        replacements: &HashMap<String, Option<String>>,
+        writer: &mut dyn fmt::Write,
+    ) -> Result<(), PromptError> {
+        let now = self.current_time();
+        let segments = match self.format {
+            TemplateFormat::FString => {
+                match parser::parse_fstring_with(&self.template, self.strip_comment_lines) {
+                    Ok(segments) => segments,
+                    Err(_) => {
+                        writer.write_str(&self.render_replace(input_variables, replacements)?)?;
+                        return Ok(());
+                    }
+                }
+            }
+            #[cfg(feature = "jinja2")]
+            TemplateFormat::Jinja2 => {
+                return jinja2::render(
+                    &self.template,
+                    &input_variables.merged(),
+                    self.loader.as_ref(),
+                    &self.filters,
+                    writer,
+                );
+            }
+            #[cfg(not(feature = "jinja2"))]
+            TemplateFormat::Jinja2 => parser::parse_jinja2(&self.template),
+            #[cfg(feature = "mustache")]
+            TemplateFormat::Mustache => {
+                return mustache::render(&self.template, &input_variables.merged(), writer);
+            }
+            #[cfg(not(feature = "mustache"))]
+            TemplateFormat::Mustache => parser::parse_jinja2(&self.template),
+            TemplateFormat::Custom {
+                ref open,
+                ref close,
+            } => parser::parse_custom(&self.template, open, close)?,
+        };
+
+        // whether the current branch of each open conditional block is rendered
+        let mut blocks: Vec<bool> = Vec::new();
+        for segment in segments {
+            match segment {
+                Segment::If(path) => blocks.push(is_present(input_variables, path)),
+                Segment::Else => {
+                    if let Some(rendered) = blocks.last_mut() {
+                        *rendered = !*rendered;
+                    }
+                }
+                Segment::EndIf => {
+                    blocks.pop();
+                }
+                Segment::Comment(_) => {}
+                _ if blocks.contains(&false) => {}
+                Segment::Text(text) => writer.write_str(text)?,
+                Segment::Variable(path) => {
+                    if let Some(value) = resolve_path(input_variables, replacements, path)? {
+                        writer.write_str(&value)?
+                    } else if let Some(time) = builtin_time(path, now, None) {
+                        writer.write_str(&time)?
+                    } else {
+                        match self.format {
+                            TemplateFormat::FString => write!(writer, "{{{}}}", path)?,
+                            TemplateFormat::Jinja2 | TemplateFormat::Mustache => {
+                                write!(writer, "{{{{{}}}}}", path)?
+                            }
+                            TemplateFormat::Custom {
+                                ref open,
+                                ref close,
+                            } => write!(writer, "{}{}{}", open, path, close)?,
+                        }
+                    }
+                }
+                Segment::DateTime(path, format) => {
+                    if let Some(value) = resolve_value(input_variables, path)? {
+                        writer.write_str(&format_date(value, format))?
+                    } else if let Some(replacement) =
+                        resolve_path(input_variables, replacements, path)?
+                    {
+                        writer.write_str(&replacement)?
+                    } else if let Some(time) = builtin_time(path, now, Some(format)) {
+                        writer.write_str(&time)?
+                    } else {
+                        write!(writer, "{{{}:{}}}", path, format)?
+                    }
+                }
+                Segment::Env(name) => writer.write_str(&self.env_value(name)?)?,
+                Segment::Formatted(path, spec) => match resolve_value(input_variables, path)? {
+                    Some(value) => writer.write_str(&spec.apply(path, value)?)?,
+                    // missing variables are replaced as they are, never formatted
+                    None => match resolve_path(input_variables, replacements, path)? {
+                        Some(replacement) => writer.write_str(&replacement)?,
+                        None => write!(writer, "{{{}:{}}}", path, spec.raw)?,
+                    },
+                },
+                Segment::Filtered(path, filters) => match resolve_value(input_variables, path)? {
+                    Some(value) => writer.write_str(&filters.apply(path, value)?)?,
+                    None => match resolve_path(input_variables, replacements, path)? {
+                        Some(replacement) => writer.write_str(&replacement)?,
+                        None => write!(writer, "{{{}|{}}}", path, filters.raw)?,
+                    },
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // Used for FString templates the scanner can't parse, such as templates with unescaped
+    // raw JSON: every `{key}` of a known key is substituted and any other brace is kept as is.
+    // Like `render`, it's a single pass that never rescans substituted values.
+    fn render_replace(
+        &self,
+        input_variables: &Scope<'_>,
+        replacements: &HashMap<String, Option<String>>,
+    ) -> Result<String, PromptError> {
+        substitute_known(&self.template, "{", "}", |path| {
+            if !parser::is_path(path) && !parser::is_index(path) {
+                return Ok(None);
+            }
+            Ok(resolve_path(input_variables, replacements, path)?.map(Cow::into_owned))
+        })
+    }
+
+    /// Formats the template, then expands the placeholders found in the substituted values
+    /// from the same input, partial and default values, so a variable can itself be a small
+    /// template. Only the values the template uses are expanded, at most `max_depth` levels
+    /// deep. Placeholders that can't be resolved are left as they are.
+    ///
+    /// This is never done by `format`, which substitutes values exactly once.
+    ///
+    /// # Errors
+    /// Returns `PromptError::CyclicVariable` if a variable expands to itself, and
+    /// `PromptError::RecursionLimit` if the expansion goes deeper than `max_depth`.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let template = template_fstring!("{system_block}\n{input}", "system_block", "input");
+    /// let result = template.format_recursive(
+    ///     prompt_args! {
+    ///         "system_block" => "You work for {company_name}.",
+    ///         "company_name" => "Acme",
+    ///         "input" => "Hi",
+    ///     },
+    ///     3,
+    /// )?;
+    /// ```
+    pub fn format_recursive(
+        &self,
+        input_variables: PromptArgs,
+        max_depth: usize,
+    ) -> Result<String, PromptError> {
+        let mut input_variables = self.unalias(input_variables);
+        let used = parser::extract_variables(&self.template, &self.format).unwrap_or_else(|_| {
+            self.variables
+                .iter()
+                .chain(self.partial_variables.keys())
+                .cloned()
+                .collect()
+        });
+        let no_values = PromptArgs::new();
+        let variables = Scope([
+            &input_variables,
+            &self.partial_variables,
+            &self.default_values,
+            &no_values,
+        ]);
+        let mut expanded = Vec::new();
+        for key in used {
+            if let Some(Value::String(text)) = variables.variable(&key) {
+                let mut path = vec![key.clone()];
+                let text = self.expand(text, &variables, &mut path, max_depth)?;
+                expanded.push((key, Value::String(text)));
+            }
+        }
+
+        // an expanded partial or default value stays one, so it's still never sanitized
+        let mut prompt = Cow::Borrowed(self);
+        for (key, value) in expanded {
+            match input_variables.get_mut(&key) {
+                Some(input) => *input = value,
+                None => {
+                    prompt.to_mut().partial_variables.insert(key, value);
+                }
+            }
+        }
+        prompt.format(input_variables)
+    }
+
+    fn expand(
+        &self,
+        text: &str,
+        input_variables: &Scope<'_>,
+        path: &mut Vec<String>,
+        max_depth: usize,
+    ) -> Result<String, PromptError> {
+        let (open, close) = match &self.format {
+            TemplateFormat::FString => ("{", "}"),
+            TemplateFormat::Jinja2 | TemplateFormat::Mustache => ("{{", "}}"),
+            TemplateFormat::Custom { open, close } => (open.as_str(), close.as_str()),
+        };
+        substitute_known(text, open, close, |name| {
+            let name = name.trim();
+            let Some(value) = input_variables.variable(name) else {
+                return Ok(None);
+            };
+            if path.iter().any(|key| key == name) {
+                path.push(name.to_string());
+                return Err(PromptError::CyclicVariable(path.clone()));
+            }
+            if path.len() > max_depth {
+                return Err(PromptError::RecursionLimit {
+                    variable: path[0].clone(),
+                    max_depth,
+                });
+            }
+            path.push(name.to_string());
+            let expanded =
+                self.expand(&value_to_string(value), input_variables, path, max_depth)?;
+            path.pop();
+            Ok(Some(expanded))
+        })
     }
 }
 
-//PromptTemplate will be default transformed to an Human Input when used as FromatPrompter
-impl FormatPrompter for PromptTemplate {
-    fn format_prompt(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
-        let messages = vec![Message::new_human_message(self.format(input_variables)?)];
-        Ok(PromptValue::from_messages(messages))
+fn sanitize_value(
+    sanitizer: &dyn ValueSanitizer,
+    variable: &str,
+    value: Value,
+    format: &TemplateFormat,
+) -> Result<Value, PromptError> {
+    Ok(match value {
+        Value::String(text) => Value::String(sanitizer.sanitize(variable, &text, format)?),
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|value| sanitize_value(sanitizer, variable, value, format))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(values) => Value::Object(
+            values
+                .into_iter()
+                .map(|(key, value)| Ok((key, sanitize_value(sanitizer, variable, value, format)?)))
+                .collect::<Result<_, PromptError>>()?,
+        ),
+        value => value,
+    })
+}
+
+/// Replaces every `open name close` for which `lookup` returns a value, in a single pass.
+/// Anything else is kept as it is.
+fn substitute_known<F>(
+    text: &str,
+    open: &str,
+    close: &str,
+    mut lookup: F,
+) -> Result<String, PromptError>
+where
+    F: FnMut(&str) -> Result<Option<String>, PromptError>,
+{
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(open) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let body = &rest[open.len()..];
+        let value = match body.find(close) {
+            Some(end) => lookup(&body[..end])?.map(|value| (end, value)),
+            None => None,
+        };
+        match value {
+            Some((end, value)) => {
+                result.push_str(&value);
+                rest = &body[end + close.len()..];
+            }
+            None => {
+                result.push_str(open);
+                rest = body;
+            }
+        }
     }
-    fn get_input_variables(&self) -> Vec<String> {
-        self.variables.clone()
+    result.push_str(rest);
+    Ok(result)
+}
+
+// Removes the lines made only of whitespace and placeholders of the given variables.
+fn remove_optional_lines(template: &str, format: &TemplateFormat, absent: &[&String]) -> String {
+    let mut result: String = template
+        .split_inclusive('\n')
+        .filter(|line| {
+            let segments = match format {
+                TemplateFormat::FString => match parser::parse_fstring(line) {
+                    Ok(segments) => segments,
+                    Err(_) => return true,
+                },
+                TemplateFormat::Jinja2 | TemplateFormat::Mustache => parser::parse_jinja2(line),
+                TemplateFormat::Custom { open, close } => {
+                    match parser::parse_custom(line, open, close) {
+                        Ok(segments) => segments,
+                        Err(_) => return true,
+                    }
+                }
+            };
+            let mut has_absent = false;
+            for segment in segments {
+                match segment {
+                    Segment::Text(text) if text.trim().is_empty() => {}
+                    Segment::Comment(_) => {}
+                    Segment::Variable(path)
+                    | Segment::Formatted(path, _)
+                    | Segment::Filtered(path, _)
+                        if absent
+                            .iter()
+                            .any(|a| path.split('.').next() == Some(a.as_str())) =>
+                    {
+                        has_absent = true
+                    }
+                    _ => return true,
+                }
+            }
+            !has_absent
+        })
+        .collect();
+    // a removed last line must not leave the newline of the line before it
+    if !template.ends_with('\n') && result.ends_with('\n') {
+        result.pop();
     }
+    result
 }
 
-impl PromptFromatter for PromptTemplate {
-    fn template(&self) -> String {
-        self.template.clone()
+/// The variables set from the clock of `PromptTemplate::with_clock`.
+const BUILTIN_TIME_VARIABLES: [&str; 2] = ["now", "today"];
+
+// Formats the time of a built-in variable, `None` for other variables or without a clock.
+fn builtin_time(
+    variable: &str,
+    now: Option<DateTime<FixedOffset>>,
+    format: Option<&str>,
+) -> Option<String> {
+    let default_format = match variable {
+        "now" => "%Y-%m-%d %H:%M:%S %:z",
+        "today" => "%Y-%m-%d",
+        _ => return None,
+    };
+    Some(now?.format(format.unwrap_or(default_format)).to_string())
+}
+
+// Formats a passed value of a date placeholder: RFC 3339 dates with the format string, and
+// any other value as it is.
+fn format_date(value: &Value, format: &str) -> String {
+    match value.as_str().map(DateTime::parse_from_rfc3339) {
+        Some(Ok(date)) => date.format(format).to_string(),
+        _ => value_to_string(value),
     }
+}
 
-    fn variables(&self) -> Vec<String> {
-        self.variables.clone()
+/// Where formatting looks up its variables by name.
+pub(crate) trait Variables {
+    fn variable(&self, name: &str) -> Option<&Value>;
+}
+
+impl Variables for PromptArgs {
+    fn variable(&self, name: &str) -> Option<&Value> {
+        self.get(name)
+    }
+}
+
+// The variables of a format call, looked up in the inputs, then the partial variables, then
+// the defaults, then the values given to the template engines, instead of being merged.
+struct Scope<'a>([&'a PromptArgs; 4]);
+
+impl Scope<'_> {
+    // The variables merged into a single map, for the template engines.
+    #[cfg(any(feature = "jinja2", feature = "mustache"))]
+    fn merged(&self) -> PromptArgs {
+        let mut variables = PromptArgs::new();
+        for layer in self.0.iter().rev() {
+            variables.extend(
+                layer
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
+        }
+        variables
     }
+}
+
+impl Variables for Scope<'_> {
+    fn variable(&self, name: &str) -> Option<&Value> {
+        self.0.iter().find_map(|layer| layer.get(name))
+    }
+}
+
+/// Resolves a placeholder such as `name`, `user.name` or `items.0` to its rendered value.
+/// Returns `None` if its root variable is missing and should be left as it is.
+///
+/// # Errors
+/// Returns `PromptError::MissingVariable` naming the full path if the root variable exists but
+/// the path doesn't.
+fn resolve_path<'a, V: Variables + ?Sized>(
+    input_variables: &'a V,
+    replacements: &'a HashMap<String, Option<String>>,
+    path: &str,
+) -> Result<Option<Cow<'a, str>>, PromptError> {
+    let Some(value) = resolve_value(input_variables, path)? else {
+        let root = path.split('.').next().unwrap_or_default();
+        return Ok(replacements
+            .get(root)
+            .and_then(|replacement| replacement.as_deref().map(Cow::Borrowed)));
+    };
+    Ok(Some(match value {
+        Value::String(s) => Cow::Borrowed(s.as_str()),
+        _ => Cow::Owned(value.to_string()),
+    }))
+}
+
+// Returns the value at a dot path, `None` if its root variable isn't set.
+pub(crate) fn resolve_value<'a, V: Variables + ?Sized>(
+    input_variables: &'a V,
+    path: &str,
+) -> Result<Option<&'a Value>, PromptError> {
+    let mut keys = path.split('.');
+    let Some(mut value) = input_variables.variable(keys.next().unwrap_or_default()) else {
+        return Ok(None);
+    };
+    for key in keys {
+        let next = match value {
+            Value::Object(map) => map.get(key),
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        };
+        value = next.ok_or_else(|| PromptError::MissingVariable(path.to_string()))?;
+    }
+    Ok(Some(value))
+}
+
+// Whether the variable of an `{#if path}` block is set to anything but null, `false` or an
+// empty string, array or object.
+fn is_present<V: Variables + ?Sized>(input_variables: &V, path: &str) -> bool {
+    let mut keys = path.split('.');
+    let mut value = input_variables.variable(keys.next().unwrap_or_default());
+    for key in keys {
+        value = match value {
+            Some(Value::Object(map)) => map.get(key),
+            Some(Value::Array(items)) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        };
+    }
+    match value {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(Value::Array(items)) => !items.is_empty(),
+        Some(Value::Object(map)) => !map.is_empty(),
+        Some(_) => true,
+    }
+}
+
+fn escape_fstring(text: &str) -> String {
+    text.replace('{', "{{").replace('}', "}}")
+}
+
+pub(crate) fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+//PromptTemplate will be default transformed to an Human Input when used as FromatPrompter
+impl FormatPrompter for PromptTemplate {
+    fn format_prompt(&self, input_variables: PromptArgs) -> Result<PromptValue, PromptError> {
+        self.to_prompt_value(input_variables)
+    }
+    fn get_input_variables(&self) -> Vec<String> {
+        self.variables.clone()
+    }
+
+    /// The required variables, but the ones a resolver loads.
+    fn get_required_variables(&self) -> Vec<String> {
+        let mut variables = self.required_variables();
+        variables.retain(|variable| !self.resolvers.iter().any(|(name, _)| name == variable));
+        variables
+    }
+
+    /// Runs the resolvers, see `format_async`.
+    fn format_prompt_async(
+        &self,
+        input_variables: PromptArgs,
+    ) -> BoxFuture<'_, Result<PromptValue, PromptError>> {
+        Box::pin(async move {
+            Ok(PromptValue::from_string(
+                &self.format_async(input_variables).await?,
+            ))
+        })
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl PromptTemplate {
+    /// Formats the prompt with each arguments of the batch on rayon's thread pool. Like
+    /// `format_batch`, the results are index-aligned with the batch.
+    pub fn format_batch_parallel(&self, batch: &[PromptArgs]) -> Vec<Result<String, PromptError>> {
+        use rayon::prelude::*;
+
+        batch
+            .par_iter()
+            .map(|input_variables| self.format(input_variables.clone()))
+            .collect()
+    }
+}
+
+impl PromptFromatter for PromptTemplate {
+    fn template(&self) -> String {
+        self.template.clone()
+    }
+
+    fn variables(&self) -> Vec<String> {
+        self.variables
+            .iter()
+            .map(|variable| self.external_name(variable).to_string())
+            .collect()
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        PromptTemplate::format(self, input_variables)
+    }
+
+    fn format_into(
+        &self,
+        input_variables: PromptArgs,
+        writer: &mut dyn fmt::Write,
+    ) -> Result<(), PromptError> {
+        if self.compression.is_some() {
+            // compression needs the whole prompt
+            writer.write_str(&PromptTemplate::format(self, input_variables)?)?;
+            return Ok(());
+        }
+        self.format_with_behavior_into(input_variables, &self.missing_variable_behavior, writer)
+            .map_err(|e| self.named_error(e))?;
+        Ok(())
+    }
+
+    fn metadata(&self) -> &PromptMetadata {
+        &self.metadata
+    }
+
+    fn as_prompt_template(&self) -> Option<&PromptTemplate> {
+        Some(self)
+    }
+}
+
+/// A bare string is a FString template whose variables are its placeholders, parsed each time
+/// it's used. Malformed templates fail to format with `PromptError::InvalidTemplate`, like
+/// with `PromptTemplate::from_template`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let result = "Tell me a joke about {topic}".format(prompt_args! { "topic" => "cats" })?;
+/// ```
+macro_rules! impl_prompt_for_str {
+    ($type:ty) => {
+        impl PromptFromatter for $type {
+            fn template(&self) -> String {
+                self.to_string()
+            }
+
+            fn variables(&self) -> Vec<String> {
+                parser::extract_variables(self, &TemplateFormat::FString).unwrap_or_default()
+            }
+
+            fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+                PromptTemplate::from_template(self, TemplateFormat::FString)?
+                    .format(input_variables)
+            }
+
+            fn format_into(
+                &self,
+                input_variables: PromptArgs,
+                writer: &mut dyn fmt::Write,
+            ) -> Result<(), PromptError> {
+                PromptTemplate::from_template(self, TemplateFormat::FString)?
+                    .format_into(input_variables, writer)
+            }
+        }
+    };
+}
+
+impl_prompt_for_str!(String);
+impl_prompt_for_str!(&'static str);
+
+/// Creates a FString `PromptTemplate` with the variables inferred from its placeholders, like
+/// `PromptTemplate::from_template`.
+impl TryFrom<&str> for PromptTemplate {
+    type Error = PromptError;
+
+    fn try_from(template: &str) -> Result<Self, Self::Error> {
+        PromptTemplate::from_template(template, TemplateFormat::FString)
+    }
+}
+
+impl TryFrom<String> for PromptTemplate {
+    type Error = PromptError;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        PromptTemplate::try_from(template.as_str())
+    }
+}
+
+/// Takes the template out of the `Arc`, cloning it if it's shared.
+impl From<Arc<PromptTemplate>> for PromptTemplate {
+    fn from(template: Arc<PromptTemplate>) -> Self {
+        Arc::unwrap_or_clone(template)
+    }
+}
+
+/// `prompt_args!` is a utility macro used for creating a `std::collections::HashMap<String, serde_json::Value>`.
+/// This HashMap can then be passed as arguments to a function or method.
+///
+/// # Usage
+/// In this macro, the keys are `&str` and values are arbitrary types that get serialized into `serde_json::Value`:
+/// ```rust,ignore
+/// let name = String::from("Luis");
+/// prompt_args! {
+///     "input" => "Who is the writer of 20,000 Leagues Under the Sea, and what is my name?",
+///     "name" => name,
+///     "age" => 24,
+///     "history" => vec![
+///         Message::new_human_message("My name is: Luis"),
+///         Message::new_ai_message("Hi Luis"),
+///     ],
+/// }
+/// ```
+///
+/// # Arguments
+/// * `key` - A `&str` that will be used as the key in the resulting HashMap.<br>
+/// * `value` - Any value implementing `Serialize`, which is stored as its JSON representation,
+///   or, failing that, any value implementing `Display`, which is stored as a string.
+///
+/// The precise keys and values are dependent on your specific use case. In this example, "input", "name",
+/// "age" and "history" are keys, and the values are what gets substituted into the prompt.
+///
+/// Values can also be written as nested maps, in braces, and arrays, in brackets, and
+/// `..args` merges another `PromptArgs`, or anything convertible into it, the later keys
+/// winning:
+/// ```rust,ignore
+/// prompt_args! {
+///     ..defaults,
+///     "user" => { "name" => "Ada", "roles" => ["admin", "dev"] },
+///     "k" => 3,
+/// }
+/// ```
+///
+/// Entries must be `key => value` or `..args`:
+/// ```compile_fail
+/// let args = langchain_rust::prompt_args! { "user" => { "name" } };
+/// ```
+///
+/// ```compile_fail
+/// let args = langchain_rust::prompt_args! { "a" => 1 "b" => 2 };
+/// ```
+#[macro_export]
+macro_rules! prompt_args {
+    ( $($entries:tt)* ) => {
+        {
+            #[allow(unused_mut)]
+            let mut args = $crate::prompt::PromptArgs::new();
+            $crate::__prompt_args_entries!(args; $($entries)*);
+            args
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __prompt_args_entries {
+    ($args:ident;) => {};
+    ($args:ident; .. $other:expr $(, $($rest:tt)*)?) => {
+        $args.extend($crate::prompt::PromptArgs::from($other));
+        $crate::__prompt_args_entries!($args; $($($rest)*)?);
+    };
+    ($args:ident; $key:expr => { $($map:tt)* } $(, $($rest:tt)*)?) => {
+        $args.insert($key.to_string(), $crate::__prompt_args_value!({ $($map)* }));
+        $crate::__prompt_args_entries!($args; $($($rest)*)?);
+    };
+    ($args:ident; $key:expr => [ $($array:tt)* ] $(, $($rest:tt)*)?) => {
+        $args.insert($key.to_string(), $crate::__prompt_args_value!([ $($array)* ]));
+        $crate::__prompt_args_entries!($args; $($($rest)*)?);
+    };
+    ($args:ident; $key:expr => $value:expr $(, $($rest:tt)*)?) => {
+        $args.insert($key.to_string(), $crate::__prompt_args_value!($value));
+        $crate::__prompt_args_entries!($args; $($($rest)*)?);
+    };
+    ($args:ident; $($rest:tt)*) => {
+        compile_error!(concat!(
+            "expected `key => value` or `..args` entries separated by commas in prompt_args!, found `",
+            stringify!($($rest)*),
+            "`"
+        ));
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __prompt_args_value {
+    ({ $($map:tt)* }) => {
+        $crate::prompt::__prompt_args_object($crate::prompt_args! { $($map)* })
+    };
+    ([ $($array:tt)* ]) => {
+        {
+            #[allow(unused_mut)]
+            let mut values = ::std::vec::Vec::new();
+            $crate::__prompt_args_array!(values; $($array)*);
+            $crate::prompt::__prompt_args_array(values)
+        }
+    };
+    ($value:expr) => {
+        {
+            #[allow(unused_imports)]
+            use $crate::prompt::{DisplayArg as _, SerializeArg as _};
+            // Convert the value to serde_json::Value before inserting
+            (&&$crate::prompt::ArgValue(&$value)).to_arg_value()
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __prompt_args_array {
+    ($values:ident;) => {};
+    ($values:ident; { $($map:tt)* } $(, $($rest:tt)*)?) => {
+        $values.push($crate::__prompt_args_value!({ $($map)* }));
+        $crate::__prompt_args_array!($values; $($($rest)*)?);
+    };
+    ($values:ident; [ $($array:tt)* ] $(, $($rest:tt)*)?) => {
+        $values.push($crate::__prompt_args_value!([ $($array)* ]));
+        $crate::__prompt_args_array!($values; $($($rest)*)?);
+    };
+    ($values:ident; $value:expr $(, $($rest:tt)*)?) => {
+        $values.push($crate::__prompt_args_value!($value));
+        $crate::__prompt_args_array!($values; $($($rest)*)?);
+    };
+    ($values:ident; $($rest:tt)*) => {
+        compile_error!(concat!(
+            "expected values separated by commas in a prompt_args! array, found `",
+            stringify!($($rest)*),
+            "`"
+        ));
+    };
+}
+
+/// `prompt_pos_args` is a utility macro that creates the arguments of
+/// `PromptTemplate::format_positional` from any values implementing `Display`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let result = prompt.format_positional(&prompt_pos_args!["Rust", 2015])?;
+/// ```
+#[macro_export]
+macro_rules! prompt_pos_args {
+    ( $($value:expr),* $(,)? ) => {
+        vec![$($value.to_string()),*] as Vec<String>
+    };
+}
+
+/// Wrapper used by `prompt_args!` to convert a value through `Serialize` when it is
+/// implemented, and through `Display` otherwise.
+#[doc(hidden)]
+pub struct ArgValue<'a, T: ?Sized>(pub &'a T);
+
+#[doc(hidden)]
+pub trait SerializeArg {
+    fn to_arg_value(&self) -> Value;
+}
+
+impl<T: serde::Serialize + ?Sized> SerializeArg for &ArgValue<'_, T> {
+    fn to_arg_value(&self) -> Value {
+        serde_json::json!(self.0)
+    }
+}
+
+#[doc(hidden)]
+pub trait DisplayArg {
+    fn to_arg_value(&self) -> Value;
+}
+
+impl<T: std::fmt::Display + ?Sized> DisplayArg for ArgValue<'_, T> {
+    fn to_arg_value(&self) -> Value {
+        Value::String(self.0.to_string())
+    }
+}
+
+/// `template_fstring` is a utility macro that creates a new `PromptTemplate` with FString as the template format.
+///
+/// # Usage
+/// The macro is called with a template string and a list of variables that exist in the template. For example:
+/// ```rust,ignore
+/// template_fstring!(
+///     "Hello {name}",
+///     "name"
+/// )
+/// ```
+/// This returns a `PromptTemplate` object that contains the string "Hello {name}" as the template and ["name"] as the variables, with TemplateFormat set to FString.
+#[macro_export]
+macro_rules! template_fstring {
+    ($template:expr, $($var:expr),* $(,)?) => {
+        $crate::prompt::PromptTemplate::new(
+            $template.to_string(),
+            vec![$($var.to_string()),*],
+            $crate::prompt::TemplateFormat::FString,
+        )
+    };
+}
+
+/// `template_jinja2` is a utility macro that creates a new `PromptTemplate` with Jinja2 as the template format.
+///
+/// # Usage
+/// The macro is called with a template string and a list of variables that exist in the template. For example:
+/// ```rust,ignore
+/// template_jinja2!(
+///     "Hello {{ name }}",
+///     "name"
+/// )
+/// ```
+/// This returns a `PromptTemplate` object that contains the string "Hello {{ name }}" as the template and ["name"] as the variables, with TemplateFormat set to Jinja2.
+#[macro_export]
+macro_rules! template_jinja2 {
+    ($template:expr, $($var:expr),* $(,)?) => {
+        $crate::prompt::PromptTemplate::new(
+            $template.to_string(),
+            vec![$($var.to_string()),*],
+            $crate::prompt::TemplateFormat::Jinja2,
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt::IntoPromptArgs, prompt_args};
+
+    #[test]
+    fn should_format_jinja2_template() {
+        let template = PromptTemplate::new(
+            "Hello {{name}}!".to_string(),
+            vec!["name".to_string()],
+            TemplateFormat::Jinja2,
+        );
+
+        let input_variables = prompt_args! {};
+        let result = template.format(input_variables);
+        assert!(result.is_err());
+
+        let input_variables = prompt_args! {
+            "name" => "world",
+        };
+        let result = template.format(input_variables);
+        println!("{:?}", result);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "Hello world!");
+    }
+
+    #[test]
+    fn should_strip_comments() {
+        let source = "{# tone control,\n see PROMPT-123 #}Be {tone}.\n{# {examples} go here #}\nHi";
+        let prompt = PromptTemplate::from_template(source, TemplateFormat::FString).unwrap();
+        assert_eq!(prompt.variables(), vec!["tone"]);
+        let args = prompt_args! { "tone" => "kind" };
+        assert_eq!(prompt.format(args.clone()).unwrap(), "Be kind.\n\nHi");
+        let stripped = prompt.clone().with_strip_comment_lines(true);
+        assert_eq!(stripped.format(args.clone()).unwrap(), "Be kind.\nHi");
+
+        // comments are documentation, so they're saved and kept by partial formatting
+        let saved: PromptTemplate =
+            serde_json::from_str(&serde_json::to_string(&prompt).unwrap()).unwrap();
+        assert_eq!(saved.template_str(), source);
+        assert!(prompt
+            .format_partial(args)
+            .unwrap()
+            .template_str()
+            .contains("{# {examples} go here #}"));
+
+        assert!(matches!(
+            PromptTemplate::from_template("Hi {# note", TemplateFormat::FString),
+            Err(PromptError::InvalidTemplate { span, .. }) if span.start == 3
+        ));
+    }
+
+    #[cfg(feature = "jinja2")]
+    #[test]
+    fn should_strip_jinja2_comments() {
+        let prompt = PromptTemplate::from_template(
+            "{# see PROMPT-123 #}Hello {{ name }}!",
+            TemplateFormat::Jinja2,
+        )
+        .unwrap();
+        assert_eq!(prompt.variables(), vec!["name"]);
+        assert_eq!(
+            prompt.format(prompt_args! { "name" => "Ana" }).unwrap(),
+            "Hello Ana!"
+        );
+    }
+
+    #[test]
+    fn should_format_fstring_template() {
+        let template = PromptTemplate::new(
+            "Hello {name}!".to_string(),
+            vec!["name".to_string()],
+            TemplateFormat::FString,
+        );
+
+        let input_variables = prompt_args! {};
+        let result = template.format(input_variables);
+        assert!(result.is_err());
+
+        let input_variables = prompt_args! {
+            "name" => "world",
+        };
+        let result = template.format(input_variables);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "Hello world!");
+    }
+
+    #[test]
+    fn should_infer_variables_from_template() {
+        let template = PromptTemplate::from_template(
+            "Hello {name}, {name} is {age}!",
+            TemplateFormat::FString,
+        )
+        .unwrap();
+        assert_eq!(template.variables(), vec!["name", "age"]);
+
+        let template =
+            PromptTemplate::from_template("Hello {{name}}!", TemplateFormat::Jinja2).unwrap();
+        assert_eq!(template.variables(), vec!["name"]);
+
+        let result = template.format(prompt_args! { "name" => "world" });
+        assert_eq!(result.unwrap(), "Hello world!");
+
+        assert!(PromptTemplate::from_template("Hello {name", TemplateFormat::FString).is_err());
+    }
+
+    #[test]
+    fn should_validate_variables_on_try_new() {
+        let template = PromptTemplate::try_new(
+            "Hello {name}, you are {age}".to_string(),
+            vec!["name".to_string(), "age".to_string()],
+            TemplateFormat::FString,
+        );
+        assert!(template.is_ok());
+
+        let template = PromptTemplate::try_new(
+            "Hello {name}, you are {age}".to_string(),
+            vec!["name".to_string(), "city".to_string()],
+            TemplateFormat::FString,
+        );
+        match template {
+            Err(PromptError::VariablesMismatch { unused, undeclared }) => {
+                assert_eq!(unused, vec!["city"]);
+                assert_eq!(undeclared, vec!["age"]);
+            }
+            _ => panic!("expected a variables mismatch"),
+        }
+
+        let template = PromptTemplate::try_new(
+            "Hello {{name}}{% if age %}, you are {{ age }}{% endif %}".to_string(),
+            vec!["name".to_string()],
+            TemplateFormat::Jinja2,
+        );
+        match template {
+            Err(PromptError::VariablesMismatch { unused, undeclared }) => {
+                assert!(unused.is_empty());
+                assert_eq!(undeclared, vec!["age"]);
+            }
+            _ => panic!("expected a variables mismatch"),
+        }
+
+        let template = PromptTemplate::try_new(
+            r#"{"answer": {answer}}"#.to_string(),
+            vec!["answer".to_string()],
+            TemplateFormat::FString,
+        );
+        assert!(matches!(template, Err(PromptError::InvalidTemplate { .. })));
+    }
+
+    #[test]
+    fn should_format_fstring_escaped_braces() {
+        let template = PromptTemplate::from_template(
+            r#"Answer as JSON: {{"answer": "{answer}", "meta": {{"count": {count}}}}}"#,
+            TemplateFormat::FString,
+        )
+        .unwrap();
+        assert_eq!(template.variables(), vec!["answer", "count"]);
+
+        let result = template
+            .format(prompt_args! { "answer" => "yes", "count" => 2 })
+            .unwrap();
+        assert_eq!(
+            result,
+            r#"Answer as JSON: {"answer": "yes", "meta": {"count": 2}}"#
+        );
+
+        let template =
+            PromptTemplate::from_template("{{{name}}} and {{name}}", TemplateFormat::FString)
+                .unwrap();
+        assert_eq!(template.variables(), vec!["name"]);
+        let result = template.format(prompt_args! { "name" => "Luis" }).unwrap();
+        assert_eq!(result, "{Luis} and {name}");
+    }
+
+    #[test]
+    fn should_format_with_partial_variables() {
+        let template = template_fstring!(
+            "{persona} says {greeting} to {name}",
+            "persona",
+            "greeting",
+            "name"
+        );
+
+        let partial = template.partial(prompt_args! { "persona" => "{name}" });
+        assert_eq!(partial.variables(), vec!["greeting", "name"]);
+
+        let partial = partial.partial(prompt_args! { "greeting" => "hello" });
+        assert_eq!(partial.variables(), vec!["name"]);
+        assert!(partial.format(prompt_args! {}).is_err());
+
+        let result = partial.format(prompt_args! { "name" => "Luis" });
+        assert_eq!(result.unwrap(), "{name} says hello to Luis");
+
+        // the original template is left untouched
+        assert_eq!(template.variables().len(), 3);
+    }
+
+    #[test]
+    fn should_format_owned_typed_args() {
+        let template = template_fstring!(
+            "{name} is {age}, active: {active}, profile: {profile}",
+            "name",
+            "age",
+            "active",
+            "profile"
+        );
+
+        let name = format!("{}-{}", "Luis", 24);
+        let result = template
+            .format(prompt_args! {
+                "name" => name,
+                "age" => 24,
+                "active" => true,
+                "profile" => serde_json::json!({"editor": "nvim"}),
+            })
+            .unwrap();
+        assert_eq!(
+            result,
+            r#"Luis-24 is 24, active: true, profile: {"editor":"nvim"}"#
+        );
+    }
+
+    #[test]
+    fn should_prompt_macro_accept_mixed_values() {
+        struct Version(u8, u8);
+        impl std::fmt::Display for Version {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "v{}.{}", self.0, self.1)
+            }
+        }
+
+        let name = String::from("Luis");
+        let args = prompt_args! {
+            "name" => name,
+            "age" => 24,
+            "height" => 1.75,
+            "active" => false,
+            "greeting" => "hello",
+            "version" => Version(4, 0),
+        };
+        assert_eq!(args["name"], "Luis");
+        assert_eq!(args["age"], 24);
+        assert_eq!(args["height"], 1.75);
+        assert_eq!(args["active"], false);
+        assert_eq!(args["greeting"], "hello");
+        assert_eq!(args["version"], "v4.0");
+
+        let template = template_fstring!(
+            "{greeting} {name} ({age}, {height}m, active: {active}) {version}",
+            "greeting",
+            "name",
+            "age",
+            "height",
+            "active",
+            "version"
+        );
+        assert_eq!(
+            template.format(args).unwrap(),
+            "hello Luis (24, 1.75m, active: false) v4.0"
+        );
+    }
+
+    #[test]
+    fn should_convert_borrowed_maps_into_prompt_args() {
+        let template = template_fstring!("Hello {name}!", "name");
+
+        let mut args = std::collections::HashMap::new();
+        args.insert("name", "world");
+        let result = template.format(args.into_prompt_args());
+        assert_eq!(result.unwrap(), "Hello world!");
+
+        let args = vec![("name".to_string(), "Luis".to_string())].into_prompt_args();
+        assert_eq!(template.format(args).unwrap(), "Hello Luis!");
+    }
+
+    #[test]
+    fn should_format_from_key_value_pairs() {
+        let template = template_fstring!("{greeting} {name}!", "greeting", "name");
+        let map = std::collections::BTreeMap::from([("greeting", "Hi"), ("name", "Ada")]);
+        assert_eq!(template.format_from(map).unwrap(), "Hi Ada!");
+        let pairs = [("greeting", "Hey"), ("name", "Bob")];
+        assert_eq!(template.format_from(&pairs[..]).unwrap(), "Hey Bob!");
+        assert_eq!(template.format_from(pairs).unwrap(), "Hey Bob!");
+        let pairs = vec![("greeting".to_string(), 1), ("name".to_string(), 2)];
+        assert_eq!(template.format_from(pairs).unwrap(), "1 2!");
+
+        let pairs = [
+            ("greeting", "Hi"),
+            ("name", "Ada"),
+            ("name", "Eve"),
+            ("name", "Zoe"),
+        ];
+        let (args, duplicates) = pairs.into_prompt_args_checked();
+        assert_eq!(duplicates, vec!["name"]);
+        assert_eq!(template.format(args).unwrap(), "Hi Zoe!");
+    }
+
+    #[test]
+    fn should_report_missing_variable_name() {
+        let template = template_fstring!(
+            "Hello {name} from {city}, {country}",
+            "name",
+            "city",
+            "country"
+        );
+        match template.format(prompt_args! { "name" => "Luis" }) {
+            Err(PromptError::InvalidInput(e)) => assert_eq!(e.missing, vec!["city", "country"]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        fn boxed(template: &PromptTemplate) -> Result<String, Box<dyn std::error::Error>> {
+            Ok(template.format(prompt_args! {})?)
+        }
+        let error = boxed(&template).unwrap_err();
+        assert!(error.downcast_ref::<PromptError>().is_some());
+    }
+
+    #[test]
+    fn should_format_with_default_values() {
+        let template = template_fstring!(
+            "Reply in {language} with a {tone} tone: {input}",
+            "language",
+            "tone",
+            "input"
+        )
+        .with_defaults(HashMap::from([
+            ("language".to_string(), "English".to_string()),
+            ("tone".to_string(), "neutral".to_string()),
+        ]));
+        assert_eq!(template.variables(), vec!["language", "tone", "input"]);
+        assert_eq!(template.required_variables(), vec!["input"]);
+
+        let result = template.format(prompt_args! { "input" => "hi", "tone" => "friendly" });
+        assert_eq!(result.unwrap(), "Reply in English with a friendly tone: hi");
+
+        // an explicit empty string is not replaced by the default
+        let result = template.format(prompt_args! { "input" => "hi", "tone" => "" });
+        assert_eq!(result.unwrap(), "Reply in English with a  tone: hi");
+
+        match template.format(prompt_args! { "tone" => "formal" }) {
+            Err(PromptError::InvalidInput(e)) => assert_eq!(e.missing, vec!["input"]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // a partial variable takes precedence over the default
+        let partial = template.partial(prompt_args! { "language" => "French" });
+        let result = partial.format(prompt_args! { "input" => "hi" });
+        assert_eq!(result.unwrap(), "Reply in French with a neutral tone: hi");
+    }
+
+    #[test]
+    fn should_format_optional_variables() {
+        let template = template_fstring!(
+            "Summarize the text.\n{extra_instructions}\nText: {text}\n{footer}",
+            "extra_instructions",
+            "text",
+            "footer"
+        )
+        .with_optional_variables(["extra_instructions", "footer"]);
+        assert_eq!(template.required_variables(), vec!["text"]);
+        assert_eq!(template.missing_variables(&prompt_args! {}), vec!["text"]);
+
+        let result = template.format(prompt_args! { "text" => "abc" }).unwrap();
+        assert_eq!(result, "Summarize the text.\n\nText: abc\n");
+
+        let template = template.with_collapse_blank_lines(true);
+        let result = template.format(prompt_args! { "text" => "abc" }).unwrap();
+        assert_eq!(result, "Summarize the text.\nText: abc");
+
+        let result = template
+            .format(prompt_args! { "text" => "abc", "extra_instructions" => "Be brief." })
+            .unwrap();
+        assert_eq!(result, "Summarize the text.\nBe brief.\nText: abc");
+    }
+
+    #[test]
+    fn should_format_with_aliases() {
+        let template = template_fstring!("{q} in {lang}", "q", "lang")
+            .with_aliases(HashMap::from([("question".to_string(), "q".to_string())]))
+            .unwrap();
+        assert_eq!(template.variables(), vec!["question", "lang"]);
+        assert_eq!(
+            template
+                .format(prompt_args! { "question" => "Why?", "lang" => "French" })
+                .unwrap(),
+            "Why? in French"
+        );
+        let error = template
+            .validate_input(&prompt_args! { "q" => "Why?" })
+            .unwrap_err();
+        assert_eq!(error.missing, vec!["question", "lang"]);
+        assert_eq!(error.unexpected, vec!["q"]);
+        assert_eq!(error.locations["question"][0].column, 1);
+
+        let aliased = |aliases: &[(&str, &str)]| {
+            template_fstring!("{q} in {lang}", "q", "lang").with_aliases(
+                aliases
+                    .iter()
+                    .map(|(alias, variable)| (alias.to_string(), variable.to_string()))
+                    .collect(),
+            )
+        };
+        assert!(matches!(
+            aliased(&[("q", "lang")]),
+            Err(PromptError::InvalidAlias { alias, .. }) if alias == "q"
+        ));
+        assert!(matches!(
+            aliased(&[("question", "q"), ("query", "q")]),
+            Err(PromptError::InvalidAlias { .. })
+        ));
+        assert!(matches!(
+            aliased(&[("question", "missing")]),
+            Err(PromptError::UnknownVariable(variable)) if variable == "missing"
+        ));
+    }
+
+    #[test]
+    fn should_validate_input() {
+        let template = template_fstring!(
+            "{persona}: reply in {language} to {input} about {topic}",
+            "persona",
+            "language",
+            "input",
+            "topic"
+        )
+        .with_defaults(HashMap::from([(
+            "language".to_string(),
+            "English".to_string(),
+        )]))
+        .partial(prompt_args! { "persona" => "a pirate" });
+
+        let args = prompt_args! { "persona" => "a parrot", "topic" => "ships" };
+        assert_eq!(template.missing_variables(&args), vec!["input"]);
+        assert!(template
+            .validate_input(&prompt_args! { "input" => "hi", "topic" => "ships" })
+            .is_ok());
+
+        let error = template
+            .validate_input(&prompt_args! { "tone" => "calm", "extra" => 1 })
+            .unwrap_err();
+        assert_eq!(error.missing, vec!["input", "topic"]);
+        assert_eq!(error.unexpected, vec!["extra", "tone"]);
+        assert_eq!(
+            error.to_string(),
+            "Invalid input variables: missing [\"input\", \"topic\"], unexpected [\"extra\", \"tone\"]; input at 1:35, topic at 1:49"
+        );
+    }
+
+    #[test]
+    fn should_build_nested_prompt_args() {
+        let defaults = prompt_args! { "k" => 1, "tone" => "calm" };
+        let name = String::from("Ada");
+        let args = prompt_args! {
+            ..defaults.clone(),
+            "user" => {
+                "name" => name,
+                "roles" => ["admin", "dev",],
+                "team" => { "size" => 3 },
+            },
+            "matrix" => [[1, 2], [], [{ "x" => true }]],
+            "k" => 3,
+        };
+        assert_eq!(
+            serde_json::to_value(&args).unwrap(),
+            serde_json::json!({
+                "tone": "calm",
+                "k": 3,
+                "user": { "name": "Ada", "roles": ["admin", "dev"], "team": { "size": 3 } },
+                "matrix": [[1, 2], [], [{ "x": true }]],
+            })
+        );
+
+        let args = prompt_args! { "k" => 3, ..defaults };
+        assert_eq!(args["k"], 1);
+        assert_eq!(prompt_args! {}, PromptArgs::new());
+
+        let template =
+            PromptTemplate::from_template("{user.name} is {user.roles.1}", TemplateFormat::FString)
+                .unwrap();
+        let args = prompt_args! { "user" => { "name" => "Ada", "roles" => ["admin", "dev"] } };
+        assert_eq!(template.format(args).unwrap(), "Ada is dev");
+    }
+
+    #[test]
+    fn should_report_template_locations() {
+        let template = PromptTemplate::from_template(
+            "Hi {name},\nhere is {topic}.\n{name}, enjoy!",
+            TemplateFormat::FString,
+        )
+        .unwrap();
+        let error = template
+            .format(prompt_args! { "topic" => "rust" })
+            .unwrap_err();
+        match &error {
+            PromptError::InvalidInput(e) => {
+                let spans = &e.locations["name"];
+                assert_eq!(
+                    spans.iter().map(|s| (s.line, s.column)).collect::<Vec<_>>(),
+                    vec![(1, 4), (3, 1)]
+                );
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(error.to_string().ends_with("; name at 1:4 and 3:1"));
+
+        let error =
+            PromptTemplate::from_template("Hello,\n  {name, welcome", TemplateFormat::FString)
+                .err()
+                .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Invalid template: unclosed placeholder at line 2, column 3\n  |\n2 |   {name, welcome\n  |   ^^^^^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn should_format_conditional_blocks() {
+        let template = PromptTemplate::from_template(
+            "{#if context}Use this context:\n{context}\n{#else}No context.\n{/if}Q: {question}{#if notes} ({#if notes.0}{notes.0}{/if}){/if}",
+            TemplateFormat::FString,
+        )
+        .unwrap();
+        assert_eq!(template.variables(), vec!["context", "question", "notes"]);
+        assert_eq!(template.required_variables(), vec!["question"]);
+
+        let result = template
+            .format(prompt_args! { "context" => "docs", "question" => "why?" })
+            .unwrap();
+        assert_eq!(result, "Use this context:\ndocs\nQ: why?");
+
+        let result = template
+            .format(prompt_args! { "context" => "", "question" => "why?", "notes" => ["a"] })
+            .unwrap();
+        assert_eq!(result, "No context.\nQ: why? (a)");
+
+        let result = template
+            .format(prompt_args! { "question" => "why?", "notes" => Vec::<String>::new() })
+            .unwrap();
+        assert_eq!(result, "No context.\nQ: why?");
+
+        let partial = template
+            .format_partial(prompt_args! { "question" => "why?" })
+            .unwrap();
+        assert_eq!(
+            partial
+                .format(prompt_args! { "context" => "docs" })
+                .unwrap(),
+            "Use this context:\ndocs\nQ: why?"
+        );
+
+        match PromptTemplate::from_template("Hi {#if name}{name}", TemplateFormat::FString) {
+            Err(PromptError::InvalidTemplate { span, .. }) => assert_eq!(span.start, 3),
+            other => panic!("unexpected result: {:?}", other.map(|p| p.template())),
+        }
+    }
+
+    #[test]
+    fn should_bind_output_parser_format_instructions() {
+        struct JsonInstructions;
+        impl FormatInstructions for JsonInstructions {
+            fn format_instructions(&self) -> String {
+                "Answer with JSON like {\"answer\": {question}}".to_string()
+            }
+        }
+
+        let template = template_fstring!(
+            "{question}\n{format_instructions}",
+            "question",
+            "format_instructions"
+        )
+        .with_output_parser(&JsonInstructions)
+        .unwrap();
+        assert_eq!(template.variables(), vec!["question"]);
+        assert_eq!(
+            template
+                .format(prompt_args! { "question" => "Why?" })
+                .unwrap(),
+            "Why?\nAnswer with JSON like {\"answer\": {question}}"
+        );
+
+        let result =
+            template_fstring!("{question}", "question").with_output_parser(&JsonInstructions);
+        assert!(
+            matches!(result, Err(PromptError::UnknownVariable(v)) if v == "format_instructions")
+        );
+    }
+
+    #[test]
+    fn should_format_partial_template() {
+        let template = template_fstring!(
+            "{{{context}}}\nQuestion: {question} ({context})",
+            "context",
+            "question"
+        );
+        let partial = template
+            .format_partial(prompt_args! { "context" => "uses {braces} and {{x}}" })
+            .unwrap();
+        assert_eq!(partial.variables(), vec!["question"]);
+        assert!(partial.partial_variables().is_empty());
+        assert_eq!(
+            partial
+                .format(prompt_args! { "question" => "why?" })
+                .unwrap(),
+            "{uses {braces} and {{x}}}\nQuestion: why? (uses {braces} and {{x}})"
+        );
+
+        // missing variables are fine, unknown ones are not
+        let partial = template.format_partial(prompt_args! {}).unwrap();
+        assert_eq!(partial.variables(), vec!["context", "question"]);
+        match template.format_partial(prompt_args! { "city" => "Lima" }) {
+            Err(PromptError::UnknownVariable(name)) => assert_eq!(name, "city"),
+            other => panic!("unexpected result: {:?}", other.map(|p| p.template())),
+        }
+
+        let template = template_jinja2!("{{context}} / {{ question }}", "context", "question");
+        let partial = template
+            .format_partial(prompt_args! { "context" => "retrieved" })
+            .unwrap();
+        assert_eq!(partial.template(), "retrieved / {{question}}");
+        assert_eq!(
+            partial
+                .format(prompt_args! { "question" => "why?" })
+                .unwrap(),
+            "retrieved / why?"
+        );
+    }
+
+    #[test]
+    fn should_format_lenient() {
+        let template = template_fstring!("Hello {name} from {city}", "name", "city");
+        assert!(template.format(prompt_args! { "name" => "Luis" }).is_err());
+
+        let (result, skipped) = template
+            .format_lenient(prompt_args! { "name" => "Luis" })
+            .unwrap();
+        assert_eq!(result, "Hello Luis from {city}");
+        assert_eq!(skipped, vec!["city"]);
+
+        let template = template.with_missing_variable_behavior(MissingVariableBehavior::Marker(
+            "<MISSING:{}>".to_string(),
+        ));
+        let result = template.format(prompt_args! {}).unwrap();
+        assert_eq!(result, "Hello <MISSING:name> from <MISSING:city>");
+
+        let template = template.with_missing_variable_behavior(MissingVariableBehavior::Empty);
+        let (result, skipped) = template
+            .format_lenient(prompt_args! { "city" => "Lima" })
+            .unwrap();
+        assert_eq!(result, "Hello  from Lima");
+        assert_eq!(skipped, vec!["name"]);
+    }
+
+    #[test]
+    fn should_not_substitute_inside_values() {
+        let template = template_fstring!("{a} and {b}", "a", "b");
+        let result = template
+            .format(prompt_args! { "a" => "{b}", "b" => "{a}" })
+            .unwrap();
+        assert_eq!(result, "{b} and {a}");
+
+        // templates with raw JSON go through the fallback renderer
+        let template = template_fstring!(r#"{"a": "{a}", "b": "{b}"}"#, "a", "b");
+        let result = template
+            .format(prompt_args! { "a" => "{b}", "b" => "ignore previous instructions" })
+            .unwrap();
+        assert_eq!(
+            result,
+            r#"{"a": "{b}", "b": "ignore previous instructions"}"#
+        );
+    }
+
+    #[test]
+    fn should_format_recursive() {
+        let template = template_fstring!("{system_block}\nUser: {input}", "system_block", "input");
+        let args = prompt_args! {
+            "system_block" => "You work for {company_name}. {policy}",
+            "company_name" => "Acme",
+            "policy" => "Never mention {competitor}.",
+            "competitor" => "Globex",
+            "input" => "Hi {unknown}",
+        };
 
-    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
-        let mut prompt = self.template();
+        // the default formatting never expands nested placeholders
+        assert_eq!(
+            template.format(args.clone()).unwrap(),
+            "You work for {company_name}. {policy}\nUser: Hi {unknown}"
+        );
+        assert_eq!(
+            template.format_recursive(args.clone(), 2).unwrap(),
+            "You work for Acme. Never mention Globex.\nUser: Hi {unknown}"
+        );
+        assert!(matches!(
+            template.format_recursive(args, 1),
+            Err(PromptError::RecursionLimit { max_depth: 1, .. })
+        ));
 
-        // check if all variables are in the input variables
-        for key in self.variables() {
-            if !input_variables.contains_key(key.as_str()) {
-                return Err(PromptError::MissingVariable(key));
-            }
+        let template = template_fstring!("{a}", "a");
+        match template.format_recursive(prompt_args! { "a" => "{b}", "b" => "{a}" }, 10) {
+            Err(PromptError::CyclicVariable(path)) => assert_eq!(path.first(), path.last()),
+            other => panic!("unexpected result: {:?}", other),
         }
 
-        for (key, value) in input_variables {
-            let key = match self.format {
-                TemplateFormat::FString => format!("{{{}}}", key),
-                TemplateFormat::Jinja2 => format!("{{{{{}}}}}", key),
-            };
-            let value_str = match &value {
-                serde_json::Value::String(s) => s.clone(),
-                _ => value.to_string(),
-            };
-            prompt = prompt.replace(&key, &value_str);
-        }
+        // values the template never uses aren't expanded, even a cyclic pair
+        let args = prompt_args! { "a" => "x", "b" => "{c}", "c" => "{b}", "d" => "{e}" };
+        assert_eq!(template.format_recursive(args, 1).unwrap(), "x");
 
-        log::debug!("Formatted prompt: {}", prompt);
-        Ok(prompt)
+        // partial and default values are expanded too
+        let template = template_fstring!("{greeting}, {name}", "greeting", "name")
+            .with_defaults(HashMap::from([(
+                "name".to_string(),
+                "{title} Doe".to_string(),
+            )]))
+            .partial(prompt_args! { "greeting" => "Hello {name}" });
+        assert_eq!(
+            template
+                .format_recursive(prompt_args! { "title" => "Dr." }, 3)
+                .unwrap(),
+            "Hello Dr. Doe, Dr. Doe"
+        );
     }
-}
-
-/// `prompt_args!` is a utility macro used for creating a `std::collections::HashMap<String, serde_json::Value>`.
-/// This HashMap can then be passed as arguments to a function or method.
-///
-/// # Usage
-/// In this macro, the keys are `&str` and values are arbitrary types that get serialized into `serde_json::Value`:
-/// ```rust,ignore
-/// prompt_args! {
-///     "input" => "Who is the writer of 20,000 Leagues Under the Sea, and what is my name?",
-///     "history" => vec![
-///         Message::new_human_message("My name is: Luis"),
-///         Message::new_ai_message("Hi Luis"),
-///     ],
-/// }
-/// ```
-///
-/// # Arguments
-/// * `key` - A `&str` that will be used as the key in the resulting HashMap.<br>
-/// * `value` - An arbitrary type that will be serialized into `serde_json::Value` and associated with the corresponding key.
-///
-/// The precise keys and values are dependent on your specific use case. In this example, "input" and "history" are keys,
-/// and
-#[macro_export]
-macro_rules! prompt_args {
-    ( $($key:expr => $value:expr),* $(,)? ) => {
-        {
-            #[allow(unused_mut)]
-            let mut args = std::collections::HashMap::<String, serde_json::Value>::new();
-            $(
-                // Convert the value to serde_json::Value before inserting
-                args.insert($key.to_string(), serde_json::json!($value));
-            )*
-            args
-        }
-    };
-}
 
-/// `template_fstring` is a utility macro that creates a new `PromptTemplate` with FString as the template format.
-///
-/// # Usage
-/// The macro is called with a template string and a list of variables that exist in the template. For example:
-/// ```rust,ignore
-/// template_fstring!(
-///     "Hello {name}",
-///     "name"
-/// )
-/// ```
-/// This returns a `PromptTemplate` object that contains the string "Hello {name}" as the template and ["name"] as the variables, with TemplateFormat set to FString.
-#[macro_export]
-macro_rules! template_fstring {
-    ($template:expr, $($var:expr),* $(,)?) => {
-        $crate::prompt::PromptTemplate::new(
-            $template.to_string(),
-            vec![$($var.to_string()),*],
-            $crate::prompt::TemplateFormat::FString,
+    #[test]
+    fn should_format_dot_paths() {
+        let template = PromptTemplate::from_template(
+            "{user.name} lives in {user.address.city}, likes {items.0} and has {user.address}",
+            TemplateFormat::FString,
         )
-    };
-}
+        .unwrap();
+        assert_eq!(template.variables(), vec!["user", "items"]);
 
-/// `template_jinja2` is a utility macro that creates a new `PromptTemplate` with Jinja2 as the template format.
-///
-/// # Usage
-/// The macro is called with a template string and a list of variables that exist in the template. For example:
-/// ```rust,ignore
-/// template_jinja2!(
-///     "Hello {{ name }}",
-///     "name"
-/// )
-/// ```
-/// This returns a `PromptTemplate` object that contains the string "Hello {{ name }}" as the template and ["name"] as the variables, with TemplateFormat set to Jinja2.
-#[macro_export]
-macro_rules! template_jinja2 {
-    ($template:expr, $($var:expr),* $(,)?) => {
-        $crate::prompt::PromptTemplate::new(
-            $template.to_string(),
-            vec![$($var.to_string()),*],
-            $crate::prompt::TemplateFormat::Jinja2,
-        )
-    };
-}
+        let args = prompt_args! {
+            "user" => serde_json::json!({"name": "Luis", "address": {"city": "Lima"}}),
+            "items" => vec!["books", "music"],
+        };
+        assert_eq!(
+            template.format(args).unwrap(),
+            r#"Luis lives in Lima, likes books and has {"city":"Lima"}"#
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::prompt_args;
+        let args = prompt_args! {
+            "user" => serde_json::json!({"name": "Luis"}),
+            "items" => Vec::<String>::new(),
+        };
+        match template.format(args) {
+            Err(PromptError::MissingVariable(path)) => assert_eq!(path, "user.address.city"),
+            other => panic!("unexpected result: {:?}", other),
+        }
 
-    #[test]
-    fn should_format_jinja2_template() {
-        let template = PromptTemplate::new(
-            "Hello {{name}}!".to_string(),
-            vec!["name".to_string()],
-            TemplateFormat::Jinja2,
+        let (result, _) = template
+            .format_lenient(prompt_args! { "items" => vec!["books"] })
+            .unwrap();
+        assert_eq!(
+            result,
+            "{user.name} lives in {user.address.city}, likes books and has {user.address}"
         );
+    }
 
-        let input_variables = prompt_args! {};
-        let result = template.format(input_variables);
-        assert!(result.is_err());
+    #[test]
+    fn should_format_into_writer() {
+        let template = template_fstring!("Hello {name}, you are {age}!", "name", "age");
+        assert_eq!(template.template_str(), "Hello {name}, you are {age}!");
+        assert_eq!(template.variable_names(), ["name", "age"]);
 
-        let input_variables = prompt_args! {
-            "name" => "world",
-        };
-        let result = template.format(input_variables);
-        println!("{:?}", result);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "Hello world!");
+        let mut buffer = String::from("> ");
+        template
+            .format_into(prompt_args! { "name" => "Luis", "age" => 24 }, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, "> Hello Luis, you are 24!");
+
+        let boxed: Box<dyn PromptFromatter> = Box::new(template);
+        let mut buffer = String::new();
+        assert!(boxed
+            .format_into(prompt_args! { "name" => "Luis" }, &mut buffer)
+            .is_err());
     }
 
     #[test]
-    fn should_format_fstring_template() {
-        let template = PromptTemplate::new(
-            "Hello {name}!".to_string(),
-            vec!["name".to_string()],
-            TemplateFormat::FString,
+    fn should_use_strings_as_prompts() {
+        let prompt = "Tell me a {{joke}} about {topic}";
+        assert_eq!(prompt.variables(), vec!["topic"]);
+        assert_eq!(
+            prompt.format(prompt_args! { "topic" => "cats" }).unwrap(),
+            "Tell me a {joke} about cats"
         );
 
-        let input_variables = prompt_args! {};
-        let result = template.format(input_variables);
-        assert!(result.is_err());
+        let boxed: Box<dyn PromptFromatter> = "Hi {name".to_string().into();
+        assert!(boxed.variables().is_empty());
+        assert!(matches!(
+            boxed.format(prompt_args! { "name" => "Luis" }),
+            Err(PromptError::InvalidTemplate { .. })
+        ));
 
-        let input_variables = prompt_args! {
-            "name" => "world",
-        };
-        let result = template.format(input_variables);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "Hello world!");
+        let template = PromptTemplate::try_from("Hello {name}").unwrap();
+        assert_eq!(template.variables(), vec!["name"]);
+        assert!(matches!(
+            PromptTemplate::try_from("Vars: {\"a\": {name}}".to_string()),
+            Err(PromptError::InvalidTemplate { .. })
+        ));
     }
 
     #[test]
@@ -255,4 +2921,401 @@ mod tests {
         let formatted_jinja2 = jinja2_template.format(input_variables_jinja2).unwrap();
         assert_eq!(formatted_jinja2, "Jinja2 Chat: Bob says Hi, Alice!");
     }
+
+    #[test]
+    fn test_format_batch() {
+        let prompt = template_fstring!("Hello {name}", "name");
+        let batch = vec![
+            prompt_args! { "name" => "Ana" },
+            prompt_args! {},
+            prompt_args! { "name" => "Bo" },
+        ];
+        let results = prompt.format_batch(&batch);
+        assert_eq!(results[0].as_deref().unwrap(), "Hello Ana");
+        assert!(matches!(results[1], Err(PromptError::InvalidInput(_))));
+        assert_eq!(results[2].as_deref().unwrap(), "Hello Bo");
+
+        assert!(matches!(
+            prompt.format_batch_strict(&batch),
+            Err(PromptError::BatchError { index: 1, .. })
+        ));
+        assert_eq!(
+            prompt.format_batch_strict(&[batch[2].clone()]).unwrap(),
+            vec!["Hello Bo"]
+        );
+
+        #[cfg(feature = "rayon")]
+        {
+            let parallel = prompt.format_batch_parallel(&batch);
+            assert_eq!(parallel[0].as_deref().unwrap(), "Hello Ana");
+            assert!(parallel[1].is_err());
+            assert_eq!(parallel[2].as_deref().unwrap(), "Hello Bo");
+        }
+    }
+
+    #[test]
+    fn test_format_iter() {
+        let prompt = template_fstring!("Hello {name}", "name");
+        let names = ["Ana".to_string(), "Bo".to_string(), "Cy".to_string()];
+
+        // the iterator borrows both the prompt and `names`, and ends before them
+        let prompts: Vec<String> = prompt
+            .format_iter(names.iter().map(|name| prompt_args! { "name" => name }))
+            .filter_map(Result::ok)
+            .skip(1)
+            .collect();
+        assert_eq!(prompts, vec!["Hello Bo", "Hello Cy"]);
+
+        let mut results = Vec::new();
+        for result in prompt.format_iter(vec![prompt_args! {}, prompt_args! { "name" => "Dee" }]) {
+            results.push(result);
+        }
+        assert!(results[0].is_err());
+        assert_eq!(results[1].as_deref().unwrap(), "Hello Dee");
+    }
+
+    #[test]
+    fn test_format_positional() {
+        let prompt =
+            PromptTemplate::from_template("{0} was released in {1}. {0}!", TemplateFormat::FString)
+                .unwrap();
+        assert_eq!(prompt.variables(), vec!["0", "1"]);
+        assert_eq!(
+            prompt
+                .format_positional(&crate::prompt_pos_args!["Rust 1.0", 2015])
+                .unwrap(),
+            "Rust 1.0 was released in 2015. Rust 1.0!"
+        );
+        assert_eq!(
+            prompt
+                .format(prompt_args! { "0" => "Go", "1" => 2012 })
+                .unwrap(),
+            "Go was released in 2012. Go!"
+        );
+
+        match prompt.format_positional(&["Rust 1.0"]) {
+            Err(PromptError::PositionOutOfRange { index, len }) => assert_eq!((index, len), (1, 1)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(matches!(
+            PromptTemplate::from_template("{0} is {name}", TemplateFormat::FString),
+            Err(PromptError::InvalidTemplate { .. })
+        ));
+    }
+
+    #[test]
+    fn test_format_specs() {
+        let prompt = PromptTemplate::from_template(
+            "| {name:<6} | {score:>5.1} | {rank:03} | {{{name:^6}}}",
+            TemplateFormat::FString,
+        )
+        .unwrap();
+        assert_eq!(prompt.variables(), vec!["name", "score", "rank"]);
+        assert_eq!(
+            prompt
+                .format(prompt_args! { "name" => "Ana", "score" => 9.25, "rank" => 7 })
+                .unwrap(),
+            "| Ana    |   9.2 | 007 | { Ana  }"
+        );
+
+        match prompt.format(prompt_args! { "name" => "Ana", "score" => "high", "rank" => 1 }) {
+            Err(PromptError::FormatSpecError { variable, .. }) => assert_eq!(variable, "score"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let partial = prompt
+            .format_partial(prompt_args! { "rank" => 12 })
+            .unwrap();
+        assert_eq!(
+            partial.template(),
+            "| {name:<6} | {score:>5.1} | 012 | {{{name:^6}}}"
+        );
+    }
+
+    #[test]
+    fn test_custom_delimiters() {
+        let format = TemplateFormat::custom("<<", ">>").unwrap();
+        let prompt = PromptTemplate::from_template(
+            "{\"task\": \"<<task>>\", \"user\": \"<< user.name >>\"} <<<<raw>>",
+            format,
+        )
+        .unwrap();
+        assert_eq!(prompt.variables(), vec!["task", "user"]);
+        let args = prompt_args! { "task" => "sum", "user" => serde_json::json!({ "name": "Ana" }) };
+        assert_eq!(
+            prompt.format(args).unwrap(),
+            "{\"task\": \"sum\", \"user\": \"Ana\"} <<raw>>"
+        );
+
+        let partial = prompt
+            .format_partial(prompt_args! { "task" => "a <<b>>" })
+            .unwrap();
+        assert_eq!(
+            partial.template(),
+            "{\"task\": \"a <<<<b>>\", \"user\": \"<<user.name>>\"} <<<<raw>>"
+        );
+        assert_eq!(partial.variables(), vec!["user"]);
+
+        // Go templates, with asymmetric delimiters
+        let go = PromptTemplate::from_template(
+            "Hello {{.Name}} from {{ .Team }}!",
+            TemplateFormat::custom("{{.", "}}").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            go.format(prompt_args! { "Name" => "Ana", "Team" => "core" })
+                .unwrap(),
+            "Hello Ana from {{ .Team }}!"
+        );
+        assert_eq!(go.variables(), vec!["Name"]);
+
+        let asymmetric = PromptTemplate::from_template(
+            "<% greeting %>, [missing] <%name%>",
+            TemplateFormat::custom("<%", "%>").unwrap(),
+        )
+        .unwrap();
+        match asymmetric.format(prompt_args! { "greeting" => "Hi" }) {
+            Err(PromptError::InvalidInput(e)) => assert_eq!(e.missing, vec!["name"]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(
+            asymmetric
+                .format_with_behavior(
+                    prompt_args! { "greeting" => "Hi" },
+                    &MissingVariableBehavior::LeaveAsIs
+                )
+                .unwrap()
+                .0,
+            "Hi, [missing] <%name%>"
+        );
+
+        let invalid = TemplateFormat::Custom {
+            open: "<".into(),
+            close: "<<".into(),
+        };
+        assert!(matches!(
+            PromptTemplate::from_template("<a<<", invalid),
+            Err(PromptError::InvalidDelimiters { .. })
+        ));
+    }
+
+    #[test]
+    fn test_allow_env() {
+        std::env::set_var("LANGCHAIN_RUST_TEST_PRODUCT", "Acme");
+        let template =
+            "{env:LANGCHAIN_RUST_TEST_PRODUCT} support: {env:LANGCHAIN_RUST_TEST_URL}\n{input}";
+        let prompt = PromptTemplate::from_template(template, TemplateFormat::FString).unwrap();
+        assert_eq!(prompt.variables(), vec!["input"]);
+        assert!(prompt.lint().is_empty());
+
+        // off by default
+        match prompt.format(prompt_args! { "input" => "Hi" }) {
+            Err(PromptError::EnvNotAllowed(name)) => {
+                assert_eq!(name, "LANGCHAIN_RUST_TEST_PRODUCT")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let prompt = prompt.allow_env(["LANGCHAIN_RUST_TEST_PRODUCT", "LANGCHAIN_RUST_TEST_URL"]);
+        assert!(matches!(
+            prompt.format(prompt_args! { "input" => "Hi" }),
+            Err(PromptError::MissingEnvVariable(_))
+        ));
+        let prompt = prompt.with_env_fallback("LANGCHAIN_RUST_TEST_URL", "acme.test/help");
+        assert_eq!(
+            prompt.format(prompt_args! { "input" => "Hi" }).unwrap(),
+            "Acme support: acme.test/help\nHi"
+        );
+
+        let partial = prompt
+            .format_partial(prompt_args! { "input" => "Hi" })
+            .unwrap();
+        assert!(partial
+            .template()
+            .starts_with("{env:LANGCHAIN_RUST_TEST_PRODUCT}"));
+
+        let loaded = PromptTemplate::from_json(&prompt.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.allowed_env(), prompt.allowed_env());
+        assert_eq!(
+            loaded.format(prompt_args! { "input" => "Hi" }).unwrap(),
+            "Acme support: acme.test/help\nHi"
+        );
+    }
+
+    #[test]
+    fn test_datetime_variables() {
+        use crate::prompt::FixedClock;
+        use chrono::{FixedOffset, TimeZone, Utc};
+
+        let template = "Today is {today} ({now:%A %H:%M}). {input}";
+        let prompt = PromptTemplate::from_template(template, TemplateFormat::FString).unwrap();
+        assert_eq!(prompt.variables(), vec!["today", "now", "input"]);
+        // ordinary variables without a clock
+        assert!(prompt.format(prompt_args! { "input" => "Hi" }).is_err());
+
+        let clock = FixedClock(Utc.with_ymd_and_hms(2024, 5, 1, 22, 30, 0).unwrap());
+        let prompt = prompt.with_clock(clock);
+        assert_eq!(prompt.required_variables(), vec!["input"]);
+        assert_eq!(
+            prompt.format(prompt_args! { "input" => "Hi" }).unwrap(),
+            "Today is 2024-05-01 (Wednesday 22:30). Hi"
+        );
+
+        let tokyo = prompt.with_timezone(FixedOffset::east_opt(9 * 3600).unwrap());
+        assert_eq!(
+            tokyo.format(prompt_args! { "input" => "Hi" }).unwrap(),
+            "Today is 2024-05-02 (Thursday 07:30). Hi"
+        );
+
+        // passed values win
+        assert_eq!(
+            tokyo
+                .format(prompt_args! {
+                    "input" => "Hi",
+                    "today" => "yesterday",
+                    "now" => "2020-01-06T08:00:00Z",
+                })
+                .unwrap(),
+            "Today is yesterday (Monday 08:00). Hi"
+        );
+
+        let now = template_fstring!("{now}", "now").with_clock(clock);
+        assert_eq!(
+            now.format(PromptArgs::new()).unwrap(),
+            "2024-05-01 22:30:00 +00:00"
+        );
+
+        assert!(matches!(
+            PromptTemplate::from_template("{now:%Q}", TemplateFormat::FString),
+            Err(PromptError::InvalidTemplate { .. })
+        ));
+    }
+
+    #[test]
+    fn test_derive_into_prompt_args() {
+        #[derive(IntoPromptArgs)]
+        struct ProfileArgs<'a> {
+            #[prompt(rename = "user")]
+            name: &'a str,
+            age: u32,
+            nickname: Option<String>,
+            interests: Vec<&'a str>,
+            #[prompt(display)]
+            score: f32,
+            #[prompt(skip)]
+            request_id: u64,
+        }
+
+        let args = PromptArgs::from(ProfileArgs {
+            name: "Ana",
+            age: 30,
+            nickname: None,
+            interests: vec!["chess"],
+            score: 0.5,
+            request_id: 7,
+        });
+        assert_eq!(
+            args,
+            prompt_args! {
+                "user" => "Ana",
+                "age" => 30,
+                "interests" => vec!["chess"],
+                "score" => "0.5",
+            }
+        );
+
+        let prompt = PromptTemplate::from_template(
+            "{user} ({age}), aka {nickname}, likes {interests.0}",
+            TemplateFormat::FString,
+        )
+        .unwrap();
+        let result = prompt.format(ProfileArgs {
+            name: "Ana",
+            age: 30,
+            nickname: Some("Annie".into()),
+            interests: vec!["chess"],
+            score: 0.5,
+            request_id: 7,
+        });
+        assert_eq!(result.unwrap(), "Ana (30), aka Annie, likes chess");
+
+        #[derive(IntoPromptArgs)]
+        struct ScoresArgs {
+            name: String,
+            // maps with non-string keys can't be serialized to JSON
+            scores: HashMap<(u8, u8), u32>,
+        }
+
+        let args = || ScoresArgs {
+            name: "Ana".into(),
+            scores: HashMap::from([((1, 2), 3)]),
+        };
+        assert_eq!(args().into_prompt_args(), prompt_args! { "name" => "Ana" });
+        let prompt = template_fstring!("{name}: {scores}", "name", "scores");
+        assert!(matches!(
+            prompt.format_from(args()),
+            Err(PromptError::InvalidInput(error)) if error.missing == vec!["scores"]
+        ));
+    }
+
+    #[test]
+    fn test_prompt_template_macro() {
+        let prompt = crate::prompt::prompt_template!(
+            "{{literal}} {user.name} scored {score:>5.1}{#if note} ({note}){/if} on {env:HOME} at {now:%Y}{# {draft} #}"
+        );
+        let inferred =
+            PromptTemplate::from_template(&prompt.template, TemplateFormat::FString).unwrap();
+        assert_eq!(prompt.variables, vec!["user", "score", "note", "now"]);
+        assert_eq!(prompt.variables, inferred.variables);
+
+        let prompt = crate::prompt::prompt_template!(
+            "{% for item in items %}{{ item }}{% endfor %} {{ title | upper }}",
+            format = jinja2,
+        );
+        assert_eq!(prompt.format, TemplateFormat::Jinja2);
+        assert_eq!(prompt.variables, vec!["items", "title"]);
+
+        let prompt = crate::prompt::prompt_template!(r#"{points|join:"}, {"|upper} {name}"#);
+        assert_eq!(prompt.variables, vec!["points", "name"]);
+    }
+
+    #[test]
+    fn should_apply_fstring_filters() {
+        let prompt = PromptTemplate::from_template(
+            "Notes:\n- {points|join:\"\\n- \"} by {author | trim | upper}, {title|truncate:5}",
+            TemplateFormat::FString,
+        )
+        .unwrap();
+        assert_eq!(prompt.variables(), vec!["points", "author", "title"]);
+        let args = prompt_args! {
+            "points" => ["a | b", "c"],
+            "author" => " ana ",
+            "title" => "Release notes",
+        };
+        assert_eq!(
+            prompt.format(args.clone()).unwrap(),
+            "Notes:\n- a | b\n- c by ANA, Relea"
+        );
+        assert_eq!(
+            prompt
+                .format_partial(prompt_args! { "author" => "luis" })
+                .unwrap()
+                .format(args)
+                .unwrap(),
+            "Notes:\n- a | b\n- c by LUIS, Relea"
+        );
+
+        match prompt.format(prompt_args! { "points" => "a", "author" => "", "title" => "" }) {
+            Err(PromptError::FilterError {
+                variable, filter, ..
+            }) => assert_eq!((variable.as_str(), filter.as_str()), ("points", "join")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match PromptTemplate::from_template("{points|shout}", TemplateFormat::FString) {
+            Err(PromptError::InvalidTemplate { reason, .. }) => {
+                assert_eq!(reason, "invalid filters `shout`: unknown filter `shout`")
+            }
+            _ => panic!("expected an invalid template"),
+        }
+    }
 }
@@ -0,0 +1,491 @@
+//! Regression suites for prompts: render a prompt with the arguments of each case, then check
+//! the result.
+//!
+//! # Usage
+//! ```rust,ignore
+//! let suite = EvalSuite::from_file("prompts/support.eval.yaml")?;
+//! let report = suite.run(&support_prompt);
+//! assert!(report.passed(), "{}", report);
+//! ```
+//!
+//! Suites can be written in YAML:
+//!
+//! ```yaml
+//! cases:
+//!   - name: greets the user
+//!     args:
+//!       name: Ana
+//!     assertions:
+//!       - contains: Ana
+//!       - not_contains: "{"
+//!       - matches: "^Hello"
+//!       - max_tokens: 200
+//! ```
+
+use std::{fmt, fs, path::Path, sync::Arc};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::language_models::llm::LLM;
+
+use super::{
+    loading::file_error, HeuristicTokenCounter, PromptArgs, PromptError, PromptFromatter,
+    TokenCounter,
+};
+
+/// A check of an `EvalCase`. The `Output` ones check the output of the model, and are only
+/// run by `EvalSuite::run_with_llm`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Assertion {
+    Contains(String),
+    NotContains(String),
+    /// The prompt matches the regex somewhere.
+    Matches(String),
+    /// The prompt takes at most this many tokens, as counted by the token counter of the
+    /// suite.
+    MaxTokens(usize),
+    OutputContains(String),
+    OutputNotContains(String),
+    OutputMatches(String),
+}
+
+impl Assertion {
+    fn is_on_output(&self) -> bool {
+        matches!(
+            self,
+            Assertion::OutputContains(_)
+                | Assertion::OutputNotContains(_)
+                | Assertion::OutputMatches(_)
+        )
+    }
+
+    // Checks `text`, returning why it fails otherwise.
+    fn check(&self, text: &str, counter: &dyn TokenCounter) -> Result<(), String> {
+        match self {
+            Assertion::Contains(expected) | Assertion::OutputContains(expected) => {
+                if text.contains(expected.as_str()) {
+                    Ok(())
+                } else {
+                    Err(format!("{:?} not found", expected))
+                }
+            }
+            Assertion::NotContains(unexpected) | Assertion::OutputNotContains(unexpected) => {
+                match text.find(unexpected.as_str()) {
+                    Some(position) => Err(format!("{:?} found at byte {}", unexpected, position)),
+                    None => Ok(()),
+                }
+            }
+            Assertion::Matches(pattern) | Assertion::OutputMatches(pattern) => {
+                match Regex::new(pattern) {
+                    Ok(regex) if regex.is_match(text) => Ok(()),
+                    Ok(_) => Err(format!("no match for /{}/", pattern)),
+                    Err(e) => Err(format!("invalid regex /{}/: {}", pattern, e)),
+                }
+            }
+            Assertion::MaxTokens(max_tokens) => {
+                let tokens = counter.count_tokens(text);
+                if tokens <= *max_tokens {
+                    Ok(())
+                } else {
+                    Err(format!("{} tokens, over {}", tokens, max_tokens))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Assertion::Contains(text) => write!(f, "contains {:?}", text),
+            Assertion::NotContains(text) => write!(f, "doesn't contain {:?}", text),
+            Assertion::Matches(pattern) => write!(f, "matches /{}/", pattern),
+            Assertion::MaxTokens(max_tokens) => write!(f, "takes at most {} tokens", max_tokens),
+            Assertion::OutputContains(text) => write!(f, "output contains {:?}", text),
+            Assertion::OutputNotContains(text) => write!(f, "output doesn't contain {:?}", text),
+            Assertion::OutputMatches(pattern) => write!(f, "output matches /{}/", pattern),
+        }
+    }
+}
+
+/// A named case of an `EvalSuite`: the arguments the prompt is formatted with, and the checks
+/// of the result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalCase {
+    pub name: String,
+    #[serde(default)]
+    pub args: PromptArgs,
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+}
+
+impl EvalCase {
+    pub fn new<S: Into<String>>(name: S, args: PromptArgs) -> Self {
+        Self {
+            name: name.into(),
+            args,
+            assertions: Vec::new(),
+        }
+    }
+
+    pub fn with_assertion(mut self, assertion: Assertion) -> Self {
+        self.assertions.push(assertion);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Outcome {
+    Passed,
+    /// The check failed, for the given reason.
+    Failed(String),
+    /// The check wasn't run, like the checks of the model output by `EvalSuite::run`, or the
+    /// checks of a case whose prompt failed to format.
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AssertionResult {
+    pub assertion: Assertion,
+    pub outcome: Outcome,
+}
+
+/// The result of an `EvalCase`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CaseResult {
+    pub name: String,
+    pub assertions: Vec<AssertionResult>,
+    /// The formatting or model error of the case, which fails it.
+    pub error: Option<String>,
+    /// The rendered prompt, kept when the case failed.
+    pub rendered: Option<String>,
+    /// The output of the model, kept when the case failed.
+    pub output: Option<String>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+            && self
+                .assertions
+                .iter()
+                .all(|result| !matches!(result.outcome, Outcome::Failed(_)))
+    }
+}
+
+/// The result of `EvalSuite::run`, with a result per case, in the order of the suite.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvalReport {
+    pub cases: Vec<CaseResult>,
+}
+
+impl EvalReport {
+    pub fn passed(&self) -> bool {
+        self.cases.iter().all(CaseResult::passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CaseResult> {
+        self.cases.iter().filter(|case| !case.passed())
+    }
+}
+
+/// Lists the failed cases and checks, with the rendered prompt of each failed case.
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.failures().count();
+        write!(
+            f,
+            "{} of {} cases passed",
+            self.cases.len() - failed,
+            self.cases.len()
+        )?;
+        for case in self.failures() {
+            write!(f, "\n\nFAILED {}", case.name)?;
+            if let Some(error) = &case.error {
+                write!(f, "\n  error: {}", error)?;
+            }
+            for result in &case.assertions {
+                if let Outcome::Failed(reason) = &result.outcome {
+                    write!(f, "\n  {}: {}", result.assertion, reason)?;
+                }
+            }
+            if let Some(rendered) = &case.rendered {
+                write!(f, "\n  rendered:\n{}", rendered)?;
+            }
+            if let Some(output) = &case.output {
+                write!(f, "\n  output:\n{}", output)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct EvalSuiteData {
+    // each assertion is a single key mapping, like `contains: Ana`, instead of a YAML tag
+    #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+    cases: Vec<EvalCase>,
+}
+
+/// Struct `EvalSuite` runs its cases against a prompt, e.g. before changing a production
+/// prompt. Tokens are counted with `HeuristicTokenCounter` unless a counter is set.
+pub struct EvalSuite {
+    cases: Vec<EvalCase>,
+    counter: Arc<dyn TokenCounter>,
+}
+
+impl EvalSuite {
+    pub fn new(cases: Vec<EvalCase>) -> Self {
+        Self {
+            cases,
+            counter: Arc::new(HeuristicTokenCounter::default()),
+        }
+    }
+
+    /// Parses a suite from YAML, its cases listed under `cases`.
+    pub fn from_yaml(yaml: &str) -> Result<Self, PromptError> {
+        let data: EvalSuiteData = serde_yaml::from_str(yaml)?;
+        Ok(Self::new(data.cases))
+    }
+
+    /// Loads a suite from a YAML file, see `from_yaml`.
+    ///
+    /// # Errors
+    /// Returns `PromptError::FileError` with the path of the file on any I/O or parse error.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PromptError> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(PromptError::from)
+            .and_then(|yaml| Self::from_yaml(&yaml))
+            .map_err(|e| file_error(path, e))
+    }
+
+    pub fn with_token_counter<C: TokenCounter + 'static>(mut self, counter: C) -> Self {
+        self.counter = Arc::new(counter);
+        self
+    }
+
+    pub fn cases(&self) -> &[EvalCase] {
+        &self.cases
+    }
+
+    /// Renders the prompt for each case and checks the result. The checks of the model output
+    /// are skipped.
+    pub fn run(&self, prompt: &dyn PromptFromatter) -> EvalReport {
+        EvalReport {
+            cases: self
+                .cases
+                .iter()
+                .map(|case| self.run_case(case, prompt.format(case.args.clone()), None))
+                .collect(),
+        }
+    }
+
+    /// Runs the cases like `run`, also sending each prompt to `llm` to check its output. Model
+    /// outputs vary between runs, so these checks opt in to flaky results: keep them to
+    /// properties every acceptable answer has, and prefer a deterministic setup, like a
+    /// temperature of 0.
+    ///
+    /// Each prompt is rendered once, so the prompt assertions check the text of the exact
+    /// prompt sent to `llm`.
+    pub async fn run_with_llm(&self, prompt: &dyn PromptFromatter, llm: &dyn LLM) -> EvalReport {
+        let mut cases = Vec::with_capacity(self.cases.len());
+        for case in &self.cases {
+            let value = prompt.to_prompt_value(case.args.clone());
+            let output = match &value {
+                Ok(value) => Some(
+                    llm.generate(&value.to_chat_messages())
+                        .await
+                        .map(|result| result.generation)
+                        .map_err(|e| e.to_string()),
+                ),
+                Err(_) => None,
+            };
+            cases.push(self.run_case(case, value.map(|value| value.to_string()), output));
+        }
+        EvalReport { cases }
+    }
+
+    fn run_case(
+        &self,
+        case: &EvalCase,
+        rendered: Result<String, PromptError>,
+        output: Option<Result<String, String>>,
+    ) -> CaseResult {
+        let rendered = match rendered {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                return CaseResult {
+                    name: case.name.clone(),
+                    assertions: skipped(&case.assertions),
+                    error: Some(e.to_string()),
+                    rendered: None,
+                    output: None,
+                }
+            }
+        };
+        let (output, error) = match output {
+            Some(Ok(output)) => (Some(output), None),
+            Some(Err(e)) => (None, Some(e)),
+            None => (None, None),
+        };
+        let assertions = case
+            .assertions
+            .iter()
+            .map(|assertion| {
+                let text = if assertion.is_on_output() {
+                    output.as_deref()
+                } else {
+                    Some(rendered.as_str())
+                };
+                let outcome = match text {
+                    Some(text) => match assertion.check(text, self.counter.as_ref()) {
+                        Ok(()) => Outcome::Passed,
+                        Err(reason) => Outcome::Failed(reason),
+                    },
+                    None => Outcome::Skipped,
+                };
+                AssertionResult {
+                    assertion: assertion.clone(),
+                    outcome,
+                }
+            })
+            .collect();
+        let mut result = CaseResult {
+            name: case.name.clone(),
+            assertions,
+            error,
+            rendered: None,
+            output: None,
+        };
+        if !result.passed() {
+            result.rendered = Some(rendered);
+            result.output = output;
+        }
+        result
+    }
+}
+
+fn skipped(assertions: &[Assertion]) -> Vec<AssertionResult> {
+    assertions
+        .iter()
+        .map(|assertion| AssertionResult {
+            assertion: assertion.clone(),
+            outcome: Outcome::Skipped,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{llm::FakeLLM, prompt_args, template_fstring};
+
+    const SUITE: &str = r#"
+cases:
+  - name: greets the user
+    args:
+      name: Ana
+    assertions:
+      - contains: Ana
+      - not_contains: "{"
+      - matches: "^Hello"
+      - max_tokens: 20
+  - name: stays short
+    args:
+      name: Bob
+    assertions:
+      - contains: Robert
+      - max_tokens: 2
+      - output_contains: Bob
+  - name: missing name
+    assertions:
+      - contains: Hello
+"#;
+
+    #[test]
+    fn test_eval_suite() {
+        let prompt = template_fstring!("Hello {name}, how can I help?", "name");
+        let suite = EvalSuite::from_yaml(SUITE).unwrap();
+        let report = suite.run(&prompt);
+        assert!(!report.passed());
+        assert!(report.cases[0].passed());
+        assert_eq!(report.cases[0].rendered, None);
+
+        let short = &report.cases[1];
+        let outcomes: Vec<&Outcome> = short.assertions.iter().map(|r| &r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                &Outcome::Failed("\"Robert\" not found".to_string()),
+                &Outcome::Failed("7 tokens, over 2".to_string()),
+                &Outcome::Skipped,
+            ]
+        );
+        assert_eq!(
+            short.rendered.as_deref(),
+            Some("Hello Bob, how can I help?")
+        );
+
+        let missing = &report.cases[2];
+        assert!(missing.error.as_deref().unwrap().contains("name"));
+        assert_eq!(missing.assertions[0].outcome, Outcome::Skipped);
+
+        assert!(report.to_string().starts_with("1 of 3 cases passed"));
+        assert!(report
+            .to_string()
+            .contains("FAILED stays short\n  contains \"Robert\": \"Robert\" not found"));
+    }
+
+    #[tokio::test]
+    async fn test_eval_suite_with_llm() {
+        let prompt = template_fstring!("Repeat {word}", "word");
+        let suite = EvalSuite::new(vec![
+            EvalCase::new("echo", prompt_args! { "word" => "ping" })
+                .with_assertion(Assertion::OutputMatches("(?i)^pong".to_string())),
+            EvalCase::new("shout", prompt_args! { "word" => "hey" })
+                .with_assertion(Assertion::OutputNotContains("hey".to_string())),
+        ]);
+        let llm = FakeLLM::new().with_responses(vec!["Pong!", "HEY hey"]);
+        let report = suite.run_with_llm(&prompt, &llm).await;
+        assert!(report.cases[0].passed());
+        assert_eq!(
+            report.cases[1].assertions[0].outcome,
+            Outcome::Failed("\"hey\" found at byte 4".to_string())
+        );
+        assert_eq!(report.cases[1].output.as_deref(), Some("HEY hey"));
+    }
+
+    // Renders a different prompt each time, like a prompt selected at random.
+    struct CountingPrompt(AtomicUsize);
+
+    impl PromptFromatter for CountingPrompt {
+        fn template(&self) -> String {
+            "Render {n}".to_string()
+        }
+
+        fn variables(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        fn format(&self, _: PromptArgs) -> Result<String, PromptError> {
+            Ok(format!("Render {}", self.0.fetch_add(1, Ordering::SeqCst)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_eval_suite_with_llm_renders_once() {
+        let prompt = CountingPrompt(AtomicUsize::new(0));
+        let suite = EvalSuite::new(vec![EvalCase::new("once", prompt_args! {})
+            .with_assertion(Assertion::Contains("Render 0".to_string()))]);
+        let report = suite
+            .run_with_llm(&prompt, &FakeLLM::new().with_responses(vec!["ok"]))
+            .await;
+        assert!(report.cases[0].passed());
+        assert_eq!(prompt.0.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,730 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use semver::{Version, VersionReq};
+
+use super::{load_prompt, PromptArgs, PromptError, PromptFromatter, PromptMetadata};
+
+/// What `PromptRegistry::register` does with a name that is already registered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Fail with `PromptError::DuplicatePrompt`.
+    #[default]
+    Error,
+    /// Replace the registered prompt.
+    Replace,
+}
+
+// A name holds either a single prompt, or the versions of a prompt.
+enum Entry {
+    Single(Arc<dyn PromptFromatter>),
+    Versioned(BTreeMap<Version, Arc<dyn PromptFromatter>>),
+}
+
+impl Entry {
+    // The prompt served by `get`: the prompt, or its latest version.
+    fn served(&self) -> Option<(Option<&Version>, &Arc<dyn PromptFromatter>)> {
+        match self {
+            Entry::Single(prompt) => Some((None, prompt)),
+            Entry::Versioned(versions) => latest(versions).map(|(v, p)| (Some(v), p)),
+        }
+    }
+}
+
+// The highest stable version, or the highest pre-release if there are no stable versions.
+fn latest(
+    versions: &BTreeMap<Version, Arc<dyn PromptFromatter>>,
+) -> Option<(&Version, &Arc<dyn PromptFromatter>)> {
+    versions
+        .iter()
+        .rev()
+        .find(|(version, _)| version.pre.is_empty())
+        .or_else(|| versions.iter().next_back())
+}
+
+/// Which version of a prompt `PromptRegistry` serves for a name, see
+/// `PromptRegistry::resolve_report`. It's displayed like `qa/main@1.3.0 (latest of 1.2.0,
+/// 1.3.0)`, to be logged with each request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveReport {
+    pub name: String,
+    /// The version requirement of a pinned resolution.
+    pub requirement: Option<String>,
+    /// The served version, `None` for a prompt registered without versions.
+    pub version: Option<String>,
+    /// The registered versions, in ascending order.
+    pub available: Vec<String>,
+}
+
+impl fmt::Display for ResolveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(version) = &self.version else {
+            return write!(f, "{} (unversioned)", self.name);
+        };
+        let rule = match &self.requirement {
+            Some(requirement) => format!("pinned to {}", requirement),
+            None => "latest".to_string(),
+        };
+        write!(
+            f,
+            "{}@{} ({} of {})",
+            self.name,
+            version,
+            rule,
+            self.available.join(", ")
+        )
+    }
+}
+
+/// Struct `PromptRegistry` holds prompts by name. It's synchronized internally, so it can be
+/// shared between threads, e.g. in a `static` `OnceLock`.
+///
+/// A name can also hold several immutable versions of a prompt, registered with
+/// `register_versioned`, of which `get` serves the latest.
+///
+/// # Usage
+/// ```rust,ignore
+/// static PROMPTS: OnceLock<PromptRegistry> = OnceLock::new();
+///
+/// let registry = PROMPTS.get_or_init(PromptRegistry::new);
+/// registry.register("greeting", Arc::new(template_fstring!("Hello {name}", "name")))?;
+/// registry.register_versioned("qa/main", "1.3.0", Arc::new(qa_prompt))?;
+/// registry.register_dir("prompts")?;
+/// let result = registry.format("greeting", prompt_args! { "name" => "Luis" })?;
+/// ```
+#[derive(Default)]
+pub struct PromptRegistry {
+    prompts: RwLock<HashMap<String, Entry>>,
+    duplicate_policy: DuplicatePolicy,
+}
+
+impl PromptRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets what `register` does with a name that is already registered. Defaults to
+    /// `DuplicatePolicy::Error`.
+    pub fn with_duplicate_policy(mut self, duplicate_policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = duplicate_policy;
+        self
+    }
+
+    /// Registers a prompt under `name`.
+    ///
+    /// # Errors
+    /// Returns `PromptError::DuplicatePrompt` if the name is taken and the policy is
+    /// `DuplicatePolicy::Error`, or if it holds versioned prompts.
+    pub fn register<S: Into<String>>(
+        &self,
+        name: S,
+        prompt: Arc<dyn PromptFromatter>,
+    ) -> Result<(), PromptError> {
+        let name = name.into();
+        let mut prompts = self.prompts.write().unwrap_or_else(PoisonError::into_inner);
+        match prompts.get(&name) {
+            Some(Entry::Versioned(_)) => return Err(PromptError::DuplicatePrompt(name)),
+            Some(Entry::Single(_)) if self.duplicate_policy == DuplicatePolicy::Error => {
+                return Err(PromptError::DuplicatePrompt(name))
+            }
+            _ => {}
+        }
+        prompts.insert(name, Entry::Single(prompt));
+        Ok(())
+    }
+
+    /// Registers a version of the prompt `name`. Versions are immutable, whatever the
+    /// duplicate policy.
+    ///
+    /// # Errors
+    /// Returns `PromptError::InvalidVersion` if `version` isn't a semver version,
+    /// `PromptError::DuplicateVersion` if it's already registered and
+    /// `PromptError::DuplicatePrompt` if the name holds a prompt registered with `register`.
+    pub fn register_versioned<S: Into<String>>(
+        &self,
+        name: S,
+        version: &str,
+        prompt: Arc<dyn PromptFromatter>,
+    ) -> Result<(), PromptError> {
+        let name = name.into();
+        let parsed = Version::parse(version).map_err(|e| PromptError::InvalidVersion {
+            version: version.to_string(),
+            reason: e.to_string(),
+        })?;
+        let mut prompts = self.prompts.write().unwrap_or_else(PoisonError::into_inner);
+        let entry = prompts
+            .entry(name.clone())
+            .or_insert_with(|| Entry::Versioned(BTreeMap::new()));
+        let Entry::Versioned(versions) = entry else {
+            return Err(PromptError::DuplicatePrompt(name));
+        };
+        if versions.contains_key(&parsed) {
+            return Err(PromptError::DuplicateVersion {
+                name,
+                version: parsed.to_string(),
+            });
+        }
+        versions.insert(parsed, prompt);
+        Ok(())
+    }
+
+    /// Returns the prompt registered under `name`, or its latest version: the highest stable
+    /// one, or the highest pre-release when there are only pre-releases.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn PromptFromatter>> {
+        self.read()
+            .get(name)
+            .and_then(Entry::served)
+            .map(|(_, prompt)| prompt.clone())
+    }
+
+    /// Returns the highest version of the prompt `name` matching `requirement`. A bare version
+    /// pins its given parts, `1.2` matching any `1.2.x` and `1.2.3` only itself, and other
+    /// requirements, like `^1.2` or `>=1.2, <1.5`, are read as in Cargo.
+    ///
+    /// # Errors
+    /// Returns `PromptError::InvalidVersion` if `requirement` isn't a semver requirement.
+    pub fn get_pinned(
+        &self,
+        name: &str,
+        requirement: &str,
+    ) -> Result<Option<Arc<dyn PromptFromatter>>, PromptError> {
+        let requirement = parse_requirement(requirement)?;
+        Ok(self
+            .find_pinned(name, &requirement)
+            .map(|(_, prompt)| prompt))
+    }
+
+    /// Returns the prompt registered under `name`, or named `name` in its metadata, whose
+    /// version is `version`.
+    pub fn get_version(&self, name: &str, version: &str) -> Option<Arc<dyn PromptFromatter>> {
+        let prompts = self.read();
+        if let Some(Entry::Versioned(versions)) = prompts.get(name) {
+            let prompt = Version::parse(version)
+                .ok()
+                .and_then(|version| versions.get(&version));
+            if let Some(prompt) = prompt {
+                return Some(prompt.clone());
+            }
+        }
+        prompts
+            .iter()
+            .filter_map(|(key, entry)| match entry {
+                Entry::Single(prompt) => Some((key, prompt)),
+                Entry::Versioned(_) => None,
+            })
+            .filter(|(key, prompt)| {
+                let metadata = prompt.metadata();
+                *key == name || metadata.name.as_deref() == Some(name)
+            })
+            .find(|(_, prompt)| prompt.metadata().version.as_deref() == Some(version))
+            .map(|(_, prompt)| prompt.clone())
+    }
+
+    /// Reports which version `get` serves for `name`, or `None` if nothing is registered.
+    pub fn resolve_report(&self, name: &str) -> Option<ResolveReport> {
+        let prompts = self.read();
+        let entry = prompts.get(name)?;
+        let (version, _) = entry.served()?;
+        Some(ResolveReport {
+            name: name.to_string(),
+            requirement: None,
+            version: version.map(Version::to_string),
+            available: available_versions(entry),
+        })
+    }
+
+    /// Reports which version `get_pinned` serves for `name` and `requirement`, or `None` if no
+    /// version matches.
+    ///
+    /// # Errors
+    /// Returns `PromptError::InvalidVersion` if `requirement` isn't a semver requirement.
+    pub fn resolve_pinned_report(
+        &self,
+        name: &str,
+        requirement: &str,
+    ) -> Result<Option<ResolveReport>, PromptError> {
+        let parsed = parse_requirement(requirement)?;
+        let Some((version, _)) = self.find_pinned(name, &parsed) else {
+            return Ok(None);
+        };
+        let available = self.read().get(name).map(available_versions);
+        Ok(Some(ResolveReport {
+            name: name.to_string(),
+            requirement: Some(requirement.to_string()),
+            version: Some(version.to_string()),
+            available: available.unwrap_or_default(),
+        }))
+    }
+
+    /// Formats the prompt registered under `name`. Its errors carry the name, unless the
+    /// prompt already adds its own.
+    ///
+    /// # Errors
+    /// Returns `PromptError::PromptNotFound` if no prompt is registered under `name`.
+    pub fn format(&self, name: &str, input_variables: PromptArgs) -> Result<String, PromptError> {
+        let prompt = self
+            .get(name)
+            .ok_or_else(|| PromptError::PromptNotFound(name.to_string()))?;
+        prompt.format(input_variables).map_err(|e| match e {
+            PromptError::NamedPromptError { .. } => e,
+            e => PromptError::NamedPromptError {
+                name: name.to_string(),
+                source: Box::new(e),
+            },
+        })
+    }
+
+    /// Returns the names whose served prompt has metadata matching `predicate`, sorted.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let owned = registry.filter(|_, metadata| metadata.owner.as_deref() == Some("search"));
+    /// ```
+    pub fn filter<F>(&self, predicate: F) -> Vec<String>
+    where
+        F: Fn(&str, &PromptMetadata) -> bool,
+    {
+        let mut names: Vec<String> = self
+            .read()
+            .iter()
+            .filter(|(name, entry)| {
+                entry
+                    .served()
+                    .is_some_and(|(_, prompt)| predicate(name, prompt.metadata()))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Returns the names of the prompts tagged `tag`, sorted.
+    pub fn find_by_tag(&self, tag: &str) -> Vec<String> {
+        self.filter(|_, metadata| metadata.has_tag(tag))
+    }
+
+    /// Returns the registered names, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.read().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Loads every `.json`, `.yaml` and `.yml` prompt file of a directory with `load_prompt`,
+    /// registering each one under its file name without the extension. Returns the registered
+    /// names, sorted.
+    pub fn register_dir<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<String>, PromptError> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() && is_prompt_file(&path) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut names = Vec::new();
+        for path in paths {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default()
+                .to_string();
+            self.register(name.clone(), Arc::new(load_prompt(&path)?))?;
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    /// Saves the registry to a directory, as YAML prompt files: `qa/main/1.3.0.yaml` for each
+    /// version of `qa/main`, and `greeting.yaml` for a prompt registered without versions.
+    /// `register_tree` loads it back.
+    ///
+    /// # Errors
+    /// Returns `PromptError::OtherError` if a prompt isn't a `PromptTemplate`, or if its name
+    /// isn't a relative path, like `../qa`.
+    pub fn save_dir<P: AsRef<Path>>(&self, dir: P) -> Result<(), PromptError> {
+        let dir = dir.as_ref();
+        let prompts = self.read();
+        for (name, entry) in prompts.iter() {
+            let path = relative_path(name)?;
+            let files: Vec<(PathBuf, &Arc<dyn PromptFromatter>)> = match entry {
+                Entry::Single(prompt) => {
+                    let mut file = dir.join(&path).into_os_string();
+                    file.push(".yaml");
+                    vec![(PathBuf::from(file), prompt)]
+                }
+                Entry::Versioned(versions) => versions
+                    .iter()
+                    .map(|(version, prompt)| {
+                        (dir.join(&path).join(format!("{}.yaml", version)), prompt)
+                    })
+                    .collect(),
+            };
+            for (file, prompt) in files {
+                let template = prompt.as_prompt_template().ok_or_else(|| {
+                    PromptError::OtherError(format!(
+                        "Prompt {} can't be saved, only a PromptTemplate can",
+                        name
+                    ))
+                })?;
+                if let Some(parent) = file.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(file, template.to_yaml()?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads a directory saved by `save_dir`, recursively: a prompt file named after a semver
+    /// version, like `qa/main/1.3.0.yaml`, is registered as that version of `qa/main`, and any
+    /// other, like `greeting.yaml`, under its path without the extension. Returns the
+    /// registered names, sorted.
+    pub fn register_tree<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<String>, PromptError> {
+        let dir = dir.as_ref();
+        let mut paths = Vec::new();
+        collect_prompt_files(dir, &mut paths)?;
+        paths.sort();
+
+        let mut names = Vec::new();
+        for path in paths {
+            let relative = path.strip_prefix(dir).unwrap_or(&path);
+            let stem = relative
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+            let parent = relative.parent().map(path_to_name).unwrap_or_default();
+            let prompt = Arc::new(load_prompt(&path)?);
+            let name = match Version::parse(stem) {
+                Ok(_) if !parent.is_empty() => {
+                    self.register_versioned(parent.clone(), stem, prompt)?;
+                    parent
+                }
+                _ => {
+                    let name = path_to_name(&relative.with_extension(""));
+                    self.register(name.clone(), prompt)?;
+                    name
+                }
+            };
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Entry>> {
+        self.prompts.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn find_pinned(
+        &self,
+        name: &str,
+        requirement: &VersionReq,
+    ) -> Option<(Version, Arc<dyn PromptFromatter>)> {
+        match self.read().get(name)? {
+            Entry::Versioned(versions) => versions
+                .iter()
+                .rev()
+                .find(|(version, _)| requirement.matches(version))
+                .map(|(version, prompt)| (version.clone(), prompt.clone())),
+            Entry::Single(_) => None,
+        }
+    }
+}
+
+// Parses a version requirement, reading a bare version like `1.2` as `=1.2`.
+fn parse_requirement(requirement: &str) -> Result<VersionReq, PromptError> {
+    let trimmed = requirement.trim();
+    let pinned = if trimmed.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("={}", trimmed)
+    } else {
+        trimmed.to_string()
+    };
+    VersionReq::parse(&pinned).map_err(|e| PromptError::InvalidVersion {
+        version: requirement.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+fn available_versions(entry: &Entry) -> Vec<String> {
+    match entry {
+        Entry::Single(_) => Vec::new(),
+        Entry::Versioned(versions) => versions.keys().map(Version::to_string).collect(),
+    }
+}
+
+fn is_prompt_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("json") | Some("yaml") | Some("yml")
+    )
+}
+
+fn collect_prompt_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), PromptError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_prompt_files(&path, paths)?;
+        } else if is_prompt_file(&path) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+// The path of a prompt under the saved directory, rejecting names escaping it.
+fn relative_path(name: &str) -> Result<PathBuf, PromptError> {
+    let path = PathBuf::from(name);
+    let is_relative = !name.is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if !is_relative {
+        return Err(PromptError::OtherError(format!(
+            "Prompt {} can't be saved, its name must be a relative path",
+            name
+        )));
+    }
+    Ok(path)
+}
+
+fn path_to_name(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt::PromptTemplate, prompt_args, template_fstring};
+
+    #[test]
+    fn test_prompt_registry() {
+        let registry = PromptRegistry::new();
+        registry
+            .register(
+                "greeting",
+                Arc::new(template_fstring!("Hello {name}", "name")),
+            )
+            .unwrap();
+        registry
+            .register("joke", Arc::new("Tell me a joke about {topic}"))
+            .unwrap();
+        assert_eq!(registry.names(), vec!["greeting", "joke"]);
+        assert_eq!(
+            registry
+                .format("greeting", prompt_args! { "name" => "Luis" })
+                .unwrap(),
+            "Hello Luis"
+        );
+        assert!(matches!(
+            registry.format("missing", prompt_args! {}),
+            Err(PromptError::PromptNotFound(name)) if name == "missing"
+        ));
+
+        let result = registry.register("joke", Arc::new("Another {topic}"));
+        assert!(matches!(result, Err(PromptError::DuplicatePrompt(name)) if name == "joke"));
+
+        let registry = registry.with_duplicate_policy(DuplicatePolicy::Replace);
+        registry
+            .register("joke", Arc::new("Another {topic}"))
+            .unwrap();
+        assert_eq!(registry.get("joke").unwrap().template(), "Another {topic}");
+    }
+
+    #[test]
+    fn test_prompt_registry_metadata() {
+        let registry = PromptRegistry::new();
+        let prompt = |version: &str, tag: &str| {
+            PromptTemplate::builder()
+                .template("Answer {question}")
+                .name("qa/main")
+                .tag(tag)
+                .version(version)
+                .build()
+                .unwrap()
+        };
+        registry
+            .register("qa/main@1", prompt("1.0.0", "rag"))
+            .unwrap();
+        registry
+            .register("qa/main@2", prompt("1.2.0", "chat"))
+            .unwrap();
+        registry.register("joke", Arc::new("Joke {topic}")).unwrap();
+
+        assert_eq!(registry.find_by_tag("rag"), vec!["qa/main@1"]);
+        assert_eq!(
+            registry.filter(|_, metadata| metadata.name.is_some()),
+            vec!["qa/main@1", "qa/main@2"]
+        );
+        let pinned = registry.get_version("qa/main", "1.2.0").unwrap();
+        assert!(pinned.metadata().has_tag("chat"));
+        assert!(registry.get_version("qa/main", "2.0.0").is_none());
+
+        match registry.format("joke", prompt_args! {}) {
+            Err(PromptError::NamedPromptError { name, .. }) => assert_eq!(name, "joke"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match registry.format("qa/main@2", prompt_args! {}) {
+            Err(PromptError::NamedPromptError { name, source }) => {
+                assert_eq!(name, "qa/main");
+                assert!(matches!(*source, PromptError::InvalidInput(_)));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_prompt_registry_versions() {
+        let registry = PromptRegistry::new();
+        for version in ["1.2.0", "1.2.5", "1.10.0", "2.0.0-beta.1"] {
+            registry
+                .register_versioned(
+                    "qa/main",
+                    version,
+                    Arc::new(template_fstring!(format!("v{} {{q}}", version), "q")),
+                )
+                .unwrap();
+        }
+        assert_eq!(registry.get("qa/main").unwrap().template(), "v1.10.0 {q}");
+        let pinned = |requirement| {
+            registry
+                .get_pinned("qa/main", requirement)
+                .unwrap()
+                .map(|prompt| prompt.template())
+        };
+        assert_eq!(pinned("1.2").as_deref(), Some("v1.2.5 {q}"));
+        assert_eq!(pinned("1.2.0").as_deref(), Some("v1.2.0 {q}"));
+        assert_eq!(pinned("^1.2").as_deref(), Some("v1.10.0 {q}"));
+        assert_eq!(pinned("3"), None);
+        assert!(matches!(
+            registry.get_pinned("qa/main", "one"),
+            Err(PromptError::InvalidVersion { .. })
+        ));
+
+        let result = registry.register_versioned("qa/main", "1.2.0", Arc::new("{q}"));
+        assert!(matches!(result, Err(PromptError::DuplicateVersion { .. })));
+        let result = registry.register_versioned("qa/main", "latest", Arc::new("{q}"));
+        assert!(matches!(result, Err(PromptError::InvalidVersion { .. })));
+        assert!(matches!(
+            registry.register("qa/main", Arc::new("{q}")),
+            Err(PromptError::DuplicatePrompt(_))
+        ));
+
+        let report = registry.resolve_report("qa/main").unwrap();
+        assert_eq!(report.version.as_deref(), Some("1.10.0"));
+        assert_eq!(
+            report.to_string(),
+            "qa/main@1.10.0 (latest of 1.2.0, 1.2.5, 1.10.0, 2.0.0-beta.1)"
+        );
+        let report = registry
+            .resolve_pinned_report("qa/main", "1.2")
+            .unwrap()
+            .unwrap();
+        assert!(report
+            .to_string()
+            .starts_with("qa/main@1.2.5 (pinned to 1.2 of"));
+        assert!(registry.resolve_report("missing").is_none());
+    }
+
+    #[test]
+    fn test_prompt_registry_save_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("prompts");
+        let registry = PromptRegistry::new();
+        for version in ["1.2.0", "1.3.0"] {
+            let prompt = PromptTemplate::builder()
+                .template(format!("Answer {{question}} ({})", version))
+                .tag("rag")
+                .build()
+                .unwrap();
+            registry
+                .register_versioned("qa/main", version, prompt)
+                .unwrap();
+        }
+        registry
+            .register("greeting", Arc::new(template_fstring!("Hi {name}", "name")))
+            .unwrap();
+        registry.save_dir(&dir).unwrap();
+        assert!(dir.join("qa/main/1.3.0.yaml").is_file());
+        assert!(dir.join("greeting.yaml").is_file());
+
+        let loaded = PromptRegistry::new();
+        assert_eq!(
+            loaded.register_tree(&dir).unwrap(),
+            vec!["greeting", "qa/main"]
+        );
+        assert_eq!(
+            loaded.resolve_report("qa/main"),
+            registry.resolve_report("qa/main")
+        );
+        assert_eq!(
+            loaded.get("qa/main").unwrap().template(),
+            "Answer {question} (1.3.0)"
+        );
+        assert_eq!(loaded.find_by_tag("rag"), vec!["qa/main"]);
+
+        registry.register("../escape", Arc::new("{x}")).unwrap();
+        assert!(registry.save_dir(&dir).is_err());
+        let unsaved = PromptRegistry::new();
+        unsaved.register("joke", Arc::new("Joke {topic}")).unwrap();
+        assert!(unsaved.save_dir(&dir).is_err());
+    }
+
+    #[test]
+    fn test_prompt_registry_shared_between_threads() {
+        let registry = Arc::new(PromptRegistry::new());
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let registry = registry.clone();
+                std::thread::spawn(move || {
+                    registry
+                        .register(format!("prompt_{}", i), Arc::new("Hi {name}"))
+                        .unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(registry.names().len(), 4);
+    }
+
+    #[test]
+    fn test_prompt_registry_register_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::write(
+            dir.join("summarize.json"),
+            r#"{"input_variables": ["text"], "template": "Summarize: {text}"}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("translate.yaml"),
+            "input_variables: [text]\ntemplate: \"Translate: {text}\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("notes.txt"), "not a prompt").unwrap();
+
+        let registry = PromptRegistry::new();
+        assert_eq!(
+            registry.register_dir(dir).unwrap(),
+            vec!["summarize", "translate"]
+        );
+        assert_eq!(
+            registry
+                .format("translate", prompt_args! { "text" => "hola" })
+                .unwrap(),
+            "Translate: hola"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::{error::Error, future::Future};
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+
+use super::PromptArgs;
+
+pub type ResolverFuture = BoxFuture<'static, Result<Value, Box<dyn Error + Send + Sync>>>;
+
+/// Resolves the value of a variable asynchronously, e.g. from a database or an API, for
+/// `PromptTemplate::format_async`. It's implemented for async closures taking the explicitly
+/// passed variables.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = template_fstring!("{user_profile}\n{input}", "user_profile", "input")
+///     .with_resolver("user_profile", |args: PromptArgs| async move {
+///         let user_id = args["user_id"].as_str().unwrap_or_default().to_string();
+///         Ok::<_, sqlx::Error>(load_profile(&user_id).await?)
+///     });
+/// let result = prompt.format_async(prompt_args! { "user_id" => "42", "input" => "Hi" }).await?;
+/// ```
+pub trait VariableResolver: Send + Sync {
+    fn resolve(&self, input_variables: PromptArgs) -> ResolverFuture;
+}
+
+impl<F, Fut, V, E> VariableResolver for F
+where
+    F: Fn(PromptArgs) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<V, E>> + Send + 'static,
+    V: Into<Value>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    fn resolve(&self, input_variables: PromptArgs) -> ResolverFuture {
+        let future = self(input_variables);
+        Box::pin(async move { future.await.map(Into::into).map_err(Into::into) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::{prompt::PromptError, prompt_args, template_fstring};
+
+    fn prompt() -> crate::prompt::PromptTemplate {
+        template_fstring!(
+            "{user_profile} | {current_weather} | {input}",
+            "user_profile",
+            "current_weather",
+            "input"
+        )
+        .with_resolver("user_profile", |args: PromptArgs| async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok::<_, PromptError>(format!("user {}", args["user_id"]))
+        })
+        .with_resolver("current_weather", |_| async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok::<_, PromptError>("sunny")
+        })
+    }
+
+    #[tokio::test]
+    async fn test_format_async() {
+        let start = Instant::now();
+        let result = prompt()
+            .format_async(prompt_args! { "user_id" => 42, "input" => "Hi" })
+            .await
+            .unwrap();
+        assert_eq!(result, "user 42 | sunny | Hi");
+        assert!(start.elapsed() < Duration::from_millis(190));
+
+        let result = prompt()
+            .format_async(prompt_args! {
+                "user_id" => 42,
+                "current_weather" => "rainy",
+                "input" => "Hi",
+            })
+            .await
+            .unwrap();
+        assert_eq!(result, "user 42 | rainy | Hi");
+    }
+
+    #[tokio::test]
+    async fn test_format_async_errors() {
+        let prompt = prompt().with_resolver("current_weather", |_| async {
+            Err::<String, _>("weather API is down")
+        });
+        match prompt
+            .format_async(prompt_args! { "user_id" => 1, "input" => "Hi" })
+            .await
+        {
+            Err(PromptError::ResolverError { variable, source }) => {
+                assert_eq!(variable, "current_weather");
+                assert_eq!(source.to_string(), "weather API is down");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        match prompt.format(prompt_args! { "input" => "Hi" }) {
+            Err(PromptError::InvalidInput(e)) => {
+                assert_eq!(e.missing, vec!["user_profile", "current_weather"])
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
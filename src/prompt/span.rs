@@ -0,0 +1,99 @@
+use std::{fmt, ops::Range};
+
+/// Struct `Span` locates a piece of a template, like a placeholder: its bytes, and the line
+/// and column it starts at, both counted from 1, columns in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// Locates the bytes `range` of `template`. The range is clamped to the template and to
+    /// character boundaries.
+    pub fn new(template: &str, range: Range<usize>) -> Self {
+        let start = floor_char_boundary(template, range.start);
+        let end = floor_char_boundary(template, range.end).max(start);
+        let line_start = template[..start].rfind('\n').map_or(0, |p| p + 1);
+        Self {
+            start,
+            end,
+            line: template[..start].matches('\n').count() + 1,
+            column: template[line_start..start].chars().count() + 1,
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Renders the line of `template` the span starts on, with the span underlined by carets,
+    /// like rustc diagnostics:
+    ///
+    /// ```text
+    ///   |
+    /// 3 | Hi {name, welcome
+    ///   |    ^^^^^^^^^^^^^^
+    /// ```
+    pub fn snippet(&self, template: &str) -> String {
+        let start = self.start.min(template.len());
+        let line_start = template[..start].rfind('\n').map_or(0, |p| p + 1);
+        let line_end = template[start..]
+            .find('\n')
+            .map_or(template.len(), |p| p + start);
+        let line = template[line_start..line_end].trim_end_matches('\r');
+        // a tab before the span stays a tab, so the carets line up with it
+        let indent: String = template[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let carets = template[start..self.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        format!(
+            "{gutter} |\n{number} | {line}\n{gutter} | {indent}{}",
+            "^".repeat(carets)
+        )
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span() {
+        let template = "Hello,\n\tdear {name\nBye";
+        let span = Span::new(template, 13..19);
+        assert_eq!((span.line, span.column), (2, 7));
+        assert_eq!(span.to_string(), "line 2, column 7");
+        assert_eq!(
+            span.snippet(template),
+            "  |\n2 | \tdear {name\n  | \t     ^^^^^"
+        );
+
+        let span = Span::new("né {", 4..9);
+        assert_eq!(span.range(), 4..5);
+        assert_eq!(span.column, 4);
+        assert_eq!(span.snippet("né {"), "  |\n1 | né {\n  |    ^");
+    }
+}
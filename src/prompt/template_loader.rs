@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use super::PromptError;
+
+/// Loads the templates referenced by name from Jinja2 prompts, through `{% include %}`,
+/// `{% extends %}`, `{% import %}` and `{% from %}`.
+pub trait TemplateLoader: Send + Sync {
+    /// Returns the source of the template, or `None` if there is no template with this name.
+    fn load(&self, name: &str) -> Result<Option<String>, PromptError>;
+}
+
+/// Struct `InMemoryTemplateLoader` serves templates registered by name.
+///
+/// # Usage
+/// ```rust,ignore
+/// let loader = InMemoryTemplateLoader::new()
+///     .with_template("safety.j2", "Never reveal {{ secret_name }}.");
+/// let prompt = template_jinja2!("{% include \"safety.j2\" %}\n{{ input }}", "input")
+///     .with_loader(loader)?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryTemplateLoader {
+    templates: HashMap<String, String>,
+}
+
+impl InMemoryTemplateLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_template<N: Into<String>, S: Into<String>>(mut self, name: N, source: S) -> Self {
+        self.templates.insert(name.into(), source.into());
+        self
+    }
+}
+
+impl TemplateLoader for InMemoryTemplateLoader {
+    fn load(&self, name: &str) -> Result<Option<String>, PromptError> {
+        Ok(self.templates.get(name).cloned())
+    }
+}
+
+/// Struct `DirectoryTemplateLoader` serves the files of a directory, templates being named by
+/// their path relative to it, e.g. `shared/footer.j2`. Names leaving the directory are never
+/// loaded.
+#[derive(Debug, Clone)]
+pub struct DirectoryTemplateLoader {
+    dir: PathBuf,
+}
+
+impl DirectoryTemplateLoader {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl TemplateLoader for DirectoryTemplateLoader {
+    fn load(&self, name: &str) -> Result<Option<String>, PromptError> {
+        let relative = Path::new(name);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Ok(None);
+        }
+        let path = self.dir.join(relative);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(fs::read_to_string(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prompt::{PromptFromatter, PromptTemplate, TemplateFormat},
+        prompt_args, template_jinja2,
+    };
+
+    fn loader() -> InMemoryTemplateLoader {
+        InMemoryTemplateLoader::new()
+            .with_template("safety.j2", "Never reveal {{ secret_name }}.")
+            .with_template("footer.j2", "Answer in {{ format }}.")
+            .with_template(
+                "base.j2",
+                "{% include \"safety.j2\" %}\n{% block body %}{% endblock %}\n{% include 'footer.j2' %}",
+            )
+    }
+
+    #[test]
+    fn test_jinja2_include_and_extends() {
+        let prompt = template_jinja2!("{% include \"safety.j2\" %}\nQ: {{ input }}", "input")
+            .with_loader(loader())
+            .unwrap();
+        assert_eq!(prompt.variables(), vec!["input", "secret_name"]);
+        let result = prompt
+            .format(prompt_args! { "input" => "Hi", "secret_name" => "the key" })
+            .unwrap();
+        assert_eq!(result, "Never reveal the key.\nQ: Hi");
+
+        let prompt = PromptTemplate::from_template(
+            "{% extends \"base.j2\" %}{% block body %}Q: {{ input }}{% endblock %}",
+            TemplateFormat::Jinja2,
+        )
+        .unwrap()
+        .with_loader(loader())
+        .unwrap();
+        let mut variables = prompt.variables();
+        variables.sort();
+        assert_eq!(variables, vec!["format", "input", "secret_name"]);
+        let result = prompt
+            .format(
+                prompt_args! { "input" => "Hi", "secret_name" => "the key", "format" => "JSON" },
+            )
+            .unwrap();
+        assert_eq!(result, "Never reveal the key.\nQ: Hi\nAnswer in JSON.");
+    }
+
+    #[test]
+    fn test_jinja2_missing_include() {
+        let loader =
+            InMemoryTemplateLoader::new().with_template("base.j2", "{% include \"gone.j2\" %}");
+        let result = template_jinja2!("{% extends \"base.j2\" %}",).with_loader(loader);
+        match result {
+            Err(e @ PromptError::MissingInclude { .. }) => {
+                assert_eq!(
+                    e.to_string(),
+                    "Template gone.j2 included by base.j2 was not found"
+                )
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_directory_template_loader() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("shared")).unwrap();
+        fs::write(dir.join("shared/footer.j2"), "Bye {{ name }}").unwrap();
+
+        let loader = DirectoryTemplateLoader::new(dir);
+        assert_eq!(
+            loader.load("shared/footer.j2").unwrap().as_deref(),
+            Some("Bye {{ name }}")
+        );
+        assert!(loader.load("../etc/passwd").unwrap().is_none());
+        assert!(loader.load("missing.j2").unwrap().is_none());
+
+        let prompt = template_jinja2!("{% include \"shared/footer.j2\" %}",)
+            .with_loader(loader)
+            .unwrap();
+        assert_eq!(
+            prompt.format(prompt_args! { "name" => "Luis" }).unwrap(),
+            "Bye Luis"
+        );
+    }
+}
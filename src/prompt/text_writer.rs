@@ -0,0 +1,70 @@
+use std::{fmt, io};
+
+/// Adapts a `fmt::Write` to the `io::Write` the template engines render to, so their output
+/// goes to the writer as it's rendered. A character split between two writes is held back
+/// until it's complete.
+pub(crate) struct TextWriter<'a> {
+    writer: &'a mut dyn fmt::Write,
+    pending: Vec<u8>,
+}
+
+impl<'a> TextWriter<'a> {
+    pub(crate) fn new(writer: &'a mut dyn fmt::Write) -> Self {
+        Self {
+            writer,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl io::Write for TextWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            // an incomplete character at the end
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let text = std::str::from_utf8(&self.pending[..valid]).expect("checked to be valid");
+        self.writer.write_str(text).map_err(io::Error::other)?;
+        self.pending.drain(..valid);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "incomplete UTF-8 character at the end of the output",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_text_writer() {
+        let mut output = String::new();
+        let mut writer = TextWriter::new(&mut output);
+        let bytes = "héllo".as_bytes();
+        // splits the `é` in two
+        writer.write_all(&bytes[..2]).unwrap();
+        writer.write_all(&bytes[2..]).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(output, "héllo");
+
+        let mut output = String::new();
+        let mut writer = TextWriter::new(&mut output);
+        writer.write_all(&bytes[..2]).unwrap();
+        assert!(writer.flush().is_err());
+        assert!(writer.write_all(&[0xff]).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use tiktoken_rs::{get_bpe_from_model, get_bpe_from_tokenizer, tokenizer::Tokenizer, CoreBPE};
+
+use super::{value_to_string, PromptArgs, PromptError, PromptTemplate};
+
+/// Counts the tokens a text takes in a model's context window.
+pub trait TokenCounter: Send + Sync {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+/// Struct `TiktokenCounter` counts tokens exactly as OpenAI models do, using tiktoken.
+pub struct TiktokenCounter {
+    bpe: CoreBPE,
+}
+
+impl TiktokenCounter {
+    pub fn new(tokenizer: Tokenizer) -> Result<Self, PromptError> {
+        let bpe = get_bpe_from_tokenizer(tokenizer)
+            .map_err(|e| PromptError::OtherError(format!("Invalid tokenizer: {}", e)))?;
+        Ok(Self { bpe })
+    }
+
+    /// Uses the tokenizer of the given model, e.g. `gpt-4`.
+    pub fn from_model(model: &str) -> Result<Self, PromptError> {
+        let bpe = get_bpe_from_model(model)
+            .map_err(|e| PromptError::OtherError(format!("Invalid model {}: {}", model, e)))?;
+        Ok(Self { bpe })
+    }
+}
+
+impl TokenCounter for TiktokenCounter {
+    fn count_tokens(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+}
+
+/// Enum `HeuristicTokenCounter` approximates token counts without a tokenizer, either as the
+/// number of whitespace separated words or as one token every few characters.
+/// Defaults to one token every 4 characters, a common estimate for English text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeuristicTokenCounter {
+    Words,
+    Chars(usize),
+}
+
+impl Default for HeuristicTokenCounter {
+    fn default() -> Self {
+        HeuristicTokenCounter::Chars(4)
+    }
+}
+
+impl TokenCounter for HeuristicTokenCounter {
+    fn count_tokens(&self, text: &str) -> usize {
+        match self {
+            HeuristicTokenCounter::Words => text.split_whitespace().count(),
+            HeuristicTokenCounter::Chars(chars_per_token) => {
+                text.chars().count().div_ceil((*chars_per_token).max(1))
+            }
+        }
+    }
+}
+
+/// What `format_with_limit` does when the formatted prompt doesn't fit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TruncationStrategy {
+    /// Fails with `PromptError::TokenLimitExceeded`.
+    Error,
+    /// Cuts the end of the named variable.
+    TruncateEnd(String),
+    /// Cuts the middle of the named variable, keeping its start and end around the marker.
+    TruncateMiddle { variable: String, marker: String },
+}
+
+impl PromptTemplate {
+    /// Formats the template and counts the tokens of the result.
+    pub fn count_tokens(
+        &self,
+        input_variables: PromptArgs,
+        counter: &dyn TokenCounter,
+    ) -> Result<usize, PromptError> {
+        Ok(counter.count_tokens(&self.format(input_variables)?))
+    }
+
+    /// Formats the template so the result takes at most `max_tokens` tokens, shortening the
+    /// variable named by the strategy as little as possible when it doesn't fit.
+    ///
+    /// # Errors
+    /// Returns `PromptError::TokenLimitExceeded` if the prompt doesn't fit with the
+    /// `TruncationStrategy::Error` strategy, or still doesn't fit once the variable is empty.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let result = prompt.format_with_limit(
+    ///     prompt_args! { "context" => document, "question" => question },
+    ///     4096,
+    ///     &TiktokenCounter::from_model("gpt-4")?,
+    ///     TruncationStrategy::TruncateEnd("context".to_string()),
+    /// )?;
+    /// ```
+    pub fn format_with_limit(
+        &self,
+        input_variables: PromptArgs,
+        max_tokens: usize,
+        counter: &dyn TokenCounter,
+        strategy: TruncationStrategy,
+    ) -> Result<String, PromptError> {
+        let result = self.format(input_variables.clone())?;
+        let tokens = counter.count_tokens(&result);
+        if tokens <= max_tokens {
+            return Ok(result);
+        }
+
+        let (variable, marker) = match &strategy {
+            TruncationStrategy::Error => {
+                return Err(PromptError::TokenLimitExceeded { tokens, max_tokens })
+            }
+            TruncationStrategy::TruncateEnd(variable) => (variable, None),
+            TruncationStrategy::TruncateMiddle { variable, marker } => (variable, Some(marker)),
+        };
+        let value = input_variables
+            .get(variable)
+            .map(value_to_string)
+            .ok_or_else(|| PromptError::MissingVariable(variable.clone()))?;
+        let chars: Vec<(usize, char)> = value.char_indices().collect();
+
+        // keeps the first and last chars of the value, `kept` of them in total
+        let format_kept = |kept: usize| -> Result<String, PromptError> {
+            let truncated = match marker {
+                None => value[..byte_offset(&value, &chars, kept)].to_string(),
+                Some(marker) => {
+                    let head = byte_offset(&value, &chars, kept.div_ceil(2));
+                    let tail = byte_offset(&value, &chars, chars.len() - kept / 2);
+                    format!("{}{}{}", &value[..head], marker, &value[tail..])
+                }
+            };
+            let mut input_variables = input_variables.clone();
+            input_variables.insert(variable.clone(), truncated.into());
+            self.format(input_variables)
+        };
+
+        let shortest = format_kept(0)?;
+        let tokens = counter.count_tokens(&shortest);
+        if tokens > max_tokens {
+            return Err(PromptError::TokenLimitExceeded { tokens, max_tokens });
+        }
+
+        // binary search for the most chars that fit, the full value is known not to
+        let (mut low, mut high, mut best) = (0, chars.len(), shortest);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            let result = format_kept(mid)?;
+            if counter.count_tokens(&result) <= max_tokens {
+                low = mid;
+                best = result;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(best)
+    }
+}
+
+fn byte_offset(value: &str, chars: &[(usize, char)], index: usize) -> usize {
+    chars.get(index).map_or(value.len(), |(offset, _)| *offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, template_fstring};
+
+    fn prompt() -> PromptTemplate {
+        template_fstring!("Context: {context}\nQ: {question}", "context", "question")
+    }
+
+    #[test]
+    fn test_heuristic_token_counter() {
+        assert_eq!(HeuristicTokenCounter::Words.count_tokens("a b  c\nd"), 4);
+        assert_eq!(
+            HeuristicTokenCounter::default().count_tokens("abcdefghi"),
+            3
+        );
+        assert_eq!(HeuristicTokenCounter::Chars(1).count_tokens("héllo"), 5);
+
+        let counter = TiktokenCounter::from_model("gpt-4").unwrap();
+        assert_eq!(counter.count_tokens("hello world"), 2);
+    }
+
+    #[test]
+    fn test_format_with_limit_exact_fit() {
+        let counter = HeuristicTokenCounter::Chars(1);
+        let args = prompt_args! { "context" => "0123456789", "question" => "why" };
+        // "Context: " + 10 chars + "\nQ: why"
+        assert_eq!(prompt().count_tokens(args.clone(), &counter).unwrap(), 26);
+
+        let result = prompt()
+            .format_with_limit(args.clone(), 26, &counter, TruncationStrategy::Error)
+            .unwrap();
+        assert_eq!(result, "Context: 0123456789\nQ: why");
+
+        let result = prompt().format_with_limit(args, 25, &counter, TruncationStrategy::Error);
+        assert!(matches!(
+            result,
+            Err(PromptError::TokenLimitExceeded {
+                tokens: 26,
+                max_tokens: 25
+            })
+        ));
+    }
+
+    #[test]
+    fn test_format_with_limit_truncates() {
+        let counter = HeuristicTokenCounter::Chars(1);
+        let args = prompt_args! { "context" => "0123456789", "question" => "why" };
+
+        let end = TruncationStrategy::TruncateEnd("context".to_string());
+        let result = prompt()
+            .format_with_limit(args.clone(), 20, &counter, end.clone())
+            .unwrap();
+        assert_eq!(result, "Context: 0123\nQ: why");
+
+        let middle = TruncationStrategy::TruncateMiddle {
+            variable: "context".to_string(),
+            marker: "...".to_string(),
+        };
+        let result = prompt()
+            .format_with_limit(args.clone(), 24, &counter, middle)
+            .unwrap();
+        assert_eq!(result, "Context: 012...89\nQ: why");
+
+        // the rest of the prompt alone is too long
+        let result = prompt().format_with_limit(args, 15, &counter, end);
+        assert!(matches!(
+            result,
+            Err(PromptError::TokenLimitExceeded { tokens: 16, .. })
+        ));
+    }
+}
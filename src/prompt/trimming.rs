@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use crate::schemas::messages::{Message, MessageType};
+
+use super::{HeuristicTokenCounter, TokenCounter};
+
+/// How `MessageTrimmer` drops messages over its budget. Messages are always dropped whole,
+/// the oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TrimStrategy {
+    #[default]
+    DropOldest,
+    /// Drops the oldest messages but never system messages.
+    DropOldestKeepSystem,
+    /// Drops the oldest messages and puts a system message with this marker in their place.
+    SummarizePlaceholder(String),
+}
+
+/// Struct `MessageTrimmer` keeps a messages history within a budget of tokens and/or messages,
+/// for a trimmed messages placeholder of a `ChatPromptTemplate`.
+///
+/// The most recent exchange, from the last human message answered by an AI message, is never
+/// dropped, even if it's over the budget alone.
+///
+/// # Usage
+/// ```rust,ignore
+/// let trimmer = MessageTrimmer::new()
+///     .with_max_tokens(2000)
+///     .with_strategy(TrimStrategy::SummarizePlaceholder("[earlier messages elided]".into()))
+///     .with_token_counter(TiktokenCounter::from_model("gpt-4")?);
+/// let prompt = chat_prompt! { system: "You are {persona}." }?
+///     .with_trimmed_messages_placeholder("history", trimmer);
+/// let (messages, trimmed) = prompt.format_messages_trimmed(args)?;
+/// ```
+#[derive(Clone)]
+pub struct MessageTrimmer {
+    max_tokens: Option<usize>,
+    max_messages: Option<usize>,
+    strategy: TrimStrategy,
+    counter: Arc<dyn TokenCounter>,
+}
+
+impl Default for MessageTrimmer {
+    fn default() -> Self {
+        Self {
+            max_tokens: None,
+            max_messages: None,
+            strategy: TrimStrategy::default(),
+            counter: Arc::new(HeuristicTokenCounter::default()),
+        }
+    }
+}
+
+impl MessageTrimmer {
+    /// Creates a trimmer without any budget. Tokens are counted with the default
+    /// `HeuristicTokenCounter`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of tokens of the messages' contents.
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = Some(max_messages);
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: TrimStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_token_counter<C: TokenCounter + 'static>(mut self, counter: C) -> Self {
+        self.counter = Arc::new(counter);
+        self
+    }
+
+    /// Trims the messages to the budget, returning the kept messages and the number of dropped
+    /// ones.
+    pub fn trim(&self, messages: Vec<Message>) -> (Vec<Message>, usize) {
+        let marker = match &self.strategy {
+            TrimStrategy::SummarizePlaceholder(marker) => Some(marker),
+            _ => None,
+        };
+        let marker_tokens = marker.map_or(0, |marker| self.counter.count_tokens(marker));
+        let tokens: Vec<usize> = messages
+            .iter()
+            .map(|message| self.counter.count_tokens(&message.content))
+            .collect();
+
+        let mut total_tokens: usize = tokens.iter().sum();
+        let mut total_messages = messages.len();
+        let mut dropped = vec![false; messages.len()];
+        let mut trimmed = 0;
+        for i in 0..protected_start(&messages) {
+            let (tokens_used, messages_used) = match marker {
+                Some(_) if trimmed > 0 => (total_tokens + marker_tokens, total_messages + 1),
+                _ => (total_tokens, total_messages),
+            };
+            let over_budget = self.max_tokens.is_some_and(|max| tokens_used > max)
+                || self.max_messages.is_some_and(|max| messages_used > max);
+            if !over_budget {
+                break;
+            }
+            if self.strategy == TrimStrategy::DropOldestKeepSystem
+                && messages[i].message_type == MessageType::SystemMessage
+            {
+                continue;
+            }
+            dropped[i] = true;
+            total_tokens -= tokens[i];
+            total_messages -= 1;
+            trimmed += 1;
+        }
+
+        let mut result = Vec::with_capacity(total_messages + 1);
+        let mut marked = false;
+        for (message, dropped) in messages.into_iter().zip(dropped) {
+            if !dropped {
+                result.push(message);
+            } else if let (Some(marker), false) = (marker, marked) {
+                result.push(Message::new_system_message(marker));
+                marked = true;
+            }
+        }
+        (result, trimmed)
+    }
+}
+
+// Returns the index of the most recent exchange: the last human message followed by an AI
+// message, or the last message if there is none.
+fn protected_start(messages: &[Message]) -> usize {
+    messages
+        .windows(2)
+        .rposition(|pair| {
+            pair[0].message_type == MessageType::HumanMessage
+                && pair[1].message_type == MessageType::AIMessage
+        })
+        .unwrap_or(messages.len().saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<Message> {
+        vec![
+            Message::new_system_message("Be brief"),
+            Message::new_human_message("one two three"),
+            Message::new_ai_message("four five"),
+            Message::new_human_message("six"),
+            Message::new_ai_message("seven eight nine"),
+        ]
+    }
+
+    fn contents(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn test_trim_messages() {
+        let trimmer = MessageTrimmer::new()
+            .with_max_tokens(6)
+            .with_token_counter(HeuristicTokenCounter::Words);
+        let (messages, trimmed) = trimmer.trim(history());
+        assert_eq!(
+            contents(&messages),
+            vec!["four five", "six", "seven eight nine"]
+        );
+        assert_eq!(trimmed, 2);
+
+        let trimmer = trimmer.with_strategy(TrimStrategy::DropOldestKeepSystem);
+        let (messages, trimmed) = trimmer.trim(history());
+        assert_eq!(
+            contents(&messages),
+            vec!["Be brief", "six", "seven eight nine"]
+        );
+        assert_eq!(trimmed, 2);
+
+        let trimmer = MessageTrimmer::new()
+            .with_max_messages(3)
+            .with_strategy(TrimStrategy::SummarizePlaceholder("[elided]".into()));
+        let (messages, trimmed) = trimmer.trim(history());
+        assert_eq!(
+            contents(&messages),
+            vec!["[elided]", "six", "seven eight nine"]
+        );
+        assert_eq!(messages[0].message_type, MessageType::SystemMessage);
+        assert_eq!(trimmed, 3);
+    }
+
+    #[test]
+    fn test_trim_keeps_most_recent_exchange() {
+        let trimmer = MessageTrimmer::new()
+            .with_max_tokens(1)
+            .with_token_counter(HeuristicTokenCounter::Words);
+        let (messages, trimmed) = trimmer.trim(history());
+        assert_eq!(contents(&messages), vec!["six", "seven eight nine"]);
+        assert_eq!(trimmed, 3);
+
+        let (messages, trimmed) = MessageTrimmer::new().trim(history());
+        assert_eq!(messages.len(), 5);
+        assert_eq!(trimmed, 0);
+    }
+}
@@ -0,0 +1,279 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{prompt::value_to_string, PromptArgs, PromptError, PromptFromatter};
+
+/// Struct `WeightedPromptSelector` runs a prompt experiment: each `format` serves one of its
+/// variants, picked at random in proportion to their weights, e.g. `0.9` and `0.1` for a
+/// 90/10 split. Use `format_with_variant` to know which variant served a request.
+///
+/// With a variant key, like a user id, the same key always gets the same variant, as long as
+/// the variants and their weights don't change.
+///
+/// # Usage
+/// ```rust,ignore
+/// let selector = WeightedPromptSelector::new(vec![
+///     (Arc::new(prompt_a) as Arc<dyn PromptFromatter>, 0.9, "control".to_string()),
+///     (Arc::new(prompt_b), 0.1, "concise".to_string()),
+/// ])?
+/// .with_variant_key("user_id");
+/// let (prompt, variant) = selector.format_with_variant(prompt_args! {
+///     "user_id" => "u-42",
+///     "question" => "Why?",
+/// })?;
+/// ```
+pub struct WeightedPromptSelector {
+    variants: Vec<(Arc<dyn PromptFromatter>, f64, String)>,
+    total_weight: f64,
+    rng: Mutex<StdRng>,
+    variant_key: Option<String>,
+}
+
+impl WeightedPromptSelector {
+    /// Creates a selector of `(prompt, weight, label)` variants.
+    ///
+    /// # Errors
+    /// Returns `PromptError::InvalidWeights` if there are no variants, if a weight is negative
+    /// or not finite, or if all weights are zero.
+    pub fn new(
+        variants: Vec<(Arc<dyn PromptFromatter>, f64, String)>,
+    ) -> Result<Self, PromptError> {
+        if variants.is_empty() {
+            return Err(PromptError::InvalidWeights("no variants".to_string()));
+        }
+        if let Some((_, weight, label)) = variants
+            .iter()
+            .find(|(_, weight, _)| !weight.is_finite() || *weight < 0.0)
+        {
+            return Err(PromptError::InvalidWeights(format!(
+                "variant {} has weight {}, expected a non-negative number",
+                label, weight
+            )));
+        }
+        let total_weight: f64 = variants.iter().map(|(_, weight, _)| weight).sum();
+        if total_weight <= 0.0 {
+            return Err(PromptError::InvalidWeights(
+                "all weights are zero".to_string(),
+            ));
+        }
+        Ok(Self {
+            variants,
+            total_weight,
+            rng: Mutex::new(StdRng::from_entropy()),
+            variant_key: None,
+        })
+    }
+
+    /// Seeds the random picks, so tests get the same sequence of variants.
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            ..self
+        }
+    }
+
+    /// Picks the variant from the value of the `key` input variable, like a user id, instead
+    /// of at random. Inputs without it still get a random variant.
+    pub fn with_variant_key<S: Into<String>>(mut self, key: S) -> Self {
+        self.variant_key = Some(key.into());
+        self
+    }
+
+    /// Returns the labels of the variants, in order.
+    pub fn labels(&self) -> Vec<&str> {
+        self.variants
+            .iter()
+            .map(|(_, _, label)| label.as_str())
+            .collect()
+    }
+
+    /// Formats the prompt with the picked variant, returning the prompt and the label of the
+    /// variant.
+    pub fn format_with_variant(
+        &self,
+        input_variables: PromptArgs,
+    ) -> Result<(String, String), PromptError> {
+        let (prompt, _, label) = self.pick(&input_variables);
+        Ok((prompt.format(input_variables)?, label.clone()))
+    }
+
+    fn pick(&self, input_variables: &PromptArgs) -> &(Arc<dyn PromptFromatter>, f64, String) {
+        let key = self
+            .variant_key
+            .as_ref()
+            .and_then(|key| input_variables.get(key));
+        let point = match key {
+            Some(value) => unit_hash(&value_to_string(value)),
+            None => self
+                .rng
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .gen::<f64>(),
+        } * self.total_weight;
+
+        let mut cumulative = 0.0;
+        for variant in &self.variants {
+            cumulative += variant.1;
+            if point < cumulative {
+                return variant;
+            }
+        }
+        // rounding can leave the point past the last weight
+        self.variants
+            .iter()
+            .rev()
+            .find(|(_, weight, _)| *weight > 0.0)
+            .expect("a variant has a positive weight")
+    }
+}
+
+// Maps the key to [0, 1) with FNV-1a, which unlike `DefaultHasher` is stable across Rust
+// releases, so assignments survive upgrades. The MurmurHash3 finalizer spreads similar keys,
+// like `u-1` and `u-2`, over the whole range.
+fn unit_hash(key: &str) -> f64 {
+    let mut hash = key.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^= hash >> 33;
+    (hash >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+impl PromptFromatter for WeightedPromptSelector {
+    /// Returns the template of the heaviest variant.
+    fn template(&self) -> String {
+        self.variants
+            .iter()
+            .fold(&self.variants[0], |heaviest, variant| {
+                if variant.1 > heaviest.1 {
+                    variant
+                } else {
+                    heaviest
+                }
+            })
+            .0
+            .template()
+    }
+
+    /// Returns the variables of every variant, so the input can be validated whatever the
+    /// variant.
+    fn variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        for variable in self
+            .variants
+            .iter()
+            .flat_map(|(prompt, _, _)| prompt.variables())
+        {
+            if !variables.contains(&variable) {
+                variables.push(variable);
+            }
+        }
+        variables
+    }
+
+    fn format(&self, input_variables: PromptArgs) -> Result<String, PromptError> {
+        self.format_with_variant(input_variables)
+            .map(|(prompt, _)| prompt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prompt_args, template_fstring};
+
+    fn experiment() -> WeightedPromptSelector {
+        WeightedPromptSelector::new(vec![
+            (
+                Arc::new(template_fstring!("A: {question}", "question")),
+                0.9,
+                "a".to_string(),
+            ),
+            (
+                Arc::new(template_fstring!(
+                    "B: {question} ({style})",
+                    "question",
+                    "style"
+                )),
+                0.1,
+                "b".to_string(),
+            ),
+            (Arc::new("never {question}"), 0.0, "off".to_string()),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_weighted_prompt_selector() {
+        let selector = experiment().with_seed(7);
+        assert_eq!(selector.variables(), vec!["question", "style"]);
+        assert_eq!(selector.template(), "A: {question}");
+
+        let mut counts = [0; 3];
+        for _ in 0..1000 {
+            let args = prompt_args! { "question" => "why?", "style" => "short" };
+            let (prompt, label) = selector.format_with_variant(args).unwrap();
+            let index = selector.labels().iter().position(|l| *l == label).unwrap();
+            assert!(prompt.starts_with(&label.to_uppercase()));
+            counts[index] += 1;
+        }
+        assert!((850..950).contains(&counts[0]), "{:?}", counts);
+        assert_eq!(counts[2], 0);
+
+        let replay = experiment().with_seed(7);
+        let labels = |selector: &WeightedPromptSelector| {
+            (0..20)
+                .map(|_| {
+                    let args = prompt_args! { "question" => "why?", "style" => "short" };
+                    selector.format_with_variant(args).unwrap().1
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(labels(&replay), labels(&experiment().with_seed(7)));
+    }
+
+    #[test]
+    fn test_weighted_prompt_selector_variant_key() {
+        let selector = experiment().with_variant_key("user_id");
+        let label = |user: &str| {
+            let args = prompt_args! { "user_id" => user, "question" => "why?", "style" => "s" };
+            selector.format_with_variant(args).unwrap().1
+        };
+        for user in ["u-1", "u-2", "u-3"] {
+            assert_eq!(label(user), label(user));
+        }
+        let b_users = (0..1000)
+            .filter(|i| label(&format!("u-{}", i)) == "b")
+            .count();
+        assert!((50..150).contains(&b_users), "{}", b_users);
+    }
+
+    #[test]
+    fn test_weighted_prompt_selector_weights() {
+        let invalid = |weights: &[f64]| {
+            let variants = weights
+                .iter()
+                .map(|weight| {
+                    (
+                        Arc::new("{x}") as Arc<dyn PromptFromatter>,
+                        *weight,
+                        weight.to_string(),
+                    )
+                })
+                .collect();
+            matches!(
+                WeightedPromptSelector::new(variants),
+                Err(PromptError::InvalidWeights(_))
+            )
+        };
+        assert!(invalid(&[]));
+        assert!(invalid(&[0.0, 0.0]));
+        assert!(invalid(&[1.0, -0.5]));
+        assert!(invalid(&[f64::NAN]));
+        assert!(!invalid(&[0.0, 2.0]));
+    }
+}
@@ -0,0 +1,351 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::{
+    messages::{ContentPart, Message, MessageType},
+    prompt::PromptValue,
+};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AnthropicConversionError {
+    #[error(
+        "The conversation starts with an assistant message, it must start with a user message"
+    )]
+    StartsWithAssistant,
+
+    #[error("The conversation has no user or assistant message")]
+    NoMessages,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnthropicRole {
+    User,
+    Assistant,
+}
+
+/// Where the data of an image comes from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicImageSource {
+    Base64 { media_type: String, data: String },
+    Url { url: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicContentBlock {
+    Text {
+        text: String,
+    },
+    Image {
+        source: AnthropicImageSource,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
+}
+
+/// The `content` of an `AnthropicMessage`: a string, or content blocks for images and tools.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum AnthropicContent {
+    Text(String),
+    Blocks(Vec<AnthropicContentBlock>),
+}
+
+impl AnthropicContent {
+    fn into_blocks(self) -> Vec<AnthropicContentBlock> {
+        match self {
+            AnthropicContent::Text(text) => vec![AnthropicContentBlock::Text { text }],
+            AnthropicContent::Blocks(blocks) => blocks,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnthropicMessage {
+    pub role: AnthropicRole,
+    pub content: AnthropicContent,
+}
+
+/// Struct `AnthropicPrompt` is a prompt in the shape of the Anthropic Messages API: the
+/// system prompt apart, and messages alternating between user and assistant turns.
+///
+/// # Usage
+/// ```rust,ignore
+/// let prompt = prompt.to_prompt_value(args)?.to_anthropic()?;
+/// let body = json!({ "model": model, "max_tokens": 1024, "system": prompt.system, "messages": prompt.messages });
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnthropicPrompt {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    pub messages: Vec<AnthropicMessage>,
+}
+
+impl PromptValue {
+    /// Converts the prompt to the shape of the Anthropic Messages API:
+    ///
+    /// - The system messages, wherever they are, are joined by blank lines into `system`.
+    /// - Tool results become user turns, and tool calls assistant `tool_use` blocks.
+    /// - Consecutive turns of the same role are merged, text by blank lines.
+    ///
+    /// # Errors
+    /// Returns `AnthropicConversionError::StartsWithAssistant` if the first turn is an
+    /// assistant one, and `AnthropicConversionError::NoMessages` if there are only system
+    /// messages.
+    pub fn to_anthropic(&self) -> Result<AnthropicPrompt, AnthropicConversionError> {
+        let mut system = Vec::new();
+        let mut messages: Vec<AnthropicMessage> = Vec::new();
+        for message in self.to_chat_messages() {
+            let (role, content) = match message.message_type {
+                MessageType::SystemMessage => {
+                    system.push(message.content);
+                    continue;
+                }
+                MessageType::HumanMessage => (AnthropicRole::User, user_content(&message)),
+                MessageType::AIMessage => (AnthropicRole::Assistant, assistant_content(&message)),
+                MessageType::ToolMessage => (
+                    AnthropicRole::User,
+                    AnthropicContent::Blocks(vec![AnthropicContentBlock::ToolResult {
+                        tool_use_id: message.id.clone().unwrap_or_default(),
+                        content: message.content,
+                    }]),
+                ),
+            };
+            match messages.last_mut() {
+                Some(last) if last.role == role => {
+                    last.content = merge(last.content.clone(), content);
+                }
+                _ => messages.push(AnthropicMessage { role, content }),
+            }
+        }
+
+        match messages.first() {
+            None => Err(AnthropicConversionError::NoMessages),
+            Some(first) if first.role == AnthropicRole::Assistant => {
+                Err(AnthropicConversionError::StartsWithAssistant)
+            }
+            Some(_) => Ok(AnthropicPrompt {
+                system: (!system.is_empty()).then(|| system.join("\n\n")),
+                messages,
+            }),
+        }
+    }
+
+    /// Renders the prompt for the legacy Anthropic completions endpoint, like
+    /// `System\n\nHuman: Hi\n\nAssistant:`, with the turns merged as by `to_anthropic`.
+    /// Images are shown as `[image: ...]` markers and tool results as their text.
+    ///
+    /// # Errors
+    /// Returns the errors of `to_anthropic`.
+    pub fn to_anthropic_text(&self) -> Result<String, AnthropicConversionError> {
+        let prompt = self.to_anthropic()?;
+        let mut text = prompt.system.unwrap_or_default();
+        for message in prompt.messages {
+            let label = match message.role {
+                AnthropicRole::User => "Human",
+                AnthropicRole::Assistant => "Assistant",
+            };
+            let content: Vec<String> = message
+                .content
+                .into_blocks()
+                .into_iter()
+                .filter_map(|block| match block {
+                    AnthropicContentBlock::Text { text } => Some(text),
+                    AnthropicContentBlock::ToolResult { content, .. } => Some(content),
+                    AnthropicContentBlock::Image { source } => Some(match source {
+                        AnthropicImageSource::Url { url } => format!("[image: {}]", url),
+                        AnthropicImageSource::Base64 { media_type, .. } => {
+                            format!("[image: {} data]", media_type)
+                        }
+                    }),
+                    AnthropicContentBlock::ToolUse { .. } => None,
+                })
+                .collect();
+            text.push_str(&format!("\n\n{}: {}", label, content.join("\n\n")));
+        }
+        text.push_str("\n\nAssistant:");
+        Ok(text)
+    }
+}
+
+fn user_content(message: &Message) -> AnthropicContent {
+    let Some(parts) = &message.parts else {
+        return AnthropicContent::Text(message.content.clone());
+    };
+    let blocks = parts
+        .iter()
+        .map(|part| match part {
+            ContentPart::Text { text } => AnthropicContentBlock::Text { text: text.clone() },
+            ContentPart::ImageUrl { url, .. } => AnthropicContentBlock::Image {
+                source: image_source(url),
+            },
+        })
+        .collect();
+    AnthropicContent::Blocks(blocks)
+}
+
+// A `data:image/png;base64,...` URI is sent as its data, other URLs as they are.
+fn image_source(url: &str) -> AnthropicImageSource {
+    let data_uri = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"));
+    match data_uri {
+        Some((media_type, data)) => AnthropicImageSource::Base64 {
+            media_type: media_type.to_string(),
+            data: data.to_string(),
+        },
+        None => AnthropicImageSource::Url {
+            url: url.to_string(),
+        },
+    }
+}
+
+// The text of the message, followed by a `tool_use` block per OpenAI-style tool call.
+fn assistant_content(message: &Message) -> AnthropicContent {
+    let calls = message
+        .tool_calls
+        .as_ref()
+        .and_then(Value::as_array)
+        .filter(|calls| !calls.is_empty());
+    let Some(calls) = calls else {
+        return AnthropicContent::Text(message.content.clone());
+    };
+    let mut blocks = Vec::new();
+    if !message.content.is_empty() {
+        blocks.push(AnthropicContentBlock::Text {
+            text: message.content.clone(),
+        });
+    }
+    for call in calls {
+        let arguments = &call["function"]["arguments"];
+        let input = match arguments.as_str() {
+            Some(arguments) => serde_json::from_str(arguments)
+                .unwrap_or_else(|_| Value::String(arguments.to_string())),
+            None => arguments.clone(),
+        };
+        blocks.push(AnthropicContentBlock::ToolUse {
+            id: call["id"].as_str().unwrap_or_default().to_string(),
+            name: call["function"]["name"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            input,
+        });
+    }
+    AnthropicContent::Blocks(blocks)
+}
+
+fn merge(first: AnthropicContent, second: AnthropicContent) -> AnthropicContent {
+    match (first, second) {
+        (AnthropicContent::Text(first), AnthropicContent::Text(second)) => {
+            AnthropicContent::Text(format!("{}\n\n{}", first, second))
+        }
+        (first, second) => {
+            let mut blocks = first.into_blocks();
+            blocks.extend(second.into_blocks());
+            AnthropicContent::Blocks(blocks)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_to_anthropic() {
+        let value = PromptValue::from_messages(vec![
+            Message::new_system_message("You are a pirate."),
+            Message::new_human_message("Hi"),
+            Message::new_human_message("Are you there?"),
+            Message::new_system_message("Answer in one line."),
+            Message::new_ai_message("Arr, I be here."),
+            Message::new_human_message("Where's the treasure?"),
+        ]);
+        let prompt = value.to_anthropic().unwrap();
+        assert_eq!(
+            serde_json::to_value(&prompt).unwrap(),
+            json!({
+                "system": "You are a pirate.\n\nAnswer in one line.",
+                "messages": [
+                    {"role": "user", "content": "Hi\n\nAre you there?"},
+                    {"role": "assistant", "content": "Arr, I be here."},
+                    {"role": "user", "content": "Where's the treasure?"}
+                ]
+            })
+        );
+        assert_eq!(
+            value.to_anthropic_text().unwrap(),
+            "You are a pirate.\n\nAnswer in one line.\n\nHuman: Hi\n\nAre you there?\n\n\
+             Assistant: Arr, I be here.\n\nHuman: Where's the treasure?\n\nAssistant:"
+        );
+        assert_eq!(
+            PromptValue::from_string("Hi").to_anthropic_text().unwrap(),
+            "\n\nHuman: Hi\n\nAssistant:"
+        );
+    }
+
+    #[test]
+    fn test_to_anthropic_tools_and_images() {
+        let value = PromptValue::from_messages(vec![
+            Message::new_human_message("").with_parts(vec![
+                ContentPart::text("Weather where this was taken?"),
+                ContentPart::image_url("data:image/png;base64,iVBORw0KGgo="),
+            ]),
+            Message::new_ai_message("").with_tool_calls(json!([{
+                "id": "call_1",
+                "type": "function",
+                "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+            }])),
+            Message::new_tool_message("18°C", "call_1"),
+            Message::new_human_message("Thanks"),
+        ]);
+        let prompt = value.to_anthropic().unwrap();
+        assert_eq!(prompt.system, None);
+        assert_eq!(
+            serde_json::to_value(&prompt.messages).unwrap(),
+            json!([
+                {"role": "user", "content": [
+                    {"type": "text", "text": "Weather where this was taken?"},
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="}}
+                ]},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "call_1", "name": "get_weather", "input": {"city": "Paris"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "call_1", "content": "18°C"},
+                    {"type": "text", "text": "Thanks"}
+                ]}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_to_anthropic_errors() {
+        let value = PromptValue::from_messages(vec![
+            Message::new_system_message("Be brief"),
+            Message::new_ai_message("Hello"),
+        ]);
+        assert_eq!(
+            value.to_anthropic(),
+            Err(AnthropicConversionError::StartsWithAssistant)
+        );
+        let value = PromptValue::from_messages(vec![Message::new_system_message("Be brief")]);
+        assert_eq!(
+            value.to_anthropic_text(),
+            Err(AnthropicConversionError::NoMessages)
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use thiserror::Error;
+
+use super::{
+    messages::{Message, MessageType},
+    prompt::PromptValue,
+};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ChatTemplateError {
+    #[error("The {template} chat template has no {} role", .role.to_string())]
+    UnsupportedRole {
+        template: &'static str,
+        role: MessageType,
+    },
+
+    #[error(
+        "The {template} chat template expects {expected} message at {index}, got {}",
+        .role.to_string()
+    )]
+    InvalidOrder {
+        template: &'static str,
+        index: usize,
+        expected: &'static str,
+        role: MessageType,
+    },
+}
+
+/// Trait `ChatTemplateAdapter` renders messages to the chat template of a model, for
+/// backends like llama.cpp that take the prompt as a string.
+pub trait ChatTemplateAdapter: Send + Sync {
+    fn render(&self, messages: &[Message]) -> Result<String, ChatTemplateError>;
+}
+
+/// Struct `Llama2Adapter` renders the Llama-2 chat template, as the reference
+/// implementation does:
+///
+/// ```text
+/// <s>[INST] <<SYS>>
+/// {system}
+/// <</SYS>>
+///
+/// {user} [/INST] {assistant} </s><s>[INST] {user} [/INST]
+/// ```
+///
+/// The turns must alternate from a user one, after an optional system message, which is
+/// folded into the first user turn.
+#[derive(Debug, Clone, Default)]
+pub struct Llama2Adapter {}
+
+impl Llama2Adapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ChatTemplateAdapter for Llama2Adapter {
+    fn render(&self, messages: &[Message]) -> Result<String, ChatTemplateError> {
+        let (system, turns) = match messages.split_first() {
+            Some((first, rest)) if first.message_type == MessageType::SystemMessage => {
+                (Some(first.content.as_str()), rest)
+            }
+            _ => (None, messages),
+        };
+        let offset = messages.len() - turns.len();
+
+        let mut out = String::new();
+        for (i, message) in turns.iter().enumerate() {
+            let expected = if i % 2 == 0 {
+                MessageType::HumanMessage
+            } else {
+                MessageType::AIMessage
+            };
+            if message.message_type != expected {
+                return Err(match message.message_type {
+                    MessageType::ToolMessage => ChatTemplateError::UnsupportedRole {
+                        template: "Llama-2",
+                        role: MessageType::ToolMessage,
+                    },
+                    _ => ChatTemplateError::InvalidOrder {
+                        template: "Llama-2",
+                        index: i + offset,
+                        expected: if i % 2 == 0 { "a human" } else { "an ai" },
+                        role: message.message_type.clone(),
+                    },
+                });
+            }
+            let content = message.content.trim();
+            match (&message.message_type, system) {
+                (MessageType::HumanMessage, Some(system)) if i == 0 => out.push_str(&format!(
+                    "<s>[INST] <<SYS>>\n{}\n<</SYS>>\n\n{} [/INST]",
+                    system, content
+                )),
+                (MessageType::HumanMessage, _) => {
+                    out.push_str(&format!("<s>[INST] {} [/INST]", content))
+                }
+                _ => out.push_str(&format!(" {} </s>", content)),
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Struct `ChatMlAdapter` renders the ChatML template of models like Qwen and OpenHermes:
+///
+/// ```text
+/// <|im_start|>system
+/// {system}<|im_end|>
+/// <|im_start|>user
+/// {user}<|im_end|>
+/// <|im_start|>assistant
+/// ```
+///
+/// The last line, which prompts the model to answer, is left out with
+/// `with_generation_prompt(false)`.
+#[derive(Debug, Clone)]
+pub struct ChatMlAdapter {
+    generation_prompt: bool,
+}
+
+impl Default for ChatMlAdapter {
+    fn default() -> Self {
+        Self {
+            generation_prompt: true,
+        }
+    }
+}
+
+impl ChatMlAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_generation_prompt(mut self, generation_prompt: bool) -> Self {
+        self.generation_prompt = generation_prompt;
+        self
+    }
+}
+
+impl ChatTemplateAdapter for ChatMlAdapter {
+    fn render(&self, messages: &[Message]) -> Result<String, ChatTemplateError> {
+        let mut out = String::new();
+        for message in messages {
+            let role = match message.message_type {
+                MessageType::SystemMessage => "system",
+                MessageType::HumanMessage => "user",
+                MessageType::AIMessage => "assistant",
+                MessageType::ToolMessage => {
+                    return Err(ChatTemplateError::UnsupportedRole {
+                        template: "ChatML",
+                        role: MessageType::ToolMessage,
+                    })
+                }
+            };
+            out.push_str(&format!(
+                "<|im_start|>{}\n{}<|im_end|>\n",
+                role, message.content
+            ));
+        }
+        if self.generation_prompt {
+            out.push_str("<|im_start|>assistant\n");
+        }
+        Ok(out)
+    }
+}
+
+impl PromptValue {
+    /// Renders the prompt to the chat template of a model.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let text = prompt.to_prompt_value(args)?.to_model_string(&Llama2Adapter::new())?;
+    /// ```
+    pub fn to_model_string(
+        &self,
+        adapter: &dyn ChatTemplateAdapter,
+    ) -> Result<String, ChatTemplateError> {
+        adapter.render(&self.to_chat_messages())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Renderings of the reference implementations, the Llama-2 `chat_completion` and the
+    // Hugging Face ChatML template, for the same conversation.
+    fn golden(name: &str) -> String {
+        let path = format!("./src/schemas/test_data/chat_templates/{}", name);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn conversation() -> PromptValue {
+        PromptValue::from_messages(vec![
+            Message::new_system_message("You are a helpful assistant."),
+            Message::new_human_message("What is the capital of France?"),
+            Message::new_ai_message("The capital of France is Paris."),
+            Message::new_human_message("And of Italy?"),
+        ])
+    }
+
+    #[test]
+    fn test_llama2_adapter() {
+        let adapter = Llama2Adapter::new();
+        assert_eq!(
+            conversation().to_model_string(&adapter).unwrap(),
+            golden("llama2_multi_turn.txt")
+        );
+        assert_eq!(
+            PromptValue::from_string(" Hi ")
+                .to_model_string(&adapter)
+                .unwrap(),
+            "<s>[INST] Hi [/INST]"
+        );
+
+        let messages = [
+            Message::new_system_message("Be brief"),
+            Message::new_human_message("Hi"),
+            Message::new_human_message("Hello?"),
+        ];
+        assert_eq!(
+            adapter.render(&messages),
+            Err(ChatTemplateError::InvalidOrder {
+                template: "Llama-2",
+                index: 2,
+                expected: "an ai",
+                role: MessageType::HumanMessage,
+            })
+        );
+        let messages = [
+            Message::new_human_message("Hi"),
+            Message::new_system_message("Be brief"),
+        ];
+        assert!(adapter.render(&messages).is_err());
+        let messages = [Message::new_tool_message("42", "call_1")];
+        assert!(matches!(
+            adapter.render(&messages),
+            Err(ChatTemplateError::UnsupportedRole { .. })
+        ));
+    }
+
+    #[test]
+    fn test_chat_ml_adapter() {
+        assert_eq!(
+            conversation()
+                .to_model_string(&ChatMlAdapter::new())
+                .unwrap(),
+            golden("chatml_multi_turn.txt")
+        );
+        let adapter = ChatMlAdapter::new().with_generation_prompt(false);
+        assert_eq!(
+            PromptValue::from_string("Hi")
+                .to_model_string(&adapter)
+                .unwrap(),
+            "<|im_start|>user\nHi<|im_end|>\n"
+        );
+        let messages = [Message::new_tool_message("42", "call_1")];
+        assert_eq!(
+            adapter.render(&messages),
+            Err(ChatTemplateError::UnsupportedRole {
+                template: "ChatML",
+                role: MessageType::ToolMessage,
+            })
+        );
+    }
+}
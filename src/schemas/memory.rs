@@ -1,5 +1,12 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+
+use crate::{prompt::PromptArgs, prompt_args};
+
 use super::messages::Message;
 
+#[async_trait]
 pub trait BaseMemory: Send + Sync {
     fn messages(&self) -> Vec<Message>;
 
@@ -26,6 +33,23 @@ pub trait BaseMemory: Send + Sync {
             .collect::<Vec<String>>()
             .join("\n")
     }
+
+    /// Returns the variables the memory adds to a prompt. Defaults to the `history` variable
+    /// with the messages rendered by `to_string`.
+    fn load_memory_variables(&self) -> PromptArgs {
+        prompt_args! { "history" => self.to_string() }
+    }
+
+    /// Saves a turn of the conversation, the human's input and the AI's output.
+    async fn save_context(
+        &mut self,
+        input: &str,
+        output: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.add_message(Message::new_human_message(input));
+        self.add_message(Message::new_ai_message(output));
+        Ok(())
+    }
 }
 
 impl<M> From<M> for Box<dyn BaseMemory>
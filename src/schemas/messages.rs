@@ -11,7 +11,7 @@ use serde_json::Value;
 /// let ai_message_type = MessageType::AIMessage;
 /// let human_message_type = MessageType::HumanMessage;
 /// ```
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum MessageType {
     #[serde(rename = "system")]
     SystemMessage,
@@ -40,8 +40,73 @@ impl MessageType {
     }
 }
 
+/// The detail level a vision model sees an image at.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    #[default]
+    Auto,
+    Low,
+    High,
+}
+
+/// A part of a multimodal message.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text {
+        text: String,
+    },
+    /// An image, by URL or as a base64 `data:` URI.
+    ImageUrl {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<ImageDetail>,
+    },
+}
+
+impl ContentPart {
+    pub fn text<S: Into<String>>(text: S) -> Self {
+        ContentPart::Text { text: text.into() }
+    }
+
+    pub fn image_url<S: Into<String>>(url: S) -> Self {
+        ContentPart::ImageUrl {
+            url: url.into(),
+            detail: None,
+        }
+    }
+
+    /// Returns the part as text, images as a `[image: ...]` marker showing their URL, or the
+    /// media type of a `data:` URI.
+    pub fn to_plain_text(&self) -> String {
+        match self {
+            ContentPart::Text { text } => text.clone(),
+            ContentPart::ImageUrl { url, .. } => match url.strip_prefix("data:") {
+                Some(data) => {
+                    let media_type = data.split([';', ',']).next().unwrap_or_default();
+                    format!("[image: {} data]", media_type)
+                }
+                None => format!("[image: {}]", url),
+            },
+        }
+    }
+}
+
+/// The content of a message: its text, or its parts when it's multimodal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
 /// Struct `Message` represents a message with its content and type.
 ///
+/// A multimodal message also has `parts`, such as images for vision models. Its `content` is
+/// then the text of the parts, with a `[image: ...]` marker for each image, for the models
+/// that only read text.
+///
 /// # Usage
 /// ```rust,ignore
 /// let human_message = Message::new_human_message("Hello");
@@ -54,6 +119,11 @@ pub struct Message {
     pub message_type: MessageType,
     pub id: Option<String>,
     pub tool_calls: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parts: Option<Vec<ContentPart>>,
+    /// The name of the participant, telling apart participants of the same role.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
 }
 
 impl Message {
@@ -64,6 +134,8 @@ impl Message {
             message_type: MessageType::HumanMessage,
             id: None,
             tool_calls: None,
+            parts: None,
+            name: None,
         }
     }
 
@@ -74,6 +146,8 @@ impl Message {
             message_type: MessageType::SystemMessage,
             id: None,
             tool_calls: None,
+            parts: None,
+            name: None,
         }
     }
 
@@ -84,6 +158,8 @@ impl Message {
             message_type: MessageType::AIMessage,
             id: None,
             tool_calls: None,
+            parts: None,
+            name: None,
         }
     }
 
@@ -94,6 +170,8 @@ impl Message {
             message_type: MessageType::ToolMessage,
             id: Some(id.into()),
             tool_calls: None,
+            parts: None,
+            name: None,
         }
     }
 
@@ -111,6 +189,31 @@ impl Message {
         self
     }
 
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Makes the message multimodal, with `content` set to the text of the parts. The text
+    /// parts are separated by newlines.
+    pub fn with_parts(mut self, parts: Vec<ContentPart>) -> Self {
+        self.content = parts
+            .iter()
+            .map(ContentPart::to_plain_text)
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.parts = Some(parts);
+        self
+    }
+
+    /// Returns the parts of a multimodal message, or its text.
+    pub fn message_content(&self) -> MessageContent {
+        match &self.parts {
+            Some(parts) => MessageContent::Parts(parts.clone()),
+            None => MessageContent::Text(self.content.clone()),
+        }
+    }
+
     pub fn messages_from_value(value: &Value) -> Result<Vec<Message>, serde_json::error::Error> {
         serde_json::from_value(value.clone()).map_err(|e| e.into())
     }
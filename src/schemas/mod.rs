@@ -19,5 +19,14 @@ pub use retrievers::*;
 mod tools_openai_like;
 pub use tools_openai_like::*;
 
+mod openai_messages;
+pub use openai_messages::*;
+
+mod anthropic_messages;
+pub use anthropic_messages::*;
+
+mod chat_templates;
+pub use chat_templates::*;
+
 mod stream;
 pub use stream::*;
@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    messages::{ContentPart, ImageDetail, Message, MessageType},
+    prompt::PromptValue,
+};
+
+/// The role of an `OpenAiMessage`. The `developer` role of newer models is read as `system`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenAiRole {
+    #[serde(alias = "developer")]
+    System,
+    User,
+    Assistant,
+    Tool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OpenAiImageUrl {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<ImageDetail>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OpenAiContentPart {
+    Text { text: String },
+    ImageUrl { image_url: OpenAiImageUrl },
+}
+
+/// The `content` of an `OpenAiMessage`: a string, or an array of parts for multimodal messages.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum OpenAiContent {
+    Text(String),
+    Parts(Vec<OpenAiContentPart>),
+}
+
+/// Struct `OpenAiMessage` is a message of the OpenAI chat completions API, as sent in its
+/// `messages` array. It converts from and into `Message`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let value = prompt.to_prompt_value(args)?.to_openai_messages();
+/// let messages = Message::from_openai_messages(&value)?;
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OpenAiMessage {
+    pub role: OpenAiRole,
+    /// `None` for assistant messages holding only tool calls.
+    #[serde(default)]
+    pub content: Option<OpenAiContent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Value>,
+}
+
+impl From<&Message> for OpenAiMessage {
+    fn from(message: &Message) -> Self {
+        let role = match message.message_type {
+            MessageType::SystemMessage => OpenAiRole::System,
+            MessageType::HumanMessage => OpenAiRole::User,
+            MessageType::AIMessage => OpenAiRole::Assistant,
+            MessageType::ToolMessage => OpenAiRole::Tool,
+        };
+        let content = match &message.parts {
+            Some(parts) => Some(OpenAiContent::Parts(
+                parts.iter().map(OpenAiContentPart::from).collect(),
+            )),
+            None if message.content.is_empty() && message.tool_calls.is_some() => None,
+            None => Some(OpenAiContent::Text(message.content.clone())),
+        };
+        Self {
+            role,
+            content,
+            name: message.name.clone(),
+            tool_call_id: match message.message_type {
+                MessageType::ToolMessage => message.id.clone(),
+                _ => None,
+            },
+            tool_calls: message.tool_calls.clone(),
+        }
+    }
+}
+
+impl From<OpenAiMessage> for Message {
+    fn from(message: OpenAiMessage) -> Self {
+        let message_type = match message.role {
+            OpenAiRole::System => MessageType::SystemMessage,
+            OpenAiRole::User => MessageType::HumanMessage,
+            OpenAiRole::Assistant => MessageType::AIMessage,
+            OpenAiRole::Tool => MessageType::ToolMessage,
+        };
+        let mut result = Message {
+            message_type,
+            id: message.tool_call_id,
+            tool_calls: message.tool_calls,
+            name: message.name,
+            ..Default::default()
+        };
+        match message.content {
+            Some(OpenAiContent::Text(text)) => result.content = text,
+            Some(OpenAiContent::Parts(parts)) => {
+                result = result.with_parts(parts.into_iter().map(ContentPart::from).collect())
+            }
+            None => {}
+        }
+        result
+    }
+}
+
+impl From<&ContentPart> for OpenAiContentPart {
+    fn from(part: &ContentPart) -> Self {
+        match part {
+            ContentPart::Text { text } => OpenAiContentPart::Text { text: text.clone() },
+            ContentPart::ImageUrl { url, detail } => OpenAiContentPart::ImageUrl {
+                image_url: OpenAiImageUrl {
+                    url: url.clone(),
+                    detail: *detail,
+                },
+            },
+        }
+    }
+}
+
+impl From<OpenAiContentPart> for ContentPart {
+    fn from(part: OpenAiContentPart) -> Self {
+        match part {
+            OpenAiContentPart::Text { text } => ContentPart::Text { text },
+            OpenAiContentPart::ImageUrl { image_url } => ContentPart::ImageUrl {
+                url: image_url.url,
+                detail: image_url.detail,
+            },
+        }
+    }
+}
+
+impl Message {
+    /// Reads the `messages` array of an OpenAI chat completions request, e.g. to replay a
+    /// logged conversation into a memory.
+    pub fn from_openai_messages(value: &Value) -> Result<Vec<Message>, serde_json::Error> {
+        let messages: Vec<OpenAiMessage> = serde_json::from_value(value.clone())?;
+        Ok(messages.into_iter().map(Message::from).collect())
+    }
+}
+
+impl PromptValue {
+    /// Returns the messages of the prompt as OpenAI chat completions messages.
+    pub fn openai_messages(&self) -> Vec<OpenAiMessage> {
+        self.to_chat_messages()
+            .iter()
+            .map(OpenAiMessage::from)
+            .collect()
+    }
+
+    /// Returns the messages of the prompt as the `messages` array of an OpenAI chat
+    /// completions request.
+    pub fn to_openai_messages(&self) -> Value {
+        serde_json::to_value(self.openai_messages()).expect("OpenAI messages serialize to JSON")
+    }
+
+    /// Reads the `messages` array of an OpenAI chat completions request into a chat prompt.
+    pub fn from_openai_messages(value: &Value) -> Result<Self, serde_json::Error> {
+        Ok(PromptValue::Chat(Message::from_openai_messages(value)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    // A request captured from an agent run calling a tool.
+    fn tool_call_payload() -> Value {
+        json!([
+            {"role": "system", "content": "You are a helpful assistant."},
+            {"role": "user", "content": "What's the weather in Paris?", "name": "ana"},
+            {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_abc123",
+                    "type": "function",
+                    "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+                }]
+            },
+            {"role": "tool", "content": "18°C, cloudy", "tool_call_id": "call_abc123"},
+            {"role": "assistant", "content": "It's 18°C and cloudy in Paris."}
+        ])
+    }
+
+    // A request captured from a vision model call.
+    fn vision_payload() -> Value {
+        json!([
+            {"role": "user", "content": [
+                {"type": "text", "text": "What's in this image?"},
+                {"type": "image_url", "image_url": {"url": "https://example.com/boardwalk.jpg", "detail": "high"}},
+                {"type": "image_url", "image_url": {"url": "data:image/jpeg;base64,/9j/4AAQSkZJRg=="}}
+            ]}
+        ])
+    }
+
+    #[test]
+    fn test_openai_messages_round_trip() {
+        for payload in [tool_call_payload(), vision_payload()] {
+            let value = PromptValue::from_openai_messages(&payload).unwrap();
+            assert_eq!(value.to_openai_messages(), payload);
+        }
+
+        let messages = Message::from_openai_messages(&tool_call_payload()).unwrap();
+        assert_eq!(messages[1].name.as_deref(), Some("ana"));
+        assert_eq!(messages[2].message_type, MessageType::AIMessage);
+        assert!(messages[2].tool_calls.is_some());
+        assert_eq!(messages[3].id.as_deref(), Some("call_abc123"));
+
+        let messages = Message::from_openai_messages(&vision_payload()).unwrap();
+        assert_eq!(
+            messages[0].content,
+            "What's in this image?\n[image: https://example.com/boardwalk.jpg]\n\
+             [image: image/jpeg data]"
+        );
+    }
+
+    #[test]
+    fn test_prompt_value_to_openai_messages() {
+        let value = PromptValue::from_messages(vec![
+            Message::new_system_message("Be brief"),
+            Message::new_human_message("Hi"),
+            Message::new_ai_message("Hello!"),
+            Message::new_tool_message("42", "call_1"),
+        ]);
+        assert_eq!(
+            value.to_openai_messages(),
+            json!([
+                {"role": "system", "content": "Be brief"},
+                {"role": "user", "content": "Hi"},
+                {"role": "assistant", "content": "Hello!"},
+                {"role": "tool", "content": "42", "tool_call_id": "call_1"}
+            ])
+        );
+        assert_eq!(
+            PromptValue::from_string("Hi").to_openai_messages(),
+            json!([{"role": "user", "content": "Hi"}])
+        );
+        let developer = json!([{"role": "developer", "content": "Be brief"}]);
+        let messages = Message::from_openai_messages(&developer).unwrap();
+        assert_eq!(messages[0].message_type, MessageType::SystemMessage);
+    }
+}
@@ -1,29 +1,72 @@
-use super::messages::Message;
+use super::messages::{Message, MessageType};
 
+/// Enum `PromptValue` is a formatted prompt, usable by completion models as a string and by
+/// chat models as a list of messages, whichever kind of prompt produced it.
 #[derive(Debug, Clone)]
-pub struct PromptValue {
-    messages: Vec<Message>,
+pub enum PromptValue {
+    /// A plain text prompt, sent to chat models as a single human message.
+    String(String),
+    /// A list of role-tagged messages, rendered as text one `Role: content` line each.
+    Chat(Vec<Message>),
 }
 impl PromptValue {
     pub fn from_string(text: &str) -> Self {
-        let message = Message::new_human_message(text);
-        Self {
-            messages: vec![message],
-        }
+        PromptValue::String(text.to_string())
     }
     pub fn from_messages(messages: Vec<Message>) -> Self {
-        Self { messages }
+        PromptValue::Chat(messages)
     }
 
     pub fn to_string(&self) -> String {
-        self.messages
-            .iter()
-            .map(|m| format!("{}: {}", m.message_type.to_string(), m.content))
-            .collect::<Vec<String>>()
-            .join("\n")
+        match self {
+            PromptValue::String(text) => text.clone(),
+            PromptValue::Chat(messages) => messages
+                .iter()
+                .map(|m| format!("{}: {}", role_label(&m.message_type), m.content))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        }
     }
 
     pub fn to_chat_messages(&self) -> Vec<Message> {
-        self.messages.clone()
+        match self {
+            PromptValue::String(text) => vec![Message::new_human_message(text)],
+            PromptValue::Chat(messages) => messages.clone(),
+        }
+    }
+}
+
+fn role_label(message_type: &MessageType) -> &'static str {
+    match message_type {
+        MessageType::SystemMessage => "System",
+        MessageType::AIMessage => "AI",
+        MessageType::HumanMessage => "Human",
+        MessageType::ToolMessage => "Tool",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt_value_representations() {
+        let value = PromptValue::from_string("Tell me a joke");
+        assert_eq!(value.to_string(), "Tell me a joke");
+        let messages = value.to_chat_messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_type, MessageType::HumanMessage);
+        assert_eq!(messages[0].content, "Tell me a joke");
+
+        let value = PromptValue::from_messages(vec![
+            Message::new_system_message("You are a pirate"),
+            Message::new_human_message("Hello"),
+            Message::new_ai_message("Arr"),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            "System: You are a pirate\nHuman: Hello\nAI: Arr"
+        );
+        assert_eq!(value.to_chat_messages().len(), 3);
     }
 }
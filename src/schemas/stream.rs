@@ -1,19 +1,34 @@
 use serde_json::Value;
 use std::io::{self, Write};
 
+use crate::language_models::TokenUsage;
+
+/// A chunk of a streamed response. Build it with `new` and `with_tokens`: more fields may be
+/// added, so it can't be built with a struct literal.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct StreamData {
     pub value: Value,
     pub content: String,
+    /// The tokens used by the whole response, carried by the final chunk of models reporting
+    /// them.
+    pub tokens: Option<TokenUsage>,
 }
 impl StreamData {
     pub fn new<S: Into<String>>(value: Value, content: S) -> Self {
         Self {
             value,
             content: content.into(),
+            tokens: None,
         }
     }
 
+    /// Sets the tokens used by the whole response.
+    pub fn with_tokens(mut self, tokens: TokenUsage) -> Self {
+        self.tokens = Some(tokens);
+        self
+    }
+
     pub fn to_stdout(&self) -> io::Result<()> {
         let stdout = io::stdout();
         let mut handle = stdout.lock();
@@ -17,6 +17,12 @@ pub enum TextSplitterError {
     #[error("Tokenizer creation failed due to invalid model")]
     InvalidModel,
 
+    #[error("Chunk overlap {chunk_overlap} must be smaller than the chunk size {chunk_size}")]
+    InvalidChunkOverlap {
+        chunk_size: usize,
+        chunk_overlap: usize,
+    },
+
     #[error("Error: {0}")]
     OtherError(String),
 }
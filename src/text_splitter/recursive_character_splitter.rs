@@ -0,0 +1,361 @@
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::{
+    prompt::{HeuristicTokenCounter, TokenCounter},
+    schemas::Document,
+};
+
+use super::{TextSplitter, TextSplitterError};
+
+const DEFAULT_SEPARATORS: [&str; 4] = ["\n\n", "\n", " ", ""];
+
+// The separators of Python LangChain's markdown splitter: headings, code fences, horizontal
+// rules, then paragraphs, lines and words.
+const MARKDOWN_SEPARATORS: [&str; 9] = [
+    r"\n#{1,6} ",
+    r"```\n",
+    r"\n\*\*\*+\n",
+    r"\n---+\n",
+    r"\n___+\n",
+    r"\n\n",
+    r"\n",
+    r" ",
+    "",
+];
+
+/// Struct `RecursiveCharacterTextSplitter` splits text on the first separator of its hierarchy
+/// found in it, merging the pieces into chunks of at most `chunk_size`, and splits the pieces
+/// still too long with the next separators. The empty separator splits between characters, so
+/// no chunk is ever longer than `chunk_size`, as measured by the length function: characters
+/// by default, or tokens with a `TokenCounter` such as `TiktokenCounter`.
+///
+/// Separators are kept at the start of the piece following them, and chunks are trimmed.
+///
+/// `split_documents` returns the chunks with the metadata of their document, plus
+/// `chunk_index` and the `start_index` and `end_index` byte offsets of the chunk in the
+/// document's content.
+///
+/// # Usage
+/// ```rust,ignore
+/// let splitter = RecursiveCharacterTextSplitter::markdown()
+///     .with_chunk_size(500)
+///     .with_chunk_overlap(50)
+///     .with_length_function(TiktokenCounter::from_model("gpt-4")?);
+/// let chunks = splitter.split_documents(&documents).await?;
+/// ```
+#[derive(Clone)]
+pub struct RecursiveCharacterTextSplitter {
+    chunk_size: usize,
+    chunk_overlap: usize,
+    separators: Vec<Regex>,
+    length_function: Arc<dyn TokenCounter>,
+    trim_chunks: bool,
+}
+
+impl Default for RecursiveCharacterTextSplitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecursiveCharacterTextSplitter {
+    /// Creates a splitter into chunks of 1000 characters overlapping by 200, on paragraphs,
+    /// lines, words and characters.
+    pub fn new() -> Self {
+        Self {
+            chunk_size: 1000,
+            chunk_overlap: 200,
+            separators: Vec::new(),
+            length_function: Arc::new(HeuristicTokenCounter::Chars(1)),
+            trim_chunks: true,
+        }
+        .with_separators(DEFAULT_SEPARATORS.to_vec())
+    }
+
+    /// Creates a splitter on markdown headings, code blocks and horizontal rules before
+    /// paragraphs, lines, words and characters.
+    pub fn markdown() -> Self {
+        Self::new().with_separator_regexes(
+            MARKDOWN_SEPARATORS
+                .iter()
+                .map(|separator| Regex::new(separator).unwrap())
+                .collect(),
+        )
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets how much of the end of a chunk is repeated at the start of the next one, which
+    /// must be smaller than the chunk size.
+    pub fn with_chunk_overlap(mut self, chunk_overlap: usize) -> Self {
+        self.chunk_overlap = chunk_overlap;
+        self
+    }
+
+    /// Sets the separators, from the one preferred to split on to the last resort. Add `""`
+    /// last to split between characters when no other separator is left.
+    pub fn with_separators<S: AsRef<str>>(self, separators: Vec<S>) -> Self {
+        self.with_separator_regexes(
+            separators
+                .iter()
+                .map(|separator| Regex::new(&regex::escape(separator.as_ref())).unwrap())
+                .collect(),
+        )
+    }
+
+    /// Sets the separators as regular expressions.
+    pub fn with_separator_regexes(mut self, separators: Vec<Regex>) -> Self {
+        self.separators = separators;
+        self
+    }
+
+    /// Sets how chunks are measured. Defaults to their number of characters.
+    pub fn with_length_function<C: TokenCounter + 'static>(mut self, length_function: C) -> Self {
+        self.length_function = Arc::new(length_function);
+        self
+    }
+
+    /// Trims the whitespace around chunks. Defaults to true.
+    pub fn with_trim_chunks(mut self, trim_chunks: bool) -> Self {
+        self.trim_chunks = trim_chunks;
+        self
+    }
+
+    /// Returns the byte ranges of the chunks in the text.
+    pub fn split_ranges(&self, text: &str) -> Result<Vec<Range<usize>>, TextSplitterError> {
+        if self.chunk_overlap >= self.chunk_size {
+            return Err(TextSplitterError::InvalidChunkOverlap {
+                chunk_size: self.chunk_size,
+                chunk_overlap: self.chunk_overlap,
+            });
+        }
+        let mut chunks = Vec::new();
+        self.split_recursive(text, 0..text.len(), &self.separators, &mut chunks);
+        Ok(chunks)
+    }
+
+    fn len(&self, text: &str) -> usize {
+        self.length_function.count_tokens(text)
+    }
+
+    fn split_recursive(
+        &self,
+        text: &str,
+        range: Range<usize>,
+        separators: &[Regex],
+        chunks: &mut Vec<Range<usize>>,
+    ) {
+        let slice = &text[range.clone()];
+        let separator = separators
+            .iter()
+            .position(|separator| separator.as_str().is_empty() || separator.is_match(slice));
+        let (pieces, next_separators) = match separator {
+            Some(i) if !separators[i].as_str().is_empty() => (
+                split_on(slice, &separators[i], range.start),
+                &separators[i + 1..],
+            ),
+            _ => (split_chars(slice, range.start), &[][..]),
+        };
+
+        let mut fitting: Vec<Range<usize>> = Vec::new();
+        for piece in pieces {
+            if self.len(&text[piece.clone()]) <= self.chunk_size {
+                fitting.push(piece);
+                continue;
+            }
+            self.merge(text, &fitting, chunks);
+            fitting.clear();
+            if text[piece.clone()].chars().nth(1).is_none() {
+                // a single character longer than a chunk can't be split further
+                self.push_chunk(text, piece, chunks);
+            } else {
+                self.split_recursive(text, piece, next_separators, chunks);
+            }
+        }
+        self.merge(text, &fitting, chunks);
+    }
+
+    // Merges contiguous pieces into chunks, starting each chunk with the end of the previous
+    // one up to the overlap.
+    fn merge(&self, text: &str, pieces: &[Range<usize>], chunks: &mut Vec<Range<usize>>) {
+        let Some(last) = pieces.last() else {
+            return;
+        };
+        let mut first = 0;
+        for i in 1..pieces.len() {
+            if self.len(&text[pieces[first].start..pieces[i].end]) <= self.chunk_size {
+                continue;
+            }
+            self.push_chunk(text, pieces[first].start..pieces[i - 1].end, chunks);
+            while first < i
+                && (self.len(&text[pieces[first].start..pieces[i - 1].end]) > self.chunk_overlap
+                    || self.len(&text[pieces[first].start..pieces[i].end]) > self.chunk_size)
+            {
+                first += 1;
+            }
+        }
+        self.push_chunk(text, pieces[first].start..last.end, chunks);
+    }
+
+    fn push_chunk(&self, text: &str, range: Range<usize>, chunks: &mut Vec<Range<usize>>) {
+        let mut range = range;
+        if self.trim_chunks {
+            let chunk = &text[range.clone()];
+            range.start += chunk.len() - chunk.trim_start().len();
+            range.end -= chunk.len() - chunk.trim_end().len();
+        }
+        if !range.is_empty() {
+            chunks.push(range);
+        }
+    }
+}
+
+// Splits before every match of the separator, so each separator starts a piece.
+fn split_on(text: &str, separator: &Regex, offset: usize) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for separator in separator.find_iter(text) {
+        if separator.start() > start {
+            pieces.push(offset + start..offset + separator.start());
+            start = separator.start();
+        }
+    }
+    pieces.push(offset + start..offset + text.len());
+    pieces
+}
+
+fn split_chars(text: &str, offset: usize) -> Vec<Range<usize>> {
+    text.char_indices()
+        .map(|(i, c)| offset + i..offset + i + c.len_utf8())
+        .collect()
+}
+
+#[async_trait]
+impl TextSplitter for RecursiveCharacterTextSplitter {
+    async fn split_text(&self, text: &str) -> Result<Vec<String>, TextSplitterError> {
+        Ok(self
+            .split_ranges(text)?
+            .into_iter()
+            .map(|range| text[range].to_string())
+            .collect())
+    }
+
+    async fn create_documents(
+        &self,
+        text: &[String],
+        metadatas: &[HashMap<String, Value>],
+    ) -> Result<Vec<Document>, TextSplitterError> {
+        if !metadatas.is_empty() && text.len() != metadatas.len() {
+            return Err(TextSplitterError::MetadataTextMismatch);
+        }
+
+        let mut documents: Vec<Document> = Vec::new();
+        for (i, text) in text.iter().enumerate() {
+            for (chunk_index, range) in self.split_ranges(text)?.into_iter().enumerate() {
+                let mut metadata = metadatas.get(i).cloned().unwrap_or_default();
+                metadata.insert("chunk_index".to_string(), json!(chunk_index));
+                metadata.insert("start_index".to_string(), json!(range.start));
+                metadata.insert("end_index".to_string(), json!(range.end));
+                documents.push(Document::new(&text[range]).with_metadata(metadata));
+            }
+        }
+        Ok(documents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "Rust is a language.\n\nIt is fast and safe. It has no garbage collector.\nCargo builds it.";
+
+    #[tokio::test]
+    async fn test_recursive_character_splitter() {
+        let splitter = RecursiveCharacterTextSplitter::new()
+            .with_chunk_size(30)
+            .with_chunk_overlap(0);
+        let chunks = splitter.split_text(TEXT).await.unwrap();
+        assert_eq!(
+            chunks,
+            vec![
+                "Rust is a language.",
+                "It is fast and safe. It has",
+                "no garbage collector.",
+                "Cargo builds it."
+            ]
+        );
+
+        let splitter = splitter.with_chunk_overlap(10);
+        let chunks = splitter.split_text(TEXT).await.unwrap();
+        assert_eq!(chunks[1], "It is fast and safe. It has");
+        assert_eq!(chunks[2], "It has no garbage collector.");
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 30));
+    }
+
+    #[tokio::test]
+    async fn test_recursive_character_splitter_limits() {
+        // no separator left, so the word and the multi-byte characters are split
+        let splitter = RecursiveCharacterTextSplitter::new()
+            .with_chunk_size(4)
+            .with_chunk_overlap(1)
+            .with_separators(vec![" ", ""]);
+        let chunks = splitter.split_text("añoñoño ab").await.unwrap();
+        assert_eq!(chunks, vec!["añoñ", "ñoño", "ab"]);
+
+        let splitter = RecursiveCharacterTextSplitter::new()
+            .with_chunk_size(3)
+            .with_length_function(HeuristicTokenCounter::Words);
+        assert!(matches!(
+            splitter.split_text("a b").await,
+            Err(TextSplitterError::InvalidChunkOverlap { .. })
+        ));
+        let chunks = splitter
+            .with_chunk_overlap(1)
+            .split_text("one two three four five")
+            .await
+            .unwrap();
+        assert_eq!(chunks, vec!["one two three", "three four five"]);
+    }
+
+    #[tokio::test]
+    async fn test_recursive_character_splitter_documents() {
+        let splitter = RecursiveCharacterTextSplitter::new()
+            .with_chunk_size(30)
+            .with_chunk_overlap(0);
+        let document = Document::new(TEXT)
+            .with_metadata(HashMap::from([("source".to_string(), json!("rust.md"))]));
+        let chunks = splitter.split_documents(&[document]).await.unwrap();
+        assert_eq!(chunks.len(), 4);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.metadata["source"], "rust.md");
+            assert_eq!(chunk.metadata["chunk_index"], i);
+            let start = chunk.metadata["start_index"].as_u64().unwrap() as usize;
+            let end = chunk.metadata["end_index"].as_u64().unwrap() as usize;
+            assert_eq!(&TEXT[start..end], chunk.page_content);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_markdown_preset() {
+        let text = "# Title\n\nIntro text here.\n\n## Section\n\nBody of the section.\n```\nlet x = 1;\n```";
+        let splitter = RecursiveCharacterTextSplitter::markdown()
+            .with_chunk_size(40)
+            .with_chunk_overlap(0);
+        let chunks = splitter.split_text(text).await.unwrap();
+        assert_eq!(
+            chunks,
+            vec![
+                "# Title\n\nIntro text here.",
+                "## Section\n\nBody of the section.",
+                "```\nlet x = 1;\n```"
+            ]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::{error::Error, iter::Peekable, str::Chars};
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::{Tool, ToolError};
+
+/// Struct `Calculator` is a tool computing arithmetic expressions with `+`, `-`, `*`, `/`,
+/// `^` and parentheses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Calculator {}
+
+impl Calculator {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Computes the expression.
+    pub fn evaluate(&self, expression: &str) -> Result<f64, ToolError> {
+        let mut parser = ExpressionParser {
+            chars: expression.chars().peekable(),
+        };
+        let value = parser.expression()?;
+        parser.skip_whitespace();
+        if let Some(c) = parser.chars.next() {
+            return Err(ToolError::InvalidInput(format!("unexpected {:?}", c)));
+        }
+        if !value.is_finite() {
+            return Err(ToolError::ExecutionError(format!(
+                "{} has no finite result",
+                expression.trim()
+            )));
+        }
+        Ok(value)
+    }
+}
+
+#[async_trait]
+impl Tool for Calculator {
+    fn name(&self) -> String {
+        "calculator".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Useful to compute arithmetic expressions, like `2 * (3 + 4) / 5`".to_string()
+    }
+
+    async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
+        let input = input.as_str().ok_or("Input should be a string")?;
+        let value = self.evaluate(input)?;
+        Ok(value.to_string())
+    }
+}
+
+// A recursive descent parser of expressions, from the lowest precedence to the highest.
+struct ExpressionParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl ExpressionParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn next_operator(&mut self, operators: &[char]) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.next_if(|c| operators.contains(c))
+    }
+
+    fn expression(&mut self) -> Result<f64, ToolError> {
+        let mut value = self.term()?;
+        while let Some(operator) = self.next_operator(&['+', '-']) {
+            let rhs = self.term()?;
+            value = if operator == '+' {
+                value + rhs
+            } else {
+                value - rhs
+            };
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64, ToolError> {
+        let mut value = self.power()?;
+        while let Some(operator) = self.next_operator(&['*', '/']) {
+            let rhs = self.power()?;
+            value = if operator == '*' {
+                value * rhs
+            } else {
+                value / rhs
+            };
+        }
+        Ok(value)
+    }
+
+    // `^` is right associative and binds tighter than a leading minus, so `-2^2` is -4.
+    fn power(&mut self) -> Result<f64, ToolError> {
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'-').is_some() {
+            return Ok(-self.power()?);
+        }
+        let base = self.atom()?;
+        if self.next_operator(&['^']).is_some() {
+            return Ok(base.powf(self.power()?));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<f64, ToolError> {
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'(').is_some() {
+            let value = self.expression()?;
+            if self.next_operator(&[')']).is_none() {
+                return Err(ToolError::InvalidInput("missing `)`".into()));
+            }
+            return Ok(value);
+        }
+
+        let mut number = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+            number.push(c);
+        }
+        if number.is_empty() {
+            return Err(match self.chars.peek() {
+                Some(c) => ToolError::InvalidInput(format!("unexpected {:?}", c)),
+                None => ToolError::InvalidInput("unexpected end of the expression".into()),
+            });
+        }
+        number
+            .parse()
+            .map_err(|_| ToolError::InvalidInput(format!("invalid number {}", number)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculator() {
+        let calculator = Calculator::new();
+        assert_eq!(calculator.evaluate("2 * (3 + 4) / 5").unwrap(), 2.8);
+        assert_eq!(calculator.evaluate("10 - 4 - 3").unwrap(), 3.0);
+        assert_eq!(calculator.evaluate("-2^2 + 2^3^2").unwrap(), 508.0);
+        assert_eq!(calculator.evaluate(" 1.5*-2 ").unwrap(), -3.0);
+
+        assert!(matches!(
+            calculator.evaluate("2 +"),
+            Err(ToolError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            calculator.evaluate("(1 + 2"),
+            Err(ToolError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            calculator.evaluate("2 x 3"),
+            Err(ToolError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            calculator.evaluate("1 / 0"),
+            Err(ToolError::ExecutionError(_))
+        ));
+    }
+}
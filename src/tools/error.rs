@@ -0,0 +1,19 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ToolError {
+    #[error("Invalid tool name {0:?}: it must be non-empty and without whitespace")]
+    InvalidName(String),
+
+    #[error("A tool is already registered as {0}")]
+    DuplicateTool(String),
+
+    #[error("Tool {0} not found")]
+    NotFound(String),
+
+    #[error("Invalid tool input: {0}")]
+    InvalidInput(String),
+
+    #[error("Tool execution error: {0}")]
+    ExecutionError(String),
+}
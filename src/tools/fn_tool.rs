@@ -0,0 +1,93 @@
+use std::{error::Error, future::Future, sync::Arc};
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use serde_json::Value;
+
+use super::{Tool, ToolError};
+
+type ToolFn = dyn Fn(String) -> BoxFuture<'static, Result<String, ToolError>> + Send + Sync;
+
+/// Struct `FnTool` adapts a closure into a `Tool`. The closure gets the input as a string.
+///
+/// # Usage
+/// ```rust,ignore
+/// let shout = FnTool::new("shout", "Upper-cases the input", |input: &str| Ok(input.to_uppercase()));
+/// let weather = FnTool::new_async("weather", "Gets the weather of a city", |city| async move {
+///     fetch_weather(&city).await.map_err(|e| ToolError::ExecutionError(e.to_string()))
+/// });
+/// ```
+#[derive(Clone)]
+pub struct FnTool {
+    name: String,
+    description: String,
+    function: Arc<ToolFn>,
+}
+
+impl FnTool {
+    pub fn new<N, D, F>(name: N, description: D, function: F) -> Self
+    where
+        N: Into<String>,
+        D: Into<String>,
+        F: Fn(&str) -> Result<String, ToolError> + Send + Sync + 'static,
+    {
+        Self::new_async(name, description, move |input| {
+            let result = function(&input);
+            async move { result }
+        })
+    }
+
+    pub fn new_async<N, D, F, Fut>(name: N, description: D, function: F) -> Self
+    where
+        N: Into<String>,
+        D: Into<String>,
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, ToolError>> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            function: Arc::new(move |input| Box::pin(function(input))),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for FnTool {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn description(&self) -> String {
+        self.description.clone()
+    }
+
+    async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
+        let input = match input {
+            Value::String(input) => input,
+            input => input.to_string(),
+        };
+        Ok((self.function)(input).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fn_tool() {
+        let tool = FnTool::new_async("length", "Counts characters", |input| async move {
+            if input.is_empty() {
+                return Err(ToolError::InvalidInput("empty input".into()));
+            }
+            Ok(input.chars().count().to_string())
+        });
+        assert_eq!(tool.call("héllo").await.unwrap(), "5");
+        assert_eq!(tool.call(r#"{"input": "abc"}"#).await.unwrap(), "3");
+        assert_eq!(
+            tool.call("").await.unwrap_err().to_string(),
+            "Invalid tool input: empty input"
+        );
+    }
+}
@@ -1,6 +1,18 @@
 mod tool;
 pub use tool::*;
 
+mod error;
+pub use error::*;
+
+mod registry;
+pub use registry::*;
+
+mod fn_tool;
+pub use fn_tool::*;
+
+mod calculator;
+pub use calculator::*;
+
 pub use wolfram::*;
 mod wolfram;
 
@@ -0,0 +1,164 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{prompt::PromptArgs, prompt_args};
+
+use super::{Tool, ToolError};
+
+/// Checks that a tool name can be parsed back out of a model's output: it must be non-empty
+/// and without whitespace.
+pub fn validate_tool_name(name: &str) -> Result<(), ToolError> {
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        return Err(ToolError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Renders the tools into the `tools` variable, one `name: description` line per tool, and the
+/// `tool_names` variable, the names separated by commas.
+pub fn render_tools(tools: &[Arc<dyn Tool>]) -> PromptArgs {
+    let descriptions = tools
+        .iter()
+        .map(|tool| format!("{}: {}", tool.name(), tool.description()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let names = tools
+        .iter()
+        .map(|tool| tool.name())
+        .collect::<Vec<_>>()
+        .join(", ");
+    prompt_args! {
+        "tools" => descriptions,
+        "tool_names" => names,
+    }
+}
+
+/// Struct `ToolRegistry` holds tools by name, in the order they were registered.
+///
+/// # Usage
+/// ```rust,ignore
+/// let registry = ToolRegistry::from_tools(vec![Arc::new(Calculator::new())])?;
+/// let observation = registry.call("calculator", "2 * (3 + 4)").await?;
+/// ```
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+    by_name: HashMap<String, usize>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_tools<I: IntoIterator<Item = Arc<dyn Tool>>>(tools: I) -> Result<Self, ToolError> {
+        let mut registry = Self::new();
+        for tool in tools {
+            registry.register(tool)?;
+        }
+        Ok(registry)
+    }
+
+    /// Registers a tool.
+    ///
+    /// # Errors
+    /// Returns `ToolError::InvalidName` for a name with whitespace, and
+    /// `ToolError::DuplicateTool` if a tool has the same name.
+    pub fn register(&mut self, tool: Arc<dyn Tool>) -> Result<(), ToolError> {
+        let name = tool.name();
+        validate_tool_name(&name)?;
+        if self.by_name.contains_key(&name) {
+            return Err(ToolError::DuplicateTool(name));
+        }
+        self.by_name.insert(name, self.tools.len());
+        self.tools.push(tool);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Tool>> {
+        self.by_name.get(name).map(|&index| &self.tools[index])
+    }
+
+    pub fn tools(&self) -> &[Arc<dyn Tool>] {
+        &self.tools
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.tools.iter().map(|tool| tool.name()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Renders the tools into the `tools` and `tool_names` variables, see `render_tools`.
+    pub fn render(&self) -> PromptArgs {
+        render_tools(&self.tools)
+    }
+
+    /// Calls the tool with the name. Its errors are returned as `ToolError::ExecutionError`.
+    pub async fn call(&self, name: &str, input: &str) -> Result<String, ToolError> {
+        let tool = self
+            .get(name)
+            .ok_or_else(|| ToolError::NotFound(name.to_string()))?;
+        tool.call(input)
+            .await
+            .map_err(|e| ToolError::ExecutionError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{Calculator, FnTool};
+
+    #[tokio::test]
+    async fn test_tool_registry() {
+        let echo = FnTool::new("echo", "Repeats the input", |input: &str| {
+            Ok(input.to_string())
+        });
+        let registry = ToolRegistry::from_tools(vec![
+            Arc::new(Calculator::new()) as Arc<dyn Tool>,
+            Arc::new(echo),
+        ])
+        .unwrap();
+        assert_eq!(registry.names(), vec!["calculator", "echo"]);
+        assert_eq!(
+            registry.call("calculator", "2 * (3 + 4)").await.unwrap(),
+            "14"
+        );
+        assert_eq!(registry.call("echo", "hi").await.unwrap(), "hi");
+        assert!(matches!(
+            registry.call("search", "hi").await,
+            Err(ToolError::NotFound(name)) if name == "search"
+        ));
+
+        let args = registry.render();
+        assert_eq!(
+            args["tools"],
+            "calculator: Useful to compute arithmetic expressions, like `2 * (3 + 4) / 5`\necho: Repeats the input"
+        );
+        assert_eq!(args["tool_names"], "calculator, echo");
+    }
+
+    #[test]
+    fn test_tool_registry_validation() {
+        let mut registry = ToolRegistry::new();
+        registry.register(Arc::new(Calculator::new())).unwrap();
+        assert!(matches!(
+            registry.register(Arc::new(Calculator::new())),
+            Err(ToolError::DuplicateTool(_))
+        ));
+        let tool = FnTool::new("web search", "Searches", |_: &str| Ok(String::new()));
+        assert!(matches!(
+            registry.register(Arc::new(tool)),
+            Err(ToolError::InvalidName(name)) if name == "web search"
+        ));
+        assert!(validate_tool_name("line\nbreak").is_err());
+        assert!(validate_tool_name("").is_err());
+        assert!(validate_tool_name("Web_Scraper").is_ok());
+    }
+}
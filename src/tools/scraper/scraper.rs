@@ -17,7 +17,7 @@ impl WebScrapper {
 #[async_trait]
 impl Tool for WebScrapper {
     fn name(&self) -> String {
-        String::from("Web Scraper")
+        String::from("Web_Scraper")
     }
     fn description(&self) -> String {
         String::from(
@@ -0,0 +1,268 @@
+use std::{cmp::Ordering, error::Error, fs, path::Path, sync::Arc, sync::RwLock};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    embedding::embedder_trait::Embedder,
+    schemas::Document,
+    vectorstore::{VecStoreOptions, VectorStore},
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Entry {
+    id: String,
+    name_space: Option<String>,
+    document: Document,
+    embedding: Vec<f64>,
+}
+
+/// Struct `InMemoryVectorStore` keeps documents and their embeddings in memory and searches
+/// them by cosine similarity, comparing the query with every document. Documents with the
+/// same score are returned in the order they were added.
+///
+/// The `filters` option is a JSON object of metadata values the documents must equal, and
+/// the `name_space` option puts documents in, and searches, a separate name space.
+///
+/// # Usage
+/// ```rust,ignore
+/// let store = InMemoryVectorStore::new(OpenAiEmbedder::default());
+/// add_documents!(store, &documents).await?;
+/// let results = similarity_search!(store, "What is Rust?", 4).await?;
+/// store.save("store.json")?;
+/// ```
+pub struct InMemoryVectorStore {
+    embedder: Arc<dyn Embedder>,
+    entries: RwLock<Vec<Entry>>,
+}
+
+impl InMemoryVectorStore {
+    pub fn new<E: Embedder + 'static>(embedder: E) -> Self {
+        Self {
+            embedder: Arc::new(embedder),
+            entries: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Loads a store saved with `save`. The embedder must be the one the documents were
+    /// embedded with.
+    pub fn load<P: AsRef<Path>, E: Embedder + 'static>(
+        path: P,
+        embedder: E,
+    ) -> Result<Self, Box<dyn Error>> {
+        let entries: Vec<Entry> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(Self {
+            embedder: Arc::new(embedder),
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// Saves the documents and their embeddings to a JSON file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_string(&*self.entries.read().unwrap_or_else(|e| e.into_inner()))?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Searches the documents for which the predicate returns true, along with the options.
+    /// The documents are returned with their score.
+    pub async fn similarity_search_by<F>(
+        &self,
+        query: &str,
+        limit: usize,
+        opt: &VecStoreOptions,
+        predicate: F,
+    ) -> Result<Vec<Document>, Box<dyn Error>>
+    where
+        F: Fn(&Document) -> bool + Send,
+    {
+        let embedder = opt.embedder.as_ref().unwrap_or(&self.embedder);
+        let query = embedder.embed_query(query).await?;
+        let filters = match &opt.filters {
+            None => None,
+            Some(Value::Object(filters)) => Some(filters),
+            Some(_) => return Err("The filters must be a JSON object".into()),
+        };
+
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        let mut results: Vec<Document> = entries
+            .iter()
+            .filter(|entry| entry.name_space == opt.name_space)
+            .filter(|entry| {
+                filters.is_none_or(|filters| {
+                    filters
+                        .iter()
+                        .all(|(key, value)| entry.document.metadata.get(key) == Some(value))
+                })
+            })
+            .filter(|entry| predicate(&entry.document))
+            .map(|entry| {
+                let mut document = entry.document.clone();
+                document.score = cosine_similarity(&query, &entry.embedding);
+                document
+            })
+            .filter(|document| {
+                opt.score_threshold
+                    .is_none_or(|threshold| document.score >= threshold as f64)
+            })
+            .collect();
+
+        // a stable sort, so equal scores keep the insertion order
+        results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+        results.truncate(limit);
+        Ok(results)
+    }
+}
+
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norms =
+        a.iter().map(|x| x * x).sum::<f64>().sqrt() * b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    /// Returns the ids of the added documents.
+    async fn add_documents(
+        &self,
+        docs: &[Document],
+        opt: &VecStoreOptions,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let embedder = opt.embedder.as_ref().unwrap_or(&self.embedder);
+        let texts: Vec<String> = docs.iter().map(|d| d.page_content.clone()).collect();
+        let embeddings = embedder.embed_documents(&texts).await?;
+
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        let first_id = entries.len();
+        let mut ids = Vec::with_capacity(docs.len());
+        for (i, (document, embedding)) in docs.iter().zip(embeddings).enumerate() {
+            let id = (first_id + i).to_string();
+            entries.push(Entry {
+                id: id.clone(),
+                name_space: opt.name_space.clone(),
+                document: document.clone(),
+                embedding,
+            });
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    async fn similarity_search(
+        &self,
+        query: &str,
+        limit: usize,
+        opt: &VecStoreOptions,
+    ) -> Result<Vec<Document>, Box<dyn Error>> {
+        self.similarity_search_by(query, limit, opt, |_| true).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{add_documents, embedding::FakeEmbedder, similarity_search};
+
+    fn document(content: &str, language: &str) -> Document {
+        Document::new(content)
+            .with_metadata(HashMap::from([("language".to_string(), json!(language))]))
+    }
+
+    async fn store() -> InMemoryVectorStore {
+        let store = InMemoryVectorStore::new(FakeEmbedder::default());
+        add_documents!(
+            store,
+            &[
+                document("rust is fast and safe", "rust"),
+                document("python is easy", "python"),
+                document("rust is fast", "rust"),
+                document("unrelated words here", "none"),
+            ]
+        )
+        .await
+        .unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_similarity_search() {
+        let store = store().await;
+        let results = similarity_search!(store, "rust is fast", 2).await.unwrap();
+        let contents: Vec<&str> = results.iter().map(|d| d.page_content.as_str()).collect();
+        assert_eq!(contents, vec!["rust is fast", "rust is fast and safe"]);
+        assert!((results[0].score - 1.0).abs() < 1e-9);
+
+        let results = store
+            .similarity_search_with_score("is", 4, &VecStoreOptions::default())
+            .await
+            .unwrap();
+        // "rust is fast" and "python is easy" have the same score, so keep their order
+        let contents: Vec<&str> = results
+            .iter()
+            .map(|(d, _)| d.page_content.as_str())
+            .collect();
+        assert_eq!(contents[..2], ["python is easy", "rust is fast"]);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_filters() {
+        let store = store().await;
+        let options = VecStoreOptions::new().with_filters(json!({"language": "python"}));
+        let results = similarity_search!(store, "rust is fast", 4, &options)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].page_content, "python is easy");
+
+        let results = store
+            .similarity_search_by("rust", 4, &VecStoreOptions::default(), |d| {
+                d.page_content.len() < 15
+            })
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        let options = VecStoreOptions::new().with_score_threshold(0.5);
+        let results = similarity_search!(store, "rust is fast", 4, &options)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        let options = VecStoreOptions::new().with_name_space("other");
+        assert!(similarity_search!(store, "rust", 4, &options)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_persistence() {
+        let store = store().await;
+        let path =
+            std::env::temp_dir().join(format!("in_memory_store_{}.json", std::process::id()));
+        store.save(&path).unwrap();
+        let loaded = InMemoryVectorStore::load(&path, FakeEmbedder::default()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.len(), 4);
+        let results = similarity_search!(loaded, "python", 1).await.unwrap();
+        assert_eq!(results[0].page_content, "python is easy");
+    }
+}
@@ -1,5 +1,7 @@
 mod options;
 
+pub mod in_memory;
+
 #[cfg(feature = "postgres")]
 pub mod pgvector;
 
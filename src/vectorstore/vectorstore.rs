@@ -22,6 +22,24 @@ pub trait VectorStore: Send + Sync {
         limit: usize,
         opt: &VecStoreOptions,
     ) -> Result<Vec<Document>, Box<dyn Error>>;
+
+    /// Returns the documents most similar to the query along with their score.
+    async fn similarity_search_with_score(
+        &self,
+        query: &str,
+        limit: usize,
+        opt: &VecStoreOptions,
+    ) -> Result<Vec<(Document, f64)>, Box<dyn Error>> {
+        Ok(self
+            .similarity_search(query, limit, opt)
+            .await?
+            .into_iter()
+            .map(|document| {
+                let score = document.score;
+                (document, score)
+            })
+            .collect())
+    }
 }
 impl<VS> From<VS> for Box<dyn VectorStore>
 where